@@ -0,0 +1,47 @@
+-- Baseline schema
+--
+-- NB: statements are idempotent, so that databases created before the migrations were
+-- introduced can be baselined.
+
+CREATE EXTENSION IF NOT EXISTS vector;
+
+DO $$
+BEGIN
+    CREATE TYPE subscription AS ENUM ('FREE', 'MID');
+EXCEPTION
+    WHEN duplicate_object THEN NULL;
+END
+$$;
+
+CREATE TABLE IF NOT EXISTS users (
+    id              UUID PRIMARY KEY,
+    name            TEXT NOT NULL,
+    email           TEXT NOT NULL,
+    password        TEXT NOT NULL,
+    subscription    subscription NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS feeds (
+    id          UUID PRIMARY KEY,
+    user_id     UUID NOT NULL,
+    url         TEXT NOT NULL,
+    name        TEXT,
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
+
+CREATE TABLE IF NOT EXISTS summaries (
+    id          UUID PRIMARY KEY,
+    url         TEXT NOT NULL UNIQUE,
+    summary     TEXT,
+    keywords    TEXT[],
+    embeddings  VECTOR(1536)
+);
+
+CREATE TABLE IF NOT EXISTS idempotency_keys (
+    key         TEXT NOT NULL,
+    scope       TEXT NOT NULL,
+    status      SMALLINT NOT NULL,
+    body        BYTEA NOT NULL,
+    created_at  TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (key, scope)
+);
@@ -0,0 +1,37 @@
+-- Indexes and constraints
+
+-- NB: duplicated feeds must be removed before adding the unique constraint
+DELETE FROM feeds a
+USING feeds b
+WHERE a.user_id = b.user_id
+AND a.url = b.url
+AND a.id > b.id;
+
+-- NB: the unique index is also used for the lookups by user_id
+CREATE UNIQUE INDEX IF NOT EXISTS feeds_user_id_url_key ON feeds (user_id, url);
+
+-- NB: duplicated users are not merged (their feeds and passwords may differ), they must be
+-- resolved by hand before the migration
+DO $$
+DECLARE
+    duplicates TEXT;
+BEGIN
+    SELECT string_agg(format('%s (%s)', email, ids), ', ' ORDER BY email)
+    INTO duplicates
+    FROM (
+        SELECT email, string_agg(id::text, ', ' ORDER BY id) AS ids
+        FROM users
+        GROUP BY email
+        HAVING count(*) > 1
+    ) d;
+    IF duplicates IS NOT NULL THEN
+        RAISE EXCEPTION 'duplicated user emails: %', duplicates
+            USING HINT = 'remove or rename the duplicated users, then restart the migration';
+    END IF;
+END
+$$;
+
+CREATE UNIQUE INDEX IF NOT EXISTS users_email_key ON users (email);
+
+-- NB: already created by the UNIQUE constraint on databases created with the baseline
+CREATE UNIQUE INDEX IF NOT EXISTS summaries_url_key ON summaries (url);
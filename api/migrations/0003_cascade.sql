@@ -0,0 +1,7 @@
+-- Cascade the deletion of a user to its data
+
+-- NB: the baseline foreign key was created without a name, so it has the default name
+ALTER TABLE feeds DROP CONSTRAINT IF EXISTS feeds_user_id_fkey;
+ALTER TABLE feeds
+    ADD CONSTRAINT feeds_user_id_fkey
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE;
@@ -0,0 +1,9 @@
+-- Timestamps of the summaries, used by the retention job
+
+-- NB: existing summaries are considered created and accessed at the migration time
+ALTER TABLE summaries ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT now();
+ALTER TABLE summaries ADD COLUMN IF NOT EXISTS accessed_at TIMESTAMPTZ NOT NULL DEFAULT now();
+
+CREATE INDEX IF NOT EXISTS summaries_created_at_idx ON summaries (created_at);
+CREATE INDEX IF NOT EXISTS summaries_accessed_at_idx ON summaries (accessed_at);
+CREATE INDEX IF NOT EXISTS idempotency_keys_created_at_idx ON idempotency_keys (created_at);
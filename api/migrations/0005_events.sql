@@ -0,0 +1,17 @@
+-- Notify the API replicas of the new summaries (see the `events` module)
+
+CREATE OR REPLACE FUNCTION notify_summary_created() RETURNS trigger AS $$
+BEGIN
+    PERFORM pg_notify(
+        'newsie_events',
+        json_build_object('type', 'summary_created', 'id', NEW.id, 'url', NEW.url)::text
+    );
+    RETURN NULL;
+END
+$$ LANGUAGE plpgsql;
+
+-- NB: an upsert of an existing URL does not fire the insert trigger
+DROP TRIGGER IF EXISTS summaries_notify_created ON summaries;
+CREATE TRIGGER summaries_notify_created
+    AFTER INSERT ON summaries
+    FOR EACH ROW EXECUTE FUNCTION notify_summary_created();
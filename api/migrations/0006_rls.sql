@@ -0,0 +1,19 @@
+-- Row-level security policies
+--
+-- NB: the policies are only enforced if row-level security is enabled on the tables, which
+-- is done at startup with the `postgres.rls` option. The `app.user_id` setting is the ID of
+-- the current user, or `*` for all the users.
+
+DROP POLICY IF EXISTS tenant_isolation ON users;
+CREATE POLICY tenant_isolation ON users
+    USING (
+        current_setting('app.user_id', true) = '*'
+        OR id::text = current_setting('app.user_id', true)
+    );
+
+DROP POLICY IF EXISTS tenant_isolation ON feeds;
+CREATE POLICY tenant_isolation ON feeds
+    USING (
+        current_setting('app.user_id', true) = '*'
+        OR user_id::text = current_setting('app.user_id', true)
+    );
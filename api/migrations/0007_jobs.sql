@@ -0,0 +1,19 @@
+-- Background jobs queue
+--
+-- NB: the workers claim the jobs with `FOR UPDATE SKIP LOCKED`, so that the replicas do not
+-- run the same job. A running job whose lease has expired (`locked_until`) is claimed again.
+
+CREATE TABLE IF NOT EXISTS jobs (
+    id              UUID PRIMARY KEY,
+    kind            TEXT NOT NULL,
+    payload         JSONB NOT NULL,
+    status          TEXT NOT NULL,
+    attempts        INTEGER NOT NULL DEFAULT 0,
+    max_attempts    INTEGER NOT NULL,
+    last_error      TEXT,
+    run_at          TIMESTAMPTZ NOT NULL,
+    locked_until    TIMESTAMPTZ,
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS jobs_status_run_at_idx ON jobs (status, run_at);
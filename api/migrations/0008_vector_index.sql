@@ -0,0 +1,19 @@
+-- Approximate nearest neighbors index of the embeddings (requires pgvector 0.5)
+--
+-- NB: the operator class depends on the type of the embeddings (see the `postgres.halfvec`
+-- option). The recall of the index can be checked with `/admin/maintenance/vector-index`.
+
+DO $$
+DECLARE
+    embeddings_type TEXT;
+BEGIN
+    SELECT t.typname INTO embeddings_type
+    FROM pg_attribute a JOIN pg_type t ON t.oid = a.atttypid
+    WHERE a.attrelid = 'summaries'::regclass AND a.attname = 'embeddings';
+
+    EXECUTE format(
+        'CREATE INDEX IF NOT EXISTS summaries_embeddings_idx ON summaries USING hnsw (embeddings %s_cosine_ops)',
+        embeddings_type
+    );
+END
+$$;
@@ -0,0 +1,9 @@
+-- Reservation of the idempotency keys
+--
+-- NB: a key is reserved (`completed` is false) while its request is running, so that a
+-- concurrent retry is rejected instead of being processed twice. The hash of the request body
+-- is kept to reject the reuse of a key with another payload. The existing responses are
+-- completed, and have no hash.
+
+ALTER TABLE idempotency_keys ADD COLUMN IF NOT EXISTS request_hash TEXT NOT NULL DEFAULT '';
+ALTER TABLE idempotency_keys ADD COLUMN IF NOT EXISTS completed BOOLEAN NOT NULL DEFAULT TRUE;
@@ -0,0 +1,5 @@
+-- Content type of the stored idempotent responses (eg. JSON or MessagePack)
+--
+-- NB: the existing responses are JSON, which is the default when replaying without a type
+
+ALTER TABLE idempotency_keys ADD COLUMN IF NOT EXISTS content_type TEXT;
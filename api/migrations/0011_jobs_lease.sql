@@ -0,0 +1,7 @@
+-- Owner and lease of the background jobs
+--
+-- NB: a claim sets a new lease ID, so that a worker whose lease has expired (and whose job
+-- has been claimed again) cannot record the outcome of the job.
+
+ALTER TABLE jobs ADD COLUMN IF NOT EXISTS owner_id UUID;
+ALTER TABLE jobs ADD COLUMN IF NOT EXISTS lease_id UUID;
@@ -0,0 +1,30 @@
+//! Exports the summaries and their embeddings to Parquet
+//!
+//! Usage: `export <path>` (requires the `export` feature)
+//!
+//! The data store is selected with the same configuration as the server.
+
+use std::{fs::File, io::BufWriter, time::Duration};
+
+use newsie_api::{
+    config::AppConfig,
+    db::{init_schema_with_retry, init_store},
+    svc::export::{export_summaries, EXPORT_PAGE_SIZE},
+};
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let Some(path) = std::env::args().nth(1) else {
+        eprintln!("usage: export <path>");
+        std::process::exit(1);
+    };
+
+    let cfg = AppConfig::load();
+    let db = init_store(&cfg)?;
+    init_schema_with_retry(&db, Duration::from_secs(cfg.store.timeout)).await?;
+
+    let file = BufWriter::new(File::create(&path)?);
+    let count = export_summaries(&db, file, EXPORT_PAGE_SIZE).await?;
+    eprintln!("Exported {count} summaries to {path}");
+    Ok(())
+}
@@ -1,15 +1,63 @@
 //! Generates the OpenAPI documentation
+//!
+//! Usage: `openapi [--format yaml|json|md|ts]`
+//!
+//! - `yaml` (default), `json`: OpenAPI specs
+//! - `md`: Markdown API reference
+//! - `ts`: TypeScript models
+//!
+//! NB: the Rust client uses the models of the API crate, which are not generated
 
 use newsie_api::{
     config::AppConfig,
+    docgen::{gen_markdown, gen_typescript, Format},
     http::{gen_openapi_specs, init_api_services, init_router},
 };
 
 #[tokio::main]
 async fn main() {
+    let format = parse_format();
+
     let cfg = AppConfig::load();
     let api_services = init_api_services(&cfg).await.unwrap();
-    let router = init_router(api_services).await;
+    let router = init_router(&cfg, api_services).await;
     let openapi = gen_openapi_specs(&router);
-    println!("{}", openapi.to_yaml().unwrap());
+
+    let output = match format {
+        Format::Yaml => openapi.to_yaml().unwrap(),
+        Format::Json => openapi.to_pretty_json().unwrap(),
+        format => {
+            let specs = serde_json::to_value(&openapi).unwrap();
+            match format {
+                Format::Markdown => gen_markdown(&specs),
+                _ => gen_typescript(&specs),
+            }
+        }
+    };
+    println!("{output}");
+}
+
+/// Parses the `--format` argument
+fn parse_format() -> Format {
+    let mut args = std::env::args().skip(1);
+    let mut format = Format::Yaml;
+    while let Some(arg) = args.next() {
+        let value = match arg.strip_prefix("--format=") {
+            Some(v) => Some(v.to_string()),
+            None if arg == "--format" || arg == "-f" => args.next(),
+            None => None,
+        };
+        match value.map(|v| v.parse::<Format>()) {
+            Some(Ok(f)) => format = f,
+            Some(Err(err)) => {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+            None => {
+                eprintln!("unexpected argument '{arg}'");
+                std::process::exit(1);
+            }
+        }
+    }
+    format
 }
@@ -0,0 +1,177 @@
+//! DB metrics
+//!
+//! The store operations are timed, and the waits to acquire a pooled connection are
+//! recorded, so that the slow queries and the pool exhaustion are visible
+//! (see the `/admin/metrics` endpoint). The operations slower than a threshold are also
+//! logged at WARN, within the span of the request (which holds the request ID).
+
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use tracing::warn;
+
+use crate::error::Error;
+pub use crate::mdl::report::{DbMetricsReport, PoolStats, QueryStats};
+
+/// DB metrics recorder (since the start of the service)
+#[derive(Debug, Default)]
+pub struct DbMetrics {
+    /// Threshold above which an operation is logged as slow
+    slow: Option<Duration>,
+    /// Number of connection acquisitions
+    acquisitions: AtomicU64,
+    /// Number of failed connection acquisitions (eg. pool timeouts)
+    acquire_errors: AtomicU64,
+    /// Cumulated wait time to acquire a connection (µs)
+    wait_us: AtomicU64,
+    /// Longest wait time to acquire a connection (µs)
+    wait_max_us: AtomicU64,
+    /// Counters per operation
+    queries: Mutex<BTreeMap<&'static str, QueryCounters>>,
+}
+
+/// Counters of an operation
+#[derive(Debug, Default, Clone, Copy)]
+struct QueryCounters {
+    /// Number of calls
+    count: u64,
+    /// Number of failed calls
+    errors: u64,
+    /// Cumulated duration (µs)
+    total_us: u64,
+    /// Longest duration (µs)
+    max_us: u64,
+}
+
+impl DbMetrics {
+    /// Creates a new recorder
+    pub fn new(slow: Option<Duration>) -> Self {
+        Self {
+            slow,
+            ..Default::default()
+        }
+    }
+
+    /// Records the acquisition of a pooled connection
+    pub fn record_acquire(&self, wait: Duration, ok: bool) {
+        let wait_us = wait.as_micros() as u64;
+        self.acquisitions.fetch_add(1, Ordering::Relaxed);
+        if !ok {
+            self.acquire_errors.fetch_add(1, Ordering::Relaxed);
+        }
+        self.wait_us.fetch_add(wait_us, Ordering::Relaxed);
+        self.wait_max_us.fetch_max(wait_us, Ordering::Relaxed);
+    }
+
+    /// Records the execution of an operation
+    pub fn record_query(&self, name: &'static str, duration: Duration, ok: bool) {
+        if self.slow.map_or(false, |slow| duration > slow) {
+            warn!(
+                query = name,
+                duration_ms = duration.as_secs_f64() * 1000.0,
+                "slow query"
+            );
+        }
+
+        let duration_us = duration.as_micros() as u64;
+        let mut queries = self.queries.lock().unwrap();
+        let counters = queries.entry(name).or_default();
+        counters.count += 1;
+        if !ok {
+            counters.errors += 1;
+        }
+        counters.total_us += duration_us;
+        counters.max_us = counters.max_us.max(duration_us);
+    }
+
+    /// Times an operation
+    pub async fn observe<T, F>(&self, name: &'static str, fut: F) -> Result<T, Error>
+    where
+        F: Future<Output = Result<T, Error>>,
+    {
+        let start = Instant::now();
+        let res = fut.await;
+        self.record_query(name, start.elapsed(), res.is_ok());
+        res
+    }
+
+    /// Returns a report of the metrics
+    pub fn report(&self, pool: Option<PoolStats>) -> DbMetricsReport {
+        let acquisitions = self.acquisitions.load(Ordering::Relaxed);
+        let wait_us = self.wait_us.load(Ordering::Relaxed);
+        let queries = self
+            .queries
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, c)| QueryStats {
+                name: name.to_string(),
+                count: c.count,
+                errors: c.errors,
+                total_ms: to_ms(c.total_us),
+                mean_ms: if c.count > 0 {
+                    to_ms(c.total_us) / c.count as f64
+                } else {
+                    0.0
+                },
+                max_ms: to_ms(c.max_us),
+            })
+            .collect();
+
+        DbMetricsReport {
+            pool,
+            acquisitions,
+            acquire_errors: self.acquire_errors.load(Ordering::Relaxed),
+            wait_mean_ms: if acquisitions > 0 {
+                to_ms(wait_us) / acquisitions as f64
+            } else {
+                0.0
+            },
+            wait_max_ms: to_ms(self.wait_max_us.load(Ordering::Relaxed)),
+            queries,
+        }
+    }
+}
+
+/// Converts microseconds to milliseconds
+fn to_ms(us: u64) -> f64 {
+    us as f64 / 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_metrics() {
+        let metrics = DbMetrics::default();
+        metrics.record_acquire(Duration::from_millis(2), true);
+        metrics.record_acquire(Duration::from_millis(4), false);
+        metrics
+            .observe("read_user", async { Ok::<_, Error>(()) })
+            .await
+            .unwrap();
+        let _res = metrics
+            .observe("read_user", async {
+                Err::<(), _>(Error::Internal("failed".to_string(), None))
+            })
+            .await;
+
+        let report = metrics.report(None);
+        assert_eq!(report.acquisitions, 2);
+        assert_eq!(report.acquire_errors, 1);
+        assert_eq!(report.wait_mean_ms, 3.0);
+        assert_eq!(report.wait_max_ms, 4.0);
+        assert_eq!(report.queries.len(), 1);
+        assert_eq!(report.queries[0].name, "read_user");
+        assert_eq!(report.queries[0].count, 2);
+        assert_eq!(report.queries[0].errors, 1);
+    }
+}
@@ -1,3 +1,321 @@
 //! Databases
+//!
+//! The services access the data through the [Store] trait, which is implemented by:
+//!
+//! - [postgres::PostgresClient]: the default backend (requires the `pgvector` extension)
+//! - [sqlite::SqliteClient]: a single file database, so that the API can be self-hosted
+//!   without any external service (the vector search is a brute force scan)
+//!
+//! The backend is selected with the `store.backend` configuration.
 
+use std::{sync::Arc, time::Duration};
+
+use salvo::async_trait;
+use time::OffsetDateTime;
+use tokio::sync::broadcast;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{
+    config::{AppConfig, StoreBackend},
+    error::Error,
+    mdl::{
+        query::ListOptions, DailyCount, Event, Feed, FeedCount, FeedUpdate, IdempotentResponse,
+        Job, JobCount, NewUser, SubscriptionUpdate, Summary, User, UserUpdate, Vector,
+        VectorIndexReport,
+    },
+};
+
+use self::{metrics::DbMetricsReport, page::Page, postgres::PostgresClient, sqlite::SqliteClient};
+
+pub mod metrics;
+pub mod page;
 pub mod postgres;
+pub mod sqlite;
+pub mod tenant;
+
+/// Data store
+#[async_trait]
+pub trait Store: std::fmt::Debug + Send + Sync {
+    /// Initializes the DB schema
+    async fn init_schema(&self) -> Result<(), Error>;
+
+    /// Checks that a connection can be acquired and used
+    async fn ping(&self) -> Result<(), Error>;
+
+    /// Checks if the DB schema is initialized
+    async fn is_schema_init(&self) -> Result<bool, Error>;
+
+    /// Subscribes to the data events
+    ///
+    /// With PostgreSQL, the events of all the API replicas are received (LISTEN/NOTIFY).
+    fn subscribe(&self) -> broadcast::Receiver<Event>;
+
+    /// Returns the query and pool metrics (`None` if the backend does not record them)
+    fn metrics(&self) -> Option<DbMetricsReport>;
+
+    /// Begins a transaction
+    async fn begin(&self) -> Result<Box<dyn StoreTx>, Error>;
+
+    /// Creates a user
+    async fn create_user(&self, new_user: NewUser) -> Result<User, Error>;
+
+    /// Reads a user
+    async fn read_user(&self, id: Uuid) -> Result<Option<User>, Error>;
+
+    /// Reads all the users
+    async fn read_users(&self) -> Result<Vec<User>, Error>;
+
+    /// Reads a user with its email
+    async fn read_user_with_email(&self, email: &str) -> Result<Option<User>, Error>;
+
+    /// Updates a user
+    async fn update_user(&self, id: Uuid, fields: UserUpdate) -> Result<User, Error>;
+
+    /// Updates the subscription of a user
+    async fn update_user_subscription(
+        &self,
+        id: Uuid,
+        subscription_update: SubscriptionUpdate,
+    ) -> Result<User, Error>;
+
+    /// Deletes a user
+    async fn delete_user(&self, id: Uuid) -> Result<(), Error>;
+
+    /// Reads the feeds of all the users
+    async fn read_feeds(&self) -> Result<Vec<Feed>, Error>;
+
+    /// Reads all the feeds of a user
+    async fn read_user_feeds(&self, user_id: Uuid) -> Result<Vec<Feed>, Error>;
+
+    /// Queries the feeds of a user (paginated if a limit is set)
+    async fn query_user_feeds(
+        &self,
+        user_id: Uuid,
+        opts: &ListOptions,
+    ) -> Result<Page<Feed>, Error>;
+
+    /// Replaces the feeds of a user
+    async fn sync_user_feeds(
+        &self,
+        user_id: Uuid,
+        feeds: Vec<FeedUpdate>,
+    ) -> Result<Vec<Feed>, Error>;
+
+    /// Deletes all the feeds of a user
+    async fn delete_user_feeds(&self, user_id: Uuid) -> Result<(), Error>;
+
+    /// Reads all the summaries
+    async fn read_summaries(&self) -> Result<Vec<Summary>, Error>;
+
+    /// Reads a page of the summaries, by ascending ID
+    async fn read_summaries_page(
+        &self,
+        cursor: Option<&str>,
+        limit: usize,
+    ) -> Result<Page<Summary>, Error>;
+
+    /// Searches the summaries of a list of URLs
+    async fn search_summaries_by_urls(&self, urls: &[&str]) -> Result<Vec<Summary>, Error>;
+
+    /// Searches the summaries closest to an embeddings vector (cosine distance)
+    async fn search_summaries_by_embeddings(
+        &self,
+        embeddings: &Vector,
+        limit: usize,
+    ) -> Result<Vec<Summary>, Error>;
+
+    /// Measures the recall of the vector index on a sample of `sample` summaries
+    ///
+    /// For each sampled summary, the `k` closest summaries found with the index are compared
+    /// to the exact ones.
+    async fn check_vector_index(&self, sample: usize, k: usize)
+        -> Result<VectorIndexReport, Error>;
+
+    /// Rebuilds the vector index
+    async fn reindex_vectors(&self) -> Result<(), Error>;
+
+    /// Inserts summaries
+    async fn insert_summaries(&self, summaries: Vec<Summary>) -> Result<Vec<Summary>, Error>;
+
+    /// Removes summaries
+    async fn remove_summaries(&self, summaries: Vec<Summary>) -> Result<(), Error>;
+
+    /// Marks the summaries of a list of URLs as accessed now
+    async fn touch_summaries(&self, urls: &[&str]) -> Result<(), Error>;
+
+    /// Deletes the summaries created before a date
+    async fn delete_summaries_created_before(&self, before: OffsetDateTime) -> Result<u64, Error>;
+
+    /// Deletes the summaries last accessed before a date
+    async fn delete_summaries_accessed_before(&self, before: OffsetDateTime) -> Result<u64, Error>;
+
+    /// Reads the response stored for an idempotency key, if created after `since`
+    async fn read_idempotent_response(
+        &self,
+        key: &str,
+        scope: &str,
+        since: OffsetDateTime,
+    ) -> Result<Option<IdempotentResponse>, Error>;
+
+    /// Stores the response for an idempotency key
+    async fn upsert_idempotent_response(&self, response: &IdempotentResponse) -> Result<(), Error>;
+
+    /// Reserves an idempotency key while its request is running
+    ///
+    /// A stored response created before `expired_before`, or a reservation created before
+    /// `stale_before` (eg. its replica crashed), is replaced. Returns `false` if the key is
+    /// already used.
+    async fn reserve_idempotency_key(
+        &self,
+        pending: &IdempotentResponse,
+        expired_before: OffsetDateTime,
+        stale_before: OffsetDateTime,
+    ) -> Result<bool, Error>;
+
+    /// Deletes the response (or the reservation) of an idempotency key
+    async fn delete_idempotent_response(&self, key: &str, scope: &str) -> Result<(), Error>;
+
+    /// Deletes the responses created before a date
+    async fn delete_idempotent_responses_before(
+        &self,
+        before: OffsetDateTime,
+    ) -> Result<u64, Error>;
+
+    /// Counts the users
+    async fn count_users(&self) -> Result<u64, Error>;
+
+    /// Counts the users by number of feeds
+    async fn count_users_by_feeds(&self) -> Result<Vec<FeedCount>, Error>;
+
+    /// Counts the summaries created per day since a date
+    async fn count_summaries_by_day(&self, since: OffsetDateTime)
+        -> Result<Vec<DailyCount>, Error>;
+
+    /// Enqueues a job
+    async fn enqueue_job(&self, job: &Job) -> Result<(), Error>;
+
+    /// Claims the due jobs of some kinds, which are locked until a date
+    ///
+    /// With PostgreSQL, the jobs claimed by other replicas are skipped (`SKIP LOCKED`).
+    async fn claim_jobs(
+        &self,
+        kinds: &[&str],
+        limit: usize,
+        locked_until: OffsetDateTime,
+    ) -> Result<Vec<Job>, Error>;
+
+    /// Reads a job
+    async fn read_job(&self, id: Uuid) -> Result<Option<Job>, Error>;
+
+    /// Extends the lease of a running job until a date
+    ///
+    /// An [Error::Conflict] is returned if the lease is not held anymore.
+    async fn renew_job(
+        &self,
+        id: Uuid,
+        lease_id: Uuid,
+        locked_until: OffsetDateTime,
+    ) -> Result<(), Error>;
+
+    /// Marks a job as completed
+    ///
+    /// An [Error::Conflict] is returned if the lease is not held anymore.
+    async fn complete_job(&self, id: Uuid, lease_id: Uuid) -> Result<(), Error>;
+
+    /// Records the failure of a job, which is retried at a date or dead-lettered
+    ///
+    /// An [Error::Conflict] is returned if the lease is not held anymore.
+    async fn fail_job(
+        &self,
+        id: Uuid,
+        lease_id: Uuid,
+        error: &str,
+        retry_at: Option<OffsetDateTime>,
+    ) -> Result<(), Error>;
+
+    /// Deletes the completed jobs created before a date
+    async fn delete_jobs_done_before(&self, before: OffsetDateTime) -> Result<u64, Error>;
+
+    /// Counts the jobs by status
+    async fn count_jobs(&self) -> Result<Vec<JobCount>, Error>;
+}
+
+/// Data store transaction (unit of work)
+///
+/// The writes are applied atomically when the transaction is committed. A transaction
+/// which is dropped without being committed is rolled back.
+#[async_trait]
+pub trait StoreTx: Send {
+    /// Inserts or updates a user (all the fields are set)
+    async fn upsert_user(&mut self, user: &User) -> Result<(), Error>;
+
+    /// Reads all the feeds of a user, which cannot be changed by other transactions until
+    /// this one ends
+    async fn lock_user_feeds(&mut self, user_id: Uuid) -> Result<Vec<Feed>, Error>;
+
+    /// Replaces the feeds of a user
+    async fn sync_user_feeds(
+        &mut self,
+        user_id: Uuid,
+        feeds: Vec<FeedUpdate>,
+    ) -> Result<Vec<Feed>, Error>;
+
+    /// Deletes all the feeds of a user
+    async fn delete_user_feeds(&mut self, user_id: Uuid) -> Result<(), Error>;
+
+    /// Deletes a user
+    async fn delete_user(&mut self, id: Uuid) -> Result<(), Error>;
+
+    /// Inserts summaries (the existing summary of an URL is kept)
+    async fn insert_summaries(&mut self, summaries: Vec<Summary>) -> Result<Vec<Summary>, Error>;
+
+    /// Commits the transaction
+    async fn commit(self: Box<Self>) -> Result<(), Error>;
+
+    /// Rolls back the transaction
+    async fn rollback(self: Box<Self>) -> Result<(), Error>;
+}
+
+/// Number of events buffered for a slow subscriber (older events are dropped)
+pub const EVENTS_CAPACITY: usize = 1024;
+
+/// Shared data store
+pub type Db = Arc<dyn Store>;
+
+/// Initializes the data store selected by the configuration
+pub fn init_store(cfg: &AppConfig) -> Result<Db, Error> {
+    match cfg.store.backend {
+        StoreBackend::Postgres => Ok(Arc::new(
+            PostgresClient::new(cfg.postgres.new_pool())
+                .replica(cfg.postgres.new_replica_pool())
+                .rls(cfg.postgres.rls)
+                .halfvec(cfg.postgres.halfvec)
+                .slow(cfg.trace.slow_threshold())
+                .listen(&cfg.postgres.url),
+        )),
+        StoreBackend::Sqlite => Ok(Arc::new(SqliteClient::open(&cfg.store.path)?)),
+    }
+}
+
+/// Initializes the DB schema, retrying until a deadline
+///
+/// At startup, the DB may not accept connections yet (eg. when the containers are started
+/// together), so failed attempts are retried with an exponential backoff.
+pub async fn init_schema_with_retry(db: &Db, timeout: Duration) -> Result<(), Error> {
+    let deadline = tokio::time::Instant::now() + timeout;
+    let mut backoff = Duration::from_millis(250);
+    loop {
+        match db.init_schema().await {
+            Ok(()) => return Ok(()),
+            Err(err) => {
+                if tokio::time::Instant::now() + backoff > deadline {
+                    return Err(err);
+                }
+                warn!(%err, "failed to initialize the DB schema, retrying in {backoff:?}");
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(Duration::from_secs(10));
+            }
+        }
+    }
+}
@@ -0,0 +1,214 @@
+//! Pagination
+//!
+//! The listings are paginated with a keyset: the items are sorted by a total order, and the
+//! next page starts right after the sort key of the last item. The helpers build the SQL
+//! clauses of both backends.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::Error,
+    mdl::query::{Sort, SortDir},
+};
+
+/// Page of items
+#[derive(Debug, Clone)]
+pub struct Page<T> {
+    /// Items
+    pub items: Vec<T>,
+    /// Cursor of the next page (none if this is the last page)
+    pub next_cursor: Option<Cursor>,
+}
+
+/// Keyset pagination cursor
+///
+/// The cursor holds the sort key of the last item of a page, and the next page starts right
+/// after it. It is encoded as an opaque string for the clients.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cursor(pub Vec<String>);
+
+impl Cursor {
+    /// Encodes the cursor (hex encoded JSON)
+    pub fn encode(&self) -> String {
+        serde_json::to_vec(&self.0)
+            .unwrap_or_default()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect()
+    }
+
+    /// Decodes a cursor
+    pub fn decode(value: &str) -> Result<Self, Error> {
+        let invalid = || Error::InvalidRequest("invalid cursor".to_string(), None);
+        if value.len() % 2 != 0 || !value.is_ascii() {
+            return Err(invalid());
+        }
+        let bytes = (0..value.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&value[i..i + 2], 16))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| invalid())?;
+        let values = serde_json::from_slice::<Vec<String>>(&bytes).map_err(|_| invalid())?;
+        Ok(Cursor(values))
+    }
+}
+
+/// Sort key of a keyset pagination
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Key {
+    /// Field name
+    pub field: String,
+    /// SQL expression (must evaluate to a non null text)
+    pub expr: String,
+    /// Direction
+    pub dir: SortDir,
+}
+
+/// Builds the sort keys of a keyset pagination
+///
+/// Only the fields mapped to an expression are used, so that the keys are safe to interpolate.
+/// The unique tiebreaker (field and expression) is always the last key, so that the order
+/// is total.
+pub fn keyset(sort: &[Sort], exprs: &[(&str, &str)], tiebreaker: (&str, &str)) -> Vec<Key> {
+    let mut keys = sort
+        .iter()
+        .filter_map(|s| {
+            exprs
+                .iter()
+                .find(|(field, _)| *field == s.field)
+                .map(|(field, expr)| Key {
+                    field: field.to_string(),
+                    expr: expr.to_string(),
+                    dir: s.dir,
+                })
+        })
+        .collect::<Vec<_>>();
+    keys.push(Key {
+        field: tiebreaker.0.to_string(),
+        expr: tiebreaker.1.to_string(),
+        dir: SortDir::Asc,
+    });
+    keys
+}
+
+/// Builds the `ORDER BY` clause of a keyset pagination
+pub fn keyset_order_by(keys: &[Key]) -> String {
+    let items = keys
+        .iter()
+        .map(|k| match k.dir {
+            SortDir::Asc => format!("{} ASC", k.expr),
+            SortDir::Desc => format!("{} DESC", k.expr),
+        })
+        .collect::<Vec<_>>();
+    format!("ORDER BY {}", items.join(", "))
+}
+
+/// Builds the condition selecting the rows after a cursor
+///
+/// The cursor values are the parameters starting at index `first`, and `placeholder` formats
+/// a parameter index (eg. `$1` for Postgres, `?1` for SQLite).
+pub fn keyset_condition(keys: &[Key], first: usize, placeholder: fn(usize) -> String) -> String {
+    let alternatives = (0..keys.len())
+        .map(|i| {
+            let mut terms = keys[..i]
+                .iter()
+                .enumerate()
+                .map(|(j, k)| format!("{} = {}", k.expr, placeholder(first + j)))
+                .collect::<Vec<_>>();
+            let op = match keys[i].dir {
+                SortDir::Asc => ">",
+                SortDir::Desc => "<",
+            };
+            terms.push(format!("{} {op} {}", keys[i].expr, placeholder(first + i)));
+            format!("({})", terms.join(" AND "))
+        })
+        .collect::<Vec<_>>();
+    format!("({})", alternatives.join(" OR "))
+}
+
+/// Decodes the cursor of a keyset pagination
+pub fn keyset_cursor(keys: &[Key], cursor: Option<&str>) -> Result<Option<Cursor>, Error> {
+    match cursor {
+        Some(value) => {
+            let cursor = Cursor::decode(value)?;
+            if cursor.0.len() != keys.len() {
+                // NB: the sort criteria changed between the pages
+                return Err(Error::InvalidRequest("invalid cursor".to_string(), None));
+            }
+            Ok(Some(cursor))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Builds a page from the rows of a keyset query
+///
+/// The query must fetch `limit + 1` rows, so that the existence of a next page is known.
+/// The cursor is built from the sort key of the last item.
+pub fn paginate<T>(
+    mut items: Vec<T>,
+    limit: Option<usize>,
+    keys: &[Key],
+    key_value: fn(&T, &str) -> String,
+) -> Page<T> {
+    match limit {
+        Some(limit) if items.len() > limit => {
+            items.truncate(limit);
+            let next_cursor = items
+                .last()
+                .map(|item| Cursor(keys.iter().map(|k| key_value(item, &k.field)).collect()));
+            Page { items, next_cursor }
+        }
+        _ => Page {
+            items,
+            next_cursor: None,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keyset() {
+        let exprs = [("name", "COALESCE(name, '')"), ("url", "url")];
+        let sort = vec![
+            Sort {
+                field: "name".to_string(),
+                dir: SortDir::Desc,
+            },
+            Sort {
+                field: "id; DROP TABLE feeds".to_string(),
+                dir: SortDir::Asc,
+            },
+        ];
+        let keys = keyset(&sort, &exprs, ("id", "id::text"));
+        assert_eq!(
+            keyset_order_by(&keys),
+            "ORDER BY COALESCE(name, '') DESC, id::text ASC"
+        );
+        assert_eq!(
+            keyset_condition(&keys, 2, |i| format!("${i}")),
+            "((COALESCE(name, '') < $2) OR (COALESCE(name, '') = $2 AND id::text > $3))"
+        );
+    }
+
+    #[test]
+    fn test_cursor() {
+        let cursor = Cursor(vec!["news".to_string(), "1234".to_string()]);
+        assert_eq!(Cursor::decode(&cursor.encode()).unwrap(), cursor);
+        assert!(Cursor::decode("zz").is_err());
+        assert!(Cursor::decode("abc").is_err());
+    }
+
+    #[test]
+    fn test_paginate() {
+        let keys = keyset(&[], &[], ("id", "id"));
+        let page = paginate(vec![1, 2, 3], Some(2), &keys, |i, _| i.to_string());
+        assert_eq!(page.items, vec![1, 2]);
+        assert_eq!(page.next_cursor, Some(Cursor(vec!["2".to_string()])));
+        let page = paginate(vec![1, 2], Some(2), &keys, |i, _| i.to_string());
+        assert!(page.next_cursor.is_none());
+    }
+}
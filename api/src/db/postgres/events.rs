@@ -0,0 +1,130 @@
+//! Events
+//!
+//! The summaries inserted by any API replica are notified on the [EVENTS_CHANNEL] channel
+//! (see the `events` migration). Each replica listens to the channel on a dedicated
+//! connection, and forwards the events to its local subscribers.
+
+use std::time::Duration;
+
+use futures::StreamExt;
+use tokio::sync::{broadcast, mpsc};
+use tokio_postgres::{AsyncMessage, NoTls};
+use tracing::{trace, warn};
+
+use crate::{error::Error, mdl::Event};
+
+use super::PostgresClient;
+
+/// Notification channel of the events
+pub const EVENTS_CHANNEL: &str = "newsie_events";
+
+impl PostgresClient {
+    /// Listens to the events notified by the DB
+    ///
+    /// A dedicated connection (outside the pool) is opened with the connection string.
+    /// It is reopened with a backoff if it is lost. NB: the events notified while the
+    /// connection is lost are missed.
+    pub fn listen(self, url: &str) -> Self {
+        let url = url.to_string();
+        let sender = self.events.clone();
+        tokio::spawn(async move {
+            let mut backoff = Duration::from_millis(250);
+            loop {
+                match listen_events(&url, &sender).await {
+                    Ok(()) => {
+                        warn!("event listener disconnected, reconnecting in {backoff:?}");
+                        backoff = Duration::from_millis(250);
+                    }
+                    Err(err) => {
+                        warn!(%err, "event listener failed, retrying in {backoff:?}");
+                    }
+                }
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(Duration::from_secs(10));
+            }
+        });
+        self
+    }
+
+    /// Subscribes to the events
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.events.subscribe()
+    }
+}
+
+/// Forwards the notified events until the connection is closed
+async fn listen_events(url: &str, sender: &broadcast::Sender<Event>) -> Result<(), Error> {
+    let (client, mut connection) = tokio_postgres::connect(url, NoTls).await?;
+
+    // NB: the connection must be polled for the notifications to be received
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let mut messages = futures::stream::poll_fn(move |cx| connection.poll_message(cx));
+        while let Some(message) = messages.next().await {
+            match message {
+                Ok(AsyncMessage::Notification(notification)) => {
+                    if tx.send(notification).is_err() {
+                        break;
+                    }
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    warn!(%err, "event listener connection error");
+                    break;
+                }
+            }
+        }
+    });
+
+    client
+        .batch_execute(&format!("LISTEN {EVENTS_CHANNEL}"))
+        .await?;
+    while let Some(notification) = rx.recv().await {
+        match serde_json::from_str::<Event>(notification.payload()) {
+            Ok(event) => {
+                trace!(?event, "received event");
+                // NB: sending fails only if there are no subscribers
+                let _res = sender.send(event);
+            }
+            Err(err) => {
+                warn!(%err, payload = notification.payload(), "invalid event");
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::config::AppConfig;
+
+    #[tokio::test]
+    async fn test_listen() {
+        let cfg = AppConfig::load();
+        let db = PostgresClient::new(cfg.postgres.new_pool()).listen(&cfg.postgres.url);
+        let mut events = db.subscribe();
+
+        // NB: give the listener time to connect
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        let client = db.client().await.unwrap();
+        let payload = serde_json::to_string(&Event::SummaryCreated {
+            id: uuid::Uuid::new_v4(),
+            url: "https://www.link.com/event".to_string(),
+        })
+        .unwrap();
+        client
+            .execute("SELECT pg_notify($1, $2)", &[&EVENTS_CHANNEL, &payload])
+            .await
+            .unwrap();
+
+        let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(
+            matches!(event, Event::SummaryCreated { url, .. } if url == "https://www.link.com/event")
+        );
+    }
+}
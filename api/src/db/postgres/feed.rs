@@ -1,14 +1,16 @@
 //! Feeds
 
+use deadpool_postgres::GenericClient;
 use tokio_postgres::{types::ToSql, Row};
 use uuid::Uuid;
 
 use crate::{
+    db::page::{keyset, keyset_condition, keyset_cursor, keyset_order_by, paginate, Page},
     error::Error,
-    mdl::{Feed, FeedUpdate},
+    mdl::{query::ListOptions, Feed, FeedUpdate},
 };
 
-use super::PostgresClient;
+use super::{util::is_unique_violation, PostgresClient};
 
 impl From<Row> for Feed {
     fn from(value: Row) -> Self {
@@ -21,39 +23,116 @@ impl From<Row> for Feed {
     }
 }
 
-impl PostgresClient {
-    /// Creates the `feeds` table
-    pub async fn create_table_feeds(&self) -> Result<(), Error> {
-        let client = self.client().await?;
+/// Sort keys of the feeds (NB: compared as bytes, to match the cursor values)
+const FEED_KEYS: &[(&str, &str)] = &[
+    ("name", "COALESCE(name, '') COLLATE \"C\""),
+    ("url", "url COLLATE \"C\""),
+];
 
-        Ok(client
-            .batch_execute(
-                "
-                CREATE TABLE IF NOT EXISTS feeds (
-                    id          UUID PRIMARY KEY,
-                    user_id     UUID NOT NULL,
-                    url         TEXT NOT NULL,
-                    name        TEXT,
-                    FOREIGN KEY (user_id) REFERENCES users(id)
-                )
-            ",
-            )
-            .await?)
+/// Tiebreaker key of the feeds
+const FEED_TIEBREAKER: (&str, &str) = ("id", "id::text COLLATE \"C\"");
+
+/// Returns the value of a feed sort key
+pub(crate) fn feed_key_value(feed: &Feed, field: &str) -> String {
+    match field {
+        "name" => feed.name.clone().unwrap_or_default(),
+        "url" => feed.url.clone(),
+        _ => feed.id.to_string(),
     }
+}
 
+impl PostgresClient {
     /// Reads all user feeds for a user
     pub async fn read_user_feeds(&self, user_id: Uuid) -> Result<Vec<Feed>, Error> {
-        let client = self.client().await?;
+        let client = self.read_client().await?;
+        let stmt = client
+            .prepare_cached("SELECT * FROM feeds WHERE user_id = $1")
+            .await?;
 
         Ok(client
-            .query("SELECT * FROM feeds WHERE user_id = $1", &[&user_id])
+            .query(&stmt, &[&user_id])
             .await?
             .into_iter()
             .map(|row| row.into())
             .collect())
     }
 
+    /// Reads the feeds of all the users
+    pub async fn read_feeds(&self) -> Result<Vec<Feed>, Error> {
+        let client = self.read_client().await?;
+
+        Ok(client
+            .query("SELECT * FROM feeds ORDER BY user_id, id", &[])
+            .await?
+            .into_iter()
+            .map(|row| row.into())
+            .collect())
+    }
+
+    /// Queries the user feeds with listing options
+    ///
+    /// Feeds can be filtered on and sorted by `name` and `url`. If a limit is set, the feeds
+    /// are paginated with a keyset cursor.
+    ///
+    /// NB: all the feeds are read on the primary, since their version is compared with the
+    /// version checked on the primary when they are replaced (see [FeedService::sync_feeds]).
+    ///
+    /// [FeedService::sync_feeds]: crate::svc::feed::FeedService::sync_feeds
+    pub async fn query_user_feeds(
+        &self,
+        user_id: Uuid,
+        opts: &ListOptions,
+    ) -> Result<Page<Feed>, Error> {
+        let client = if opts.is_all() {
+            self.client().await?
+        } else {
+            self.read_client().await?
+        };
+
+        let keys = keyset(&opts.sort, FEED_KEYS, FEED_TIEBREAKER);
+        let cursor = keyset_cursor(&keys, opts.cursor.as_deref())?;
+        let limit = opts.limit.map(|l| l as i64 + 1);
+
+        let mut conditions = vec!["user_id = $1".to_string()];
+        let mut params: Vec<&(dyn ToSql + Sync)> = vec![&user_id];
+        if let Some(name) = opts.filters.get("name") {
+            params.push(name);
+            conditions.push(format!("name = ${}", params.len()));
+        }
+        if let Some(url) = opts.filters.get("url") {
+            params.push(url);
+            conditions.push(format!("url = ${}", params.len()));
+        }
+        if let Some(cursor) = cursor.as_ref() {
+            conditions.push(keyset_condition(&keys, params.len() + 1, |i| {
+                format!("${i}")
+            }));
+            for value in cursor.0.iter() {
+                params.push(value);
+            }
+        }
+        let mut stmt = format!(
+            "SELECT * FROM feeds WHERE {} {}",
+            conditions.join(" AND "),
+            keyset_order_by(&keys)
+        );
+        if let Some(limit) = limit.as_ref() {
+            params.push(limit);
+            stmt.push_str(&format!(" LIMIT ${}", params.len()));
+        }
+
+        let feeds = client
+            .query(&stmt, &params)
+            .await?
+            .into_iter()
+            .map(|row| row.into())
+            .collect();
+        Ok(paginate(feeds, opts.limit, &keys, feed_key_value))
+    }
+
     /// Sync all the user feeds
+    ///
+    /// A user cannot have several feeds with the same URL.
     pub async fn sync_user_feeds(
         &self,
         user_id: Uuid,
@@ -62,38 +141,59 @@ impl PostgresClient {
         let mut client = self.client().await?;
         let trx = client.transaction().await?;
 
-        // // read all user feeds
-        // let curr_feeds = trx
-        //     .query("SELECT * FROM users WHERE user_id=$1", &[&user_id])
-        //     .await?
-        //     .into_iter()
-        //     .map(|row| row.into())
-        //     .collect::<Vec<Feed>>();
-
-        // remove all feeds
-        let _res = trx
-            .execute("DELETE FROM feeds WHERE user_id=$1", &[&user_id])
-            .await?;
+        let new_feeds = sync_feeds(&trx, user_id, feeds).await?;
 
-        // insert all feeds
-        let new_feeds = if !feeds.is_empty() {
-            let mut insert_stmt_values: Vec<String> = vec![];
-            let mut insert_params: Vec<(Uuid, &Uuid, &String, &Option<String>)> = vec![];
-            for (i, f) in feeds.iter().enumerate() {
-                let id = match f.id {
-                    Some(id) => id,
-                    None => Uuid::new_v4(),
-                };
-                insert_stmt_values.push(format!(
-                    "(${}, ${}, ${}, ${})",
-                    i * 4 + 1,
-                    i * 4 + 2,
-                    i * 4 + 3,
-                    i * 4 + 4
-                ));
-                insert_params.push((id, &user_id, &f.url, &f.name));
-            }
-            trx.query(
+        // commit the transaction
+        trx.commit().await?;
+        Ok(new_feeds)
+    }
+
+    /// Delete all user feeds
+    pub async fn delete_user_feeds(&self, user_id: Uuid) -> Result<(), Error> {
+        let client = self.client().await?;
+        delete_feeds(&*client, user_id).await
+    }
+}
+
+/// Sync all the user feeds with a client (or a transaction)
+pub(super) async fn sync_feeds(
+    client: &impl GenericClient,
+    user_id: Uuid,
+    feeds: Vec<FeedUpdate>,
+) -> Result<Vec<Feed>, Error> {
+    // // read all user feeds
+    // let curr_feeds = client
+    //     .query("SELECT * FROM users WHERE user_id=$1", &[&user_id])
+    //     .await?
+    //     .into_iter()
+    //     .map(|row| row.into())
+    //     .collect::<Vec<Feed>>();
+
+    // remove all feeds
+    let _res = client
+        .execute("DELETE FROM feeds WHERE user_id=$1", &[&user_id])
+        .await?;
+
+    // insert all feeds
+    if !feeds.is_empty() {
+        let mut insert_stmt_values: Vec<String> = vec![];
+        let mut insert_params: Vec<(Uuid, &Uuid, &String, &Option<String>)> = vec![];
+        for (i, f) in feeds.iter().enumerate() {
+            let id = match f.id {
+                Some(id) => id,
+                None => Uuid::new_v4(),
+            };
+            insert_stmt_values.push(format!(
+                "(${}, ${}, ${}, ${})",
+                i * 4 + 1,
+                i * 4 + 2,
+                i * 4 + 3,
+                i * 4 + 4
+            ));
+            insert_params.push((id, &user_id, &f.url, &f.name));
+        }
+        Ok(client
+            .query(
                 &format!(
                     "INSERT into feeds (id, user_id, url, name) VALUES {} RETURNING *",
                     insert_stmt_values.join(", ")
@@ -106,44 +206,56 @@ impl PostgresClient {
                     })
                     .collect::<Vec<_>>(),
             )
-            .await?
+            .await
+            .map_err(|err| {
+                if is_unique_violation(&err) {
+                    Error::InvalidRequest("duplicate feed URLs".to_string(), None)
+                } else {
+                    err.into()
+                }
+            })?
             .into_iter()
             .map(|row| row.into())
-            .collect::<Vec<Feed>>()
-        } else {
-            vec![]
-        };
-
-        // commit the transaction
-        trx.commit().await?;
-        Ok(new_feeds)
+            .collect::<Vec<Feed>>())
+    } else {
+        Ok(vec![])
     }
+}
 
-    /// Delete all user feeds
-    pub async fn delete_user_feeds(&self, user_id: Uuid) -> Result<(), Error> {
-        let client = self.client().await?;
-        let _res = client
-            .execute("DELETE FROM feeds WHERE user_id=$1", &[&user_id])
-            .await?;
+/// Reads all the user feeds in a transaction, locking them until the end of the transaction
+///
+/// NB: the user row is locked, so that concurrent syncs of the same user are serialized
+/// (even if the user has no feeds yet).
+pub(super) async fn lock_feeds(
+    client: &impl GenericClient,
+    user_id: Uuid,
+) -> Result<Vec<Feed>, Error> {
+    let _res = client
+        .query_opt("SELECT id FROM users WHERE id=$1 FOR UPDATE", &[&user_id])
+        .await?;
+    Ok(client
+        .query("SELECT * FROM feeds WHERE user_id=$1", &[&user_id])
+        .await?
+        .into_iter()
+        .map(|row| row.into())
+        .collect())
+}
 
-        Ok(())
-    }
+/// Delete all the user feeds with a client (or a transaction)
+pub(super) async fn delete_feeds(client: &impl GenericClient, user_id: Uuid) -> Result<(), Error> {
+    let _res = client
+        .execute("DELETE FROM feeds WHERE user_id=$1", &[&user_id])
+        .await?;
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    use crate::config::AppConfig;
     use crate::db::postgres::user::tests::{setup_test_user, teardown_test_user};
     use crate::mdl::User;
 
-    /// Initializes the user store
-    fn init_db() -> PostgresClient {
-        let cfg = AppConfig::load();
-        PostgresClient::new(cfg.postgres.new_pool())
-    }
-
     /// Setup a test
     pub async fn setup() -> (PostgresClient, User, Vec<Feed>) {
         let (db, user) = setup_test_user().await;
@@ -175,12 +287,6 @@ mod tests {
         teardown_test_user(db, user).await;
     }
 
-    #[tokio::test]
-    async fn test_create_table() {
-        let db = init_db();
-        db.create_table_feeds().await.unwrap();
-    }
-
     #[tokio::test]
     async fn test_read_feeds() {
         let (db, test_user, _test_feeds) = setup().await;
@@ -188,4 +294,12 @@ mod tests {
         assert_eq!(feeds.len(), 2);
         teardown(db, test_user).await;
     }
+
+    #[tokio::test]
+    async fn test_delete_user_cascade() {
+        let (db, test_user, _test_feeds) = setup().await;
+        db.delete_user(test_user.id).await.unwrap();
+        let feeds = db.read_user_feeds(test_user.id).await.unwrap();
+        assert!(feeds.is_empty());
+    }
 }
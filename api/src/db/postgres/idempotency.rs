@@ -0,0 +1,232 @@
+//! Idempotency keys
+
+use time::OffsetDateTime;
+use tokio_postgres::Row;
+
+use crate::{error::Error, mdl::IdempotentResponse};
+
+use super::PostgresClient;
+
+impl From<Row> for IdempotentResponse {
+    fn from(value: Row) -> Self {
+        IdempotentResponse {
+            key: value.get::<_, String>("key"),
+            scope: value.get::<_, String>("scope"),
+            status: value.get::<_, i16>("status") as u16,
+            content_type: value.get::<_, Option<String>>("content_type"),
+            body: value.get::<_, Vec<u8>>("body"),
+            created_at: value.get::<_, OffsetDateTime>("created_at"),
+            request_hash: value.get::<_, String>("request_hash"),
+            completed: value.get::<_, bool>("completed"),
+        }
+    }
+}
+
+impl PostgresClient {
+    /// Reads a stored response created after a given time
+    pub async fn read_idempotent_response(
+        &self,
+        key: &str,
+        scope: &str,
+        since: OffsetDateTime,
+    ) -> Result<Option<IdempotentResponse>, Error> {
+        let client = self.client().await?;
+
+        Ok(client
+            .query_opt(
+                "SELECT * FROM idempotency_keys WHERE key = $1 AND scope = $2 AND created_at > $3",
+                &[&key, &scope, &since],
+            )
+            .await?
+            .map(|row| row.into()))
+    }
+
+    /// Stores a response
+    ///
+    /// If a response is already stored for the same key, it is replaced.
+    pub async fn upsert_idempotent_response(
+        &self,
+        response: &IdempotentResponse,
+    ) -> Result<(), Error> {
+        let client = self.client().await?;
+
+        let _res = client
+            .execute(
+                "
+                INSERT INTO idempotency_keys
+                    (key, scope, status, body, created_at, request_hash, completed, content_type)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                ON CONFLICT (key, scope) DO UPDATE
+                SET status = $3, body = $4, created_at = $5, request_hash = $6, completed = $7,
+                    content_type = $8
+                ",
+                &[
+                    &response.key,
+                    &response.scope,
+                    &(response.status as i16),
+                    &response.body,
+                    &response.created_at,
+                    &response.request_hash,
+                    &response.completed,
+                    &response.content_type,
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Reserves a key while its request is running
+    ///
+    /// Returns `false` if the key is already used (see [crate::db::Store::reserve_idempotency_key]).
+    pub async fn reserve_idempotency_key(
+        &self,
+        pending: &IdempotentResponse,
+        expired_before: OffsetDateTime,
+        stale_before: OffsetDateTime,
+    ) -> Result<bool, Error> {
+        let client = self.client().await?;
+
+        // NB: the conflicting row is only replaced if it has expired, so that a concurrent
+        // request does not reserve the same key
+        Ok(client
+            .query_opt(
+                "
+                INSERT INTO idempotency_keys
+                    (key, scope, status, body, created_at, request_hash, completed, content_type)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                ON CONFLICT (key, scope) DO UPDATE
+                SET status = $3, body = $4, created_at = $5, request_hash = $6, completed = $7,
+                    content_type = $8
+                WHERE idempotency_keys.created_at <= $9
+                    OR (NOT idempotency_keys.completed AND idempotency_keys.created_at <= $10)
+                RETURNING key
+                ",
+                &[
+                    &pending.key,
+                    &pending.scope,
+                    &(pending.status as i16),
+                    &pending.body,
+                    &pending.created_at,
+                    &pending.request_hash,
+                    &pending.completed,
+                    &pending.content_type,
+                    &expired_before,
+                    &stale_before,
+                ],
+            )
+            .await?
+            .is_some())
+    }
+
+    /// Deletes the response (or the reservation) of a key
+    pub async fn delete_idempotent_response(&self, key: &str, scope: &str) -> Result<(), Error> {
+        let client = self.client().await?;
+
+        let _res = client
+            .execute(
+                "DELETE FROM idempotency_keys WHERE key = $1 AND scope = $2",
+                &[&key, &scope],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Deletes the responses stored before a given time
+    pub async fn delete_idempotent_responses_before(
+        &self,
+        before: OffsetDateTime,
+    ) -> Result<u64, Error> {
+        let client = self.client().await?;
+
+        Ok(client
+            .execute(
+                "DELETE FROM idempotency_keys WHERE created_at <= $1",
+                &[&before],
+            )
+            .await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    use crate::config::AppConfig;
+
+    /// Initializes the client
+    fn init_db() -> PostgresClient {
+        let cfg = AppConfig::load();
+        PostgresClient::new(cfg.postgres.new_pool())
+    }
+
+    #[tokio::test]
+    async fn test_upsert_read() {
+        let db = init_db();
+        let now = OffsetDateTime::now_utc();
+        let response = IdempotentResponse {
+            key: Uuid::new_v4().to_string(),
+            scope: "POST /summaries".to_string(),
+            status: 200,
+            content_type: None,
+            body: b"{}".to_vec(),
+            created_at: now,
+            request_hash: String::new(),
+            completed: true,
+        };
+        db.upsert_idempotent_response(&response).await.unwrap();
+
+        let found = db
+            .read_idempotent_response(
+                &response.key,
+                &response.scope,
+                now - time::Duration::minutes(1),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.status, 200);
+        assert_eq!(found.body, response.body);
+
+        db.delete_idempotent_responses_before(now).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reserve() {
+        let db = init_db();
+        let now = OffsetDateTime::now_utc();
+        let pending = IdempotentResponse {
+            key: Uuid::new_v4().to_string(),
+            scope: "POST /summaries".to_string(),
+            status: 0,
+            content_type: None,
+            body: vec![],
+            created_at: now,
+            request_hash: "hash".to_string(),
+            completed: false,
+        };
+        let (expired_before, stale_before) = (
+            now - time::Duration::days(1),
+            now - time::Duration::minutes(5),
+        );
+        assert!(db
+            .reserve_idempotency_key(&pending, expired_before, stale_before)
+            .await
+            .unwrap());
+        assert!(!db
+            .reserve_idempotency_key(&pending, expired_before, stale_before)
+            .await
+            .unwrap());
+
+        // NB: a stale reservation is replaced
+        assert!(db
+            .reserve_idempotency_key(&pending, expired_before, now)
+            .await
+            .unwrap());
+
+        db.delete_idempotent_response(&pending.key, &pending.scope)
+            .await
+            .unwrap();
+    }
+}
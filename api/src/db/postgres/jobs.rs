@@ -0,0 +1,325 @@
+//! Background jobs
+
+use time::OffsetDateTime;
+use tokio_postgres::Row;
+use uuid::Uuid;
+
+use crate::{
+    error::Error,
+    mdl::{Job, JobCount, JobStatus},
+};
+
+use super::PostgresClient;
+
+impl From<Row> for Job {
+    fn from(value: Row) -> Self {
+        Job {
+            id: value.get::<_, Uuid>("id"),
+            kind: value.get::<_, String>("kind"),
+            payload: value.get::<_, serde_json::Value>("payload"),
+            // NB: an unknown status is not run again
+            status: value
+                .get::<_, String>("status")
+                .parse()
+                .unwrap_or(JobStatus::Dead),
+            owner_id: value.get::<_, Option<Uuid>>("owner_id"),
+            lease_id: value.get::<_, Option<Uuid>>("lease_id"),
+            attempts: value.get::<_, i32>("attempts"),
+            max_attempts: value.get::<_, i32>("max_attempts"),
+            last_error: value.get::<_, Option<String>>("last_error"),
+            run_at: value.get::<_, OffsetDateTime>("run_at"),
+            created_at: value.get::<_, OffsetDateTime>("created_at"),
+        }
+    }
+}
+
+/// Checks that a job update has matched the lease of the worker
+fn check_lease(count: u64) -> Result<(), Error> {
+    if count == 0 {
+        return Err(Error::Conflict(
+            "the job lease is not held anymore".to_string(),
+            None,
+        ));
+    }
+    Ok(())
+}
+
+impl PostgresClient {
+    /// Enqueues a job
+    pub async fn enqueue_job(&self, job: &Job) -> Result<(), Error> {
+        let client = self.client().await?;
+
+        let _res = client
+            .execute(
+                "
+                INSERT INTO jobs (id, kind, payload, status, owner_id, attempts, max_attempts, run_at, created_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                ",
+                &[
+                    &job.id,
+                    &job.kind,
+                    &job.payload,
+                    &job.status.as_str(),
+                    &job.owner_id,
+                    &job.attempts,
+                    &job.max_attempts,
+                    &job.run_at,
+                    &job.created_at,
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Claims the jobs which are due
+    ///
+    /// The claimed jobs are marked as running until `locked_until` with a new lease, and their
+    /// number of attempts is incremented. The jobs claimed by other workers are skipped.
+    pub async fn claim_jobs(
+        &self,
+        kinds: &[&str],
+        limit: usize,
+        locked_until: OffsetDateTime,
+    ) -> Result<Vec<Job>, Error> {
+        let client = self.client().await?;
+        let stmt = client
+            .prepare_cached(
+                "
+                UPDATE jobs
+                SET status = 'running', attempts = attempts + 1, locked_until = $3, lease_id = $4
+                WHERE id IN (
+                    SELECT id FROM jobs
+                    WHERE kind = ANY($1)
+                    AND (
+                        (status = 'pending' AND run_at <= now())
+                        OR (status = 'running' AND locked_until < now())
+                    )
+                    ORDER BY run_at
+                    LIMIT $2
+                    FOR UPDATE SKIP LOCKED
+                )
+                RETURNING *
+                ",
+            )
+            .await?;
+
+        Ok(client
+            .query(
+                &stmt,
+                &[&kinds, &(limit as i64), &locked_until, &Uuid::new_v4()],
+            )
+            .await?
+            .into_iter()
+            .map(|row| row.into())
+            .collect())
+    }
+
+    /// Reads a job
+    ///
+    /// NB: the primary is used, since a job is usually polled right after it is enqueued
+    pub async fn read_job(&self, id: Uuid) -> Result<Option<Job>, Error> {
+        let client = self.client().await?;
+
+        Ok(client
+            .query_opt("SELECT * FROM jobs WHERE id = $1", &[&id])
+            .await?
+            .map(|row| row.into()))
+    }
+
+    /// Extends the lease of a running job
+    pub async fn renew_job(
+        &self,
+        id: Uuid,
+        lease_id: Uuid,
+        locked_until: OffsetDateTime,
+    ) -> Result<(), Error> {
+        let client = self.client().await?;
+
+        let count = client
+            .execute(
+                "
+                UPDATE jobs SET locked_until = $3
+                WHERE id = $1 AND lease_id = $2 AND status = 'running'
+                ",
+                &[&id, &lease_id, &locked_until],
+            )
+            .await?;
+        check_lease(count)
+    }
+
+    /// Marks a job as completed
+    pub async fn complete_job(&self, id: Uuid, lease_id: Uuid) -> Result<(), Error> {
+        let client = self.client().await?;
+
+        let count = client
+            .execute(
+                "
+                UPDATE jobs SET status = 'done', locked_until = NULL, lease_id = NULL
+                WHERE id = $1 AND lease_id = $2 AND status = 'running'
+                ",
+                &[&id, &lease_id],
+            )
+            .await?;
+        check_lease(count)
+    }
+
+    /// Records the failure of a job
+    ///
+    /// The job is retried at `retry_at`, or dead-lettered if `None`.
+    pub async fn fail_job(
+        &self,
+        id: Uuid,
+        lease_id: Uuid,
+        error: &str,
+        retry_at: Option<OffsetDateTime>,
+    ) -> Result<(), Error> {
+        let client = self.client().await?;
+        let status = match retry_at {
+            Some(_) => JobStatus::Pending,
+            None => JobStatus::Dead,
+        };
+
+        let count = client
+            .execute(
+                "
+                UPDATE jobs
+                SET status = $3, last_error = $4, run_at = COALESCE($5, run_at),
+                    locked_until = NULL, lease_id = NULL
+                WHERE id = $1 AND lease_id = $2 AND status = 'running'
+                ",
+                &[&id, &lease_id, &status.as_str(), &error, &retry_at],
+            )
+            .await?;
+        check_lease(count)
+    }
+
+    /// Deletes the completed jobs which have been created before a date
+    ///
+    /// Returns the number of deleted jobs.
+    pub async fn delete_jobs_done_before(&self, before: OffsetDateTime) -> Result<u64, Error> {
+        let client = self.client().await?;
+
+        Ok(client
+            .execute(
+                "DELETE FROM jobs WHERE status = 'done' AND created_at < $1",
+                &[&before],
+            )
+            .await?)
+    }
+
+    /// Counts the jobs by status
+    pub async fn count_jobs(&self) -> Result<Vec<JobCount>, Error> {
+        let client = self.read_client().await?;
+
+        Ok(client
+            .query(
+                "SELECT status, COUNT(*) AS count FROM jobs GROUP BY status ORDER BY status",
+                &[],
+            )
+            .await?
+            .into_iter()
+            .map(|row| JobCount {
+                status: row
+                    .get::<_, String>("status")
+                    .parse()
+                    .unwrap_or(JobStatus::Dead),
+                count: row.get::<_, i64>("count") as u64,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::AppConfig;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_jobs() {
+        let cfg = AppConfig::load();
+        let client = PostgresClient::new(cfg.postgres.new_pool());
+        client.init_schema().await.unwrap();
+
+        // NB: a unique kind, so that the jobs of other tests are not claimed
+        let kind = format!("test-{}", Uuid::new_v4());
+        let owner_id = Uuid::new_v4();
+        let job = Job::new(&kind, serde_json::json!({ "n": 1 }), 2).owner(owner_id);
+        client.enqueue_job(&job).await.unwrap();
+
+        let locked_until = OffsetDateTime::now_utc() + time::Duration::minutes(1);
+        let claimed = client
+            .claim_jobs(&[kind.as_str()], 10, locked_until)
+            .await
+            .unwrap();
+        assert_eq!(claimed.len(), 1);
+        assert_eq!(claimed[0].id, job.id);
+        assert_eq!(claimed[0].status, JobStatus::Running);
+        assert_eq!(claimed[0].attempts, 1);
+        assert_eq!(claimed[0].payload, job.payload);
+        assert_eq!(claimed[0].owner_id, Some(owner_id));
+        let lease_id = claimed[0].lease_id.unwrap();
+        client
+            .renew_job(job.id, lease_id, locked_until)
+            .await
+            .unwrap();
+
+        // NB: a running job is not claimed again
+        let claimed = client
+            .claim_jobs(&[kind.as_str()], 10, locked_until)
+            .await
+            .unwrap();
+        assert!(claimed.is_empty());
+
+        // retry now
+        client
+            .fail_job(job.id, lease_id, "failed", Some(OffsetDateTime::now_utc()))
+            .await
+            .unwrap();
+        let claimed = client
+            .claim_jobs(&[kind.as_str()], 10, locked_until)
+            .await
+            .unwrap();
+        assert_eq!(claimed[0].attempts, 2);
+        assert_eq!(claimed[0].last_error.as_deref(), Some("failed"));
+
+        // NB: the lease of the previous attempt is not held anymore
+        assert!(matches!(
+            client.complete_job(job.id, lease_id).await,
+            Err(Error::Conflict(_, _))
+        ));
+
+        // dead-letter
+        let lease_id = claimed[0].lease_id.unwrap();
+        client
+            .fail_job(job.id, lease_id, "failed", None)
+            .await
+            .unwrap();
+        let claimed = client
+            .claim_jobs(&[kind.as_str()], 10, locked_until)
+            .await
+            .unwrap();
+        assert!(claimed.is_empty());
+        let dead = client.read_job(job.id).await.unwrap().unwrap();
+        assert_eq!(dead.status, JobStatus::Dead);
+
+        // complete
+        let job = Job::new(&kind, serde_json::json!({ "n": 2 }), 2);
+        client.enqueue_job(&job).await.unwrap();
+        let claimed = client
+            .claim_jobs(&[kind.as_str()], 10, locked_until)
+            .await
+            .unwrap();
+        let lease_id = claimed[0].lease_id.unwrap();
+        client.complete_job(job.id, lease_id).await.unwrap();
+        let done = client.read_job(job.id).await.unwrap().unwrap();
+        assert_eq!(done.status, JobStatus::Done);
+
+        let count = client
+            .delete_jobs_done_before(OffsetDateTime::now_utc() + time::Duration::minutes(1))
+            .await
+            .unwrap();
+        assert!(count >= 1);
+        assert!(client.read_job(job.id).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,91 @@
+//! Maintenance
+
+use uuid::Uuid;
+
+use crate::{
+    error::Error,
+    mdl::{Vector, VectorIndexReport},
+};
+
+use super::{summary::VECTOR_INDEX, PostgresClient};
+
+impl PostgresClient {
+    /// Measures the recall of the vector index on a random sample of summaries
+    ///
+    /// The exact neighbors are searched with the index scans disabled.
+    pub async fn check_vector_index(
+        &self,
+        sample: usize,
+        k: usize,
+    ) -> Result<VectorIndexReport, Error> {
+        let mut client = self.client().await?;
+        let samples = client
+            .query(
+                "SELECT embeddings FROM summaries ORDER BY random() LIMIT $1",
+                &[&(sample as i64)],
+            )
+            .await?
+            .into_iter()
+            .map(|row| row.get::<_, Vector>("embeddings"))
+            .collect::<Vec<_>>();
+
+        let search = "SELECT id FROM summaries ORDER BY embeddings <=> $1 LIMIT $2";
+        let mut recalls = vec![];
+        for embeddings in samples {
+            let found = client
+                .query(search, &[&embeddings, &(k as i64)])
+                .await?
+                .into_iter()
+                .map(|row| row.get::<_, Uuid>("id"))
+                .collect::<Vec<_>>();
+
+            // NB: the settings are reset when the transaction ends
+            let trx = client.transaction().await?;
+            trx.batch_execute(
+                "SET LOCAL enable_indexscan = off; SET LOCAL enable_bitmapscan = off",
+            )
+            .await?;
+            let exact = trx
+                .query(search, &[&embeddings, &(k as i64)])
+                .await?
+                .into_iter()
+                .map(|row| row.get::<_, Uuid>("id"))
+                .collect::<Vec<_>>();
+            trx.rollback().await?;
+
+            recalls.push(VectorIndexReport::recall(&found, &exact));
+        }
+
+        Ok(VectorIndexReport::new(true, k, &recalls))
+    }
+
+    /// Rebuilds the vector index
+    ///
+    /// NB: the index is rebuilt concurrently, so that the summaries can still be written.
+    pub async fn reindex_vectors(&self) -> Result<(), Error> {
+        let client = self.client().await?;
+        client
+            .batch_execute(&format!("REINDEX INDEX CONCURRENTLY {VECTOR_INDEX}"))
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::AppConfig;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_check_vector_index() {
+        let cfg = AppConfig::load();
+        let client = PostgresClient::new(cfg.postgres.new_pool());
+        client.init_schema().await.unwrap();
+
+        let report = client.check_vector_index(5, 10).await.unwrap();
+        assert!(report.indexed);
+        assert!(report.sample <= 5);
+        assert!((0.0..=1.0).contains(&report.recall));
+    }
+}
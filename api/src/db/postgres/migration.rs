@@ -0,0 +1,240 @@
+//! Schema migrations
+//!
+//! Migrations are SQL scripts embedded from the `migrations` folder. They are applied in order
+//! of version, each one in its own transaction, and recorded in the `schema_migrations` table.
+
+use tracing::info;
+
+use crate::error::Error;
+
+use super::PostgresClient;
+
+/// Schema migration
+#[derive(Debug, Clone, Copy)]
+pub struct Migration {
+    /// Version
+    pub version: i32,
+    /// Name
+    pub name: &'static str,
+    /// SQL script
+    pub sql: &'static str,
+}
+
+/// Schema migrations, by ascending version
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "baseline",
+        sql: include_str!("../../../migrations/0001_baseline.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "indexes",
+        sql: include_str!("../../../migrations/0002_indexes.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "cascade",
+        sql: include_str!("../../../migrations/0003_cascade.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "retention",
+        sql: include_str!("../../../migrations/0004_retention.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "events",
+        sql: include_str!("../../../migrations/0005_events.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "rls",
+        sql: include_str!("../../../migrations/0006_rls.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "jobs",
+        sql: include_str!("../../../migrations/0007_jobs.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "vector_index",
+        sql: include_str!("../../../migrations/0008_vector_index.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "idempotency_lock",
+        sql: include_str!("../../../migrations/0009_idempotency_lock.sql"),
+    },
+    Migration {
+        version: 10,
+        name: "idempotency_content_type",
+        sql: include_str!("../../../migrations/0010_idempotency_content_type.sql"),
+    },
+    Migration {
+        version: 11,
+        name: "jobs_lease",
+        sql: include_str!("../../../migrations/0011_jobs_lease.sql"),
+    },
+];
+
+/// Advisory lock held while migrating, so that replicas do not migrate concurrently
+const MIGRATIONS_LOCK_ID: i64 = 0x6e65_7773_6965;
+
+/// Returns the latest schema version
+pub fn latest_version() -> i32 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or_default()
+}
+
+impl PostgresClient {
+    /// Applies the pending migrations
+    ///
+    /// Returns the versions which have been applied.
+    pub async fn migrate(&self) -> Result<Vec<i32>, Error> {
+        let mut client = self.client().await?;
+        client
+            .batch_execute(
+                "
+                CREATE TABLE IF NOT EXISTS schema_migrations (
+                    version     INTEGER PRIMARY KEY,
+                    name        TEXT NOT NULL,
+                    applied_at  TIMESTAMPTZ NOT NULL DEFAULT now()
+                )
+            ",
+            )
+            .await?;
+
+        let mut applied = vec![];
+        for migration in MIGRATIONS {
+            let trx = client.transaction().await?;
+            trx.execute("SELECT pg_advisory_xact_lock($1)", &[&MIGRATIONS_LOCK_ID])
+                .await?;
+
+            let is_applied = trx
+                .query_opt(
+                    "SELECT version FROM schema_migrations WHERE version = $1",
+                    &[&migration.version],
+                )
+                .await?
+                .is_some();
+            if is_applied {
+                trx.commit().await?;
+                continue;
+            }
+
+            trx.batch_execute(migration.sql).await.map_err(|err| {
+                Error::Internal(
+                    format!(
+                        "migration {} ({}) failed",
+                        migration.version, migration.name
+                    ),
+                    Some(err.to_string()),
+                )
+            })?;
+            trx.execute(
+                "INSERT INTO schema_migrations (version, name) VALUES ($1, $2)",
+                &[&migration.version, &migration.name],
+            )
+            .await?;
+            trx.commit().await?;
+
+            info!(
+                version = migration.version,
+                name = migration.name,
+                "applied migration"
+            );
+            applied.push(migration.version);
+        }
+
+        Ok(applied)
+    }
+
+    /// Returns the current schema version
+    ///
+    /// None is returned if no migration has been applied.
+    pub async fn schema_version(&self) -> Result<Option<i32>, Error> {
+        let client = self.client().await?;
+        let is_init = client
+            .query_one("SELECT to_regclass('schema_migrations') IS NOT NULL", &[])
+            .await?
+            .get::<_, bool>(0);
+        if !is_init {
+            return Ok(None);
+        }
+
+        Ok(client
+            .query_one("SELECT MAX(version) FROM schema_migrations", &[])
+            .await?
+            .get::<_, Option<i32>>(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::config::AppConfig;
+
+    /// Initializes the client
+    fn init_db() -> PostgresClient {
+        let cfg = AppConfig::load();
+        PostgresClient::new(cfg.postgres.new_pool())
+    }
+
+    #[test]
+    fn test_migrations_order() {
+        let versions = MIGRATIONS.iter().map(|m| m.version).collect::<Vec<_>>();
+        let mut sorted = versions.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(versions, sorted);
+    }
+
+    #[tokio::test]
+    async fn test_migrate() {
+        let db = init_db();
+        db.migrate().await.unwrap();
+        // NB: migrating again is a no-op
+        let applied = db.migrate().await.unwrap();
+        assert!(applied.is_empty());
+        assert_eq!(db.schema_version().await.unwrap(), Some(latest_version()));
+    }
+
+    #[tokio::test]
+    async fn test_migrate_duplicated_emails() {
+        // NB: the tables are created in their own schema, with the baseline only
+        let cfg = AppConfig::load();
+        let admin = cfg.postgres.new_pool().get().await.unwrap();
+        admin
+            .batch_execute("DROP SCHEMA IF EXISTS migrate_dup CASCADE; CREATE SCHEMA migrate_dup")
+            .await
+            .unwrap();
+        let mut pg = cfg.postgres.clone();
+        let sep = if pg.url.contains('?') { '&' } else { '?' };
+        pg.url = format!(
+            "{}{sep}options=-c%20search_path%3Dmigrate_dup%2Cpublic",
+            pg.url
+        );
+        let db = PostgresClient::new(pg.new_pool());
+        let client = db.client().await.unwrap();
+        client.batch_execute(MIGRATIONS[0].sql).await.unwrap();
+        client
+            .batch_execute(
+                "INSERT INTO users (id, name, email, password, subscription) VALUES
+                ('00000000-0000-0000-0000-000000000001', 'a', 'dup@newsie.rocks', '', 'FREE'),
+                ('00000000-0000-0000-0000-000000000002', 'b', 'dup@newsie.rocks', '', 'FREE')",
+            )
+            .await
+            .unwrap();
+        drop(client);
+
+        let err = db.migrate().await.unwrap_err();
+        let Error::Internal(_, Some(detail)) = err else {
+            panic!("unexpected error: {err:?}");
+        };
+        assert!(detail.contains("dup@newsie.rocks"));
+        assert!(detail.contains("00000000-0000-0000-0000-000000000002"));
+        assert_eq!(db.schema_version().await.unwrap(), Some(1));
+    }
+}
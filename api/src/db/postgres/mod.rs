@@ -1,9 +1,40 @@
 //! Postgres DB
 
-use crate::error::Error;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
+use salvo::async_trait;
+use time::OffsetDateTime;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::{
+    error::Error,
+    mdl::{
+        query::ListOptions, DailyCount, Event, Feed, FeedCount, FeedUpdate, IdempotentResponse,
+        Job, JobCount, NewUser, SubscriptionUpdate, Summary, User, UserUpdate, Vector,
+        VectorIndexReport,
+    },
+};
+
+use super::{
+    metrics::{DbMetrics, DbMetricsReport, PoolStats},
+    page::Page,
+    tenant::{tenant_setting, with_system},
+    Store, StoreTx, EVENTS_CAPACITY,
+};
+
+pub mod events;
 pub mod feed;
+pub mod idempotency;
+pub mod jobs;
+pub mod maintenance;
+pub mod migration;
+pub mod stats;
 pub mod summary;
+pub mod tx;
 pub mod user;
 pub mod util;
 
@@ -12,6 +43,16 @@ pub mod util;
 pub struct PostgresClient {
     /// Postgres pool
     pool: deadpool_postgres::Pool,
+    /// Postgres pool of the read-only replica
+    replica_pool: Option<deadpool_postgres::Pool>,
+    /// Events (see [events])
+    events: broadcast::Sender<Event>,
+    /// Row-level security (see [Self::rls])
+    rls: bool,
+    /// Half-precision embeddings (see [Self::halfvec])
+    halfvec: bool,
+    /// Query and pool metrics
+    metrics: Arc<DbMetrics>,
 }
 
 impl PostgresClient {
@@ -19,45 +60,561 @@ impl PostgresClient {
     pub fn new(postgres_pool: deadpool_postgres::Pool) -> Self {
         Self {
             pool: postgres_pool,
+            replica_pool: None,
+            events: broadcast::channel(EVENTS_CAPACITY).0,
+            rls: false,
+            halfvec: false,
+            metrics: Arc::new(DbMetrics::default()),
         }
     }
 
+    /// Sets the pool of the read-only replica
+    pub fn replica(mut self, replica_pool: Option<deadpool_postgres::Pool>) -> Self {
+        self.replica_pool = replica_pool;
+        self
+    }
+
+    /// Enables the row-level security
+    ///
+    /// The `app.user_id` setting of the connections is set to the current tenant (see
+    /// [crate::db::tenant]) when they are acquired, and the policies of the `rls` migration
+    /// are enforced once the schema is initialized.
+    ///
+    /// NB: the row-level security is not disabled on the tables without this option, this
+    /// must be done manually (`ALTER TABLE ... DISABLE ROW LEVEL SECURITY`).
+    pub fn rls(mut self, rls: bool) -> Self {
+        self.rls = rls;
+        self
+    }
+
+    /// Stores the embeddings with half-precision floats (`halfvec`, requires pgvector 0.7)
+    ///
+    /// The summaries table is roughly half the size. The embeddings column is converted
+    /// once the schema is initialized, in either direction.
+    pub fn halfvec(mut self, halfvec: bool) -> Self {
+        self.halfvec = halfvec;
+        self
+    }
+
+    /// Sets the threshold above which the operations are logged as slow
+    ///
+    /// NB: the metrics recorded so far are reset.
+    pub fn slow(mut self, slow: Option<Duration>) -> Self {
+        self.metrics = Arc::new(DbMetrics::new(slow));
+        self
+    }
+
     /// Returns a postgres client instance
     async fn client(&self) -> Result<deadpool_postgres::Object, Error> {
-        Ok(self.pool.get().await?)
+        let client = self.acquire(&self.pool).await?;
+        self.set_tenant(&client).await?;
+        Ok(client)
+    }
+
+    /// Acquires a connection from a pool, recording the wait time
+    async fn acquire(
+        &self,
+        pool: &deadpool_postgres::Pool,
+    ) -> Result<deadpool_postgres::Object, Error> {
+        let start = Instant::now();
+        let res = pool.get().await;
+        self.metrics.record_acquire(start.elapsed(), res.is_ok());
+        Ok(res?)
+    }
+
+    /// Sets the current tenant on a connection, if the row-level security is enabled
+    ///
+    /// NB: the setting is kept by the pooled connection, so it is set on every acquisition.
+    async fn set_tenant(&self, client: &deadpool_postgres::Object) -> Result<(), Error> {
+        if self.rls {
+            let stmt = client
+                .prepare_cached("SELECT set_config('app.user_id', $1, false)")
+                .await?;
+            client.execute(&stmt, &[&tenant_setting()]).await?;
+        }
+        Ok(())
+    }
+
+    /// Returns a postgres client instance for read-only queries
+    ///
+    /// The replica is used if configured, otherwise the primary. NB: the replica may lag
+    /// behind the primary, so reads which must see a previous write should use [Self::client].
+    async fn read_client(&self) -> Result<deadpool_postgres::Object, Error> {
+        match &self.replica_pool {
+            Some(pool) => {
+                let client = self.acquire(pool).await?;
+                self.set_tenant(&client).await?;
+                Ok(client)
+            }
+            None => self.client().await,
+        }
     }
 
     /// Initializes the DB schema
+    ///
+    /// The pending migrations are applied (see [migration]).
     pub async fn init_schema(&self) -> Result<(), Error> {
-        self.init_pgvector().await?;
-        self.init_custom_types().await?;
-        self.create_table_users().await?;
-        self.create_table_feeds().await?;
-        self.create_table_summaries().await?;
-        Ok(())
+        // NB: the migrations may access all the rows
+        with_system(async {
+            self.migrate().await?;
+            if self.rls {
+                self.enforce_rls().await?;
+            }
+            self.convert_embeddings(self.halfvec).await
+        })
+        .await
     }
 
-    /// Initializes the PG vector extension
-    async fn init_pgvector(&self) -> Result<(), Error> {
+    /// Enables the row-level security on the tenant tables
+    ///
+    /// NB: it is forced, so that the policies also apply to the owner of the tables.
+    async fn enforce_rls(&self) -> Result<(), Error> {
         let client = self.client().await?;
-        Ok(client
-            .batch_execute("CREATE EXTENSION IF NOT EXISTS vector;")
-            .await?)
+        for table in ["users", "feeds"] {
+            client
+                .batch_execute(&format!(
+                    "ALTER TABLE {table} ENABLE ROW LEVEL SECURITY, FORCE ROW LEVEL SECURITY"
+                ))
+                .await?;
+        }
+        Ok(())
     }
 
-    /// Initializes custom types (eg enums, ...)
-    async fn init_custom_types(&self) -> Result<(), Error> {
+    /// Checks that a connection can be acquired and used
+    pub async fn ping(&self) -> Result<(), Error> {
         let client = self.client().await?;
-        Ok(client
-            .batch_execute(
-                "
-                CREATE TYPE subscription AS ENUM (
-                    'FREE',
-                    'MID'
-                )
-                ",
+        client.query_one("SELECT 1", &[]).await?;
+        Ok(())
+    }
+
+    /// Checks if the DB schema is initialized
+    ///
+    /// The schema is initialized if all the migrations have been applied.
+    pub async fn is_schema_init(&self) -> Result<bool, Error> {
+        let version = self.schema_version().await?;
+        Ok(version == Some(migration::latest_version()))
+    }
+
+    /// Returns the query and pool metrics
+    ///
+    /// NB: the pool state is the one of the primary.
+    pub fn metrics(&self) -> DbMetricsReport {
+        let status = self.pool.status();
+        self.metrics.report(Some(PoolStats {
+            max_size: status.max_size as u64,
+            size: status.size as u64,
+            available: status.available.max(0) as u64,
+            waiting: (-status.available).max(0) as u64,
+        }))
+    }
+}
+
+#[async_trait]
+impl Store for PostgresClient {
+    async fn init_schema(&self) -> Result<(), Error> {
+        self.metrics
+            .observe("init_schema", PostgresClient::init_schema(self))
+            .await
+    }
+
+    async fn ping(&self) -> Result<(), Error> {
+        self.metrics
+            .observe("ping", PostgresClient::ping(self))
+            .await
+    }
+
+    async fn is_schema_init(&self) -> Result<bool, Error> {
+        self.metrics
+            .observe("is_schema_init", PostgresClient::is_schema_init(self))
+            .await
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<Event> {
+        PostgresClient::subscribe(self)
+    }
+
+    fn metrics(&self) -> Option<DbMetricsReport> {
+        Some(PostgresClient::metrics(self))
+    }
+
+    async fn begin(&self) -> Result<Box<dyn StoreTx>, Error> {
+        Ok(Box::new(PostgresClient::begin(self).await?))
+    }
+
+    async fn create_user(&self, new_user: NewUser) -> Result<User, Error> {
+        self.metrics
+            .observe("create_user", PostgresClient::create_user(self, new_user))
+            .await
+    }
+
+    async fn read_user(&self, id: Uuid) -> Result<Option<User>, Error> {
+        self.metrics
+            .observe("read_user", PostgresClient::read_user(self, id))
+            .await
+    }
+
+    async fn read_users(&self) -> Result<Vec<User>, Error> {
+        self.metrics
+            .observe("read_users", PostgresClient::read_users(self))
+            .await
+    }
+
+    async fn read_user_with_email(&self, email: &str) -> Result<Option<User>, Error> {
+        self.metrics
+            .observe(
+                "read_user_with_email",
+                PostgresClient::read_user_with_email(self, email),
+            )
+            .await
+    }
+
+    async fn update_user(&self, id: Uuid, fields: UserUpdate) -> Result<User, Error> {
+        self.metrics
+            .observe("update_user", PostgresClient::update_user(self, id, fields))
+            .await
+    }
+
+    async fn update_user_subscription(
+        &self,
+        id: Uuid,
+        subscription_update: SubscriptionUpdate,
+    ) -> Result<User, Error> {
+        self.metrics
+            .observe(
+                "update_user_subscription",
+                PostgresClient::update_user_subscription(self, id, subscription_update),
+            )
+            .await
+    }
+
+    async fn delete_user(&self, id: Uuid) -> Result<(), Error> {
+        self.metrics
+            .observe("delete_user", PostgresClient::delete_user(self, id))
+            .await
+    }
+
+    async fn read_feeds(&self) -> Result<Vec<Feed>, Error> {
+        self.metrics
+            .observe("read_feeds", PostgresClient::read_feeds(self))
+            .await
+    }
+
+    async fn read_user_feeds(&self, user_id: Uuid) -> Result<Vec<Feed>, Error> {
+        self.metrics
+            .observe(
+                "read_user_feeds",
+                PostgresClient::read_user_feeds(self, user_id),
+            )
+            .await
+    }
+
+    async fn query_user_feeds(
+        &self,
+        user_id: Uuid,
+        opts: &ListOptions,
+    ) -> Result<Page<Feed>, Error> {
+        self.metrics
+            .observe(
+                "query_user_feeds",
+                PostgresClient::query_user_feeds(self, user_id, opts),
             )
-            .await?)
+            .await
+    }
+
+    async fn sync_user_feeds(
+        &self,
+        user_id: Uuid,
+        feeds: Vec<FeedUpdate>,
+    ) -> Result<Vec<Feed>, Error> {
+        self.metrics
+            .observe(
+                "sync_user_feeds",
+                PostgresClient::sync_user_feeds(self, user_id, feeds),
+            )
+            .await
+    }
+
+    async fn delete_user_feeds(&self, user_id: Uuid) -> Result<(), Error> {
+        self.metrics
+            .observe(
+                "delete_user_feeds",
+                PostgresClient::delete_user_feeds(self, user_id),
+            )
+            .await
+    }
+
+    async fn read_summaries(&self) -> Result<Vec<Summary>, Error> {
+        self.metrics
+            .observe("read_summaries", PostgresClient::read_summaries(self))
+            .await
+    }
+
+    async fn read_summaries_page(
+        &self,
+        cursor: Option<&str>,
+        limit: usize,
+    ) -> Result<Page<Summary>, Error> {
+        self.metrics
+            .observe(
+                "read_summaries_page",
+                PostgresClient::read_summaries_page(self, cursor, limit),
+            )
+            .await
+    }
+
+    async fn search_summaries_by_urls(&self, urls: &[&str]) -> Result<Vec<Summary>, Error> {
+        self.metrics
+            .observe(
+                "search_summaries_by_urls",
+                PostgresClient::search_summaries_by_urls(self, urls),
+            )
+            .await
+    }
+
+    async fn search_summaries_by_embeddings(
+        &self,
+        embeddings: &Vector,
+        limit: usize,
+    ) -> Result<Vec<Summary>, Error> {
+        self.metrics
+            .observe(
+                "search_summaries_by_embeddings",
+                PostgresClient::search_summaries_by_embeddings(self, embeddings, limit),
+            )
+            .await
+    }
+
+    async fn check_vector_index(
+        &self,
+        sample: usize,
+        k: usize,
+    ) -> Result<VectorIndexReport, Error> {
+        self.metrics
+            .observe(
+                "check_vector_index",
+                PostgresClient::check_vector_index(self, sample, k),
+            )
+            .await
+    }
+
+    async fn reindex_vectors(&self) -> Result<(), Error> {
+        self.metrics
+            .observe("reindex_vectors", PostgresClient::reindex_vectors(self))
+            .await
+    }
+
+    async fn insert_summaries(&self, summaries: Vec<Summary>) -> Result<Vec<Summary>, Error> {
+        self.metrics
+            .observe(
+                "insert_summaries",
+                PostgresClient::insert_summaries(self, summaries),
+            )
+            .await
+    }
+
+    async fn remove_summaries(&self, summaries: Vec<Summary>) -> Result<(), Error> {
+        self.metrics
+            .observe(
+                "remove_summaries",
+                PostgresClient::remove_summaries(self, summaries),
+            )
+            .await
+    }
+
+    async fn touch_summaries(&self, urls: &[&str]) -> Result<(), Error> {
+        self.metrics
+            .observe(
+                "touch_summaries",
+                PostgresClient::touch_summaries(self, urls),
+            )
+            .await
+    }
+
+    async fn delete_summaries_created_before(&self, before: OffsetDateTime) -> Result<u64, Error> {
+        self.metrics
+            .observe(
+                "delete_summaries_created_before",
+                PostgresClient::delete_summaries_created_before(self, before),
+            )
+            .await
+    }
+
+    async fn delete_summaries_accessed_before(&self, before: OffsetDateTime) -> Result<u64, Error> {
+        self.metrics
+            .observe(
+                "delete_summaries_accessed_before",
+                PostgresClient::delete_summaries_accessed_before(self, before),
+            )
+            .await
+    }
+
+    async fn read_idempotent_response(
+        &self,
+        key: &str,
+        scope: &str,
+        since: OffsetDateTime,
+    ) -> Result<Option<IdempotentResponse>, Error> {
+        self.metrics
+            .observe(
+                "read_idempotent_response",
+                PostgresClient::read_idempotent_response(self, key, scope, since),
+            )
+            .await
+    }
+
+    async fn upsert_idempotent_response(&self, response: &IdempotentResponse) -> Result<(), Error> {
+        self.metrics
+            .observe(
+                "upsert_idempotent_response",
+                PostgresClient::upsert_idempotent_response(self, response),
+            )
+            .await
+    }
+
+    async fn reserve_idempotency_key(
+        &self,
+        pending: &IdempotentResponse,
+        expired_before: OffsetDateTime,
+        stale_before: OffsetDateTime,
+    ) -> Result<bool, Error> {
+        self.metrics
+            .observe(
+                "reserve_idempotency_key",
+                PostgresClient::reserve_idempotency_key(
+                    self,
+                    pending,
+                    expired_before,
+                    stale_before,
+                ),
+            )
+            .await
+    }
+
+    async fn delete_idempotent_response(&self, key: &str, scope: &str) -> Result<(), Error> {
+        self.metrics
+            .observe(
+                "delete_idempotent_response",
+                PostgresClient::delete_idempotent_response(self, key, scope),
+            )
+            .await
+    }
+
+    async fn delete_idempotent_responses_before(
+        &self,
+        before: OffsetDateTime,
+    ) -> Result<u64, Error> {
+        self.metrics
+            .observe(
+                "delete_idempotent_responses_before",
+                PostgresClient::delete_idempotent_responses_before(self, before),
+            )
+            .await
+    }
+
+    async fn count_users(&self) -> Result<u64, Error> {
+        self.metrics
+            .observe("count_users", PostgresClient::count_users(self))
+            .await
+    }
+
+    async fn count_users_by_feeds(&self) -> Result<Vec<FeedCount>, Error> {
+        self.metrics
+            .observe(
+                "count_users_by_feeds",
+                PostgresClient::count_users_by_feeds(self),
+            )
+            .await
+    }
+
+    async fn count_summaries_by_day(
+        &self,
+        since: OffsetDateTime,
+    ) -> Result<Vec<DailyCount>, Error> {
+        self.metrics
+            .observe(
+                "count_summaries_by_day",
+                PostgresClient::count_summaries_by_day(self, since),
+            )
+            .await
+    }
+
+    async fn enqueue_job(&self, job: &Job) -> Result<(), Error> {
+        self.metrics
+            .observe("enqueue_job", PostgresClient::enqueue_job(self, job))
+            .await
+    }
+
+    async fn claim_jobs(
+        &self,
+        kinds: &[&str],
+        limit: usize,
+        locked_until: OffsetDateTime,
+    ) -> Result<Vec<Job>, Error> {
+        self.metrics
+            .observe(
+                "claim_jobs",
+                PostgresClient::claim_jobs(self, kinds, limit, locked_until),
+            )
+            .await
+    }
+
+    async fn read_job(&self, id: Uuid) -> Result<Option<Job>, Error> {
+        self.metrics
+            .observe("read_job", PostgresClient::read_job(self, id))
+            .await
+    }
+
+    async fn renew_job(
+        &self,
+        id: Uuid,
+        lease_id: Uuid,
+        locked_until: OffsetDateTime,
+    ) -> Result<(), Error> {
+        self.metrics
+            .observe(
+                "renew_job",
+                PostgresClient::renew_job(self, id, lease_id, locked_until),
+            )
+            .await
+    }
+
+    async fn complete_job(&self, id: Uuid, lease_id: Uuid) -> Result<(), Error> {
+        self.metrics
+            .observe(
+                "complete_job",
+                PostgresClient::complete_job(self, id, lease_id),
+            )
+            .await
+    }
+
+    async fn fail_job(
+        &self,
+        id: Uuid,
+        lease_id: Uuid,
+        error: &str,
+        retry_at: Option<OffsetDateTime>,
+    ) -> Result<(), Error> {
+        self.metrics
+            .observe(
+                "fail_job",
+                PostgresClient::fail_job(self, id, lease_id, error, retry_at),
+            )
+            .await
+    }
+
+    async fn delete_jobs_done_before(&self, before: OffsetDateTime) -> Result<u64, Error> {
+        self.metrics
+            .observe(
+                "delete_jobs_done_before",
+                PostgresClient::delete_jobs_done_before(self, before),
+            )
+            .await
+    }
+
+    async fn count_jobs(&self) -> Result<Vec<JobCount>, Error> {
+        self.metrics
+            .observe("count_jobs", PostgresClient::count_jobs(self))
+            .await
     }
 }
 
@@ -73,10 +630,32 @@ pub mod tests {
         PostgresClient::new(cfg.postgres.new_pool())
     }
 
-    #[ignore]
+    #[tokio::test]
+    async fn test_ping() {
+        let client = init_db();
+        client.ping().await.unwrap();
+    }
+
     #[tokio::test]
     async fn test_init_schema() {
         let client = init_db();
         client.init_schema().await.unwrap();
     }
+
+    #[tokio::test]
+    async fn test_set_tenant() {
+        // NB: the row-level security is not enforced, so that the other tests are not affected
+        let client = init_db().rls(true);
+        let user_id = Uuid::new_v4();
+
+        let setting = crate::db::tenant::with_tenant(user_id, async {
+            let conn = client.client().await.unwrap();
+            conn.query_one("SELECT current_setting('app.user_id')", &[])
+                .await
+                .unwrap()
+                .get::<_, String>(0)
+        })
+        .await;
+        assert_eq!(setting, user_id.to_string());
+    }
 }
@@ -0,0 +1,94 @@
+//! Statistics
+
+use time::OffsetDateTime;
+
+use crate::{
+    error::Error,
+    mdl::{DailyCount, FeedCount},
+};
+
+use super::PostgresClient;
+
+impl PostgresClient {
+    /// Counts the users
+    pub async fn count_users(&self) -> Result<u64, Error> {
+        let client = self.read_client().await?;
+
+        Ok(client
+            .query_one("SELECT COUNT(*) FROM users", &[])
+            .await?
+            .get::<_, i64>(0) as u64)
+    }
+
+    /// Counts the users by number of feeds
+    ///
+    /// The users without feeds are counted with 0 feeds.
+    pub async fn count_users_by_feeds(&self) -> Result<Vec<FeedCount>, Error> {
+        let client = self.read_client().await?;
+
+        Ok(client
+            .query(
+                "
+                SELECT feeds, COUNT(*) AS users FROM (
+                    SELECT COUNT(f.id) AS feeds
+                    FROM users u LEFT JOIN feeds f ON f.user_id = u.id
+                    GROUP BY u.id
+                ) t
+                GROUP BY feeds ORDER BY feeds
+                ",
+                &[],
+            )
+            .await?
+            .into_iter()
+            .map(|row| FeedCount {
+                feeds: row.get::<_, i64>("feeds") as u64,
+                users: row.get::<_, i64>("users") as u64,
+            })
+            .collect())
+    }
+
+    /// Counts the summaries created per day (UTC) since a given time
+    ///
+    /// The days without summaries are omitted.
+    pub async fn count_summaries_by_day(
+        &self,
+        since: OffsetDateTime,
+    ) -> Result<Vec<DailyCount>, Error> {
+        let client = self.read_client().await?;
+
+        Ok(client
+            .query(
+                "
+                SELECT to_char(created_at AT TIME ZONE 'UTC', 'YYYY-MM-DD') AS day, COUNT(*) AS count
+                FROM summaries WHERE created_at >= $1
+                GROUP BY day ORDER BY day
+                ",
+                &[&since],
+            )
+            .await?
+            .into_iter()
+            .map(|row| DailyCount {
+                day: row.get::<_, String>("day"),
+                count: row.get::<_, i64>("count") as u64,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::db::postgres::user::tests::{setup_test_user, teardown_test_user};
+
+    #[tokio::test]
+    async fn test_stats() {
+        let (db, user) = setup_test_user().await;
+        assert!(db.count_users().await.unwrap() >= 1);
+        let by_feeds = db.count_users_by_feeds().await.unwrap();
+        assert!(by_feeds.iter().map(|c| c.users).sum::<u64>() >= 1);
+        let since = OffsetDateTime::now_utc() - time::Duration::days(7);
+        let _by_day = db.count_summaries_by_day(since).await.unwrap();
+        teardown_test_user(db, user).await;
+    }
+}
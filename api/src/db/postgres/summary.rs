@@ -1,11 +1,21 @@
 //! Articles
 
+use deadpool_postgres::GenericClient;
+use time::OffsetDateTime;
 use tokio_postgres::{types::ToSql, Row};
+use tracing::info;
 use uuid::Uuid;
 
-use crate::{error::Error, mdl::Summary};
+use crate::{
+    db::page::{keyset, keyset_condition, keyset_cursor, keyset_order_by, paginate, Page},
+    error::Error,
+    mdl::{Summary, Vector},
+};
 
-use super::{util::Vector, PostgresClient};
+use super::PostgresClient;
+
+/// Tiebreaker key of the summaries (NB: the column itself, so that the primary key index is used)
+const SUMMARY_TIEBREAKER: (&str, &str) = ("id", "id");
 
 impl From<Row> for Summary {
     fn from(value: Row) -> Self {
@@ -19,46 +29,137 @@ impl From<Row> for Summary {
     }
 }
 
+/// Dimension of the embeddings
+const EMBEDDINGS_DIM: usize = 1536;
+
+/// Approximate nearest neighbors index of the embeddings (see the `vector_index` migration)
+pub const VECTOR_INDEX: &str = "summaries_embeddings_idx";
+
 impl PostgresClient {
-    /// Creates the `summaries` table
-    pub async fn create_table_summaries(&self) -> Result<(), Error> {
+    /// Converts the embeddings column to half-precision floats or back
+    ///
+    /// NB: the table is rewritten, which locks it, so it is only done if the type changes.
+    /// The vector index is rebuilt with the operator class of the new type.
+    pub async fn convert_embeddings(&self, halfvec: bool) -> Result<(), Error> {
         let client = self.client().await?;
-        Ok(client
-            .batch_execute(
-                "
-                    CREATE TABLE IF NOT EXISTS summaries (
-                        id          UUID PRIMARY KEY,
-                        url         TEXT NOT NULL UNIQUE,    
-                        summary     TEXT,
-                        keywords    TEXT[],
-                        embeddings  VECTOR(1536)
-                    )",
+        let column_type = client
+            .query_one(
+                "SELECT format_type(atttypid, atttypmod) FROM pg_attribute \
+                WHERE attrelid = 'summaries'::regclass AND attname = 'embeddings'",
+                &[],
             )
-            .await?)
+            .await?
+            .get::<_, String>(0);
+        let base_type = if halfvec { "halfvec" } else { "vector" };
+        let target_type = format!("{base_type}({EMBEDDINGS_DIM})");
+        if column_type != target_type {
+            info!(from = %column_type, to = %target_type, "converting the embeddings");
+            client
+                .batch_execute(&format!(
+                    "
+                    BEGIN;
+                    DROP INDEX IF EXISTS {VECTOR_INDEX};
+                    ALTER TABLE summaries ALTER COLUMN embeddings TYPE {target_type}
+                        USING embeddings::{target_type};
+                    CREATE INDEX {VECTOR_INDEX} ON summaries
+                        USING hnsw (embeddings {base_type}_cosine_ops);
+                    COMMIT;
+                    "
+                ))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Reads all the summaries
+    pub async fn read_summaries(&self) -> Result<Vec<Summary>, Error> {
+        let client = self.read_client().await?;
+
+        Ok(client
+            .query("SELECT * FROM summaries ORDER BY id", &[])
+            .await?
+            .into_iter()
+            .map(|row| row.into())
+            .collect::<Vec<_>>())
+    }
+
+    /// Reads a page of the summaries, by ascending ID
+    ///
+    /// The first page is read without cursor, the next ones with the cursor of the previous
+    /// page.
+    pub async fn read_summaries_page(
+        &self,
+        cursor: Option<&str>,
+        limit: usize,
+    ) -> Result<Page<Summary>, Error> {
+        let client = self.read_client().await?;
+
+        let keys = keyset(&[], &[], SUMMARY_TIEBREAKER);
+        let after = match keyset_cursor(&keys, cursor)? {
+            Some(cursor) => Some(
+                Uuid::parse_str(&cursor.0[0])
+                    .map_err(|_| Error::InvalidRequest("invalid cursor".to_string(), None))?,
+            ),
+            None => None,
+        };
+        let limit_param = limit as i64 + 1;
+
+        let mut params: Vec<&(dyn ToSql + Sync)> = vec![];
+        let mut stmt = "SELECT * FROM summaries".to_string();
+        if let Some(after) = after.as_ref() {
+            params.push(after);
+            stmt.push_str(&format!(
+                " WHERE {}",
+                keyset_condition(&keys, 1, |i| format!("${i}"))
+            ));
+        }
+        params.push(&limit_param);
+        stmt.push_str(&format!(
+            " {} LIMIT ${}",
+            keyset_order_by(&keys),
+            params.len()
+        ));
+        let stmt = client.prepare_cached(&stmt).await?;
+
+        let summaries = client
+            .query(&stmt, &params)
+            .await?
+            .into_iter()
+            .map(|row| row.into())
+            .collect();
+        Ok(paginate(summaries, Some(limit), &keys, |s: &Summary, _| {
+            s.id.to_string()
+        }))
     }
-}
 
-impl PostgresClient {
     /// Search summaries by url
+    ///
+    /// NB: the URLs are passed as an array, so that the statement can be cached.
     pub async fn search_summaries_by_urls(&self, urls: &[&str]) -> Result<Vec<Summary>, Error> {
-        let client = self.client().await?;
+        let client = self.read_client().await?;
+        let stmt = client
+            .prepare_cached("SELECT * FROM summaries WHERE url = ANY($1)")
+            .await?;
+
+        Ok(client
+            .query(&stmt, &[&urls])
+            .await?
+            .into_iter()
+            .map(|row| row.into())
+            .collect::<Vec<_>>())
+    }
+
+    /// Search the summaries closest to an embeddings vector (cosine distance)
+    pub async fn search_summaries_by_embeddings(
+        &self,
+        embeddings: &Vector,
+        limit: usize,
+    ) -> Result<Vec<Summary>, Error> {
+        let client = self.read_client().await?;
         Ok(client
             .query(
-                &format!(
-                    "SELECT * FROM summaries WHERE url IN({})",
-                    urls.iter()
-                        .enumerate()
-                        .map(|(i, _url)| format!("${}", i + 1))
-                        .collect::<Vec<_>>()
-                        .join(", ")
-                ),
-                urls.iter()
-                    .map(|u| {
-                        let v: &(dyn ToSql + Sync) = u;
-                        v
-                    })
-                    .collect::<Vec<_>>()
-                    .as_slice(),
+                "SELECT * FROM summaries ORDER BY embeddings <=> $1 LIMIT $2",
+                &[embeddings, &(limit as i64)],
             )
             .await?
             .into_iter()
@@ -67,72 +168,107 @@ impl PostgresClient {
     }
 
     /// Insert summaries in the DB
+    ///
+    /// If a summary already exists for an URL (eg. inserted concurrently), the existing
+    /// summary is returned.
     pub async fn insert_summaries(&self, articles: Vec<Summary>) -> Result<Vec<Summary>, Error> {
         let client = self.client().await?;
-        let stmt = format!(
-            "INSERT INTO summaries (id, url, summary, keywords, embeddings) VALUES {} RETURNING *",
-            articles
-                .iter()
-                .enumerate()
-                .map(|(i, _art)| {
-                    format!(
-                        "(${}, ${}, ${}, ${}, ${})",
-                        i * 5 + 1,
-                        i * 5 + 2,
-                        i * 5 + 3,
-                        i * 5 + 4,
-                        i * 5 + 5
-                    )
-                })
-                .collect::<Vec<_>>()
-                .join(", ")
-        );
-        let params = articles
-            .iter()
-            .flat_map(|art| {
-                let params: Vec<&(dyn ToSql + Sync)> = vec![
-                    &art.id,
-                    &art.url,
-                    &art.summary,
-                    &art.keywords,
-                    &art.embeddings,
-                ];
-                params
-            })
-            .collect::<Vec<_>>();
+        insert_summaries(&**client, articles).await
+    }
+
+    /// Marks the summaries of a list of URLs as accessed now
+    pub async fn touch_summaries(&self, urls: &[&str]) -> Result<(), Error> {
+        let client = self.client().await?;
+        let stmt = client
+            .prepare_cached("UPDATE summaries SET accessed_at = now() WHERE url = ANY($1)")
+            .await?;
+
+        let _res = client.execute(&stmt, &[&urls]).await?;
+        Ok(())
+    }
+
+    /// Deletes the summaries created before a given time
+    pub async fn delete_summaries_created_before(
+        &self,
+        before: OffsetDateTime,
+    ) -> Result<u64, Error> {
+        let client = self.client().await?;
+
         Ok(client
-            .query(&stmt, &params)
-            .await?
-            .into_iter()
-            .map(|row| row.into())
-            .collect::<Vec<_>>())
+            .execute("DELETE FROM summaries WHERE created_at <= $1", &[&before])
+            .await?)
+    }
+
+    /// Deletes the summaries last accessed before a given time
+    pub async fn delete_summaries_accessed_before(
+        &self,
+        before: OffsetDateTime,
+    ) -> Result<u64, Error> {
+        let client = self.client().await?;
+
+        Ok(client
+            .execute("DELETE FROM summaries WHERE accessed_at <= $1", &[&before])
+            .await?)
     }
 
     /// Remove summaries in the DB
     pub async fn remove_summaries(&self, summaries: Vec<Summary>) -> Result<(), Error> {
         let client = self.client().await?;
+        let ids = summaries.iter().map(|art| art.id).collect::<Vec<_>>();
         let _res = client
-            .execute(
-                format!(
-                    "DELETE FROM summaries WHERE id IN({})",
-                    summaries
-                        .iter()
-                        .enumerate()
-                        .map(|(i, _art)| format!("${}", i + 1))
-                        .collect::<Vec<_>>()
-                        .join(", ")
-                )
-                .as_str(),
-                &summaries
-                    .iter()
-                    .map(|art| &art.id as &(dyn ToSql + Sync))
-                    .collect::<Vec<_>>(),
-            )
+            .execute("DELETE FROM summaries WHERE id = ANY($1)", &[&ids])
             .await?;
         Ok(())
     }
 }
 
+/// Inserts summaries with a client (or a transaction)
+///
+/// If a summary already exists for an URL, the existing summary is returned.
+pub(super) async fn insert_summaries(
+    client: &impl GenericClient,
+    articles: Vec<Summary>,
+) -> Result<Vec<Summary>, Error> {
+    let stmt = format!(
+        "INSERT INTO summaries (id, url, summary, keywords, embeddings) VALUES {} \
+        ON CONFLICT (url) DO UPDATE SET url = EXCLUDED.url RETURNING *",
+        articles
+            .iter()
+            .enumerate()
+            .map(|(i, _art)| {
+                format!(
+                    "(${}, ${}, ${}, ${}, ${})",
+                    i * 5 + 1,
+                    i * 5 + 2,
+                    i * 5 + 3,
+                    i * 5 + 4,
+                    i * 5 + 5
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    let params = articles
+        .iter()
+        .flat_map(|art| {
+            let params: Vec<&(dyn ToSql + Sync)> = vec![
+                &art.id,
+                &art.url,
+                &art.summary,
+                &art.keywords,
+                &art.embeddings,
+            ];
+            params
+        })
+        .collect::<Vec<_>>();
+    Ok(client
+        .query(&stmt, &params)
+        .await?
+        .into_iter()
+        .map(|row| row.into())
+        .collect::<Vec<_>>())
+}
+
 #[cfg(test)]
 mod tests {
     use fake::faker::lorem::en::Word;
@@ -159,12 +295,6 @@ mod tests {
     /// Teardown a test
     async fn teardown(_db: PostgresClient) {}
 
-    #[tokio::test]
-    async fn test_create_table() {
-        let client = init_client();
-        client.create_table_summaries().await.unwrap();
-    }
-
     #[tokio::test]
     async fn test_insert_summaries() {
         let client = setup().await;
@@ -191,7 +321,25 @@ mod tests {
         let summaries = client.insert_summaries(summaries).await.unwrap();
         assert_eq!(summaries.len(), 5);
 
+        let urls = summaries.iter().map(|s| s.url.as_str()).collect::<Vec<_>>();
+        client.touch_summaries(&urls).await.unwrap();
+
         client.remove_summaries(summaries).await.unwrap();
         teardown(client).await;
     }
+
+    #[tokio::test]
+    async fn test_search_many_urls() {
+        let client = setup().await;
+
+        // NB: more URLs than the maximum number of parameters of a statement
+        let urls = (0..70_000)
+            .map(|i| format!("https:://www.link.com/__test__{i}"))
+            .collect::<Vec<_>>();
+        let urls = urls.iter().map(|u| u.as_str()).collect::<Vec<_>>();
+        let found = client.search_summaries_by_urls(&urls).await.unwrap();
+        assert!(found.is_empty());
+
+        teardown(client).await;
+    }
 }
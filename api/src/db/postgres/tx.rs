@@ -0,0 +1,135 @@
+//! Transactions
+
+use salvo::async_trait;
+use uuid::Uuid;
+
+use crate::{
+    db::StoreTx,
+    error::Error,
+    mdl::{Feed, FeedUpdate, Summary, User},
+};
+
+use super::{feed, summary, user, PostgresClient};
+
+/// Postgres transaction
+///
+/// The transaction holds a pooled connection until it is committed or rolled back.
+/// If it is dropped before, it is rolled back in the background (or its connection is closed
+/// outside of a Tokio runtime).
+pub struct PostgresTx {
+    /// Connection (`None` once the transaction is finished)
+    client: Option<deadpool_postgres::Object>,
+}
+
+impl PostgresClient {
+    /// Begins a transaction
+    pub async fn begin(&self) -> Result<PostgresTx, Error> {
+        let client = self.client().await?;
+        client.batch_execute("BEGIN").await?;
+        Ok(PostgresTx {
+            client: Some(client),
+        })
+    }
+}
+
+impl PostgresTx {
+    /// Returns the connection of an ongoing transaction
+    fn client(&self) -> Result<&deadpool_postgres::Object, Error> {
+        self.client
+            .as_ref()
+            .ok_or(Error::Internal("transaction is finished".to_string(), None))
+    }
+
+    /// Ends the transaction with a statement (`COMMIT` or `ROLLBACK`)
+    async fn end(&mut self, stmt: &str) -> Result<(), Error> {
+        // NB: the connection is kept on failure, so that the transaction is rolled back on drop
+        self.client()?.batch_execute(stmt).await?;
+        self.client = None;
+        Ok(())
+    }
+}
+
+impl Drop for PostgresTx {
+    fn drop(&mut self) {
+        if let Some(client) = self.client.take() {
+            match tokio::runtime::Handle::try_current() {
+                // NB: the connection is returned to the pool once rolled back
+                Ok(handle) => {
+                    handle.spawn(async move {
+                        if let Err(err) = client.batch_execute("ROLLBACK").await {
+                            tracing::warn!(%err, "failed to roll back a dropped transaction");
+                        }
+                    });
+                }
+                // NB: without a runtime, the connection is closed, which aborts the transaction
+                Err(_) => {
+                    tracing::warn!(
+                        "dropped transaction outside of a runtime, closing its connection"
+                    );
+                    drop(deadpool_postgres::Object::take(client));
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl StoreTx for PostgresTx {
+    async fn upsert_user(&mut self, user: &User) -> Result<(), Error> {
+        user::upsert_user(&**self.client()?, user).await
+    }
+
+    async fn lock_user_feeds(&mut self, user_id: Uuid) -> Result<Vec<Feed>, Error> {
+        feed::lock_feeds(&**self.client()?, user_id).await
+    }
+
+    async fn sync_user_feeds(
+        &mut self,
+        user_id: Uuid,
+        feeds: Vec<FeedUpdate>,
+    ) -> Result<Vec<Feed>, Error> {
+        feed::sync_feeds(&**self.client()?, user_id, feeds).await
+    }
+
+    async fn delete_user_feeds(&mut self, user_id: Uuid) -> Result<(), Error> {
+        feed::delete_feeds(&**self.client()?, user_id).await
+    }
+
+    async fn delete_user(&mut self, id: Uuid) -> Result<(), Error> {
+        user::delete_user(&**self.client()?, id).await
+    }
+
+    async fn insert_summaries(&mut self, summaries: Vec<Summary>) -> Result<Vec<Summary>, Error> {
+        summary::insert_summaries(&**self.client()?, summaries).await
+    }
+
+    async fn commit(mut self: Box<Self>) -> Result<(), Error> {
+        self.end("COMMIT").await
+    }
+
+    async fn rollback(mut self: Box<Self>) -> Result<(), Error> {
+        self.end("ROLLBACK").await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::db::postgres::user::tests::setup_test_user;
+
+    #[tokio::test]
+    async fn test_rollback() {
+        let (db, user) = setup_test_user().await;
+
+        let mut tx = Box::new(db.begin().await.unwrap());
+        tx.delete_user(user.id).await.unwrap();
+        tx.rollback().await.unwrap();
+        assert!(db.read_user(user.id).await.unwrap().is_some());
+
+        let mut tx = Box::new(db.begin().await.unwrap());
+        tx.delete_user(user.id).await.unwrap();
+        tx.commit().await.unwrap();
+        assert!(db.read_user(user.id).await.unwrap().is_none());
+    }
+}
@@ -1,5 +1,6 @@
 //! Users
 
+use deadpool_postgres::GenericClient;
 use tokio_postgres::Row;
 use uuid::Uuid;
 
@@ -8,7 +9,10 @@ use crate::{
     mdl::{NewUser, Subscription, SubscriptionUpdate, User, UserUpdate},
 };
 
-use super::PostgresClient;
+use super::{
+    util::{is_unique_violation, UpdateBuilder},
+    PostgresClient,
+};
 
 impl From<Row> for User {
     fn from(value: Row) -> Self {
@@ -23,31 +27,13 @@ impl From<Row> for User {
 }
 
 impl PostgresClient {
-    /// Creates the `users` table
-    pub async fn create_table_users(&self) -> Result<(), Error> {
-        let client = self.client().await?;
-
-        Ok(client
-            .batch_execute(
-                "
-                    CREATE TABLE IF NOT EXISTS users (
-                        id              UUID PRIMARY KEY,
-                        name            TEXT NOT NULL,
-                        email           TEXT NOT NULL,
-                        password        TEXT NOT NULL,
-                        subscription    subscription NOT NULL 
-                    )",
-            )
-            .await?)
-    }
-
     /// Creates a new user
     ///
-    /// A new user is created and its ID is populated
+    /// A new user is created and its ID is populated. Emails are unique.
     pub async fn create_user(&self, new_user: NewUser) -> Result<User, Error> {
         let client = self.client().await?;
 
-        Ok(client
+        client
             .query_one(
                 "INSERT into users (id, name, email, password, subscription) VALUES ($1, $2, $3, $4, $5) RETURNING *",
                 &[
@@ -58,18 +44,33 @@ impl PostgresClient {
                     &Subscription::Free
                 ],
             )
-            .await?
-            .into())
+            .await
+            .map(|row| row.into())
+            .map_err(|err| email_conflict_error(err, &new_user.email))
     }
 
     /// Reads a user with its id
+    ///
+    /// NB: this query runs on every authenticated request, so its statement is cached.
     pub async fn read_user(&self, id: Uuid) -> Result<Option<User>, Error> {
         let client = self.client().await?;
+        let stmt = client
+            .prepare_cached("SELECT * FROM users WHERE id = $1")
+            .await?;
+
+        Ok(client.query_opt(&stmt, &[&id]).await?.map(|row| row.into()))
+    }
+
+    /// Reads all the users
+    pub async fn read_users(&self) -> Result<Vec<User>, Error> {
+        let client = self.read_client().await?;
 
         Ok(client
-            .query_opt("SELECT * FROM users WHERE id = $1", &[&id])
+            .query("SELECT * FROM users ORDER BY id", &[])
             .await?
-            .map(|row| row.into()))
+            .into_iter()
+            .map(|row| row.into())
+            .collect())
     }
 
     /// Reads a user with its email
@@ -86,39 +87,26 @@ impl PostgresClient {
     pub async fn update_user(&self, id: Uuid, fields: UserUpdate) -> Result<User, Error> {
         let client = self.client().await?;
 
-        let mut cols: Vec<&str> = vec![];
-        let mut params: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> = vec![];
-        params.push(&id);
-
-        if let Some(name) = fields.name.as_ref() {
-            cols.push("name");
-            params.push(name);
-        }
-        if let Some(email) = fields.email.as_ref() {
-            cols.push("email");
-            params.push(email);
-        }
-        if let Some(password) = fields.password.as_ref() {
-            cols.push("password");
-            params.push(password);
-        }
+        let update = UpdateBuilder::new("users")
+            .set_opt("name", &fields.name)
+            .set_opt("email", &fields.email)
+            .set_opt("password", &fields.password);
         // ... add other fields here
 
-        if cols.is_empty() {
+        if update.is_empty() {
             match self.read_user(id).await? {
                 Some(u) => Ok(u),
                 None => Err(Error::NotFound(format!("no user for id {id}"), None)),
             }
         } else {
-            let stmt = format!(
-                "UPDATE users SET {} WHERE id=$1 RETURNING *",
-                cols.iter()
-                    .enumerate()
-                    .map(|(i, c)| format!("{}=${}", c, i + 2))
-                    .collect::<Vec<_>>()
-                    .join(", ")
-            );
-            Ok(client.query_one(&stmt, &params).await?.into())
+            let (stmt, params) = update.build("id", &id);
+            client
+                .query_one(&stmt, &params)
+                .await
+                .map(|row| row.into())
+                .map_err(|err| {
+                    email_conflict_error(err, fields.email.as_deref().unwrap_or_default())
+                })
         }
     }
 
@@ -130,27 +118,55 @@ impl PostgresClient {
     ) -> Result<User, Error> {
         let client = self.client().await?;
 
-        let mut params: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> = vec![];
-        params.push(&id);
-        params.push(&subscription_update.subscription);
+        let (stmt, params) = UpdateBuilder::new("users")
+            .set("subscription", &subscription_update.subscription)
+            .build("id", &id);
 
-        Ok(client
-            .query_one(
-                "UPDATE users SET id=$1, subscription=$2 WHERE id=$1 RETURNING *",
-                &params,
-            )
-            .await?
-            .into())
+        Ok(client.query_one(&stmt, &params).await?.into())
     }
 
     /// Delete a user
     pub async fn delete_user(&self, id: Uuid) -> Result<(), Error> {
         let client = self.client().await?;
+        delete_user(&*client, id).await
+    }
+}
 
-        let _res = client
-            .execute("DELETE FROM users WHERE id=$1", &[&id])
-            .await?;
-        Ok(())
+/// Insert or update a user with a client (or a transaction)
+///
+/// All the fields are set, including the ID and the password hash.
+pub(super) async fn upsert_user(client: &impl GenericClient, user: &User) -> Result<(), Error> {
+    let _res = client
+        .execute(
+            "INSERT INTO users (id, name, email, password, subscription) VALUES ($1, $2, $3, $4, $5) \
+            ON CONFLICT (id) DO UPDATE SET name = $2, email = $3, password = $4, subscription = $5",
+            &[
+                &user.id,
+                &user.name,
+                &user.email,
+                &user.password,
+                &user.subscription,
+            ],
+        )
+        .await
+        .map_err(|err| email_conflict_error(err, &user.email))?;
+    Ok(())
+}
+
+/// Delete a user with a client (or a transaction)
+pub(super) async fn delete_user(client: &impl GenericClient, id: Uuid) -> Result<(), Error> {
+    let _res = client
+        .execute("DELETE FROM users WHERE id=$1", &[&id])
+        .await?;
+    Ok(())
+}
+
+/// Converts a DB error, reporting email conflicts as invalid requests
+fn email_conflict_error(err: tokio_postgres::Error, email: &str) -> Error {
+    if is_unique_violation(&err) {
+        Error::InvalidRequest(format!("user with email '{email}' already exists"), None)
+    } else {
+        err.into()
     }
 }
 
@@ -192,12 +208,6 @@ pub mod tests {
         db.delete_user(user.id).await.unwrap();
     }
 
-    #[tokio::test]
-    async fn test_create_table() {
-        let db = init_db();
-        db.create_table_users().await.unwrap();
-    }
-
     #[tokio::test]
     async fn test_read_with_id() {
         let (db, test_user) = setup_test_user().await;
@@ -233,6 +243,20 @@ pub mod tests {
         teardown_test_user(db, test_user).await;
     }
 
+    #[tokio::test]
+    async fn test_create_duplicate_email() {
+        let (db, test_user) = setup_test_user().await;
+        let res = db
+            .create_user(NewUser {
+                name: test_user.name.clone(),
+                email: test_user.email.clone(),
+                password: "dummy".to_string(),
+            })
+            .await;
+        assert!(matches!(res, Err(Error::InvalidRequest(_, _))));
+        teardown_test_user(db, test_user).await;
+    }
+
     #[tokio::test]
     async fn test_update_subscription() {
         let (db, test_user) = setup_test_user().await;
@@ -1,31 +1,25 @@
-//! Utilities
+//! SQL utilities (Postgres specific)
 
 use std::io::Read;
 
-use salvo::prelude::ToSchema;
-use serde::{Deserialize, Serialize};
-use tokio_postgres::types::{to_sql_checked, FromSql, ToSql};
+use half::f16;
+use tokio_postgres::{
+    error::SqlState,
+    types::{to_sql_checked, FromSql, ToSql},
+};
 
-/// A vector type
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
-pub struct Vector(Vec<f32>);
+use crate::mdl::Vector;
 
-impl From<Vec<f32>> for Vector {
-    fn from(value: Vec<f32>) -> Self {
-        Vector(value)
-    }
-}
-
-impl From<Vector> for Vec<f32> {
-    fn from(value: Vector) -> Self {
-        value.0
-    }
-}
+/// Postgres types of the embeddings
+///
+/// `vector` stores single-precision floats, `halfvec` half-precision floats (half the size,
+/// with a negligible loss of precision for the cosine distance).
+const VECTOR_TYPES: &[&str] = &["vector", "halfvec"];
 
 impl ToSql for Vector {
     fn to_sql(
         &self,
-        _ty: &tokio_postgres::types::Type,
+        ty: &tokio_postgres::types::Type,
         out: &mut tokio_postgres::types::private::BytesMut,
     ) -> Result<tokio_postgres::types::IsNull, Box<dyn std::error::Error + Sync + Send>>
     where
@@ -33,11 +27,18 @@ impl ToSql for Vector {
     {
         // NB: a vector value is passed as '[1,2,3]'
         // This code is copied from te crate `pgvector`
-        let dim: u16 = self.0.len().try_into()?;
+        let values = self.as_ref();
+        let dim: u16 = values.len().try_into()?;
         out.extend(dim.to_be_bytes());
         out.extend(0_u16.to_be_bytes());
-        for v in self.0.iter() {
-            out.extend(v.to_be_bytes())
+        if ty.name() == "halfvec" {
+            for v in values {
+                out.extend(f16::from_f32(*v).to_be_bytes())
+            }
+        } else {
+            for v in values {
+                out.extend(v.to_be_bytes())
+            }
         }
 
         Ok(tokio_postgres::types::IsNull::No)
@@ -47,7 +48,7 @@ impl ToSql for Vector {
     where
         Self: Sized,
     {
-        ty.name() == "vector"
+        VECTOR_TYPES.contains(&ty.name())
     }
 
     to_sql_checked!();
@@ -55,7 +56,7 @@ impl ToSql for Vector {
 
 impl<'a> FromSql<'a> for Vector {
     fn from_sql(
-        _ty: &tokio_postgres::types::Type,
+        ty: &tokio_postgres::types::Type,
         raw: &'a [u8],
     ) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
         let mut buf = raw;
@@ -73,15 +74,132 @@ impl<'a> FromSql<'a> for Vector {
 
         let mut values = vec![];
         for _i in 0..(dim as usize) {
-            let mut buf_f32 = [0x00_u8; 4];
-            buf.read_exact(&mut buf_f32)?;
-            let v = f32::from_be_bytes(buf_f32);
-            values.push(v);
+            if ty.name() == "halfvec" {
+                let mut buf_f16 = [0x00_u8; 2];
+                buf.read_exact(&mut buf_f16)?;
+                values.push(f16::from_be_bytes(buf_f16).to_f32());
+            } else {
+                let mut buf_f32 = [0x00_u8; 4];
+                buf.read_exact(&mut buf_f32)?;
+                values.push(f32::from_be_bytes(buf_f32));
+            }
         }
-        Ok(Vector(values))
+        Ok(Vector::from(values))
     }
 
     fn accepts(ty: &tokio_postgres::types::Type) -> bool {
-        ty.name() == "vector"
+        VECTOR_TYPES.contains(&ty.name())
+    }
+}
+
+/// Checks if an error is a violation of a unique constraint
+pub fn is_unique_violation(err: &tokio_postgres::Error) -> bool {
+    err.code() == Some(&SqlState::UNIQUE_VIOLATION)
+}
+
+/// `UPDATE` statement builder
+///
+/// The parameters are numbered in the order the columns are set, and the key is the last
+/// parameter. NB: the table and columns are static, so that they are safe to interpolate.
+pub struct UpdateBuilder<'a> {
+    /// Table
+    table: &'static str,
+    /// Updated columns
+    columns: Vec<&'static str>,
+    /// Parameters
+    params: Vec<&'a (dyn ToSql + Sync)>,
+}
+
+impl<'a> UpdateBuilder<'a> {
+    /// Creates a new builder
+    pub fn new(table: &'static str) -> Self {
+        Self {
+            table,
+            columns: vec![],
+            params: vec![],
+        }
+    }
+
+    /// Sets a column
+    pub fn set(mut self, column: &'static str, value: &'a (dyn ToSql + Sync)) -> Self {
+        self.columns.push(column);
+        self.params.push(value);
+        self
+    }
+
+    /// Sets a column if the value is defined
+    pub fn set_opt<T: ToSql + Sync>(self, column: &'static str, value: &'a Option<T>) -> Self {
+        match value {
+            Some(value) => self.set(column, value),
+            None => self,
+        }
+    }
+
+    /// Checks if no column is set
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+
+    /// Builds the statement (returning the updated row) and its parameters
+    pub fn build(
+        mut self,
+        key_column: &'static str,
+        key: &'a (dyn ToSql + Sync),
+    ) -> (String, Vec<&'a (dyn ToSql + Sync)>) {
+        let sets = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, col)| format!("{col} = ${}", i + 1))
+            .collect::<Vec<_>>();
+        self.params.push(key);
+        let stmt = format!(
+            "UPDATE {} SET {} WHERE {key_column} = ${} RETURNING *",
+            self.table,
+            sets.join(", "),
+            self.params.len()
+        );
+        (stmt, self.params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vector_halfvec() {
+        use tokio_postgres::types::{private::BytesMut, Kind, Type};
+
+        let vector = Vector::from(vec![0.5, -1.0, 0.1]);
+        for name in VECTOR_TYPES {
+            let ty = Type::new(name.to_string(), 0, Kind::Simple, "public".to_string());
+            let mut buf = BytesMut::new();
+            vector.to_sql(&ty, &mut buf).unwrap();
+            let value = Vector::from_sql(&ty, &buf).unwrap();
+            assert_eq!(value.as_ref()[..2], [0.5, -1.0]);
+            assert!((value.as_ref()[2] - 0.1).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_update_builder() {
+        let id = 1;
+        let name = Some("name".to_string());
+        let email: Option<String> = None;
+        let update = UpdateBuilder::new("users")
+            .set_opt("name", &name)
+            .set_opt("email", &email)
+            .set("password", &"pwd");
+        assert!(!update.is_empty());
+        let (stmt, params) = update.build("id", &id);
+        assert_eq!(
+            stmt,
+            "UPDATE users SET name = $1, password = $2 WHERE id = $3 RETURNING *"
+        );
+        assert_eq!(params.len(), 3);
+        assert!(UpdateBuilder::new("users")
+            .set_opt("email", &email)
+            .is_empty());
     }
 }
@@ -0,0 +1,245 @@
+//! Feeds
+
+use rusqlite::{params, params_from_iter, Connection, Row};
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        page::{keyset, keyset_condition, keyset_cursor, keyset_order_by, paginate, Page},
+        postgres::feed::feed_key_value,
+    },
+    error::Error,
+    mdl::{query::ListOptions, Feed, FeedUpdate},
+};
+
+use super::{is_unique_violation, parse_uuid, SqliteClient};
+
+impl TryFrom<&Row<'_>> for Feed {
+    type Error = rusqlite::Error;
+
+    fn try_from(value: &Row<'_>) -> Result<Self, Self::Error> {
+        Ok(Feed {
+            id: parse_uuid(value.get("id")?)?,
+            user_id: parse_uuid(value.get("user_id")?)?,
+            url: value.get("url")?,
+            name: value.get("name")?,
+        })
+    }
+}
+
+/// Sort keys of the feeds
+const FEED_KEYS: &[(&str, &str)] = &[("name", "COALESCE(name, '')"), ("url", "url")];
+
+/// Tiebreaker key of the feeds
+const FEED_TIEBREAKER: (&str, &str) = ("id", "id");
+
+impl SqliteClient {
+    /// Reads all user feeds for a user
+    pub async fn read_user_feeds(&self, user_id: Uuid) -> Result<Vec<Feed>, Error> {
+        self.run(move |conn| read_feeds(conn, user_id)).await
+    }
+
+    /// Reads the feeds of all the users
+    pub async fn read_feeds(&self) -> Result<Vec<Feed>, Error> {
+        self.run(move |conn| {
+            let mut stmt = conn.prepare("SELECT * FROM feeds ORDER BY user_id, id")?;
+            let feeds = stmt
+                .query_map([], |row| Feed::try_from(row))?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(feeds)
+        })
+        .await
+    }
+
+    /// Queries the feeds of a user
+    ///
+    /// Feeds can be filtered on and sorted by `name` and `url`. If a limit is set, the feeds
+    /// are paginated with a keyset cursor.
+    pub async fn query_user_feeds(
+        &self,
+        user_id: Uuid,
+        opts: &ListOptions,
+    ) -> Result<Page<Feed>, Error> {
+        let opts = opts.clone();
+        self.run(move |conn| {
+            let keys = keyset(&opts.sort, FEED_KEYS, FEED_TIEBREAKER);
+            let cursor = keyset_cursor(&keys, opts.cursor.as_deref())?;
+
+            let mut conditions = vec!["user_id = ?1".to_string()];
+            let mut params = vec![user_id.to_string()];
+            if let Some(name) = opts.filters.get("name") {
+                params.push(name.clone());
+                conditions.push(format!("name = ?{}", params.len()));
+            }
+            if let Some(url) = opts.filters.get("url") {
+                params.push(url.clone());
+                conditions.push(format!("url = ?{}", params.len()));
+            }
+            if let Some(cursor) = cursor {
+                conditions.push(keyset_condition(&keys, params.len() + 1, |i| {
+                    format!("?{i}")
+                }));
+                params.extend(cursor.0);
+            }
+            let mut sql = format!(
+                "SELECT * FROM feeds WHERE {} {}",
+                conditions.join(" AND "),
+                keyset_order_by(&keys)
+            );
+            if let Some(limit) = opts.limit {
+                sql.push_str(&format!(" LIMIT {}", limit + 1));
+            }
+
+            let mut stmt = conn.prepare(&sql)?;
+            let feeds = stmt
+                .query_map(params_from_iter(params), |row| Feed::try_from(row))?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(paginate(feeds, opts.limit, &keys, feed_key_value))
+        })
+        .await
+    }
+
+    /// Replaces the feeds of a user
+    ///
+    /// A user cannot have several feeds with the same URL.
+    pub async fn sync_user_feeds(
+        &self,
+        user_id: Uuid,
+        feeds: Vec<FeedUpdate>,
+    ) -> Result<Vec<Feed>, Error> {
+        self.run(move |conn| {
+            let trx = conn.transaction()?;
+            let new_feeds = sync_feeds(&trx, user_id, feeds)?;
+            trx.commit()?;
+            Ok(new_feeds)
+        })
+        .await
+    }
+
+    /// Delete all user feeds
+    pub async fn delete_user_feeds(&self, user_id: Uuid) -> Result<(), Error> {
+        self.run(move |conn| delete_feeds(conn, user_id)).await
+    }
+}
+
+/// Sync all the user feeds with a connection (or a transaction)
+pub(super) fn sync_feeds(
+    conn: &Connection,
+    user_id: Uuid,
+    feeds: Vec<FeedUpdate>,
+) -> Result<Vec<Feed>, Error> {
+    let _res = conn.execute(
+        "DELETE FROM feeds WHERE user_id = ?1",
+        [user_id.to_string()],
+    )?;
+
+    let mut new_feeds = vec![];
+    for f in feeds {
+        let id = f.id.unwrap_or_else(Uuid::new_v4);
+        let feed = conn
+            .query_row(
+                "INSERT into feeds (id, user_id, url, name) VALUES (?1, ?2, ?3, ?4) RETURNING *",
+                params![id.to_string(), user_id.to_string(), f.url, f.name],
+                |row| Feed::try_from(row),
+            )
+            .map_err(|err| {
+                if is_unique_violation(&err) {
+                    Error::InvalidRequest("duplicate feed URLs".to_string(), None)
+                } else {
+                    err.into()
+                }
+            })?;
+        new_feeds.push(feed);
+    }
+    Ok(new_feeds)
+}
+
+/// Reads all the user feeds with a connection (or a transaction)
+pub(super) fn read_feeds(conn: &Connection, user_id: Uuid) -> Result<Vec<Feed>, Error> {
+    let mut stmt = conn.prepare("SELECT * FROM feeds WHERE user_id = ?1")?;
+    let feeds = stmt
+        .query_map([user_id.to_string()], |row| Feed::try_from(row))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(feeds)
+}
+
+/// Delete all the user feeds with a connection (or a transaction)
+pub(super) fn delete_feeds(conn: &Connection, user_id: Uuid) -> Result<(), Error> {
+    let _res = conn.execute(
+        "DELETE FROM feeds WHERE user_id = ?1",
+        [user_id.to_string()],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::db::sqlite::user::tests::setup_test_user;
+
+    #[tokio::test]
+    async fn test_sync_query_feeds() {
+        let (db, user) = setup_test_user().await;
+        let feeds = db
+            .sync_user_feeds(
+                user.id,
+                vec![
+                    FeedUpdate {
+                        id: None,
+                        url: "https://ai.googleblog.com/atom.xml".to_string(),
+                        name: Some("my feed".to_string()),
+                    },
+                    FeedUpdate {
+                        id: Some(Uuid::new_v4()),
+                        url: "https://ai.googleblog.com/atom2.xml".to_string(),
+                        name: None,
+                    },
+                ],
+            )
+            .await
+            .unwrap();
+        assert_eq!(feeds.len(), 2);
+        assert_eq!(db.read_user_feeds(user.id).await.unwrap().len(), 2);
+
+        let mut opts = ListOptions::default();
+        opts.filters
+            .insert("name".to_string(), "my feed".to_string());
+        let page = db.query_user_feeds(user.id, &opts).await.unwrap();
+        assert_eq!(page.items.len(), 1);
+
+        // paginate
+        let mut opts = ListOptions {
+            limit: Some(1),
+            ..Default::default()
+        };
+        let page = db.query_user_feeds(user.id, &opts).await.unwrap();
+        assert_eq!(page.items.len(), 1);
+        opts.cursor = page.next_cursor.map(|c| c.encode());
+        assert!(opts.cursor.is_some());
+        let next_page = db.query_user_feeds(user.id, &opts).await.unwrap();
+        assert_eq!(next_page.items.len(), 1);
+        assert_ne!(next_page.items[0].id, page.items[0].id);
+        assert!(next_page.next_cursor.is_none());
+
+        db.delete_user_feeds(user.id).await.unwrap();
+        assert!(db.read_user_feeds(user.id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_delete_user_cascade() {
+        let (db, user) = setup_test_user().await;
+        db.sync_user_feeds(
+            user.id,
+            vec![FeedUpdate {
+                id: None,
+                url: "https://ai.googleblog.com/atom.xml".to_string(),
+                name: None,
+            }],
+        )
+        .await
+        .unwrap();
+        db.delete_user(user.id).await.unwrap();
+        assert!(db.read_user_feeds(user.id).await.unwrap().is_empty());
+    }
+}
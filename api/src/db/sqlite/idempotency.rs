@@ -0,0 +1,242 @@
+//! Idempotency keys
+
+use rusqlite::{params, OptionalExtension, Row};
+use time::OffsetDateTime;
+
+use crate::{error::Error, mdl::IdempotentResponse};
+
+use super::SqliteClient;
+
+impl TryFrom<&Row<'_>> for IdempotentResponse {
+    type Error = rusqlite::Error;
+
+    fn try_from(value: &Row<'_>) -> Result<Self, Self::Error> {
+        let created_at: i64 = value.get("created_at")?;
+        Ok(IdempotentResponse {
+            key: value.get("key")?,
+            scope: value.get("scope")?,
+            status: value.get("status")?,
+            content_type: value.get("content_type")?,
+            body: value.get("body")?,
+            created_at: OffsetDateTime::from_unix_timestamp(created_at).map_err(|err| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    0,
+                    rusqlite::types::Type::Integer,
+                    Box::new(err),
+                )
+            })?,
+            request_hash: value.get("request_hash")?,
+            completed: value.get("completed")?,
+        })
+    }
+}
+
+impl SqliteClient {
+    /// Reads a stored response created after a given time
+    pub async fn read_idempotent_response(
+        &self,
+        key: &str,
+        scope: &str,
+        since: OffsetDateTime,
+    ) -> Result<Option<IdempotentResponse>, Error> {
+        let (key, scope) = (key.to_string(), scope.to_string());
+        self.run(move |conn| {
+            Ok(conn
+                .query_row(
+                    "SELECT * FROM idempotency_keys WHERE key = ?1 AND scope = ?2 AND created_at > ?3",
+                    params![key, scope, since.unix_timestamp()],
+                    |row| IdempotentResponse::try_from(row),
+                )
+                .optional()?)
+        })
+        .await
+    }
+
+    /// Stores a response
+    ///
+    /// If a response is already stored for the same key, it is replaced.
+    pub async fn upsert_idempotent_response(
+        &self,
+        response: &IdempotentResponse,
+    ) -> Result<(), Error> {
+        let response = response.clone();
+        self.run(move |conn| {
+            let _res = conn.execute(
+                "
+                INSERT INTO idempotency_keys
+                    (key, scope, status, body, created_at, request_hash, completed, content_type)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+                ON CONFLICT (key, scope) DO UPDATE
+                SET status = ?3, body = ?4, created_at = ?5, request_hash = ?6, completed = ?7,
+                    content_type = ?8
+                ",
+                params![
+                    response.key,
+                    response.scope,
+                    response.status,
+                    response.body,
+                    response.created_at.unix_timestamp(),
+                    response.request_hash,
+                    response.completed,
+                    response.content_type
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Reserves a key while its request is running
+    ///
+    /// Returns `false` if the key is already used (see [crate::db::Store::reserve_idempotency_key]).
+    pub async fn reserve_idempotency_key(
+        &self,
+        pending: &IdempotentResponse,
+        expired_before: OffsetDateTime,
+        stale_before: OffsetDateTime,
+    ) -> Result<bool, Error> {
+        let pending = pending.clone();
+        self.run(move |conn| {
+            // NB: the conflicting row is only replaced if it has expired
+            Ok(conn
+                .query_row(
+                    "
+                    INSERT INTO idempotency_keys
+                        (key, scope, status, body, created_at, request_hash, completed, content_type)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+                    ON CONFLICT (key, scope) DO UPDATE
+                    SET status = ?3, body = ?4, created_at = ?5, request_hash = ?6, completed = ?7,
+                        content_type = ?8
+                    WHERE idempotency_keys.created_at <= ?9
+                        OR (NOT idempotency_keys.completed AND idempotency_keys.created_at <= ?10)
+                    RETURNING key
+                    ",
+                    params![
+                        pending.key,
+                        pending.scope,
+                        pending.status,
+                        pending.body,
+                        pending.created_at.unix_timestamp(),
+                        pending.request_hash,
+                        pending.completed,
+                        pending.content_type,
+                        expired_before.unix_timestamp(),
+                        stale_before.unix_timestamp()
+                    ],
+                    |_row| Ok(()),
+                )
+                .optional()?
+                .is_some())
+        })
+        .await
+    }
+
+    /// Deletes the response (or the reservation) of a key
+    pub async fn delete_idempotent_response(&self, key: &str, scope: &str) -> Result<(), Error> {
+        let (key, scope) = (key.to_string(), scope.to_string());
+        self.run(move |conn| {
+            let _res = conn.execute(
+                "DELETE FROM idempotency_keys WHERE key = ?1 AND scope = ?2",
+                params![key, scope],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Deletes the responses stored before a given time
+    pub async fn delete_idempotent_responses_before(
+        &self,
+        before: OffsetDateTime,
+    ) -> Result<u64, Error> {
+        self.run(move |conn| {
+            Ok(conn.execute(
+                "DELETE FROM idempotency_keys WHERE created_at <= ?1",
+                [before.unix_timestamp()],
+            )? as u64)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::db::sqlite::tests::init_db;
+
+    #[tokio::test]
+    async fn test_upsert_read() {
+        let db = init_db().await;
+        let now = OffsetDateTime::now_utc();
+        let response = IdempotentResponse {
+            key: "key".to_string(),
+            scope: "POST /summaries".to_string(),
+            status: 200,
+            content_type: None,
+            body: b"{}".to_vec(),
+            created_at: now,
+            request_hash: String::new(),
+            completed: true,
+        };
+        db.upsert_idempotent_response(&response).await.unwrap();
+
+        let since = now - time::Duration::minutes(1);
+        let stored = db
+            .read_idempotent_response(&response.key, &response.scope, since)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.status, 200);
+        assert_eq!(stored.body, response.body);
+
+        let deleted = db
+            .delete_idempotent_responses_before(now + time::Duration::minutes(1))
+            .await
+            .unwrap();
+        assert_eq!(deleted, 1);
+    }
+
+    #[tokio::test]
+    async fn test_reserve() {
+        let db = init_db().await;
+        let now = OffsetDateTime::now_utc();
+        let pending = IdempotentResponse {
+            key: "key".to_string(),
+            scope: "POST /summaries".to_string(),
+            status: 0,
+            content_type: None,
+            body: vec![],
+            created_at: now,
+            request_hash: "hash".to_string(),
+            completed: false,
+        };
+        let (expired_before, stale_before) = (
+            now - time::Duration::days(1),
+            now - time::Duration::minutes(5),
+        );
+        assert!(db
+            .reserve_idempotency_key(&pending, expired_before, stale_before)
+            .await
+            .unwrap());
+        assert!(!db
+            .reserve_idempotency_key(&pending, expired_before, stale_before)
+            .await
+            .unwrap());
+
+        // NB: a stale reservation is replaced
+        assert!(db
+            .reserve_idempotency_key(&pending, expired_before, now)
+            .await
+            .unwrap());
+
+        db.delete_idempotent_response(&pending.key, &pending.scope)
+            .await
+            .unwrap();
+        let stored = db
+            .read_idempotent_response(&pending.key, &pending.scope, expired_before)
+            .await
+            .unwrap();
+        assert!(stored.is_none());
+    }
+}
@@ -0,0 +1,368 @@
+//! Background jobs
+
+use rusqlite::{params, params_from_iter, OptionalExtension, Row};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{
+    error::Error,
+    mdl::{Job, JobCount, JobStatus},
+};
+
+use super::{in_list, parse_uuid, SqliteClient};
+
+/// Parses a unix timestamp
+fn parse_time(value: i64) -> rusqlite::Result<OffsetDateTime> {
+    OffsetDateTime::from_unix_timestamp(value).map_err(|err| {
+        rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Integer, Box::new(err))
+    })
+}
+
+impl TryFrom<&Row<'_>> for Job {
+    type Error = rusqlite::Error;
+
+    fn try_from(value: &Row<'_>) -> Result<Self, Self::Error> {
+        let payload: String = value.get("payload")?;
+        let status: String = value.get("status")?;
+        let owner_id: Option<String> = value.get("owner_id")?;
+        let lease_id: Option<String> = value.get("lease_id")?;
+        Ok(Job {
+            id: parse_uuid(value.get("id")?)?,
+            kind: value.get("kind")?,
+            payload: serde_json::from_str(&payload).map_err(|err| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    0,
+                    rusqlite::types::Type::Text,
+                    Box::new(err),
+                )
+            })?,
+            // NB: an unknown status is not run again
+            status: status.parse().unwrap_or(JobStatus::Dead),
+            owner_id: owner_id.map(parse_uuid).transpose()?,
+            lease_id: lease_id.map(parse_uuid).transpose()?,
+            attempts: value.get("attempts")?,
+            max_attempts: value.get("max_attempts")?,
+            last_error: value.get("last_error")?,
+            run_at: parse_time(value.get("run_at")?)?,
+            created_at: parse_time(value.get("created_at")?)?,
+        })
+    }
+}
+
+/// Checks that a job update has matched the lease of the worker
+fn check_lease(count: usize) -> Result<(), Error> {
+    if count == 0 {
+        return Err(Error::Conflict(
+            "the job lease is not held anymore".to_string(),
+            None,
+        ));
+    }
+    Ok(())
+}
+
+impl SqliteClient {
+    /// Enqueues a job
+    pub async fn enqueue_job(&self, job: &Job) -> Result<(), Error> {
+        let job = job.clone();
+        self.run(move |conn| {
+            conn.execute(
+                "
+                INSERT INTO jobs (id, kind, payload, status, owner_id, attempts, max_attempts, run_at, created_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+                ",
+                params![
+                    job.id.to_string(),
+                    job.kind,
+                    job.payload.to_string(),
+                    job.status.as_str(),
+                    job.owner_id.map(|id| id.to_string()),
+                    job.attempts,
+                    job.max_attempts,
+                    job.run_at.unix_timestamp(),
+                    job.created_at.unix_timestamp(),
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Claims the jobs which are due
+    ///
+    /// The claimed jobs are marked as running until `locked_until` with a new lease, and their
+    /// number of attempts is incremented.
+    pub async fn claim_jobs(
+        &self,
+        kinds: &[&str],
+        limit: usize,
+        locked_until: OffsetDateTime,
+    ) -> Result<Vec<Job>, Error> {
+        if kinds.is_empty() {
+            return Ok(vec![]);
+        }
+        let kinds = kinds.iter().map(|k| k.to_string()).collect::<Vec<_>>();
+        self.run(move |conn| {
+            let trx = conn.transaction()?;
+            let now = OffsetDateTime::now_utc().unix_timestamp();
+            let ids = {
+                let mut stmt = trx.prepare(&format!(
+                    "
+                    SELECT id FROM jobs
+                    WHERE kind IN ({})
+                    AND (
+                        (status = 'pending' AND run_at <= ?1)
+                        OR (status = 'running' AND locked_until < ?1)
+                    )
+                    ORDER BY run_at
+                    LIMIT {limit}
+                    ",
+                    in_list(2, kinds.len())
+                ))?;
+                let mut values: Vec<rusqlite::types::Value> = vec![now.into()];
+                values.extend(kinds.into_iter().map(|k| k.into()));
+                stmt.query_map(params_from_iter(values), |row| row.get::<_, String>(0))?
+                    .collect::<Result<Vec<_>, _>>()?
+            };
+
+            let mut jobs = vec![];
+            for id in ids {
+                jobs.push(trx.query_row(
+                    "
+                    UPDATE jobs
+                    SET status = 'running', attempts = attempts + 1, locked_until = ?2, lease_id = ?3
+                    WHERE id = ?1
+                    RETURNING *
+                    ",
+                    params![
+                        id,
+                        locked_until.unix_timestamp(),
+                        Uuid::new_v4().to_string()
+                    ],
+                    |row| Job::try_from(row),
+                )?);
+            }
+            trx.commit()?;
+            Ok(jobs)
+        })
+        .await
+    }
+
+    /// Reads a job
+    pub async fn read_job(&self, id: Uuid) -> Result<Option<Job>, Error> {
+        self.run(move |conn| {
+            Ok(conn
+                .query_row(
+                    "SELECT * FROM jobs WHERE id = ?1",
+                    params![id.to_string()],
+                    |row| Job::try_from(row),
+                )
+                .optional()?)
+        })
+        .await
+    }
+
+    /// Extends the lease of a running job
+    pub async fn renew_job(
+        &self,
+        id: Uuid,
+        lease_id: Uuid,
+        locked_until: OffsetDateTime,
+    ) -> Result<(), Error> {
+        self.run(move |conn| {
+            let count = conn.execute(
+                "
+                UPDATE jobs SET locked_until = ?3
+                WHERE id = ?1 AND lease_id = ?2 AND status = 'running'
+                ",
+                params![
+                    id.to_string(),
+                    lease_id.to_string(),
+                    locked_until.unix_timestamp()
+                ],
+            )?;
+            check_lease(count)
+        })
+        .await
+    }
+
+    /// Marks a job as completed
+    pub async fn complete_job(&self, id: Uuid, lease_id: Uuid) -> Result<(), Error> {
+        self.run(move |conn| {
+            let count = conn.execute(
+                "
+                UPDATE jobs SET status = 'done', locked_until = NULL, lease_id = NULL
+                WHERE id = ?1 AND lease_id = ?2 AND status = 'running'
+                ",
+                params![id.to_string(), lease_id.to_string()],
+            )?;
+            check_lease(count)
+        })
+        .await
+    }
+
+    /// Records the failure of a job
+    ///
+    /// The job is retried at `retry_at`, or dead-lettered if `None`.
+    pub async fn fail_job(
+        &self,
+        id: Uuid,
+        lease_id: Uuid,
+        error: &str,
+        retry_at: Option<OffsetDateTime>,
+    ) -> Result<(), Error> {
+        let error = error.to_string();
+        let status = match retry_at {
+            Some(_) => JobStatus::Pending,
+            None => JobStatus::Dead,
+        };
+        self.run(move |conn| {
+            let count = conn.execute(
+                "
+                UPDATE jobs
+                SET status = ?3, last_error = ?4, run_at = COALESCE(?5, run_at),
+                    locked_until = NULL, lease_id = NULL
+                WHERE id = ?1 AND lease_id = ?2 AND status = 'running'
+                ",
+                params![
+                    id.to_string(),
+                    lease_id.to_string(),
+                    status.as_str(),
+                    error,
+                    retry_at.map(|t| t.unix_timestamp())
+                ],
+            )?;
+            check_lease(count)
+        })
+        .await
+    }
+
+    /// Deletes the completed jobs which have been created before a date
+    ///
+    /// Returns the number of deleted jobs.
+    pub async fn delete_jobs_done_before(&self, before: OffsetDateTime) -> Result<u64, Error> {
+        self.run(move |conn| {
+            let count = conn.execute(
+                "DELETE FROM jobs WHERE status = 'done' AND created_at < ?1",
+                params![before.unix_timestamp()],
+            )?;
+            Ok(count as u64)
+        })
+        .await
+    }
+
+    /// Counts the jobs by status
+    pub async fn count_jobs(&self) -> Result<Vec<JobCount>, Error> {
+        self.run(move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT status, COUNT(*) AS count FROM jobs GROUP BY status ORDER BY status",
+            )?;
+            let counts = stmt
+                .query_map([], |row| {
+                    Ok(JobCount {
+                        status: row
+                            .get::<_, String>("status")?
+                            .parse()
+                            .unwrap_or(JobStatus::Dead),
+                        count: row.get::<_, i64>("count")? as u64,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(counts)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_jobs() {
+        let client = SqliteClient::open(":memory:").unwrap();
+        client.init_schema().await.unwrap();
+
+        let owner_id = Uuid::new_v4();
+        let job = Job::new("test", serde_json::json!({ "n": 1 }), 2).owner(owner_id);
+        client.enqueue_job(&job).await.unwrap();
+        let other = Job::new("other", serde_json::json!({}), 2);
+        client.enqueue_job(&other).await.unwrap();
+
+        let locked_until = OffsetDateTime::now_utc() + time::Duration::minutes(1);
+        let claimed = client
+            .claim_jobs(&["test"], 10, locked_until)
+            .await
+            .unwrap();
+        assert_eq!(claimed.len(), 1);
+        assert_eq!(claimed[0].id, job.id);
+        assert_eq!(claimed[0].status, JobStatus::Running);
+        assert_eq!(claimed[0].attempts, 1);
+        assert_eq!(claimed[0].payload, job.payload);
+        assert_eq!(claimed[0].owner_id, Some(owner_id));
+        let lease_id = claimed[0].lease_id.unwrap();
+        client
+            .renew_job(job.id, lease_id, locked_until)
+            .await
+            .unwrap();
+
+        // NB: a running job is not claimed again
+        let claimed = client
+            .claim_jobs(&["test"], 10, locked_until)
+            .await
+            .unwrap();
+        assert!(claimed.is_empty());
+
+        // retry now
+        client
+            .fail_job(job.id, lease_id, "failed", Some(OffsetDateTime::now_utc()))
+            .await
+            .unwrap();
+        let claimed = client
+            .claim_jobs(&["test"], 10, locked_until)
+            .await
+            .unwrap();
+        assert_eq!(claimed[0].attempts, 2);
+        assert_eq!(claimed[0].last_error.as_deref(), Some("failed"));
+
+        // NB: the lease of the previous attempt is not held anymore
+        assert!(matches!(
+            client.complete_job(job.id, lease_id).await,
+            Err(Error::Conflict(_, _))
+        ));
+
+        // dead-letter
+        let lease_id = claimed[0].lease_id.unwrap();
+        client
+            .fail_job(job.id, lease_id, "failed", None)
+            .await
+            .unwrap();
+        let claimed = client
+            .claim_jobs(&["test"], 10, locked_until)
+            .await
+            .unwrap();
+        assert!(claimed.is_empty());
+
+        // complete
+        let claimed = client
+            .claim_jobs(&["other"], 10, locked_until)
+            .await
+            .unwrap();
+        let lease_id = claimed[0].lease_id.unwrap();
+        client.complete_job(other.id, lease_id).await.unwrap();
+        let done = client.read_job(other.id).await.unwrap().unwrap();
+        assert_eq!(done.status, JobStatus::Done);
+
+        let counts = client.count_jobs().await.unwrap();
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts[0].status, JobStatus::Dead);
+        assert_eq!(counts[1].status, JobStatus::Done);
+
+        let count = client
+            .delete_jobs_done_before(OffsetDateTime::now_utc() + time::Duration::minutes(1))
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+        assert!(client.read_job(other.id).await.unwrap().is_none());
+        assert!(client.read_job(job.id).await.unwrap().is_some());
+    }
+}
@@ -0,0 +1,25 @@
+//! Maintenance
+
+use crate::{error::Error, mdl::VectorIndexReport};
+
+use super::SqliteClient;
+
+impl SqliteClient {
+    /// Measures the recall of the vector index
+    ///
+    /// NB: the vector search is a brute force scan, so its recall is exact.
+    pub async fn check_vector_index(
+        &self,
+        _sample: usize,
+        k: usize,
+    ) -> Result<VectorIndexReport, Error> {
+        Ok(VectorIndexReport::new(false, k, &[]))
+    }
+
+    /// Rebuilds the vector index
+    ///
+    /// NB: there is no vector index, so there is nothing to rebuild.
+    pub async fn reindex_vectors(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
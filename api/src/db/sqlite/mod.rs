@@ -0,0 +1,613 @@
+//! SQLite DB
+//!
+//! The queries are run on a blocking thread, with a single connection shared behind a mutex.
+//! NB: an ongoing transaction holds the connection, so the other queries wait for it to finish.
+
+use std::sync::Arc;
+
+use rusqlite::Connection;
+use salvo::async_trait;
+use time::OffsetDateTime;
+use tokio::sync::{broadcast, Mutex};
+use uuid::Uuid;
+
+use crate::{
+    error::Error,
+    mdl::{
+        query::ListOptions, DailyCount, Event, Feed, FeedCount, FeedUpdate, IdempotentResponse,
+        Job, JobCount, NewUser, SubscriptionUpdate, Summary, User, UserUpdate, Vector,
+        VectorIndexReport,
+    },
+};
+
+use super::{metrics::DbMetricsReport, page::Page, Store, StoreTx, EVENTS_CAPACITY};
+
+pub mod feed;
+pub mod idempotency;
+pub mod jobs;
+pub mod maintenance;
+pub mod stats;
+pub mod summary;
+pub mod tx;
+pub mod user;
+
+/// Schema version (stored in the `user_version` pragma)
+const SCHEMA_VERSION: i32 = 7;
+
+/// DB schema
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS users (
+    id              TEXT PRIMARY KEY,
+    name            TEXT NOT NULL,
+    email           TEXT NOT NULL UNIQUE,
+    password        TEXT NOT NULL,
+    subscription    TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS feeds (
+    id          TEXT PRIMARY KEY,
+    user_id     TEXT NOT NULL,
+    url         TEXT NOT NULL,
+    name        TEXT,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    UNIQUE (user_id, url)
+);
+
+CREATE TABLE IF NOT EXISTS summaries (
+    id          TEXT PRIMARY KEY,
+    url         TEXT NOT NULL UNIQUE,
+    summary     TEXT,
+    keywords    TEXT,
+    embeddings  BLOB,
+    created_at  INTEGER NOT NULL DEFAULT (unixepoch()),
+    accessed_at INTEGER NOT NULL DEFAULT (unixepoch())
+);
+
+CREATE INDEX IF NOT EXISTS summaries_created_at_idx ON summaries (created_at);
+CREATE INDEX IF NOT EXISTS summaries_accessed_at_idx ON summaries (accessed_at);
+
+CREATE TABLE IF NOT EXISTS idempotency_keys (
+    key         TEXT NOT NULL,
+    scope       TEXT NOT NULL,
+    status      INTEGER NOT NULL,
+    content_type TEXT,
+    body        BLOB NOT NULL,
+    created_at  INTEGER NOT NULL,
+    request_hash TEXT NOT NULL DEFAULT '',
+    completed   INTEGER NOT NULL DEFAULT 1,
+    PRIMARY KEY (key, scope)
+);
+
+CREATE TABLE IF NOT EXISTS jobs (
+    id              TEXT PRIMARY KEY,
+    kind            TEXT NOT NULL,
+    payload         TEXT NOT NULL,
+    status          TEXT NOT NULL,
+    owner_id        TEXT,
+    lease_id        TEXT,
+    attempts        INTEGER NOT NULL DEFAULT 0,
+    max_attempts    INTEGER NOT NULL,
+    last_error      TEXT,
+    run_at          INTEGER NOT NULL,
+    locked_until    INTEGER,
+    created_at      INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS jobs_status_run_at_idx ON jobs (status, run_at);
+";
+
+/// Upgrades of a DB created with a previous schema version, by ascending version
+///
+/// NB: the tables of a previous version are not altered by the `CREATE TABLE IF NOT EXISTS`
+/// statements of the schema, so the new columns are added here. The constraints cannot be
+/// altered, so their table is rebuilt (the feeds of deleted users are dropped in version 6).
+const SCHEMA_UPGRADES: &[(i32, &str)] = &[
+    (
+        2,
+        "
+        ALTER TABLE summaries ADD COLUMN created_at INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE summaries ADD COLUMN accessed_at INTEGER NOT NULL DEFAULT 0;
+        UPDATE summaries SET created_at = unixepoch(), accessed_at = unixepoch();
+        ",
+    ),
+    (
+        4,
+        "
+        ALTER TABLE idempotency_keys ADD COLUMN request_hash TEXT NOT NULL DEFAULT '';
+        ALTER TABLE idempotency_keys ADD COLUMN completed INTEGER NOT NULL DEFAULT 1;
+        ",
+    ),
+    (
+        5,
+        "
+        ALTER TABLE idempotency_keys ADD COLUMN content_type TEXT;
+        ",
+    ),
+    (
+        6,
+        "
+        CREATE TABLE feeds_new (
+            id          TEXT PRIMARY KEY,
+            user_id     TEXT NOT NULL,
+            url         TEXT NOT NULL,
+            name        TEXT,
+            FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+            UNIQUE (user_id, url)
+        );
+        INSERT INTO feeds_new (id, user_id, url, name)
+            SELECT id, user_id, url, name FROM feeds WHERE user_id IN (SELECT id FROM users);
+        DROP TABLE feeds;
+        ALTER TABLE feeds_new RENAME TO feeds;
+        ",
+    ),
+    (
+        7,
+        "
+        CREATE TABLE IF NOT EXISTS jobs (
+            id              TEXT PRIMARY KEY,
+            kind            TEXT NOT NULL,
+            payload         TEXT NOT NULL,
+            status          TEXT NOT NULL,
+            attempts        INTEGER NOT NULL DEFAULT 0,
+            max_attempts    INTEGER NOT NULL,
+            last_error      TEXT,
+            run_at          INTEGER NOT NULL,
+            locked_until    INTEGER,
+            created_at      INTEGER NOT NULL
+        );
+        ALTER TABLE jobs ADD COLUMN owner_id TEXT;
+        ALTER TABLE jobs ADD COLUMN lease_id TEXT;
+        ",
+    ),
+];
+
+/// SQLite DB
+#[derive(Debug, Clone)]
+pub struct SqliteClient {
+    /// Connection
+    conn: Arc<Mutex<Connection>>,
+    /// Events
+    ///
+    /// NB: a SQLite DB is used by a single process, so the events are only sent in-process.
+    events: broadcast::Sender<Event>,
+}
+
+impl SqliteClient {
+    /// Opens a DB file (created if missing)
+    ///
+    /// The path `:memory:` opens an in-memory DB.
+    pub fn open(path: &str) -> Result<Self, Error> {
+        let conn = if path == ":memory:" {
+            Connection::open_in_memory()?
+        } else {
+            Connection::open(path)?
+        };
+        conn.execute_batch("PRAGMA foreign_keys = ON; PRAGMA journal_mode = WAL;")?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+            events: broadcast::channel(EVENTS_CAPACITY).0,
+        })
+    }
+
+    /// Runs a function with the connection on a blocking thread
+    async fn run<T, F>(&self, f: F) -> Result<T, Error>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T, Error> + Send + 'static,
+    {
+        let mut conn = self.conn.clone().lock_owned().await;
+        tokio::task::spawn_blocking(move || f(&mut conn))
+            .await
+            .map_err(|err| Error::Internal(format!("failed to run query ({err})"), None))?
+    }
+
+    /// Initializes the DB schema
+    pub async fn init_schema(&self) -> Result<(), Error> {
+        self.run(|conn| {
+            let trx = conn.transaction()?;
+            let version: i32 = trx.pragma_query_value(None, "user_version", |row| row.get(0))?;
+            // NB: a new DB has the version 0
+            if version > 0 {
+                for (_, sql) in SCHEMA_UPGRADES.iter().filter(|(v, _)| *v > version) {
+                    trx.execute_batch(sql)?;
+                }
+            }
+            trx.execute_batch(SCHEMA)?;
+            trx.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+            trx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Subscribes to the events
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.events.subscribe()
+    }
+
+    /// Checks that the connection can be used
+    pub async fn ping(&self) -> Result<(), Error> {
+        self.run(|conn| {
+            conn.query_row("SELECT 1", [], |_row| Ok(()))?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Checks if the DB schema is initialized
+    pub async fn is_schema_init(&self) -> Result<bool, Error> {
+        self.run(|conn| {
+            let version: i32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+            Ok(version == SCHEMA_VERSION)
+        })
+        .await
+    }
+}
+
+/// Maximum number of values of an `IN` list
+///
+/// NB: SQLite caps the number of parameters of a statement, so longer lists are split.
+const IN_LIST_CHUNK_SIZE: usize = 500;
+
+/// Returns the placeholders of an `IN` list of `len` values, numbered from `first`
+fn in_list(first: usize, len: usize) -> String {
+    (first..first + len)
+        .map(|i| format!("?{i}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Checks if an error is a violation of a unique constraint
+fn is_unique_violation(err: &rusqlite::Error) -> bool {
+    match err {
+        rusqlite::Error::SqliteFailure(err, _) => matches!(
+            err.extended_code,
+            rusqlite::ffi::SQLITE_CONSTRAINT_UNIQUE | rusqlite::ffi::SQLITE_CONSTRAINT_PRIMARYKEY
+        ),
+        _ => false,
+    }
+}
+
+/// Parses a UUID stored as text
+fn parse_uuid(value: String) -> rusqlite::Result<Uuid> {
+    Uuid::parse_str(&value).map_err(|err| {
+        rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(err))
+    })
+}
+
+#[async_trait]
+impl Store for SqliteClient {
+    async fn init_schema(&self) -> Result<(), Error> {
+        SqliteClient::init_schema(self).await
+    }
+
+    async fn ping(&self) -> Result<(), Error> {
+        SqliteClient::ping(self).await
+    }
+
+    async fn is_schema_init(&self) -> Result<bool, Error> {
+        SqliteClient::is_schema_init(self).await
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<Event> {
+        SqliteClient::subscribe(self)
+    }
+
+    fn metrics(&self) -> Option<DbMetricsReport> {
+        None
+    }
+
+    async fn begin(&self) -> Result<Box<dyn StoreTx>, Error> {
+        Ok(Box::new(SqliteClient::begin(self).await?))
+    }
+
+    async fn create_user(&self, new_user: NewUser) -> Result<User, Error> {
+        SqliteClient::create_user(self, new_user).await
+    }
+
+    async fn read_user(&self, id: Uuid) -> Result<Option<User>, Error> {
+        SqliteClient::read_user(self, id).await
+    }
+
+    async fn read_users(&self) -> Result<Vec<User>, Error> {
+        SqliteClient::read_users(self).await
+    }
+
+    async fn read_user_with_email(&self, email: &str) -> Result<Option<User>, Error> {
+        SqliteClient::read_user_with_email(self, email).await
+    }
+
+    async fn update_user(&self, id: Uuid, fields: UserUpdate) -> Result<User, Error> {
+        SqliteClient::update_user(self, id, fields).await
+    }
+
+    async fn update_user_subscription(
+        &self,
+        id: Uuid,
+        subscription_update: SubscriptionUpdate,
+    ) -> Result<User, Error> {
+        SqliteClient::update_user_subscription(self, id, subscription_update).await
+    }
+
+    async fn delete_user(&self, id: Uuid) -> Result<(), Error> {
+        SqliteClient::delete_user(self, id).await
+    }
+
+    async fn read_feeds(&self) -> Result<Vec<Feed>, Error> {
+        SqliteClient::read_feeds(self).await
+    }
+
+    async fn read_user_feeds(&self, user_id: Uuid) -> Result<Vec<Feed>, Error> {
+        SqliteClient::read_user_feeds(self, user_id).await
+    }
+
+    async fn query_user_feeds(
+        &self,
+        user_id: Uuid,
+        opts: &ListOptions,
+    ) -> Result<Page<Feed>, Error> {
+        SqliteClient::query_user_feeds(self, user_id, opts).await
+    }
+
+    async fn sync_user_feeds(
+        &self,
+        user_id: Uuid,
+        feeds: Vec<FeedUpdate>,
+    ) -> Result<Vec<Feed>, Error> {
+        SqliteClient::sync_user_feeds(self, user_id, feeds).await
+    }
+
+    async fn delete_user_feeds(&self, user_id: Uuid) -> Result<(), Error> {
+        SqliteClient::delete_user_feeds(self, user_id).await
+    }
+
+    async fn read_summaries(&self) -> Result<Vec<Summary>, Error> {
+        SqliteClient::read_summaries(self).await
+    }
+
+    async fn read_summaries_page(
+        &self,
+        cursor: Option<&str>,
+        limit: usize,
+    ) -> Result<Page<Summary>, Error> {
+        SqliteClient::read_summaries_page(self, cursor, limit).await
+    }
+
+    async fn search_summaries_by_urls(&self, urls: &[&str]) -> Result<Vec<Summary>, Error> {
+        SqliteClient::search_summaries_by_urls(self, urls).await
+    }
+
+    async fn search_summaries_by_embeddings(
+        &self,
+        embeddings: &Vector,
+        limit: usize,
+    ) -> Result<Vec<Summary>, Error> {
+        SqliteClient::search_summaries_by_embeddings(self, embeddings, limit).await
+    }
+
+    async fn check_vector_index(
+        &self,
+        sample: usize,
+        k: usize,
+    ) -> Result<VectorIndexReport, Error> {
+        SqliteClient::check_vector_index(self, sample, k).await
+    }
+
+    async fn reindex_vectors(&self) -> Result<(), Error> {
+        SqliteClient::reindex_vectors(self).await
+    }
+
+    async fn insert_summaries(&self, summaries: Vec<Summary>) -> Result<Vec<Summary>, Error> {
+        SqliteClient::insert_summaries(self, summaries).await
+    }
+
+    async fn remove_summaries(&self, summaries: Vec<Summary>) -> Result<(), Error> {
+        SqliteClient::remove_summaries(self, summaries).await
+    }
+
+    async fn touch_summaries(&self, urls: &[&str]) -> Result<(), Error> {
+        SqliteClient::touch_summaries(self, urls).await
+    }
+
+    async fn delete_summaries_created_before(&self, before: OffsetDateTime) -> Result<u64, Error> {
+        SqliteClient::delete_summaries_created_before(self, before).await
+    }
+
+    async fn delete_summaries_accessed_before(&self, before: OffsetDateTime) -> Result<u64, Error> {
+        SqliteClient::delete_summaries_accessed_before(self, before).await
+    }
+
+    async fn read_idempotent_response(
+        &self,
+        key: &str,
+        scope: &str,
+        since: OffsetDateTime,
+    ) -> Result<Option<IdempotentResponse>, Error> {
+        SqliteClient::read_idempotent_response(self, key, scope, since).await
+    }
+
+    async fn upsert_idempotent_response(&self, response: &IdempotentResponse) -> Result<(), Error> {
+        SqliteClient::upsert_idempotent_response(self, response).await
+    }
+
+    async fn reserve_idempotency_key(
+        &self,
+        pending: &IdempotentResponse,
+        expired_before: OffsetDateTime,
+        stale_before: OffsetDateTime,
+    ) -> Result<bool, Error> {
+        SqliteClient::reserve_idempotency_key(self, pending, expired_before, stale_before).await
+    }
+
+    async fn delete_idempotent_response(&self, key: &str, scope: &str) -> Result<(), Error> {
+        SqliteClient::delete_idempotent_response(self, key, scope).await
+    }
+
+    async fn delete_idempotent_responses_before(
+        &self,
+        before: OffsetDateTime,
+    ) -> Result<u64, Error> {
+        SqliteClient::delete_idempotent_responses_before(self, before).await
+    }
+
+    async fn count_users(&self) -> Result<u64, Error> {
+        SqliteClient::count_users(self).await
+    }
+
+    async fn count_users_by_feeds(&self) -> Result<Vec<FeedCount>, Error> {
+        SqliteClient::count_users_by_feeds(self).await
+    }
+
+    async fn count_summaries_by_day(
+        &self,
+        since: OffsetDateTime,
+    ) -> Result<Vec<DailyCount>, Error> {
+        SqliteClient::count_summaries_by_day(self, since).await
+    }
+
+    async fn enqueue_job(&self, job: &Job) -> Result<(), Error> {
+        SqliteClient::enqueue_job(self, job).await
+    }
+
+    async fn claim_jobs(
+        &self,
+        kinds: &[&str],
+        limit: usize,
+        locked_until: OffsetDateTime,
+    ) -> Result<Vec<Job>, Error> {
+        SqliteClient::claim_jobs(self, kinds, limit, locked_until).await
+    }
+
+    async fn read_job(&self, id: Uuid) -> Result<Option<Job>, Error> {
+        SqliteClient::read_job(self, id).await
+    }
+
+    async fn renew_job(
+        &self,
+        id: Uuid,
+        lease_id: Uuid,
+        locked_until: OffsetDateTime,
+    ) -> Result<(), Error> {
+        SqliteClient::renew_job(self, id, lease_id, locked_until).await
+    }
+
+    async fn complete_job(&self, id: Uuid, lease_id: Uuid) -> Result<(), Error> {
+        SqliteClient::complete_job(self, id, lease_id).await
+    }
+
+    async fn fail_job(
+        &self,
+        id: Uuid,
+        lease_id: Uuid,
+        error: &str,
+        retry_at: Option<OffsetDateTime>,
+    ) -> Result<(), Error> {
+        SqliteClient::fail_job(self, id, lease_id, error, retry_at).await
+    }
+
+    async fn delete_jobs_done_before(&self, before: OffsetDateTime) -> Result<u64, Error> {
+        SqliteClient::delete_jobs_done_before(self, before).await
+    }
+
+    async fn count_jobs(&self) -> Result<Vec<JobCount>, Error> {
+        SqliteClient::count_jobs(self).await
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    /// Initializes an in-memory DB
+    pub async fn init_db() -> SqliteClient {
+        let db = SqliteClient::open(":memory:").unwrap();
+        db.init_schema().await.unwrap();
+        db
+    }
+
+    #[test]
+    fn test_in_list() {
+        assert_eq!(in_list(2, 3), "?2, ?3, ?4");
+        assert_eq!(in_list(1, 0), "");
+    }
+
+    #[tokio::test]
+    async fn test_init_schema() {
+        let db = SqliteClient::open(":memory:").unwrap();
+        assert!(!db.is_schema_init().await.unwrap());
+        db.init_schema().await.unwrap();
+        db.ping().await.unwrap();
+        assert!(db.is_schema_init().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_schema() {
+        let db = SqliteClient::open(":memory:").unwrap();
+        db.run(|conn| {
+            conn.execute_batch(
+                "
+                CREATE TABLE users (
+                    id              TEXT PRIMARY KEY,
+                    name            TEXT NOT NULL,
+                    email           TEXT NOT NULL UNIQUE,
+                    password        TEXT NOT NULL,
+                    subscription    TEXT NOT NULL
+                );
+                CREATE TABLE feeds (
+                    id          TEXT PRIMARY KEY,
+                    user_id     TEXT NOT NULL,
+                    url         TEXT NOT NULL,
+                    name        TEXT,
+                    FOREIGN KEY (user_id) REFERENCES users(id),
+                    UNIQUE (user_id, url)
+                );
+                CREATE TABLE summaries (
+                    id          TEXT PRIMARY KEY,
+                    url         TEXT NOT NULL UNIQUE,
+                    summary     TEXT,
+                    keywords    TEXT,
+                    embeddings  BLOB
+                );
+                CREATE TABLE idempotency_keys (
+                    key         TEXT NOT NULL,
+                    scope       TEXT NOT NULL,
+                    status      INTEGER NOT NULL,
+                    body        BLOB NOT NULL,
+                    created_at  INTEGER NOT NULL,
+                    PRIMARY KEY (key, scope)
+                );
+                INSERT INTO users VALUES ('u1', 'John', 'john@doe.com', 'pwd', 'Free');
+                INSERT INTO feeds VALUES ('f1', 'u1', 'https://www.feed.com', NULL);
+                PRAGMA user_version = 1;
+                ",
+            )?;
+            Ok(())
+        })
+        .await
+        .unwrap();
+        db.init_schema().await.unwrap();
+        assert!(db.is_schema_init().await.unwrap());
+        assert_eq!(
+            db.delete_summaries_accessed_before(OffsetDateTime::now_utc())
+                .await
+                .unwrap(),
+            0
+        );
+        let job = Job::new("test", serde_json::json!({}), 1).owner(Uuid::new_v4());
+        db.enqueue_job(&job).await.unwrap();
+        assert_eq!(
+            db.read_job(job.id).await.unwrap().unwrap().owner_id,
+            job.owner_id
+        );
+
+        // the feeds of a deleted user are deleted
+        let feeds = db
+            .run(|conn| {
+                conn.execute("DELETE FROM users WHERE id = 'u1'", [])?;
+                Ok(conn.query_row("SELECT COUNT(*) FROM feeds", [], |row| row.get::<_, i64>(0))?)
+            })
+            .await
+            .unwrap();
+        assert_eq!(feeds, 0);
+    }
+}
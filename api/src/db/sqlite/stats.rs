@@ -0,0 +1,122 @@
+//! Statistics
+
+use time::OffsetDateTime;
+
+use crate::{
+    error::Error,
+    mdl::{DailyCount, FeedCount},
+};
+
+use super::SqliteClient;
+
+impl SqliteClient {
+    /// Counts the users
+    pub async fn count_users(&self) -> Result<u64, Error> {
+        self.run(move |conn| {
+            Ok(
+                conn.query_row("SELECT COUNT(*) FROM users", [], |row| row.get::<_, i64>(0))?
+                    as u64,
+            )
+        })
+        .await
+    }
+
+    /// Counts the users by number of feeds
+    ///
+    /// The users without feeds are counted with 0 feeds.
+    pub async fn count_users_by_feeds(&self) -> Result<Vec<FeedCount>, Error> {
+        self.run(move |conn| {
+            let mut stmt = conn.prepare(
+                "
+                SELECT feeds, COUNT(*) AS users FROM (
+                    SELECT COUNT(f.id) AS feeds
+                    FROM users u LEFT JOIN feeds f ON f.user_id = u.id
+                    GROUP BY u.id
+                )
+                GROUP BY feeds ORDER BY feeds
+                ",
+            )?;
+            let counts = stmt
+                .query_map([], |row| {
+                    Ok(FeedCount {
+                        feeds: row.get::<_, i64>("feeds")? as u64,
+                        users: row.get::<_, i64>("users")? as u64,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(counts)
+        })
+        .await
+    }
+
+    /// Counts the summaries created per day (UTC) since a given time
+    ///
+    /// The days without summaries are omitted.
+    pub async fn count_summaries_by_day(
+        &self,
+        since: OffsetDateTime,
+    ) -> Result<Vec<DailyCount>, Error> {
+        self.run(move |conn| {
+            let mut stmt = conn.prepare(
+                "
+                SELECT date(created_at, 'unixepoch') AS day, COUNT(*) AS count
+                FROM summaries WHERE created_at >= ?1
+                GROUP BY day ORDER BY day
+                ",
+            )?;
+            let counts = stmt
+                .query_map([since.unix_timestamp()], |row| {
+                    Ok(DailyCount {
+                        day: row.get("day")?,
+                        count: row.get::<_, i64>("count")? as u64,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(counts)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        db::sqlite::user::tests::setup_test_user,
+        mdl::{FeedUpdate, NewUser},
+    };
+
+    #[tokio::test]
+    async fn test_stats() {
+        let (db, user) = setup_test_user().await;
+        db.create_user(NewUser {
+            name: "Jane Doe".to_string(),
+            email: "jane@doe.com".to_string(),
+            password: "dummy".to_string(),
+        })
+        .await
+        .unwrap();
+        db.sync_user_feeds(
+            user.id,
+            vec![FeedUpdate {
+                id: None,
+                url: "https://ai.googleblog.com/atom.xml".to_string(),
+                name: None,
+            }],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(db.count_users().await.unwrap(), 2);
+        assert_eq!(
+            db.count_users_by_feeds().await.unwrap(),
+            vec![
+                FeedCount { feeds: 0, users: 1 },
+                FeedCount { feeds: 1, users: 1 }
+            ]
+        );
+        let since = OffsetDateTime::now_utc() - time::Duration::days(7);
+        assert!(db.count_summaries_by_day(since).await.unwrap().is_empty());
+    }
+}
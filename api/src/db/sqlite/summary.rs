@@ -0,0 +1,413 @@
+//! Articles
+
+use rusqlite::{params, params_from_iter, types::Value, Connection, Row};
+use time::OffsetDateTime;
+
+use crate::{
+    db::page::{keyset, keyset_condition, keyset_cursor, keyset_order_by, paginate, Page},
+    error::Error,
+    mdl::{Event, Summary, Vector},
+};
+
+use super::{in_list, parse_uuid, SqliteClient, IN_LIST_CHUNK_SIZE};
+
+/// Tiebreaker key of the summaries
+const SUMMARY_TIEBREAKER: (&str, &str) = ("id", "id");
+
+impl TryFrom<&Row<'_>> for Summary {
+    type Error = rusqlite::Error;
+
+    fn try_from(value: &Row<'_>) -> Result<Self, Self::Error> {
+        let keywords: String = value.get("keywords")?;
+        let embeddings: Vec<u8> = value.get("embeddings")?;
+        Ok(Summary {
+            id: parse_uuid(value.get("id")?)?,
+            url: value.get("url")?,
+            summary: value.get("summary")?,
+            keywords: serde_json::from_str(&keywords).map_err(|err| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    0,
+                    rusqlite::types::Type::Text,
+                    Box::new(err),
+                )
+            })?,
+            embeddings: decode_vector(&embeddings).into(),
+        })
+    }
+}
+
+/// Encodes a vector as a blob (little endian f32 values)
+fn encode_vector(vector: &Vector) -> Vec<u8> {
+    vector
+        .as_ref()
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect()
+}
+
+/// Decodes a vector from a blob
+fn decode_vector(blob: &[u8]) -> Vec<f32> {
+    blob.chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
+
+/// Computes the cosine similarity of 2 vectors
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+impl SqliteClient {
+    /// Reads all the summaries
+    pub async fn read_summaries(&self) -> Result<Vec<Summary>, Error> {
+        self.run(move |conn| {
+            let mut stmt = conn.prepare("SELECT * FROM summaries ORDER BY id")?;
+            let summaries = stmt
+                .query_map([], |row| Summary::try_from(row))?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(summaries)
+        })
+        .await
+    }
+
+    /// Reads a page of the summaries, by ascending ID
+    ///
+    /// The first page is read without cursor, the next ones with the cursor of the previous
+    /// page.
+    pub async fn read_summaries_page(
+        &self,
+        cursor: Option<&str>,
+        limit: usize,
+    ) -> Result<Page<Summary>, Error> {
+        let keys = keyset(&[], &[], SUMMARY_TIEBREAKER);
+        let cursor = keyset_cursor(&keys, cursor)?;
+        self.run(move |conn| {
+            let mut params: Vec<Value> = vec![];
+            let mut stmt = "SELECT * FROM summaries".to_string();
+            if let Some(cursor) = cursor {
+                stmt.push_str(&format!(
+                    " WHERE {}",
+                    keyset_condition(&keys, 1, |i| format!("?{i}"))
+                ));
+                params.extend(cursor.0.into_iter().map(Value::Text));
+            }
+            params.push(Value::Integer(limit as i64 + 1));
+            stmt.push_str(&format!(
+                " {} LIMIT ?{}",
+                keyset_order_by(&keys),
+                params.len()
+            ));
+
+            let summaries = conn
+                .prepare_cached(&stmt)?
+                .query_map(params_from_iter(params), |row| Summary::try_from(row))?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(paginate(summaries, Some(limit), &keys, |s: &Summary, _| {
+                s.id.to_string()
+            }))
+        })
+        .await
+    }
+
+    /// Search summaries by url
+    ///
+    /// NB: the URLs are looked up by chunks, so that any number of URLs can be searched.
+    pub async fn search_summaries_by_urls(&self, urls: &[&str]) -> Result<Vec<Summary>, Error> {
+        let urls = urls.iter().map(|u| u.to_string()).collect::<Vec<_>>();
+        self.run(move |conn| {
+            let mut summaries = vec![];
+            for chunk in urls.chunks(IN_LIST_CHUNK_SIZE) {
+                let mut stmt = conn.prepare_cached(&format!(
+                    "SELECT * FROM summaries WHERE url IN({})",
+                    in_list(1, chunk.len())
+                ))?;
+                for summary in
+                    stmt.query_map(params_from_iter(chunk), |row| Summary::try_from(row))?
+                {
+                    summaries.push(summary?);
+                }
+            }
+            Ok(summaries)
+        })
+        .await
+    }
+
+    /// Search the summaries closest to an embeddings vector (cosine distance)
+    ///
+    /// NB: SQLite has no vector index, so all the summaries are scanned.
+    pub async fn search_summaries_by_embeddings(
+        &self,
+        embeddings: &Vector,
+        limit: usize,
+    ) -> Result<Vec<Summary>, Error> {
+        let embeddings = embeddings.clone();
+        self.run(move |conn| {
+            let mut stmt = conn.prepare("SELECT * FROM summaries")?;
+            let mut scored = stmt
+                .query_map([], |row| Summary::try_from(row))?
+                .map(|res| {
+                    res.map(|s| {
+                        let score = cosine_similarity(embeddings.as_ref(), s.embeddings.as_ref());
+                        (score, s)
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            scored.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+            Ok(scored.into_iter().take(limit).map(|(_, s)| s).collect())
+        })
+        .await
+    }
+
+    /// Insert summaries in the DB
+    ///
+    /// If a summary already exists for an URL, the existing summary is returned.
+    pub async fn insert_summaries(&self, articles: Vec<Summary>) -> Result<Vec<Summary>, Error> {
+        let ids = articles.iter().map(|art| art.id).collect::<Vec<_>>();
+        let summaries = self
+            .run(move |conn| {
+                let trx = conn.transaction()?;
+                let summaries = insert_summaries(&trx, articles)?;
+                trx.commit()?;
+                Ok(summaries)
+            })
+            .await?;
+
+        // NB: an existing summary is returned with its own ID
+        for summary in summaries.iter().filter(|s| ids.contains(&s.id)) {
+            let _res = self.events.send(Event::SummaryCreated {
+                id: summary.id,
+                url: summary.url.clone(),
+            });
+        }
+        Ok(summaries)
+    }
+
+    /// Marks the summaries of a list of URLs as accessed now
+    pub async fn touch_summaries(&self, urls: &[&str]) -> Result<(), Error> {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let urls = urls.iter().map(|u| u.to_string()).collect::<Vec<_>>();
+        self.run(move |conn| {
+            for chunk in urls.chunks(IN_LIST_CHUNK_SIZE) {
+                let mut values = vec![Value::Integer(now)];
+                values.extend(chunk.iter().map(|u| Value::Text(u.clone())));
+                let _res = conn.execute(
+                    &format!(
+                        "UPDATE summaries SET accessed_at = ?1 WHERE url IN({})",
+                        in_list(2, chunk.len())
+                    ),
+                    params_from_iter(values.iter()),
+                )?;
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// Deletes the summaries created before a given time
+    pub async fn delete_summaries_created_before(
+        &self,
+        before: OffsetDateTime,
+    ) -> Result<u64, Error> {
+        self.run(move |conn| {
+            Ok(conn.execute(
+                "DELETE FROM summaries WHERE created_at <= ?1",
+                [before.unix_timestamp()],
+            )? as u64)
+        })
+        .await
+    }
+
+    /// Deletes the summaries last accessed before a given time
+    pub async fn delete_summaries_accessed_before(
+        &self,
+        before: OffsetDateTime,
+    ) -> Result<u64, Error> {
+        self.run(move |conn| {
+            Ok(conn.execute(
+                "DELETE FROM summaries WHERE accessed_at <= ?1",
+                [before.unix_timestamp()],
+            )? as u64)
+        })
+        .await
+    }
+
+    /// Remove summaries in the DB
+    pub async fn remove_summaries(&self, summaries: Vec<Summary>) -> Result<(), Error> {
+        let ids = summaries
+            .iter()
+            .map(|s| s.id.to_string())
+            .collect::<Vec<_>>();
+        self.run(move |conn| {
+            for chunk in ids.chunks(IN_LIST_CHUNK_SIZE) {
+                let _res = conn.execute(
+                    &format!(
+                        "DELETE FROM summaries WHERE id IN({})",
+                        in_list(1, chunk.len())
+                    ),
+                    params_from_iter(chunk),
+                )?;
+            }
+            Ok(())
+        })
+        .await
+    }
+}
+
+/// Inserts summaries with a connection (or a transaction)
+///
+/// If a summary already exists for an URL, the existing summary is returned.
+pub(super) fn insert_summaries(
+    conn: &Connection,
+    articles: Vec<Summary>,
+) -> Result<Vec<Summary>, Error> {
+    let mut summaries = vec![];
+    for art in articles {
+        let summary = conn.query_row(
+            "INSERT INTO summaries (id, url, summary, keywords, embeddings) VALUES (?1, ?2, ?3, ?4, ?5) \
+            ON CONFLICT (url) DO UPDATE SET url = excluded.url RETURNING *",
+            params![
+                art.id.to_string(),
+                art.url,
+                art.summary,
+                serde_json::to_string(&art.keywords)
+                    .map_err(|err| Error::Internal(err.to_string(), None))?,
+                encode_vector(&art.embeddings)
+            ],
+            |row| Summary::try_from(row),
+        )?;
+        summaries.push(summary);
+    }
+    Ok(summaries)
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    use crate::db::sqlite::tests::init_db;
+
+    /// Creates a test summary
+    fn summary(url: &str, embeddings: Vec<f32>) -> Summary {
+        Summary {
+            id: Uuid::new_v4(),
+            url: url.to_string(),
+            summary: "Lore ipsum".to_string(),
+            keywords: vec!["kw1".to_string(), "kw2".to_string()],
+            embeddings: embeddings.into(),
+        }
+    }
+
+    #[test]
+    fn test_cosine_similarity() {
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]), 1.0);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]), 0.0);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[0.0, 1.0]), 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_insert_search_summaries() {
+        let db = init_db().await;
+        let mut events = db.subscribe();
+        let summaries = db
+            .insert_summaries(vec![
+                summary("https://www.link.com/a", vec![1.0, 0.0]),
+                summary("https://www.link.com/b", vec![0.0, 1.0]),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(summaries[0].keywords, vec!["kw1", "kw2"]);
+        assert_eq!(
+            events.try_recv().unwrap(),
+            Event::SummaryCreated {
+                id: summaries[0].id,
+                url: summaries[0].url.clone(),
+            }
+        );
+
+        let found = db
+            .search_summaries_by_urls(&["https://www.link.com/a"])
+            .await
+            .unwrap();
+        assert_eq!(found.len(), 1);
+
+        let closest = db
+            .search_summaries_by_embeddings(&vec![0.1, 0.9].into(), 1)
+            .await
+            .unwrap();
+        assert_eq!(closest[0].url, "https://www.link.com/b");
+
+        db.touch_summaries(&["https://www.link.com/a"])
+            .await
+            .unwrap();
+        let past = OffsetDateTime::now_utc() - time::Duration::days(1);
+        assert_eq!(db.delete_summaries_accessed_before(past).await.unwrap(), 0);
+        assert_eq!(db.delete_summaries_created_before(past).await.unwrap(), 0);
+
+        db.remove_summaries(summaries).await.unwrap();
+        let found = db
+            .search_summaries_by_urls(&["https://www.link.com/a"])
+            .await
+            .unwrap();
+        assert!(found.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_search_many_urls() {
+        let db = init_db().await;
+        let summaries = db
+            .insert_summaries(vec![
+                summary("https://www.link.com/0", vec![1.0, 0.0]),
+                summary("https://www.link.com/1234", vec![0.0, 1.0]),
+            ])
+            .await
+            .unwrap();
+
+        // NB: more URLs than a chunk
+        let urls = (0..3 * IN_LIST_CHUNK_SIZE)
+            .map(|i| format!("https://www.link.com/{i}"))
+            .collect::<Vec<_>>();
+        let urls = urls.iter().map(|u| u.as_str()).collect::<Vec<_>>();
+        let found = db.search_summaries_by_urls(&urls).await.unwrap();
+        assert_eq!(found.len(), 2);
+        db.touch_summaries(&urls).await.unwrap();
+
+        db.remove_summaries(summaries).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_read_summaries_page() {
+        let db = init_db().await;
+        db.insert_summaries(
+            (0..5)
+                .map(|i| summary(&format!("https://www.link.com/{i}"), vec![1.0, 0.0]))
+                .collect(),
+        )
+        .await
+        .unwrap();
+
+        let mut ids = vec![];
+        let mut cursor = None;
+        loop {
+            let page = db.read_summaries_page(cursor.as_deref(), 2).await.unwrap();
+            assert!(page.items.len() <= 2);
+            ids.extend(page.items.into_iter().map(|s| s.id));
+            match page.next_cursor {
+                Some(next) => cursor = Some(next.encode()),
+                None => break,
+            }
+        }
+        assert!(db.read_summaries_page(Some("zz"), 2).await.is_err());
+        let all = db.read_summaries().await.unwrap();
+        assert_eq!(ids, all.iter().map(|s| s.id).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,144 @@
+//! Transactions
+
+use rusqlite::Connection;
+use salvo::async_trait;
+use tokio::sync::OwnedMutexGuard;
+use uuid::Uuid;
+
+use crate::{
+    db::StoreTx,
+    error::Error,
+    mdl::{Feed, FeedUpdate, Summary, User},
+};
+
+use super::{feed, summary, user, SqliteClient};
+
+/// SQLite transaction
+///
+/// The transaction holds the connection until it is committed or rolled back.
+/// If it is dropped before, it is rolled back.
+pub struct SqliteTx {
+    /// Connection (`None` once the transaction is finished)
+    conn: Option<OwnedMutexGuard<Connection>>,
+}
+
+impl SqliteClient {
+    /// Begins a transaction
+    pub async fn begin(&self) -> Result<SqliteTx, Error> {
+        let conn = self.conn.clone().lock_owned().await;
+        conn.execute_batch("BEGIN")?;
+        Ok(SqliteTx { conn: Some(conn) })
+    }
+}
+
+impl SqliteTx {
+    /// Runs a function with the connection on a blocking thread
+    async fn run<T, F>(&mut self, f: F) -> Result<T, Error>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Connection) -> Result<T, Error> + Send + 'static,
+    {
+        let conn = self
+            .conn
+            .take()
+            .ok_or(Error::Internal("transaction is finished".to_string(), None))?;
+        let (conn, res) = tokio::task::spawn_blocking(move || {
+            let res = f(&conn);
+            (conn, res)
+        })
+        .await
+        .map_err(|err| Error::Internal(format!("failed to run query ({err})"), None))?;
+        self.conn = Some(conn);
+        res
+    }
+
+    /// Ends the transaction with a statement (`COMMIT` or `ROLLBACK`)
+    async fn end(&mut self, stmt: &'static str) -> Result<(), Error> {
+        self.run(move |conn| Ok(conn.execute_batch(stmt)?)).await?;
+        self.conn = None;
+        Ok(())
+    }
+}
+
+impl Drop for SqliteTx {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            if let Err(err) = conn.execute_batch("ROLLBACK") {
+                tracing::warn!(%err, "failed to roll back a dropped transaction");
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl StoreTx for SqliteTx {
+    async fn upsert_user(&mut self, user: &User) -> Result<(), Error> {
+        let record = user.clone();
+        self.run(move |conn| user::upsert_user(conn, &record)).await
+    }
+
+    async fn lock_user_feeds(&mut self, user_id: Uuid) -> Result<Vec<Feed>, Error> {
+        // NB: the transaction holds the connection, so the feeds cannot change concurrently
+        self.run(move |conn| feed::read_feeds(conn, user_id)).await
+    }
+
+    async fn sync_user_feeds(
+        &mut self,
+        user_id: Uuid,
+        feeds: Vec<FeedUpdate>,
+    ) -> Result<Vec<Feed>, Error> {
+        self.run(move |conn| feed::sync_feeds(conn, user_id, feeds))
+            .await
+    }
+
+    async fn delete_user_feeds(&mut self, user_id: Uuid) -> Result<(), Error> {
+        self.run(move |conn| feed::delete_feeds(conn, user_id))
+            .await
+    }
+
+    async fn delete_user(&mut self, id: Uuid) -> Result<(), Error> {
+        self.run(move |conn| user::delete_user(conn, id)).await
+    }
+
+    async fn insert_summaries(&mut self, summaries: Vec<Summary>) -> Result<Vec<Summary>, Error> {
+        // NB: no event is sent, as the summaries may be rolled back
+        self.run(move |conn| summary::insert_summaries(conn, summaries))
+            .await
+    }
+
+    async fn commit(mut self: Box<Self>) -> Result<(), Error> {
+        self.end("COMMIT").await
+    }
+
+    async fn rollback(mut self: Box<Self>) -> Result<(), Error> {
+        self.end("ROLLBACK").await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::db::sqlite::user::tests::setup_test_user;
+
+    #[tokio::test]
+    async fn test_commit_rollback() {
+        let (db, user) = setup_test_user().await;
+
+        let mut tx = Box::new(db.begin().await.unwrap());
+        tx.delete_user(user.id).await.unwrap();
+        tx.rollback().await.unwrap();
+        assert!(db.read_user(user.id).await.unwrap().is_some());
+
+        {
+            let mut tx = db.begin().await.unwrap();
+            tx.delete_user(user.id).await.unwrap();
+        }
+        assert!(db.read_user(user.id).await.unwrap().is_some());
+
+        let mut tx = Box::new(db.begin().await.unwrap());
+        tx.delete_user(user.id).await.unwrap();
+        tx.commit().await.unwrap();
+        assert!(db.read_user(user.id).await.unwrap().is_none());
+    }
+}
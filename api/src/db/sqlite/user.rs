@@ -0,0 +1,264 @@
+//! Users
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use uuid::Uuid;
+
+use crate::{
+    error::Error,
+    mdl::{NewUser, Subscription, SubscriptionUpdate, User, UserUpdate},
+};
+
+use super::{is_unique_violation, parse_uuid, SqliteClient};
+
+impl TryFrom<&Row<'_>> for User {
+    type Error = rusqlite::Error;
+
+    fn try_from(value: &Row<'_>) -> Result<Self, Self::Error> {
+        Ok(User {
+            id: parse_uuid(value.get("id")?)?,
+            name: value.get("name")?,
+            email: value.get("email")?,
+            password: value.get("password")?,
+            subscription: match value.get::<_, String>("subscription")?.as_str() {
+                "MID" => Subscription::Mid,
+                _ => Subscription::Free,
+            },
+        })
+    }
+}
+
+/// Returns the value stored for a subscription
+fn subscription_value(subscription: &Subscription) -> &'static str {
+    match subscription {
+        Subscription::Free => "FREE",
+        Subscription::Mid => "MID",
+    }
+}
+
+impl SqliteClient {
+    /// Creates a new user
+    ///
+    /// A new user is created and its ID is populated. Emails are unique.
+    pub async fn create_user(&self, new_user: NewUser) -> Result<User, Error> {
+        self.run(move |conn| {
+            conn.query_row(
+                "INSERT into users (id, name, email, password, subscription) VALUES (?1, ?2, ?3, ?4, ?5) RETURNING *",
+                params![
+                    Uuid::new_v4().to_string(),
+                    new_user.name,
+                    new_user.email,
+                    new_user.password,
+                    subscription_value(&Subscription::Free)
+                ],
+                |row| User::try_from(row),
+            )
+            .map_err(|err| email_conflict_error(err, &new_user.email))
+        })
+        .await
+    }
+
+    /// Reads a user with its id
+    pub async fn read_user(&self, id: Uuid) -> Result<Option<User>, Error> {
+        self.run(move |conn| {
+            Ok(conn
+                .query_row(
+                    "SELECT * FROM users WHERE id = ?1",
+                    [id.to_string()],
+                    |row| User::try_from(row),
+                )
+                .optional()?)
+        })
+        .await
+    }
+
+    /// Reads all the users
+    pub async fn read_users(&self) -> Result<Vec<User>, Error> {
+        self.run(move |conn| {
+            let mut stmt = conn.prepare("SELECT * FROM users ORDER BY id")?;
+            let users = stmt
+                .query_map([], |row| User::try_from(row))?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(users)
+        })
+        .await
+    }
+
+    /// Reads a user with its email
+    pub async fn read_user_with_email(&self, email: &str) -> Result<Option<User>, Error> {
+        let email = email.to_string();
+        self.run(move |conn| {
+            Ok(conn
+                .query_row("SELECT * FROM users WHERE email = ?1", [email], |row| {
+                    User::try_from(row)
+                })
+                .optional()?)
+        })
+        .await
+    }
+
+    /// Update a user
+    pub async fn update_user(&self, id: Uuid, fields: UserUpdate) -> Result<User, Error> {
+        self.run(move |conn| {
+            let trx = conn.transaction()?;
+            if let Some(name) = fields.name.as_ref() {
+                trx.execute(
+                    "UPDATE users SET name = ?2 WHERE id = ?1",
+                    params![id.to_string(), name],
+                )?;
+            }
+            if let Some(email) = fields.email.as_ref() {
+                trx.execute(
+                    "UPDATE users SET email = ?2 WHERE id = ?1",
+                    params![id.to_string(), email],
+                )
+                .map_err(|err| email_conflict_error(err, email))?;
+            }
+            if let Some(password) = fields.password.as_ref() {
+                trx.execute(
+                    "UPDATE users SET password = ?2 WHERE id = ?1",
+                    params![id.to_string(), password],
+                )?;
+            }
+            let user = trx
+                .query_row(
+                    "SELECT * FROM users WHERE id = ?1",
+                    [id.to_string()],
+                    |row| User::try_from(row),
+                )
+                .optional()?;
+            trx.commit()?;
+            user.ok_or(Error::NotFound(format!("no user for id {id}"), None))
+        })
+        .await
+    }
+
+    /// Update the user subscription
+    pub async fn update_user_subscription(
+        &self,
+        id: Uuid,
+        subscription_update: SubscriptionUpdate,
+    ) -> Result<User, Error> {
+        self.run(move |conn| {
+            Ok(conn.query_row(
+                "UPDATE users SET subscription = ?2 WHERE id = ?1 RETURNING *",
+                params![
+                    id.to_string(),
+                    subscription_value(&subscription_update.subscription)
+                ],
+                |row| User::try_from(row),
+            )?)
+        })
+        .await
+    }
+
+    /// Delete a user
+    pub async fn delete_user(&self, id: Uuid) -> Result<(), Error> {
+        self.run(move |conn| delete_user(conn, id)).await
+    }
+}
+
+/// Insert or update a user with a connection (or a transaction)
+///
+/// All the fields are set, including the ID and the password hash.
+pub(super) fn upsert_user(conn: &Connection, user: &User) -> Result<(), Error> {
+    let _res = conn
+        .execute(
+            "INSERT INTO users (id, name, email, password, subscription) VALUES (?1, ?2, ?3, ?4, ?5) \
+            ON CONFLICT (id) DO UPDATE SET name = ?2, email = ?3, password = ?4, subscription = ?5",
+            params![
+                user.id.to_string(),
+                user.name,
+                user.email,
+                user.password,
+                subscription_value(&user.subscription)
+            ],
+        )
+        .map_err(|err| email_conflict_error(err, &user.email))?;
+    Ok(())
+}
+
+/// Delete a user with a connection (or a transaction)
+pub(super) fn delete_user(conn: &Connection, id: Uuid) -> Result<(), Error> {
+    let _res = conn.execute("DELETE FROM users WHERE id = ?1", [id.to_string()])?;
+    Ok(())
+}
+
+/// Converts a DB error, reporting email conflicts as invalid requests
+fn email_conflict_error(err: rusqlite::Error, email: &str) -> Error {
+    if is_unique_violation(&err) {
+        Error::InvalidRequest(format!("user with email '{email}' already exists"), None)
+    } else {
+        err.into()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::db::sqlite::tests::init_db;
+
+    /// Setup a test
+    pub async fn setup_test_user() -> (SqliteClient, User) {
+        let db = init_db().await;
+        let user = db
+            .create_user(NewUser {
+                name: "John Doe".to_string(),
+                email: "john@doe.com".to_string(),
+                password: "dummy".to_string(),
+            })
+            .await
+            .unwrap();
+        (db, user)
+    }
+
+    #[tokio::test]
+    async fn test_read() {
+        let (db, test_user) = setup_test_user().await;
+        let user = db.read_user(test_user.id).await.unwrap();
+        assert_eq!(user.unwrap().id, test_user.id);
+        let user = db.read_user_with_email(&test_user.email).await.unwrap();
+        assert_eq!(user.unwrap().id, test_user.id);
+    }
+
+    #[tokio::test]
+    async fn test_update() {
+        let (db, test_user) = setup_test_user().await;
+        let user = db
+            .update_user(
+                test_user.id,
+                UserUpdate {
+                    name: Some("Jane Doe".to_string()),
+                    email: None,
+                    password: None,
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(user.name, "Jane Doe");
+
+        let user = db
+            .update_user_subscription(
+                test_user.id,
+                SubscriptionUpdate {
+                    subscription: Subscription::Mid,
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(user.subscription, Subscription::Mid);
+    }
+
+    #[tokio::test]
+    async fn test_create_duplicate_email() {
+        let (db, test_user) = setup_test_user().await;
+        let res = db
+            .create_user(NewUser {
+                name: test_user.name.clone(),
+                email: test_user.email.clone(),
+                password: "dummy".to_string(),
+            })
+            .await;
+        assert!(matches!(res, Err(Error::InvalidRequest(_, _))));
+    }
+}
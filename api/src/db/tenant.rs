@@ -0,0 +1,75 @@
+//! Tenant context
+//!
+//! The user on behalf of whom the queries are run is set for the duration of a request
+//! (see [crate::http::mdw::tenant]). With the row-level security option of PostgreSQL, it is
+//! set in the `app.user_id` setting of the connection, so that the policies only expose the
+//! rows of this user.
+//!
+//! The background tasks and the administrator run in the system scope, which exposes all the
+//! rows. NB: outside of a scope, no rows are exposed.
+
+use std::future::Future;
+
+use uuid::Uuid;
+
+/// Tenant of the queries
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tenant {
+    /// A user, who only accesses their own data
+    User(Uuid),
+    /// The system (background tasks, administrator), which accesses all the data
+    System,
+}
+
+tokio::task_local! {
+    /// Current tenant
+    static TENANT: Tenant;
+}
+
+/// Value of the `app.user_id` setting which gives access to all the rows (system scope)
+pub const ALL_TENANTS: &str = "*";
+
+/// Value of the `app.user_id` setting outside of a scope, which gives access to no rows
+pub const NO_TENANT: &str = "";
+
+/// Runs a future on behalf of a user
+pub async fn with_tenant<F: Future>(user_id: Uuid, f: F) -> F::Output {
+    TENANT.scope(Tenant::User(user_id), f).await
+}
+
+/// Runs a future in the system scope
+///
+/// It is used by the background tasks, the administrator, and the authentication (before
+/// the user is known).
+pub async fn with_system<F: Future>(f: F) -> F::Output {
+    TENANT.scope(Tenant::System, f).await
+}
+
+/// Returns the current tenant, if any
+pub fn current_tenant() -> Option<Tenant> {
+    TENANT.try_with(|tenant| *tenant).ok()
+}
+
+/// Returns the value of the `app.user_id` setting for the current tenant
+pub fn tenant_setting() -> String {
+    match current_tenant() {
+        Some(Tenant::User(user_id)) => user_id.to_string(),
+        Some(Tenant::System) => ALL_TENANTS.to_string(),
+        None => NO_TENANT.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_with_tenant() {
+        let user_id = Uuid::new_v4();
+        assert_eq!(tenant_setting(), NO_TENANT);
+        let setting = with_tenant(user_id, async { tenant_setting() }).await;
+        assert_eq!(setting, user_id.to_string());
+        assert_eq!(with_system(async { tenant_setting() }).await, ALL_TENANTS);
+        assert_eq!(current_tenant(), None);
+    }
+}
@@ -0,0 +1,393 @@
+//! Documentation and client code generation
+//!
+//! The generators work on the JSON representation of the OpenAPI specs, and support the subset
+//! of the specs used by the API (objects, string enums, arrays and references).
+
+use std::fmt::Write;
+
+use serde_json::Value;
+
+/// Output format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// OpenAPI specs (YAML)
+    Yaml,
+    /// OpenAPI specs (JSON)
+    Json,
+    /// Markdown API reference
+    Markdown,
+    /// TypeScript models
+    TypeScript,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "yaml" | "yml" => Ok(Format::Yaml),
+            "json" => Ok(Format::Json),
+            "md" | "markdown" => Ok(Format::Markdown),
+            "ts" | "typescript" => Ok(Format::TypeScript),
+            _ => Err(format!("invalid format '{s}' (yaml, json, md, ts)")),
+        }
+    }
+}
+
+/// Returns the identifier of a schema
+///
+/// Schema names contain the module path (eg. `newsie_api.mdl.User`) and generic arguments,
+/// which are flattened (eg. `Paginated<newsie_api.mdl.Feed>` -> `PaginatedFeed`).
+pub fn ident(name: &str) -> String {
+    name.split(|c: char| c == '<' || c == '>' || c == ',' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.rsplit(['.', ':']).next().unwrap_or(s))
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// Returns the name of the schema referenced by a `$ref`
+fn ref_ident(reference: &str) -> String {
+    ident(reference.rsplit('/').next().unwrap_or(reference))
+}
+
+/// Iterates over the component schemas, sorted by identifier
+fn schemas(specs: &Value) -> Vec<(String, &Value)> {
+    let mut schemas = specs
+        .pointer("/components/schemas")
+        .and_then(|v| v.as_object())
+        .map(|m| m.iter().map(|(k, v)| (ident(k), v)).collect::<Vec<_>>())
+        .unwrap_or_default();
+    schemas.sort_by(|a, b| a.0.cmp(&b.0));
+    schemas
+}
+
+/// Returns the properties of an object schema, with their `required` flag
+fn properties(schema: &Value) -> Vec<(&str, &Value, bool)> {
+    let required = schema
+        .get("required")
+        .and_then(|v| v.as_array())
+        .map(|a| a.iter().filter_map(|v| v.as_str()).collect::<Vec<_>>())
+        .unwrap_or_default();
+    schema
+        .get("properties")
+        .and_then(|v| v.as_object())
+        .map(|m| {
+            m.iter()
+                .map(|(k, v)| (k.as_str(), v, required.contains(&k.as_str())))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Returns the description of a schema
+fn description(schema: &Value) -> Option<&str> {
+    schema.get("description").and_then(|v| v.as_str())
+}
+
+/// Returns the string variants of an enum schema
+fn enum_variants(schema: &Value) -> Option<Vec<&str>> {
+    schema
+        .get("enum")
+        .and_then(|v| v.as_array())
+        .map(|a| a.iter().filter_map(|v| v.as_str()).collect())
+}
+
+/// Returns the first schema of a `allOf`/`oneOf`/`anyOf` composition (eg. nullable references)
+fn composed(schema: &Value) -> Option<&Value> {
+    ["allOf", "oneOf", "anyOf"]
+        .iter()
+        .filter_map(|k| schema.get(*k).and_then(|v| v.as_array()))
+        .flat_map(|a| a.iter())
+        .find(|s| s.get("type").and_then(|t| t.as_str()) != Some("null"))
+}
+
+/// Checks if a schema is nullable
+fn is_nullable(schema: &Value) -> bool {
+    schema.get("nullable").and_then(|v| v.as_bool()) == Some(true)
+        || match schema.get("type") {
+            Some(Value::Array(types)) => types.iter().any(|t| t == "null"),
+            _ => false,
+        }
+}
+
+/// Returns the type of a schema (first non-null type)
+fn schema_type(schema: &Value) -> Option<&str> {
+    match schema.get("type") {
+        Some(Value::String(t)) => Some(t.as_str()),
+        Some(Value::Array(types)) => types
+            .iter()
+            .filter_map(|t| t.as_str())
+            .find(|t| *t != "null"),
+        _ => None,
+    }
+}
+
+/// Returns the TypeScript type of a schema
+fn ts_type(schema: &Value) -> String {
+    if let Some(reference) = schema.get("$ref").and_then(|v| v.as_str()) {
+        return ref_ident(reference);
+    }
+    if let Some(inner) = composed(schema) {
+        return ts_type(inner);
+    }
+    if let Some(variants) = enum_variants(schema) {
+        return variants
+            .iter()
+            .map(|v| format!("'{v}'"))
+            .collect::<Vec<_>>()
+            .join(" | ");
+    }
+    match schema_type(schema) {
+        Some("string") => "string".to_string(),
+        Some("integer") | Some("number") => "number".to_string(),
+        Some("boolean") => "boolean".to_string(),
+        Some("array") => match schema.get("items") {
+            Some(items) => format!("{}[]", ts_type(items)),
+            None => "unknown[]".to_string(),
+        },
+        Some("object") if schema.get("properties").is_none() => {
+            "Record<string, unknown>".to_string()
+        }
+        _ => "unknown".to_string(),
+    }
+}
+
+/// Generates the TypeScript models
+pub fn gen_typescript(specs: &Value) -> String {
+    let mut out = String::new();
+    writeln!(out, "/**").unwrap();
+    writeln!(
+        out,
+        " * Models generated from the OpenAPI specs. Do not edit."
+    )
+    .unwrap();
+    writeln!(out, " * @module models").unwrap();
+    writeln!(out, " */").unwrap();
+
+    for (name, schema) in schemas(specs) {
+        writeln!(out).unwrap();
+        if let Some(desc) = description(schema) {
+            writeln!(out, "/**\n * {desc}\n */").unwrap();
+        }
+        if let Some(variants) = enum_variants(schema) {
+            let variants = variants
+                .iter()
+                .map(|v| format!("'{v}'"))
+                .collect::<Vec<_>>()
+                .join(" | ");
+            writeln!(out, "export type {name} = {variants};").unwrap();
+            continue;
+        }
+        let props = properties(schema);
+        if props.is_empty() {
+            writeln!(out, "export type {name} = {};", ts_type(schema)).unwrap();
+            continue;
+        }
+        writeln!(out, "export interface {name} {{").unwrap();
+        for (prop, prop_schema, required) in props {
+            if let Some(desc) = description(prop_schema) {
+                writeln!(out, "  /** {desc} */").unwrap();
+            }
+            let optional = if required { "" } else { "?" };
+            let nullable = if is_nullable(prop_schema) {
+                " | null"
+            } else {
+                ""
+            };
+            writeln!(
+                out,
+                "  {prop}{optional}: {}{nullable};",
+                ts_type(prop_schema)
+            )
+            .unwrap();
+        }
+        writeln!(out, "}}").unwrap();
+    }
+    out
+}
+
+/// Returns the Markdown description of a schema used in a request or response
+fn md_content(content: &Value) -> String {
+    content
+        .as_object()
+        .map(|m| {
+            m.iter()
+                .map(|(mime, media)| {
+                    let ty = media.get("schema").map(ts_type).unwrap_or_default();
+                    format!("`{ty}` ({mime})")
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+        .unwrap_or_default()
+}
+
+/// Generates the Markdown API reference
+pub fn gen_markdown(specs: &Value) -> String {
+    let mut out = String::new();
+    let title = specs
+        .pointer("/info/title")
+        .and_then(|v| v.as_str())
+        .unwrap_or("API");
+    let version = specs
+        .pointer("/info/version")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default();
+    writeln!(out, "# {title} ({version})").unwrap();
+    if let Some(desc) = specs.pointer("/info/description").and_then(|v| v.as_str()) {
+        writeln!(out, "\n{desc}").unwrap();
+    }
+
+    writeln!(out, "\n## Endpoints").unwrap();
+    let mut paths = specs
+        .get("paths")
+        .and_then(|v| v.as_object())
+        .map(|m| m.iter().collect::<Vec<_>>())
+        .unwrap_or_default();
+    paths.sort_by(|a, b| a.0.cmp(b.0));
+    for (path, item) in paths {
+        let Some(operations) = item.as_object() else {
+            continue;
+        };
+        for (method, op) in operations {
+            writeln!(out, "\n### {} `{path}`", method.to_uppercase()).unwrap();
+            if let Some(summary) = op.get("summary").and_then(|v| v.as_str()) {
+                writeln!(out, "\n{summary}").unwrap();
+            }
+            if let Some(desc) = op.get("description").and_then(|v| v.as_str()) {
+                writeln!(out, "\n{desc}").unwrap();
+            }
+            if op.get("security").is_some() {
+                writeln!(out, "\n*Requires authentication*").unwrap();
+            }
+            if let Some(content) = op.pointer("/requestBody/content") {
+                writeln!(out, "\n**Request body**: {}", md_content(content)).unwrap();
+            }
+            if let Some(responses) = op.get("responses").and_then(|v| v.as_object()) {
+                writeln!(out, "\n| Status | Description | Body |").unwrap();
+                writeln!(out, "| --- | --- | --- |").unwrap();
+                for (status, res) in responses {
+                    let desc = res
+                        .get("description")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or_default();
+                    let body = res.get("content").map(md_content).unwrap_or_default();
+                    writeln!(out, "| {status} | {desc} | {body} |").unwrap();
+                }
+            }
+        }
+    }
+
+    writeln!(out, "\n## Models").unwrap();
+    for (name, schema) in schemas(specs) {
+        writeln!(out, "\n### {name}").unwrap();
+        if let Some(desc) = description(schema) {
+            writeln!(out, "\n{desc}").unwrap();
+        }
+        if let Some(variants) = enum_variants(schema) {
+            writeln!(
+                out,
+                "\nOne of: {}",
+                variants
+                    .iter()
+                    .map(|v| format!("`{v}`"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+            .unwrap();
+            continue;
+        }
+        let props = properties(schema);
+        if props.is_empty() {
+            continue;
+        }
+        writeln!(out, "\n| Field | Type | Required | Description |").unwrap();
+        writeln!(out, "| --- | --- | --- | --- |").unwrap();
+        for (prop, prop_schema, required) in props {
+            writeln!(
+                out,
+                "| `{prop}` | `{}` | {} | {} |",
+                ts_type(prop_schema),
+                if required { "yes" } else { "no" },
+                description(prop_schema).unwrap_or_default()
+            )
+            .unwrap();
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    fn specs() -> Value {
+        json!({
+            "openapi": "3.0.3",
+            "info": { "title": "Api", "version": "0.1.0" },
+            "paths": {
+                "/feeds": {
+                    "get": {
+                        "summary": "Get all the user feeds",
+                        "security": [{ "bearerAuth": [] }],
+                        "responses": {
+                            "200": {
+                                "description": "Ok",
+                                "content": {
+                                    "application/json": {
+                                        "schema": { "$ref": "#/components/schemas/newsie_api.mdl.Feed" }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            },
+            "components": {
+                "schemas": {
+                    "newsie_api.mdl.Feed": {
+                        "type": "object",
+                        "description": "User feed",
+                        "required": ["id", "url"],
+                        "properties": {
+                            "id": { "type": "string", "format": "uuid" },
+                            "url": { "type": "string", "description": "Feed url" },
+                            "name": { "type": "string", "nullable": true }
+                        }
+                    },
+                    "newsie_api.mdl.Subscription": {
+                        "type": "string",
+                        "enum": ["Free", "Mid"]
+                    }
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn test_ident() {
+        assert_eq!(ident("newsie_api.mdl.User"), "User");
+        assert_eq!(
+            ident("newsie_api.http.paginated.Paginated<newsie_api.mdl.Feed>"),
+            "PaginatedFeed"
+        );
+    }
+
+    #[test]
+    fn test_gen_typescript() {
+        let ts = gen_typescript(&specs());
+        assert!(ts.contains("export interface Feed {"));
+        assert!(ts.contains("  name?: string | null;"));
+        assert!(ts.contains("export type Subscription = 'Free' | 'Mid';"));
+    }
+
+    #[test]
+    fn test_gen_markdown() {
+        let md = gen_markdown(&specs());
+        assert!(md.contains("### GET `/feeds`"));
+        assert!(md.contains("| `url` | `string` | yes | Feed url |"));
+    }
+}
@@ -1,5 +1,7 @@
 //! Error
 
+use http::StatusCode;
+#[cfg(feature = "server")]
 use salvo::prelude::*;
 use serde::{Deserialize, Serialize};
 
@@ -15,6 +17,24 @@ pub enum Error {
     /// Unauthenticated
     #[error("error: {0}")]
     Unauthenticated(String, Option<String>),
+    /// Forbidden
+    #[error("error: {0}")]
+    Forbidden(String, Option<String>),
+    /// Conflict with the current state of the resource
+    #[error("error: {0}")]
+    Conflict(String, Option<String>),
+    /// Method not allowed
+    #[error("error: {0}")]
+    MethodNotAllowed(String, Option<String>),
+    /// Request payload is too large
+    #[error("error: {0}")]
+    PayloadTooLarge(String, Option<String>),
+    /// Request fields are invalid
+    #[error("error: {0}")]
+    InvalidFields(String, Vec<FieldError>),
+    /// Request is well-formed but cannot be processed (eg. an idempotency key is reused)
+    #[error("error: {0}")]
+    Unprocessable(String, Option<String>),
     /// Internal server or service error
     #[error("error: {0}")]
     Internal(String, Option<String>),
@@ -27,6 +47,12 @@ impl Error {
             Error::InvalidRequest(msg, _) => msg.clone(),
             Error::NotFound(msg, _) => msg.clone(),
             Error::Unauthenticated(msg, _) => msg.clone(),
+            Error::Forbidden(msg, _) => msg.clone(),
+            Error::Conflict(msg, _) => msg.clone(),
+            Error::MethodNotAllowed(msg, _) => msg.clone(),
+            Error::PayloadTooLarge(msg, _) => msg.clone(),
+            Error::InvalidFields(msg, _) => msg.clone(),
+            Error::Unprocessable(msg, _) => msg.clone(),
             Error::Internal(msg, _) => msg.clone(),
         }
     }
@@ -37,6 +63,12 @@ impl Error {
             Error::InvalidRequest(_, _) => "INVALID_REQUEST".to_string(),
             Error::NotFound(_, _) => "NOT_FOUND".to_string(),
             Error::Unauthenticated(_, _) => "NOT_AUTHENTICATED".to_string(),
+            Error::Forbidden(_, _) => "FORBIDDEN".to_string(),
+            Error::Conflict(_, _) => "CONFLICT".to_string(),
+            Error::MethodNotAllowed(_, _) => "METHOD_NOT_ALLOWED".to_string(),
+            Error::PayloadTooLarge(_, _) => "PAYLOAD_TOO_LARGE".to_string(),
+            Error::InvalidFields(_, _) => "INVALID_FIELDS".to_string(),
+            Error::Unprocessable(_, _) => "UNPROCESSABLE".to_string(),
             Error::Internal(_, _) => "INTERNAL".to_string(),
         }
     }
@@ -47,35 +79,53 @@ impl Error {
             Error::InvalidRequest(_, _) => StatusCode::BAD_REQUEST,
             Error::NotFound(_, _) => StatusCode::NOT_FOUND,
             Error::Unauthenticated(_, _) => StatusCode::UNAUTHORIZED,
+            Error::Forbidden(_, _) => StatusCode::FORBIDDEN,
+            Error::Conflict(_, _) => StatusCode::CONFLICT,
+            Error::MethodNotAllowed(_, _) => StatusCode::METHOD_NOT_ALLOWED,
+            Error::PayloadTooLarge(_, _) => StatusCode::PAYLOAD_TOO_LARGE,
+            Error::InvalidFields(_, _) => StatusCode::UNPROCESSABLE_ENTITY,
+            Error::Unprocessable(_, _) => StatusCode::UNPROCESSABLE_ENTITY,
             Error::Internal(_, _) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
 }
 
+#[cfg(feature = "server")]
 impl From<deadpool_postgres::PoolError> for Error {
     fn from(value: deadpool_postgres::PoolError) -> Self {
         Error::Internal(value.to_string(), None)
     }
 }
 
+#[cfg(feature = "server")]
 impl From<tokio_postgres::Error> for Error {
     fn from(value: tokio_postgres::Error) -> Self {
         Error::Internal(value.to_string(), None)
     }
 }
 
+#[cfg(feature = "server")]
+impl From<rusqlite::Error> for Error {
+    fn from(value: rusqlite::Error) -> Self {
+        Error::Internal(value.to_string(), None)
+    }
+}
+
+#[cfg(feature = "server")]
 impl From<jsonwebtoken::errors::Error> for Error {
     fn from(value: jsonwebtoken::errors::Error) -> Self {
         Error::Unauthenticated(format!("invalid token ({value})"), None)
     }
 }
 
+#[cfg(feature = "server")]
 impl From<argon2::password_hash::Error> for Error {
     fn from(value: argon2::password_hash::Error) -> Self {
         Error::Internal(format!("{value}"), None)
     }
 }
 
+#[cfg(feature = "server")]
 impl From<salvo::http::ParseError> for Error {
     fn from(value: salvo::http::ParseError) -> Self {
         Error::InvalidRequest(value.to_string(), None)
@@ -83,14 +133,16 @@ impl From<salvo::http::ParseError> for Error {
 }
 
 /// Http error response
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
 pub struct HttpErrorResponse {
     /// Main error
     pub error: HttpError,
 }
 
 /// Error JSON shape
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
 pub struct HttpError {
     /// Code (string)
     pub code: String,
@@ -98,18 +150,48 @@ pub struct HttpError {
     pub message: String,
     /// Other details
     pub detail: Option<String>,
+    /// Invalid fields
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldError>,
 }
 
+/// Invalid field
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
+pub struct FieldError {
+    /// Field path (eg. `email` or `[2].url`)
+    pub field: String,
+    /// Message
+    pub message: String,
+}
+
+impl FieldError {
+    /// Creates a new field error
+    pub fn new(field: &str, message: &str) -> Self {
+        Self {
+            field: field.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+#[cfg(feature = "server")]
 #[async_trait]
 impl Writer for Error {
     async fn write(mut self, _req: &mut Request, _depot: &mut Depot, res: &mut Response) {
         let code = self.code();
         let http_code = self.http_code();
-        let (message, detail) = match self {
-            Error::InvalidRequest(message, detail) => (message, detail),
-            Error::NotFound(message, detail) => (message, detail),
-            Error::Unauthenticated(message, detail) => (message, detail),
-            Error::Internal(message, detail) => (message, detail),
+        let (message, detail, fields) = match self {
+            Error::InvalidRequest(message, detail) => (message, detail, vec![]),
+            Error::NotFound(message, detail) => (message, detail, vec![]),
+            Error::Unauthenticated(message, detail) => (message, detail, vec![]),
+            Error::Forbidden(message, detail) => (message, detail, vec![]),
+            Error::Conflict(message, detail) => (message, detail, vec![]),
+            Error::MethodNotAllowed(message, detail) => (message, detail, vec![]),
+            Error::PayloadTooLarge(message, detail) => (message, detail, vec![]),
+            Error::InvalidFields(message, fields) => (message, None, fields),
+            Error::Unprocessable(message, detail) => (message, detail, vec![]),
+            Error::Internal(message, detail) => (message, detail, vec![]),
         };
 
         let err = HttpErrorResponse {
@@ -117,6 +199,7 @@ impl Writer for Error {
                 code,
                 message,
                 detail,
+                fields,
             },
         };
         res.status_code(http_code);
@@ -124,26 +207,42 @@ impl Writer for Error {
     }
 }
 
-// NB: needed for OpenAPI specs
+// NB: needed for OpenAPI specs, each endpoint keeps the statuses it returns with `status_codes`
+#[cfg(feature = "server")]
 impl EndpointOutRegister for Error {
     fn register(components: &mut salvo::oapi::Components, operation: &mut salvo::oapi::Operation) {
         let schema = HttpErrorResponse::to_schema(components);
         let content = salvo::oapi::Content::new(schema);
 
-        let res = salvo::oapi::Response::new("Bad request")
-            .add_content("application/json", content.clone());
-        operation.responses.insert("400", res);
-
-        let res = salvo::oapi::Response::new("Unauthorized")
-            .add_content("application/json", content.clone());
-        operation.responses.insert("401", res);
-
-        let res =
-            salvo::oapi::Response::new("Server error").add_content("application/json", content);
-        operation.responses.insert("500", res);
+        for (status, description) in [
+            ("400", "Invalid request (code `INVALID_REQUEST`)"),
+            ("401", "Not authenticated (code `NOT_AUTHENTICATED`)"),
+            ("403", "Forbidden (code `FORBIDDEN`)"),
+            ("404", "Resource not found (code `NOT_FOUND`)"),
+            (
+                "405",
+                "Method not allowed on this path (code `METHOD_NOT_ALLOWED`)",
+            ),
+            (
+                "409",
+                "Conflict with the current state of the resource (code `CONFLICT`)",
+            ),
+            ("413", "Payload too large (code `PAYLOAD_TOO_LARGE`)"),
+            (
+                "422",
+                "Invalid fields, listed in `fields` (code `INVALID_FIELDS`), or request which \
+                 cannot be processed (code `UNPROCESSABLE`)",
+            ),
+            ("500", "Server error (code `INTERNAL`)"),
+        ] {
+            let res = salvo::oapi::Response::new(description)
+                .add_content("application/json", content.clone());
+            operation.responses.insert(status, res);
+        }
     }
 }
 
+#[cfg(feature = "server")]
 impl From<async_openai::error::OpenAIError> for Error {
     fn from(value: async_openai::error::OpenAIError) -> Self {
         Error::Internal(format!("OpenAI error ({value})"), None)
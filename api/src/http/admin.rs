@@ -0,0 +1,229 @@
+//! Admin endpoints
+
+use salvo::{
+    hyper::header::{HeaderValue, CONTENT_TYPE},
+    oapi::extract::JsonBody,
+    prelude::*,
+};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::{
+    db::tenant::with_system,
+    error::Error,
+    http::ApiServices,
+    mdl::{JobCount, VectorIndexReport},
+    svc::{
+        backup::{Backup, RestoreReport, BACKUP_PAGE_SIZE},
+        retention::{CleanupReport, RetentionStats},
+        stats::{InstanceStats, Metrics},
+    },
+};
+
+/// Size of the chunks of a streamed backup
+const BACKUP_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Backs up the data
+///
+/// The dump contains the data of a single user if the `user_id` query parameter is set,
+/// otherwise the data of the whole instance. It is streamed as a JSON [Backup].
+/// Reserved to the administrator.
+#[endpoint(tags("admin"), status_codes(200, 400, 401, 403, 404, 405, 500), security(["bearerAuth" = []]))]
+#[tracing::instrument(skip_all)]
+pub async fn get_backup(
+    req: &mut Request,
+    depot: &mut Depot,
+    res: &mut Response,
+) -> Result<(), Error> {
+    let services = depot.obtain::<ApiServices>().unwrap();
+
+    let user_id = match req.query::<String>("user_id") {
+        Some(s) => Some(Uuid::parse_str(&s).map_err(|err| {
+            Error::InvalidRequest("invalid user_id".to_string(), Some(err.to_string()))
+        })?),
+        None => None,
+    };
+    // NB: a missing user is reported before the dump is streamed
+    if let Some(user_id) = user_id {
+        services
+            .backup
+            .db
+            .read_user(user_id)
+            .await?
+            .ok_or(Error::NotFound(format!("no user for id {user_id}"), None))?;
+    }
+
+    let (mut writer, reader) = tokio::io::duplex(BACKUP_CHUNK_SIZE);
+    let backup = services.backup.clone();
+    // NB: the administrator scope is not inherited by the spawned task
+    tokio::spawn(with_system(async move {
+        if let Err(err) = backup
+            .write_backup(user_id, &mut writer, BACKUP_PAGE_SIZE)
+            .await
+        {
+            // NB: the response has started, so the dump is truncated
+            error!(%err, "failed to write the backup");
+        }
+    }));
+    let chunks = super::read_chunks(reader, BACKUP_CHUNK_SIZE);
+
+    res.headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    res.streaming(chunks).map_err(|err| {
+        Error::Internal(
+            "failed to stream the backup".to_string(),
+            Some(err.to_string()),
+        )
+    })
+}
+
+/// Restores a backup
+///
+/// Users are created or replaced, with their feeds. Reserved to the administrator.
+#[endpoint(tags("admin"), status_codes(200, 400, 401, 403, 405, 413, 500), security(["bearerAuth" = []]))]
+#[tracing::instrument(skip_all)]
+pub async fn post_restore(
+    depot: &mut Depot,
+    body: JsonBody<Backup>,
+) -> Result<Json<RestoreReport>, Error> {
+    let services = depot.obtain::<ApiServices>().unwrap();
+
+    let report = services.backup.restore(body.into_inner()).await?;
+    Ok(Json(report))
+}
+
+/// Returns the data retention metrics
+///
+/// The metrics are cumulated since the start of the service. Reserved to the administrator.
+#[endpoint(tags("admin"), status_codes(200, 401, 403, 405, 500), security(["bearerAuth" = []]))]
+#[tracing::instrument(skip_all)]
+pub async fn get_retention(depot: &mut Depot) -> Result<Json<RetentionStats>, Error> {
+    let services = depot.obtain::<ApiServices>().unwrap();
+
+    Ok(Json(services.retention.stats()))
+}
+
+/// Runs the data retention cleanup
+///
+/// The data past its retention period is deleted without waiting for the scheduled job.
+/// Reserved to the administrator.
+#[endpoint(tags("admin"), status_codes(200, 401, 403, 405, 500), security(["bearerAuth" = []]))]
+#[tracing::instrument(skip_all)]
+pub async fn post_retention(depot: &mut Depot) -> Result<Json<CleanupReport>, Error> {
+    let services = depot.obtain::<ApiServices>().unwrap();
+
+    let report = services.retention.run().await?;
+    Ok(Json(report))
+}
+
+/// Maximum number of days of the summaries statistics
+const STATS_MAX_DAYS: u32 = 366;
+
+/// Returns the instance statistics
+///
+/// The summaries are counted per day over the last `days` days (30 by default).
+/// Reserved to the administrator.
+#[endpoint(tags("admin"), status_codes(200, 400, 401, 403, 405, 500), security(["bearerAuth" = []]))]
+#[tracing::instrument(skip_all)]
+pub async fn get_stats(req: &mut Request, depot: &mut Depot) -> Result<Json<InstanceStats>, Error> {
+    let services = depot.obtain::<ApiServices>().unwrap();
+
+    let days = match req.query::<String>("days") {
+        Some(s) => s
+            .parse::<u32>()
+            .ok()
+            .filter(|d| (1..=STATS_MAX_DAYS).contains(d))
+            .ok_or(Error::InvalidRequest(
+                format!("days must be between 1 and {STATS_MAX_DAYS}"),
+                None,
+            ))?,
+        None => 30,
+    };
+
+    let stats = services.stats.stats(days).await?;
+    Ok(Json(stats))
+}
+
+/// Returns the service metrics
+///
+/// The DB operations are timed, and the waits to acquire a pooled connection are recorded
+/// (PostgreSQL only). The metrics are cumulated since the start of the service.
+/// Reserved to the administrator.
+#[endpoint(tags("admin"), status_codes(200, 401, 403, 405, 500), security(["bearerAuth" = []]))]
+#[tracing::instrument(skip_all)]
+pub async fn get_metrics(depot: &mut Depot) -> Result<Json<Metrics>, Error> {
+    let services = depot.obtain::<ApiServices>().unwrap();
+
+    Ok(Json(services.stats.metrics()))
+}
+
+/// Returns the number of background jobs by status
+///
+/// The `dead` jobs have failed after all their attempts, and are not run again.
+/// Reserved to the administrator.
+#[endpoint(tags("admin"), status_codes(200, 401, 403, 405, 500), security(["bearerAuth" = []]))]
+#[tracing::instrument(skip_all)]
+pub async fn get_jobs(depot: &mut Depot) -> Result<Json<Vec<JobCount>>, Error> {
+    let services = depot.obtain::<ApiServices>().unwrap();
+
+    let stats = services.jobs.stats().await?;
+    Ok(Json(stats))
+}
+
+/// Maximum number of summaries sampled to check the vector index
+const VECTOR_INDEX_MAX_SAMPLE: usize = 1000;
+
+/// Maximum number of neighbors searched to check the vector index
+const VECTOR_INDEX_MAX_K: usize = 100;
+
+/// Parses the `sample` and `k` query parameters of the vector index endpoints
+fn vector_index_params(req: &Request) -> Result<(usize, usize), Error> {
+    let param = |name: &str, default: usize, max: usize| match req.query::<String>(name) {
+        Some(s) => s
+            .parse::<usize>()
+            .ok()
+            .filter(|v| (1..=max).contains(v))
+            .ok_or(Error::InvalidRequest(
+                format!("{name} must be between 1 and {max}"),
+                None,
+            )),
+        None => Ok(default),
+    };
+    let sample = param("sample", 50, VECTOR_INDEX_MAX_SAMPLE)?;
+    let k = param("k", 10, VECTOR_INDEX_MAX_K)?;
+    Ok((sample, k))
+}
+
+/// Checks the recall of the vector index
+///
+/// The `k` (10 by default) closest summaries found with the index are compared to the exact
+/// ones, for `sample` random summaries (50 by default). Reserved to the administrator.
+#[endpoint(tags("admin"), status_codes(200, 400, 401, 403, 405, 500), security(["bearerAuth" = []]))]
+#[tracing::instrument(skip_all)]
+pub async fn get_vector_index(
+    req: &mut Request,
+    depot: &mut Depot,
+) -> Result<Json<VectorIndexReport>, Error> {
+    let services = depot.obtain::<ApiServices>().unwrap();
+
+    let (sample, k) = vector_index_params(req)?;
+    let report = services.maintenance.check_vector_index(sample, k).await?;
+    Ok(Json(report))
+}
+
+/// Rebuilds the vector index
+///
+/// The recall is checked once the index is rebuilt (same parameters as the `GET` request).
+/// Reserved to the administrator.
+#[endpoint(tags("admin"), status_codes(200, 400, 401, 403, 405, 500), security(["bearerAuth" = []]))]
+#[tracing::instrument(skip_all)]
+pub async fn post_vector_index(
+    req: &mut Request,
+    depot: &mut Depot,
+) -> Result<Json<VectorIndexReport>, Error> {
+    let services = depot.obtain::<ApiServices>().unwrap();
+
+    let (sample, k) = vector_index_params(req)?;
+    let report = services.maintenance.reindex_vectors(sample, k).await?;
+    Ok(Json(report))
+}
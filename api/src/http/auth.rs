@@ -1,37 +1,48 @@
 //! Auth handlers
 
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
 use cookie::Cookie;
-use salvo::{oapi::extract::*, prelude::*};
-use serde::{Deserialize, Serialize};
-use tracing::trace;
+use salvo::{
+    hyper::header::{HeaderValue, CONTENT_DISPOSITION, CONTENT_TYPE},
+    oapi::extract::*,
+    prelude::*,
+};
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncWrite;
+use tracing::error;
 
+pub use crate::mdl::body::{
+    GetUserRespBody, LoginReqBody, LoginRespBody, SignupRespBody, EXPORT_CHECKSUM_HEADER,
+};
 use crate::{
     error::Error,
     http::ApiServices,
-    mdl::{NewUser, SubscriptionUpdate, User, UserUpdate},
+    mdl::{validate::Validate, NewUser, PasswordChange, SubscriptionUpdate, User, UserUpdate},
+    svc::backup::write_export,
 };
 
-/// Signup response body
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
-pub struct SignupRespBody {
-    /// JWT auth token
-    pub token: String,
-    /// User
-    pub user: User,
-}
+/// Size of the chunks of a streamed export
+const EXPORT_CHUNK_SIZE: usize = 64 * 1024;
 
 /// Handles the signup request
-#[endpoint]
+///
+/// Creates a new user and returns an authentication token. The token is also set as an
+/// HTTP-only cookie.
+#[endpoint(tags("auth"), status_codes(200, 400, 405, 409, 413, 422, 500))]
 #[tracing::instrument(skip_all)]
 pub async fn signup(
     depot: &mut Depot,
     body: JsonBody<NewUser>,
     res: &mut Response,
 ) -> Result<Json<SignupRespBody>, Error> {
-    trace!("received request");
     let services = depot.obtain::<ApiServices>().unwrap();
 
     let new_user = body.into_inner();
+    new_user.validate()?;
     let user = services.auth.create_user(new_user).await?;
     let token = services.auth.issue_token(&user)?;
     let auth_cookie = issue_auth_cookie(&token);
@@ -44,33 +55,17 @@ pub async fn signup(
     }))
 }
 
-/// Login request body
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
-pub struct LoginReqBody {
-    /// Email
-    pub email: String,
-    /// Password
-    pub password: String,
-}
-
-/// Login response body
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
-pub struct LoginRespBody {
-    /// JWT auth token
-    pub token: String,
-    /// User
-    pub user: User,
-}
-
 /// Handles the login request
-#[endpoint]
+///
+/// Returns an authentication token valid for 30 days. The token is also set as an HTTP-only
+/// cookie.
+#[endpoint(tags("auth"), status_codes(200, 400, 401, 405, 413, 500))]
 #[tracing::instrument(skip_all)]
 pub async fn login(
     depot: &mut Depot,
     body: JsonBody<LoginReqBody>,
     res: &mut Response,
 ) -> Result<Json<LoginRespBody>, Error> {
-    trace!("received request");
     let services = depot.obtain::<ApiServices>().unwrap();
 
     let payload = body.into_inner();
@@ -89,18 +84,10 @@ pub async fn login(
     }))
 }
 
-/// Get user response body
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
-pub struct GetUserRespBody {
-    /// User
-    pub user: User,
-}
-
 /// Fetches the current user
-#[endpoint(security(["bearerAuth" = []]))]
+#[endpoint(tags("auth"), status_codes(200, 401, 405, 500), security(["bearerAuth" = []]))]
 #[tracing::instrument(skip_all)]
 pub async fn get_me(depot: &mut Depot) -> Result<Json<GetUserRespBody>, Error> {
-    trace!("received request");
     let user = depot.obtain::<User>().ok_or(Error::Unauthenticated(
         "not authenticated".to_string(),
         None,
@@ -110,34 +97,58 @@ pub async fn get_me(depot: &mut Depot) -> Result<Json<GetUserRespBody>, Error> {
 }
 
 /// Updates the current user
-#[endpoint(security(["bearerAuth" = []]))]
+#[endpoint(tags("auth"), status_codes(200, 400, 401, 405, 413, 422, 500), security(["bearerAuth" = []]))]
 #[tracing::instrument(skip_all)]
 pub async fn update_me(
     depot: &mut Depot,
     body: JsonBody<UserUpdate>,
 ) -> Result<Json<GetUserRespBody>, Error> {
-    trace!("received request");
     let services = depot.obtain::<ApiServices>().unwrap();
     let user = depot.obtain::<User>().ok_or(Error::Unauthenticated(
         "not authenticated".to_string(),
         None,
     ))?;
 
-    let user = services
-        .auth
-        .update_user(user.id, body.into_inner())
-        .await?;
+    let fields = body.into_inner();
+    fields.validate()?;
+    let user = services.auth.update_user(user.id, fields).await?;
 
     Ok(Json(GetUserRespBody { user }))
 }
 
+/// Changes the password of the current user
+///
+/// A new authentication token is issued, and set as an HTTP-only cookie.
+///
+/// NB: the tokens issued before remain valid until they expire
+#[endpoint(tags("auth"), status_codes(200, 400, 401, 405, 413, 422, 500), security(["bearerAuth" = []]))]
+#[tracing::instrument(skip_all)]
+pub async fn put_password(
+    depot: &mut Depot,
+    body: JsonBody<PasswordChange>,
+    res: &mut Response,
+) -> Result<Json<LoginRespBody>, Error> {
+    let services = depot.obtain::<ApiServices>().unwrap();
+    let user = depot.obtain::<User>().ok_or(Error::Unauthenticated(
+        "not authenticated".to_string(),
+        None,
+    ))?;
+
+    let change = body.into_inner();
+    change.validate()?;
+    let user = services.auth.change_password(user.id, change).await?;
+    let token = services.auth.issue_token(&user)?;
+
+    res.add_cookie(issue_auth_cookie(&token));
+    Ok(Json(LoginRespBody { token, user }))
+}
+
 /// Deletes a user
 ///
 /// The ID is retrieved from the token
-#[endpoint(security(["bearerAuth" = []]))]
+#[endpoint(tags("auth"), status_codes(200, 401, 405, 500), security(["bearerAuth" = []]))]
 #[tracing::instrument(skip_all)]
 pub async fn delete_me(depot: &mut Depot) -> Result<(), Error> {
-    trace!("received request");
     let services = depot.obtain::<ApiServices>().unwrap();
     let user = depot.obtain::<User>().ok_or(Error::Unauthenticated(
         "not authenticated".to_string(),
@@ -149,15 +160,88 @@ pub async fn delete_me(depot: &mut Depot) -> Result<(), Error> {
     Ok(())
 }
 
+/// Exports the data of the current user
+///
+/// The export contains the user (without their password) and their feeds. It is streamed as a
+/// JSON attachment, with its checksum in the `x-checksum-sha256` header.
+#[endpoint(tags("auth"), status_codes(200, 401, 405, 500), security(["bearerAuth" = []]))]
+#[tracing::instrument(skip_all)]
+pub async fn get_export(depot: &mut Depot, res: &mut Response) -> Result<(), Error> {
+    let services = depot.obtain::<ApiServices>().unwrap();
+    let user = depot.obtain::<User>().ok_or(Error::Unauthenticated(
+        "not authenticated".to_string(),
+        None,
+    ))?;
+
+    // NB: the checksum is sent before the body, so the export is serialized twice (to compute
+    // the checksum, then to stream it) from the same data
+    let export = services.backup.read_export(user.id).await?;
+    let mut hasher = ChecksumWriter::default();
+    write_export(&export, &mut hasher).await?;
+    let checksum = hasher
+        .0
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<String>();
+
+    let (mut writer, reader) = tokio::io::duplex(EXPORT_CHUNK_SIZE);
+    tokio::spawn(async move {
+        if let Err(err) = write_export(&export, &mut writer).await {
+            // NB: the response has started, so the export is truncated
+            error!(%err, "failed to write the export");
+        }
+    });
+    let chunks = super::read_chunks(reader, EXPORT_CHUNK_SIZE);
+
+    let headers = res.headers_mut();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    headers.insert(
+        CONTENT_DISPOSITION,
+        HeaderValue::from_static("attachment; filename=\"newsie-export.json\""),
+    );
+    if let Ok(v) = HeaderValue::from_str(&checksum) {
+        headers.insert(EXPORT_CHECKSUM_HEADER, v);
+    }
+    res.streaming(chunks).map_err(|err| {
+        Error::Internal(
+            "failed to stream the export".to_string(),
+            Some(err.to_string()),
+        )
+    })
+}
+
+/// Writer computing the checksum of the written bytes
+#[derive(Default)]
+struct ChecksumWriter(Sha256);
+
+impl AsyncWrite for ChecksumWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        self.get_mut().0.update(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
 /// Updates a subscription
-#[endpoint(security(["bearerAuth" = []]))]
+#[endpoint(tags("auth"), status_codes(200, 400, 401, 405, 413, 500), security(["bearerAuth" = []]))]
 #[tracing::instrument(skip_all)]
 pub async fn put_subscription(
     depot: &mut Depot,
     body: JsonBody<SubscriptionUpdate>,
     _res: &mut Response,
 ) -> Result<Json<GetUserRespBody>, Error> {
-    trace!("received request");
     let services = depot.obtain::<ApiServices>().unwrap();
 
     let user = depot.obtain::<User>().ok_or(Error::Unauthenticated(
@@ -202,7 +286,8 @@ mod tests {
     };
 
     use crate::{
-        config::AppConfig, db::postgres::PostgresClient, http::init_service, svc::auth::AuthService,
+        config::AppConfig, db::init_store, http::init_service, mdl::report::UserExport,
+        svc::auth::AuthService,
     };
 
     // Setup a test
@@ -210,9 +295,8 @@ mod tests {
         // setup
         let cfg = AppConfig::load();
         crate::trace::init_tracer(&cfg);
-        let service = init_service(&cfg).await;
-        let postgres_client = PostgresClient::new(cfg.postgres.new_pool());
-        let auth = AuthService::new(postgres_client, cfg.auth.secret.clone());
+        let service = init_service(&cfg).await.unwrap();
+        let auth = AuthService::new(init_store(&cfg).unwrap(), cfg.auth.secret.clone());
 
         // create test user
         let name: String = Name().fake();
@@ -285,4 +369,76 @@ mod tests {
         assert_eq!(res.status_code.unwrap(), StatusCode::OK);
         teardown(service, token).await;
     }
+
+    #[tokio::test]
+    async fn test_me_password() {
+        let (service, user, token) = setup().await;
+        let res = TestClient::put("http://localhost:3000/auth/me/password")
+            .add_header(AUTHORIZATION, format!("Bearer {token}"), true)
+            .json(&PasswordChange {
+                current_password: "wrong".to_string(),
+                new_password: "new-password".to_string(),
+            })
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code.unwrap(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let res = TestClient::put("http://localhost:3000/auth/me/password")
+            .add_header(AUTHORIZATION, format!("Bearer {token}"), true)
+            .json(&PasswordChange {
+                current_password: "1234".to_string(),
+                new_password: "new-password".to_string(),
+            })
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code.unwrap(), StatusCode::OK);
+
+        let res = TestClient::post("http://localhost:3000/auth/login")
+            .json(&LoginReqBody {
+                email: user.email.clone(),
+                password: "new-password".to_string(),
+            })
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code.unwrap(), StatusCode::OK);
+        teardown(service, token).await;
+    }
+
+    #[tokio::test]
+    async fn test_me_export() {
+        let (service, user, token) = setup().await;
+        let mut res = TestClient::get("http://localhost:3000/auth/me/export")
+            .add_header(AUTHORIZATION, format!("Bearer {token}"), true)
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code.unwrap(), StatusCode::OK);
+        let checksum = res
+            .headers()
+            .get(EXPORT_CHECKSUM_HEADER)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = res.take_bytes(None).await.unwrap();
+        let expected = Sha256::digest(&body)
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<String>();
+        assert_eq!(checksum, expected);
+        let value = serde_json::from_slice::<serde_json::Value>(&body).unwrap();
+        assert!(value["user"].get("password").is_none());
+        let export = serde_json::from_slice::<UserExport>(&body).unwrap();
+        assert_eq!(export.user.id, user.id);
+        assert!(export.feeds.is_empty());
+        teardown(service, token).await;
+    }
+
+    #[tokio::test]
+    async fn test_me_export_unauthenticated() {
+        let service = init_service(&AppConfig::load()).await.unwrap();
+        let res = TestClient::get("http://localhost:3000/auth/me/export")
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code.unwrap(), StatusCode::UNAUTHORIZED);
+    }
 }
@@ -0,0 +1,65 @@
+//! Error catchers
+//!
+//! Salvo renders its own HTML/plain-text pages for unmatched routes. The handlers below make
+//! sure that every error response has the [HttpErrorResponse](crate::error::HttpErrorResponse)
+//! JSON shape.
+
+use salvo::{catcher::Catcher, prelude::*};
+use tracing::trace;
+
+use crate::error::Error;
+
+/// Creates the service catcher
+pub fn init_catcher() -> Catcher {
+    Catcher::default().hoop(catch_error)
+}
+
+/// Converts empty error responses to JSON errors
+#[handler]
+pub async fn catch_error(
+    req: &mut Request,
+    depot: &mut Depot,
+    res: &mut Response,
+    ctrl: &mut FlowCtrl,
+) {
+    // NB: responses with a body have already been rendered by a handler
+    if !res.body.is_none() {
+        return;
+    }
+
+    let status = res.status_code.unwrap_or(StatusCode::NOT_FOUND);
+    let path = req.uri().path().to_string();
+    let method = req.method().to_string();
+    trace!(%status, path, method, "catching error");
+    let err = match status {
+        StatusCode::NOT_FOUND => Error::NotFound(format!("no route for '{path}'"), None),
+        StatusCode::METHOD_NOT_ALLOWED => {
+            Error::MethodNotAllowed(format!("method {method} is not allowed for '{path}'"), None)
+        }
+        _ if status.is_client_error() => Error::InvalidRequest(
+            status
+                .canonical_reason()
+                .unwrap_or("invalid request")
+                .to_string(),
+            None,
+        ),
+        _ => Error::Internal(
+            status
+                .canonical_reason()
+                .unwrap_or("internal error")
+                .to_string(),
+            None,
+        ),
+    };
+
+    err.write(req, depot, res).await;
+    // keep the catcher's status code (eg. for 4xx without a dedicated variant)
+    res.status_code(status);
+    ctrl.skip_rest();
+}
+
+/// Fallback for routes which exist but do not support the request method
+#[handler]
+pub async fn method_not_allowed(res: &mut Response) {
+    res.status_code(StatusCode::METHOD_NOT_ALLOWED);
+}
@@ -1,54 +1,141 @@
 //! Feeds endpoints
 
-use salvo::{oapi::extract::JsonBody, prelude::*};
-use serde::{Deserialize, Serialize};
-use tracing::trace;
+use salvo::{
+    hyper::header::{HeaderValue, ETAG, IF_MATCH},
+    oapi::extract::JsonBody,
+    prelude::*,
+};
 
+pub use crate::mdl::body::GetFeedsRespBody;
 use crate::{
+    config::AppConfig,
     error::Error,
     http::ApiServices,
-    mdl::{Feed, FeedUpdate, User},
+    mdl::{validate::Validate, Feed, FeedUpdate, User},
+    svc::feed::feeds_version,
 };
 
-/// Get feeds response body
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
-pub struct GetFeedsRespBody {
-    /// Feeds
-    pub feeds: Vec<Feed>,
-}
+use super::{
+    negotiate::Negotiated,
+    paginated::Paginated,
+    query::{parse_list_options, QuerySpec},
+};
+
+/// Listing options of the feeds
+const FEEDS_QUERY: QuerySpec = QuerySpec {
+    sortable: &["name", "url"],
+    filterable: &["name", "url"],
+    max_limit: 1000,
+};
 
 /// Get all the user feeds
-#[endpoint(security(["bearerAuth" = []]))]
+///
+/// Feeds can be sorted (`?sort=-name,url`) and filtered (`?filter[name]=...`) by name and url.
+/// With a `limit`, the feeds are paginated (the next page is requested with `?cursor=...`).
+/// The response is serialized as MessagePack if requested with the `Accept` header.
+///
+/// If all the feeds are returned (no filter nor pagination), their version is set in the `ETag`
+/// header (see `PUT /feeds`).
+#[endpoint(tags("feeds"), status_codes(200, 400, 401, 405, 500), security(["bearerAuth" = []]))]
 #[tracing::instrument(skip_all)]
-pub async fn get_feeds(depot: &mut Depot) -> Result<Json<GetFeedsRespBody>, Error> {
-    trace!("received request");
+pub async fn get_feeds(
+    req: &mut Request,
+    depot: &mut Depot,
+    res: &mut Response,
+) -> Result<Negotiated<Paginated<Feed>>, Error> {
     let services = depot.obtain::<ApiServices>().unwrap();
     let user = depot.obtain::<User>().ok_or(Error::Unauthenticated(
         "not authenticated".to_string(),
         None,
     ))?;
 
-    let feeds = services.feeds.get_feeds(user.id).await?;
-    Ok(Json(GetFeedsRespBody { feeds }))
+    let opts = parse_list_options(req, &FEEDS_QUERY)?;
+    let page = services.feeds.query_feeds(user.id, &opts).await?;
+    if opts.is_all() {
+        set_etag(res, &page.items);
+    }
+    match page.next_cursor {
+        // NB: without a limit, all the feeds are returned
+        None if opts.limit.is_none() => Ok(Negotiated(Paginated::all(page.items))),
+        next_cursor => Ok(Negotiated(Paginated::new(
+            page.items,
+            next_cursor.map(|c| c.encode()),
+        ))),
+    }
 }
 
 /// Sync all the user feeds
-#[endpoint(security(["bearerAuth" = []]))]
+///
+/// The user feeds are replaced by the feeds in the body. Feeds with an ID keep their ID, other
+/// feeds are created. The number of feeds per user is limited.
+///
+/// To avoid overwriting the changes of another device, send the version of the feeds
+/// (`ETag` header of `GET /feeds`) in the `If-Match` header: if the feeds have changed since,
+/// a 409 error is returned with the current feeds and version (as JSON) in its `detail`.
+/// The version of the new feeds is set in the `ETag` header.
+#[endpoint(tags("feeds"), status_codes(200, 400, 401, 405, 409, 413, 422, 500), security(["bearerAuth" = []]))]
 #[tracing::instrument(skip_all)]
 pub async fn put_feeds(
+    req: &mut Request,
     depot: &mut Depot,
+    res: &mut Response,
     body: JsonBody<Vec<FeedUpdate>>,
 ) -> Result<Json<GetFeedsRespBody>, Error> {
-    trace!("received request");
     let services = depot.obtain::<ApiServices>().unwrap();
     let user = depot.obtain::<User>().ok_or(Error::Unauthenticated(
         "not authenticated".to_string(),
         None,
     ))?;
+    let limits = &depot.obtain::<AppConfig>().unwrap().limits;
+
+    let feeds = body.into_inner();
+    if feeds.len() > limits.feeds {
+        return Err(Error::InvalidRequest(
+            format!("too many feeds (max {})", limits.feeds),
+            None,
+        ));
+    }
+    feeds.validate()?;
+
+    let if_match = match req.headers().get(IF_MATCH) {
+        Some(v) => Some(
+            v.to_str()
+                .map(parse_etag)
+                .map_err(|_| Error::InvalidRequest("Invalid if-match header".to_string(), None))?,
+        ),
+        None => None,
+    };
 
     let feeds = services
         .feeds
-        .sync_feeds(user.id, body.into_inner())
+        .sync_feeds(user.id, feeds, if_match.as_deref())
         .await?;
+    set_etag(res, &feeds);
     Ok(Json(GetFeedsRespBody { feeds }))
 }
+
+/// Sets the version of the feeds in the `ETag` header
+fn set_etag(res: &mut Response, feeds: &[Feed]) {
+    if let Ok(v) = HeaderValue::from_str(&format!("\"{}\"", feeds_version(feeds))) {
+        res.headers_mut().insert(ETAG, v);
+    }
+}
+
+/// Parses an entity tag (quoted, possibly weak) to a version
+fn parse_etag(value: &str) -> String {
+    let value = value.trim();
+    let value = value.strip_prefix("W/").unwrap_or(value);
+    value.trim_matches('"').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_etag() {
+        assert_eq!(parse_etag("\"abc\""), "abc");
+        assert_eq!(parse_etag("W/\"abc\""), "abc");
+        assert_eq!(parse_etag("*"), "*");
+    }
+}
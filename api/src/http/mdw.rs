@@ -1,11 +1,151 @@
 //! Middlewares
 
-use salvo::{hyper::header::AUTHORIZATION, prelude::*};
-use tracing::trace;
+use std::{panic::AssertUnwindSafe, time::Instant};
 
-use crate::error::Error;
+use futures::FutureExt;
+use salvo::{
+    http::ResBody,
+    hyper::header::{HeaderValue, AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE},
+    prelude::*,
+};
+use sha2::{Digest, Sha256};
+use tracing::{error, info, info_span, trace, warn, Instrument};
+use uuid::Uuid;
 
-use super::{auth::AUTH_COOKIE_NAME, ApiServices};
+use crate::{
+    config::AppConfig,
+    db::tenant::{with_system, with_tenant},
+    error::Error,
+    mdl::User,
+};
+
+use super::{auth::AUTH_COOKIE_NAME, negotiate::accepts_msgpack, ApiServices};
+
+/// Idempotency key header
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// Header set on replayed responses
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+
+/// Maximum length of an idempotency key
+const IDEMPOTENCY_KEY_MAX_LEN: usize = 255;
+
+/// Request ID header
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Maximum length of a request ID set by the client
+const REQUEST_ID_MAX_LEN: usize = 128;
+
+/// ID of the current request (see [request_id])
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+/// Middleware to identify every request
+///
+/// The ID is taken from the `x-request-id` header if set (eg. by a reverse proxy), otherwise
+/// generated. It is returned in the same header, and the request is handled within a span
+/// which holds it, so that the logs (eg. the slow queries) can be matched with the request.
+#[handler]
+pub async fn request_id(
+    req: &mut Request,
+    depot: &mut Depot,
+    res: &mut Response,
+    ctrl: &mut FlowCtrl,
+) {
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|s| !s.is_empty() && s.len() <= REQUEST_ID_MAX_LEN)
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        res.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    depot.inject(RequestId(request_id.clone()));
+
+    let span = info_span!("request", %request_id);
+    ctrl.call_next(req, depot, res).instrument(span).await;
+}
+
+/// Middleware to log every request
+///
+/// The log is emitted once the request has been handled, so that the status code, the
+/// authenticated user and the duration are known.
+#[handler]
+pub async fn access_log(
+    req: &mut Request,
+    depot: &mut Depot,
+    res: &mut Response,
+    ctrl: &mut FlowCtrl,
+) {
+    if !depot.obtain::<AppConfig>().unwrap().trace.access {
+        ctrl.call_next(req, depot, res).await;
+        return;
+    }
+
+    let start = Instant::now();
+    ctrl.call_next(req, depot, res).await;
+    let duration = start.elapsed();
+
+    let status = res.status_code.unwrap_or(StatusCode::OK);
+    let user_id = depot.obtain::<User>().map(|user| user.id);
+    info!(
+        method = %req.method(),
+        path = req.uri().path(),
+        status = status.as_u16(),
+        user_id = ?user_id,
+        duration_ms = duration.as_secs_f64() * 1000.0,
+        "request"
+    );
+}
+
+/// Middleware to catch panics in handlers
+///
+/// A panic is converted to a 500 error response which contains a request ID, so that it can
+/// be matched with the logs.
+#[handler]
+pub async fn catch_panic(
+    req: &mut Request,
+    depot: &mut Depot,
+    res: &mut Response,
+    ctrl: &mut FlowCtrl,
+) {
+    let result = AssertUnwindSafe(ctrl.call_next(req, depot, res))
+        .catch_unwind()
+        .await;
+
+    if let Err(panic) = result {
+        let request_id = depot
+            .obtain::<RequestId>()
+            .map(|id| id.0.clone())
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+        let panic_msg = if let Some(msg) = panic.downcast_ref::<&str>() {
+            msg.to_string()
+        } else if let Some(msg) = panic.downcast_ref::<String>() {
+            msg.clone()
+        } else {
+            "unknown panic".to_string()
+        };
+        error!(
+            %request_id,
+            method = %req.method(),
+            path = req.uri().path(),
+            panic = panic_msg,
+            "handler panicked"
+        );
+
+        // NB: discard anything the handler wrote before panicking
+        res.body = ResBody::None;
+        Error::Internal(
+            "internal server error".to_string(),
+            Some(format!("request id: {request_id}")),
+        )
+        .write(req, depot, res)
+        .await;
+        ctrl.skip_rest();
+    }
+}
 
 /// Middleware to authenticate the user
 #[handler]
@@ -56,3 +196,359 @@ pub async fn authenticate(req: &mut Request, depot: &mut Depot) -> Result<(), Er
 
     Ok(())
 }
+
+/// Middleware to run the request on behalf of the authenticated user
+///
+/// With the row-level security option, the queries can then only access the data of this
+/// user (see [crate::db::tenant]). NB: the administrator runs in the system scope, with
+/// access to all the data, and the unauthenticated requests have access to no data.
+#[handler]
+pub async fn tenant(req: &mut Request, depot: &mut Depot, res: &mut Response, ctrl: &mut FlowCtrl) {
+    let admin = depot.obtain::<AppConfig>().unwrap().auth.admin.clone();
+    let user = depot
+        .obtain::<User>()
+        .map(|user| (user.id, admin.as_deref() == Some(user.email.as_str())));
+
+    match user {
+        Some((_, true)) => with_system(ctrl.call_next(req, depot, res)).await,
+        Some((user_id, false)) => with_tenant(user_id, ctrl.call_next(req, depot, res)).await,
+        None => ctrl.call_next(req, depot, res).await,
+    };
+}
+
+/// Middleware to restrict a route to the administrator
+///
+/// The administrator is the user with the email set in the `auth.admin` configuration.
+/// If it is not set, the route is forbidden to all.
+#[handler]
+pub async fn require_admin(depot: &mut Depot) -> Result<(), Error> {
+    let user = depot.obtain::<User>().ok_or(Error::Unauthenticated(
+        "not authenticated".to_string(),
+        None,
+    ))?;
+    let admin = depot.obtain::<AppConfig>().unwrap().auth.admin.as_deref();
+
+    if admin == Some(user.email.as_str()) {
+        Ok(())
+    } else {
+        trace!(user_id = %user.id, "not an admin");
+        Err(Error::Forbidden(
+            "reserved to the administrator".to_string(),
+            None,
+        ))
+    }
+}
+
+/// Middleware to enforce the request body size limit
+///
+/// NB: the restore endpoint is not under this middleware, see [limit_restore_size]
+#[handler]
+pub async fn limit_body_size(req: &mut Request, depot: &mut Depot) -> Result<(), Error> {
+    let max_size = depot.obtain::<AppConfig>().unwrap().limits.body;
+    check_body_size(req, max_size)
+}
+
+/// Middleware to enforce the body size limit of the restore endpoint
+///
+/// A restore request carries a whole dump, so its limit is separate (see
+/// [crate::config::LimitsConfig]).
+#[handler]
+pub async fn limit_restore_size(req: &mut Request, depot: &mut Depot) -> Result<(), Error> {
+    let max_size = depot.obtain::<AppConfig>().unwrap().limits.restore;
+    check_body_size(req, max_size)
+}
+
+/// Enforces a body size limit
+///
+/// Requests announcing a larger body are rejected before the body is read. The limit is also
+/// applied to the body parser, so that chunked payloads cannot exceed it either.
+fn check_body_size(req: &mut Request, max_size: usize) -> Result<(), Error> {
+    req.set_secure_max_size(max_size);
+
+    if let Some(v) = req.headers().get(CONTENT_LENGTH) {
+        let size = v
+            .to_str()
+            .ok()
+            .and_then(|s| s.parse::<usize>().ok())
+            .ok_or(Error::InvalidRequest(
+                "Invalid content-length header".to_string(),
+                None,
+            ))?;
+        if size > max_size {
+            trace!(size, max_size, "payload too large");
+            return Err(Error::PayloadTooLarge(
+                format!("request body exceeds {max_size} bytes"),
+                None,
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Middleware to support the `Idempotency-Key` header
+///
+/// For mutating requests with an idempotency key, the response is stored and replayed as-is
+/// when the same request is retried (same key, method, path, user and response format, see
+/// [super::negotiate]) within the configured period. Server errors are not stored, so that the
+/// request can be retried.
+///
+/// The key is reserved while its request is running: a concurrent retry is rejected with a
+/// 409 status code, and a request reusing the key with another body with a 422 status code.
+///
+/// NB: only the status, the content type and the body are replayed (eg. cookies are not)
+#[handler]
+pub async fn idempotency(
+    req: &mut Request,
+    depot: &mut Depot,
+    res: &mut Response,
+    ctrl: &mut FlowCtrl,
+) {
+    // NB: safe methods (eg. GET) are idempotent by definition
+    let key = if req.method().is_safe() {
+        None
+    } else {
+        req.headers()
+            .get(IDEMPOTENCY_KEY_HEADER)
+            .map(|v| v.to_str().ok().map(|s| s.to_string()))
+    };
+    let key = match key {
+        None => {
+            ctrl.call_next(req, depot, res).await;
+            return;
+        }
+        Some(Some(key)) if !key.is_empty() && key.len() <= IDEMPOTENCY_KEY_MAX_LEN => key,
+        Some(_) => {
+            Error::InvalidRequest("Invalid idempotency key header".to_string(), None)
+                .write(req, depot, res)
+                .await;
+            ctrl.skip_rest();
+            return;
+        }
+    };
+
+    let services = depot.obtain::<ApiServices>().unwrap().clone();
+    let user_id = depot.obtain::<User>().map(|user| user.id.to_string());
+    // NB: the response depends on the negotiated format
+    let format = if accepts_msgpack(req) {
+        "msgpack"
+    } else {
+        "json"
+    };
+    let scope = format!(
+        "{} {} {} {}",
+        req.method(),
+        req.uri().path(),
+        user_id.unwrap_or_else(|| "anonymous".to_string()),
+        format
+    );
+
+    // NB: the body is buffered, so that it can still be parsed by the handler
+    let request_hash = req
+        .payload()
+        .await
+        .map(|body| {
+            Sha256::digest(body)
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect::<String>()
+        })
+        .map_err(Error::from);
+    let request_hash = match request_hash {
+        Ok(hash) => hash,
+        Err(err) => {
+            err.write(req, depot, res).await;
+            ctrl.skip_rest();
+            return;
+        }
+    };
+
+    // reserve the key, or replay its stored response
+    match services
+        .idempotency
+        .reserve(&key, &scope, &request_hash)
+        .await
+    {
+        Ok(None) => {}
+        Ok(Some(stored)) => {
+            trace!(key, scope, "replaying response");
+            res.status_code(StatusCode::from_u16(stored.status).unwrap_or(StatusCode::OK));
+            let content_type = stored
+                .content_type
+                .as_deref()
+                .and_then(|v| HeaderValue::from_str(v).ok())
+                .unwrap_or_else(|| HeaderValue::from_static("application/json; charset=utf-8"));
+            res.headers_mut().insert(CONTENT_TYPE, content_type);
+            res.headers_mut()
+                .insert(IDEMPOTENT_REPLAYED_HEADER, HeaderValue::from_static("true"));
+            res.write_body(stored.body).ok();
+            ctrl.skip_rest();
+            return;
+        }
+        Err(err) => {
+            trace!(key, scope, %err, "idempotency key rejected");
+            err.write(req, depot, res).await;
+            ctrl.skip_rest();
+            return;
+        }
+    }
+
+    ctrl.call_next(req, depot, res).await;
+
+    // store the response, or release the key
+    // NB: if the handler panics, the key is released once its lock expires
+    let status = res.status_code.unwrap_or(StatusCode::OK);
+    let content_type = res
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string());
+    let body = match &res.body {
+        ResBody::Once(body) if !status.is_server_error() => Some(body.to_vec()),
+        _ => None,
+    };
+    let stored = match body {
+        Some(body) => {
+            services
+                .idempotency
+                .save(
+                    &key,
+                    &scope,
+                    &request_hash,
+                    status.as_u16(),
+                    content_type,
+                    body,
+                )
+                .await
+        }
+        None => services.idempotency.release(&key, &scope).await,
+    };
+    if let Err(err) = stored {
+        warn!(%err, "failed to store idempotency key");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use salvo::{
+        hyper::header::ACCEPT,
+        test::{ResponseExt, TestClient},
+    };
+
+    use crate::{
+        config::StoreBackend,
+        error::HttpErrorResponse,
+        http::{
+            init_api_services,
+            negotiate::{Negotiated, MSGPACK_MIME},
+        },
+    };
+
+    #[handler]
+    async fn panicking() -> &'static str {
+        panic!("boom")
+    }
+
+    #[tokio::test]
+    async fn test_catch_panic() {
+        let router = Router::new().hoop(catch_panic).get(panicking);
+        let service = Service::new(router);
+        let mut res = TestClient::get("http://localhost:3000")
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code.unwrap(), StatusCode::INTERNAL_SERVER_ERROR);
+        let body = res.take_json::<HttpErrorResponse>().await.unwrap();
+        assert_eq!(body.error.code, "INTERNAL");
+        assert!(body.error.detail.unwrap().starts_with("request id: "));
+    }
+
+    #[tokio::test]
+    async fn test_request_id() {
+        let router = Router::new()
+            .hoop(request_id)
+            .hoop(catch_panic)
+            .get(panicking);
+        let service = Service::new(router);
+        let mut res = TestClient::get("http://localhost:3000")
+            .add_header(REQUEST_ID_HEADER, "req-42", true)
+            .send(&service)
+            .await;
+        assert_eq!(
+            res.headers().get(REQUEST_ID_HEADER).unwrap(),
+            &HeaderValue::from_static("req-42")
+        );
+        let body = res.take_json::<HttpErrorResponse>().await.unwrap();
+        assert_eq!(body.error.detail.unwrap(), "request id: req-42");
+    }
+
+    /// Number of calls of the [counted] handler
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    #[handler]
+    async fn counted() -> Negotiated<Vec<usize>> {
+        Negotiated(vec![CALLS.fetch_add(1, Ordering::Relaxed)])
+    }
+
+    #[tokio::test]
+    async fn test_idempotency_accept() {
+        let mut cfg = AppConfig::load();
+        cfg.store.backend = StoreBackend::Sqlite;
+        cfg.store.path = ":memory:".to_string();
+        cfg.jobs.interval = 0;
+        cfg.retention.interval = 0;
+        let services = init_api_services(&cfg).await.unwrap();
+        let router = Router::new()
+            .hoop(salvo::affix::inject(cfg))
+            .hoop(salvo::affix::inject(services))
+            .hoop(idempotency)
+            .post(counted);
+        let service = Service::new(router);
+        let send = |msgpack: bool| {
+            let req = TestClient::post("http://localhost:3000").add_header(
+                IDEMPOTENCY_KEY_HEADER,
+                "key",
+                true,
+            );
+            let req = if msgpack {
+                req.add_header(ACCEPT, MSGPACK_MIME, true)
+            } else {
+                req
+            };
+            req.send(&service)
+        };
+
+        // NB: the same key with another format is another request
+        let mut res = send(false).await;
+        let json = res.take_json::<Vec<usize>>().await.unwrap();
+        let mut res = send(true).await;
+        assert!(res.headers().get(IDEMPOTENT_REPLAYED_HEADER).is_none());
+        let bytes = res.take_bytes(None).await.unwrap();
+        let msgpack = rmp_serde::from_slice::<Vec<usize>>(&bytes).unwrap();
+        assert_ne!(json, msgpack);
+
+        // replay each response with its content type
+        let mut res = send(true).await;
+        assert!(res.headers().get(IDEMPOTENT_REPLAYED_HEADER).is_some());
+        assert_eq!(res.headers().get(CONTENT_TYPE).unwrap(), MSGPACK_MIME);
+        let bytes = res.take_bytes(None).await.unwrap();
+        assert_eq!(
+            rmp_serde::from_slice::<Vec<usize>>(&bytes).unwrap(),
+            msgpack
+        );
+
+        let mut res = send(false).await;
+        assert!(res.headers().get(IDEMPOTENT_REPLAYED_HEADER).is_some());
+        assert!(res
+            .headers()
+            .get(CONTENT_TYPE)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .starts_with("application/json"));
+        assert_eq!(res.take_json::<Vec<usize>>().await.unwrap(), json);
+    }
+}
@@ -1,5 +1,8 @@
 //! REST API
 
+use std::time::Duration;
+
+use futures::Stream;
 use salvo::{
     oapi::{
         security::{Http, HttpAuthScheme},
@@ -7,19 +10,38 @@ use salvo::{
     },
     prelude::*,
 };
-use tracing::trace;
+use tokio::io::{AsyncRead, AsyncReadExt};
 
+pub use crate::mdl::body::VersionRespBody;
 use crate::{
     config::AppConfig,
-    db::postgres::PostgresClient,
+    db::{init_schema_with_retry, init_store},
     error::Error,
-    svc::{art::ArticleService, auth::AuthService, feed::FeedService},
+    svc::{
+        art::{ArticleService, SUMMARIZE_JOB},
+        auth::AuthService,
+        backup::BackupService,
+        feed::FeedService,
+        health::{HealthService, Readiness},
+        idempotency::IdempotencyService,
+        jobs::JobService,
+        maintenance::MaintenanceService,
+        retention::RetentionService,
+        stats::StatsService,
+    },
 };
 
+pub mod admin;
 pub mod auth;
+pub mod catcher;
 pub mod feed;
 pub mod mdw;
+pub mod negotiate;
+pub mod paginated;
+pub mod query;
 pub mod summary;
+#[cfg(feature = "webui")]
+pub mod webui;
 
 /// API services
 #[derive(Clone)]
@@ -30,12 +52,26 @@ pub struct ApiServices {
     pub feeds: FeedService,
     /// Articles service
     pub art: ArticleService,
+    /// Health service
+    pub health: HealthService,
+    /// Idempotency service
+    pub idempotency: IdempotencyService,
+    /// Backup service
+    pub backup: BackupService,
+    /// Data retention service
+    pub retention: RetentionService,
+    /// Statistics service
+    pub stats: StatsService,
+    /// Background jobs service
+    pub jobs: JobService,
+    /// Maintenance service
+    pub maintenance: MaintenanceService,
 }
 
 /// Initializes the HTTP service
-pub async fn init_service(cfg: &AppConfig) -> Service {
-    let services = init_api_services(cfg).await.unwrap();
-    let router = init_router(services).await;
+pub async fn init_service(cfg: &AppConfig) -> Result<Service, Error> {
+    let services = init_api_services(cfg).await?;
+    let router = init_router(cfg, services).await;
 
     // add the OpenAPI routes to the service
     let openapi = gen_openapi_specs(&router);
@@ -43,50 +79,171 @@ pub async fn init_service(cfg: &AppConfig) -> Service {
         .push(openapi.into_router("/openapi"))
         .push(SwaggerUi::new("/openapi").into_router("/openapi/ui"));
 
-    Service::new(router)
+    // add the web UI
+    #[cfg(feature = "webui")]
+    let router = router.push(webui::router());
+
+    Ok(Service::new(router).catcher(catcher::init_catcher()))
 }
 
 /// Initializes the API services
 pub async fn init_api_services(cfg: &AppConfig) -> Result<ApiServices, Error> {
-    // init the Postgres client
-    let postgres_pool = cfg.postgres.new_pool();
-    let postgres_client = PostgresClient::new(postgres_pool);
+    // init the data store
+    let db = init_store(cfg)?;
+    init_schema_with_retry(&db, Duration::from_secs(cfg.store.timeout)).await?;
 
     // init the OpenAI client
     let openai_client = cfg.openai.new_client();
 
+    // NB: the running background tasks are watched by the readiness probe
+    let mut health = HealthService::new(db.clone());
+
+    // start the data retention job
+    let retention = RetentionService::new(db.clone(), cfg.retention.clone(), cfg.idempotency.ttl);
+    if retention.start().is_some() {
+        health = health.watch("retention", retention.heartbeat.clone());
+    }
+
+    let art = ArticleService::new(db.clone(), openai_client);
+
+    // start the background jobs worker
+    let jobs = JobService::new(db.clone(), cfg.jobs.clone()).handler(SUMMARIZE_JOB, {
+        let art = art.clone();
+        move |job| {
+            let art = art.clone();
+            async move { art.run_summarize_job(job).await }
+        }
+    });
+    if jobs.start().is_some() {
+        health = health.watch("jobs", jobs.heartbeat.clone());
+    }
+
     Ok(ApiServices {
-        auth: AuthService::new(postgres_client.clone(), cfg.auth.secret.clone()),
-        feeds: FeedService::new(postgres_client.clone()),
-        art: ArticleService::new(postgres_client, openai_client),
+        auth: AuthService::new(db.clone(), cfg.auth.secret.clone()),
+        feeds: FeedService::new(db.clone()),
+        stats: StatsService::new(db.clone(), art.cache.clone()),
+        art,
+        health,
+        idempotency: IdempotencyService::new(db.clone(), cfg.idempotency.clone()),
+        maintenance: MaintenanceService::new(db.clone()),
+        backup: BackupService::new(db),
+        retention,
+        jobs,
     })
 }
 
 /// Initializes the router
-pub async fn init_router(services: ApiServices) -> Router {
+pub async fn init_router(cfg: &AppConfig, services: ApiServices) -> Router {
     Router::new()
+        .hoop(salvo::affix::inject(cfg.clone()))
         .hoop(salvo::affix::inject(services))
+        .hoop(mdw::request_id)
+        .hoop(mdw::access_log)
+        .hoop(mdw::catch_panic)
         .hoop(mdw::authenticate)
-        .get(root)
-        .push(Router::with_path("/health").get(healthcheck))
+        .hoop(mdw::tenant)
+        // NB: the restore endpoint has its own body size limit
+        .push(allow(
+            Router::with_path("/admin/restore")
+                .hoop(mdw::limit_restore_size)
+                .hoop(mdw::require_admin)
+                .post(admin::post_restore),
+        ))
         .push(
-            Router::with_path("/auth")
-                .push(Router::with_path("/signup").post(auth::signup))
-                .push(Router::with_path("/login").post(auth::login))
+            Router::new()
+                .hoop(mdw::limit_body_size)
+                .get(root)
+                .push(allow(Router::with_path("/health").get(healthcheck)))
+                .push(allow(Router::with_path("/healthz").get(liveness)))
+                .push(allow(Router::with_path("/readyz").get(readiness)))
+                .push(allow(Router::with_path("/version").get(version)))
+                .push(
+                    Router::with_path("/auth")
+                        .push(allow(
+                            Router::with_path("/signup")
+                                .hoop(mdw::idempotency)
+                                .post(auth::signup),
+                        ))
+                        .push(allow(Router::with_path("/login").post(auth::login)))
+                        .push(allow(
+                            Router::with_path("/me")
+                                .get(auth::get_me)
+                                .patch(auth::update_me)
+                                .delete(auth::delete_me)
+                                .push(allow(
+                                    Router::with_path("/subscription").put(auth::put_subscription),
+                                ))
+                                .push(allow(
+                                    Router::with_path("/password").put(auth::put_password),
+                                ))
+                                .push(allow(Router::with_path("/export").get(auth::get_export))),
+                        )),
+                )
+                .push(allow(
+                    Router::with_path("/feeds")
+                        .hoop(mdw::idempotency)
+                        .get(feed::get_feeds)
+                        .put(feed::put_feeds),
+                ))
+                .push(allow(
+                    Router::with_path("/summaries")
+                        .hoop(mdw::idempotency)
+                        .post(summary::post_summaries)
+                        .push(allow(
+                            Router::with_path("/jobs")
+                                .post(summary::post_summaries_job)
+                                .push(allow(
+                                    Router::with_path("<id>").get(summary::get_summaries_job),
+                                )),
+                        ))
+                        .push(allow(
+                            Router::with_path("/events").get(summary::get_summaries_events),
+                        )),
+                ))
                 .push(
-                    Router::with_path("/me")
-                        .get(auth::get_me)
-                        .patch(auth::update_me)
-                        .delete(auth::delete_me)
-                        .push(Router::with_path("/subscription").put(auth::put_subscription)),
+                    Router::with_path("/admin")
+                        .hoop(mdw::require_admin)
+                        .push(allow(Router::with_path("/backup").get(admin::get_backup)))
+                        .push(allow(Router::with_path("/stats").get(admin::get_stats)))
+                        .push(allow(Router::with_path("/metrics").get(admin::get_metrics)))
+                        .push(allow(Router::with_path("/jobs").get(admin::get_jobs)))
+                        .push(allow(
+                            Router::with_path("/maintenance/vector-index")
+                                .get(admin::get_vector_index)
+                                .post(admin::post_vector_index),
+                        ))
+                        .push(allow(
+                            Router::with_path("/retention")
+                                .get(admin::get_retention)
+                                .post(admin::post_retention),
+                        )),
                 ),
         )
-        .push(
-            Router::with_path("/feeds")
-                .get(feed::get_feeds)
-                .put(feed::put_feeds),
-        )
-        .push(Router::with_path("/summaries").post(summary::post_summaries))
+}
+
+/// Streams a reader by chunks (eg. the read half of a pipe written by a spawned task)
+fn read_chunks<R: AsyncRead + Unpin + Send + 'static>(
+    reader: R,
+    chunk_size: usize,
+) -> impl Stream<Item = Result<Vec<u8>, std::io::Error>> + Send + 'static {
+    futures::stream::unfold(reader, move |mut reader| async move {
+        let mut buf = vec![0; chunk_size];
+        match reader.read(&mut buf).await {
+            Ok(0) => None,
+            Ok(n) => {
+                buf.truncate(n);
+                Some((Ok(buf), reader))
+            }
+            Err(err) => Some((Err(err), reader)),
+        }
+    })
+}
+
+/// Restricts a route to its declared methods
+///
+/// Requests with another method get a 405 instead of a 404.
+fn allow(router: Router) -> Router {
+    router.push(Router::new().goal(catcher::method_not_allowed))
 }
 
 /// Generates the OpenAPI specs
@@ -96,37 +253,80 @@ pub fn gen_openapi_specs(router: &Router) -> OpenApi {
         "bearerAuth",
         SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer).bearer_format("JWT")),
     );
-    OpenApi::new("Api", version)
-        .components(components)
-        .merge_router(router)
+    let mut openapi = OpenApi::new("Newsie API", version);
+    openapi.info.description = Some(
+        "REST API of Newsie. \
+        Authenticated endpoints expect a JWT token, either in the `Authorization: Bearer <token>` \
+        header or in the authentication cookie. \
+        Errors are returned with a common JSON shape (`HttpErrorResponse`)."
+            .to_string(),
+    );
+    openapi.components(components).merge_router(router)
 }
 
 /// Serves the root path
-#[endpoint]
+#[endpoint(tags("health"))]
 #[tracing::instrument(skip_all)]
 pub async fn root() -> &'static str {
-    trace!("root");
     "Api service"
 }
 
 /// Performs a health check
-#[endpoint]
+#[endpoint(tags("health"))]
 #[tracing::instrument(skip_all)]
 pub async fn healthcheck() -> &'static str {
-    trace!("healthcheck");
     "API is up"
 }
 
+/// Liveness probe
+///
+/// Succeeds as long as the process is able to serve requests.
+#[endpoint(tags("health"))]
+#[tracing::instrument(skip_all)]
+pub async fn liveness() -> &'static str {
+    "OK"
+}
+
+/// Readiness probe
+///
+/// Responds with a 503 status code if the service cannot accept traffic yet.
+#[endpoint(tags("health"))]
+#[tracing::instrument(skip_all)]
+pub async fn readiness(depot: &mut Depot, res: &mut Response) -> Json<Readiness> {
+    let services = depot.obtain::<ApiServices>().unwrap();
+
+    let readiness = services.health.readiness().await;
+    if !readiness.ready {
+        res.status_code(StatusCode::SERVICE_UNAVAILABLE);
+    }
+    Json(readiness)
+}
+
+/// Returns the service version
+#[endpoint(tags("health"))]
+#[tracing::instrument(skip_all)]
+pub async fn version() -> Json<VersionRespBody> {
+    Json(VersionRespBody {
+        name: env!("CARGO_PKG_NAME").to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    use salvo::test::TestClient;
+    use salvo::{
+        hyper::header::CONTENT_LENGTH,
+        test::{ResponseExt, TestClient},
+    };
+
+    use crate::error::HttpErrorResponse;
 
     // Test runner to setup and cleanup a test
     async fn setup() -> Service {
         let cfg = AppConfig::load();
-        init_service(&cfg).await
+        init_service(&cfg).await.unwrap()
     }
 
     #[tokio::test]
@@ -138,6 +338,99 @@ mod tests {
         assert_eq!(res.status_code.unwrap(), StatusCode::OK);
     }
 
+    #[tokio::test]
+    async fn test_liveness() {
+        let service = setup().await;
+        let res = TestClient::get("http://localhost:3000/healthz")
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code.unwrap(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_readiness() {
+        let service = setup().await;
+        let res = TestClient::get("http://localhost:3000/readyz")
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code.unwrap(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_not_found() {
+        let service = setup().await;
+        let mut res = TestClient::get("http://localhost:3000/not/a/route")
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code.unwrap(), StatusCode::NOT_FOUND);
+        let body = res.take_json::<HttpErrorResponse>().await.unwrap();
+        assert_eq!(body.error.code, "NOT_FOUND");
+    }
+
+    #[tokio::test]
+    async fn test_admin_unauthenticated() {
+        let service = setup().await;
+        let mut res = TestClient::get("http://localhost:3000/admin/backup")
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code.unwrap(), StatusCode::UNAUTHORIZED);
+        let body = res.take_json::<HttpErrorResponse>().await.unwrap();
+        assert_eq!(body.error.code, "NOT_AUTHENTICATED");
+    }
+
+    #[tokio::test]
+    async fn test_method_not_allowed() {
+        let service = setup().await;
+        let mut res = TestClient::delete("http://localhost:3000/summaries")
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code.unwrap(), StatusCode::METHOD_NOT_ALLOWED);
+        let body = res.take_json::<HttpErrorResponse>().await.unwrap();
+        assert_eq!(body.error.code, "METHOD_NOT_ALLOWED");
+    }
+
+    #[tokio::test]
+    async fn test_payload_too_large() {
+        let service = setup().await;
+        let res = TestClient::post("http://localhost:3000/summaries")
+            .add_header(CONTENT_LENGTH, "1000000000", true)
+            .json(&Vec::<String>::new())
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code.unwrap(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn test_restore_size_limit() {
+        let service = setup().await;
+        let cfg = AppConfig::load();
+        // a dump above the body limit is only checked against the restore limit
+        let res = TestClient::post("http://localhost:3000/admin/restore")
+            .add_header(CONTENT_LENGTH, (cfg.limits.body + 1).to_string(), true)
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code.unwrap(), StatusCode::UNAUTHORIZED);
+        let res = TestClient::post("http://localhost:3000/admin/restore")
+            .add_header(CONTENT_LENGTH, (cfg.limits.restore + 1).to_string(), true)
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code.unwrap(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn test_too_many_summaries() {
+        let service = setup().await;
+        let cfg = AppConfig::load();
+        let urls = (0..=cfg.limits.summaries)
+            .map(|i| format!("https://www.link.com/{i}"))
+            .collect::<Vec<_>>();
+        let res = TestClient::post("http://localhost:3000/summaries")
+            .json(&urls)
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code.unwrap(), StatusCode::BAD_REQUEST);
+    }
+
     #[tokio::test]
     async fn test_healthcheck() {
         let service = setup().await;
@@ -146,4 +439,33 @@ mod tests {
             .await;
         assert_eq!(res.status_code.unwrap(), StatusCode::OK);
     }
+
+    #[tokio::test]
+    async fn test_version() {
+        let service = setup().await;
+        let mut res = TestClient::get("http://localhost:3000/version")
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code.unwrap(), StatusCode::OK);
+        let body = res.take_json::<VersionRespBody>().await.unwrap();
+        assert_eq!(body.version, env!("CARGO_PKG_VERSION"));
+    }
+
+    #[tokio::test]
+    async fn test_openapi_error_responses() {
+        let service = setup().await;
+        let mut res = TestClient::get("http://localhost:3000/openapi")
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code.unwrap(), StatusCode::OK);
+        let specs = res.take_json::<serde_json::Value>().await.unwrap();
+
+        let signup = &specs["paths"]["/auth/signup"]["post"]["responses"];
+        assert!(signup.get("409").is_some());
+        assert!(signup.get("401").is_none());
+        let stats = &specs["paths"]["/admin/stats"]["get"]["responses"];
+        assert!(stats.get("403").is_some());
+        assert!(stats.get("405").is_some());
+        assert!(stats.get("409").is_none());
+    }
 }
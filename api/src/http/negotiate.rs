@@ -0,0 +1,119 @@
+//! Content negotiation
+//!
+//! Large responses (eg. summaries with embeddings) can be serialized as MessagePack instead of
+//! JSON, if the client sends an `Accept: application/msgpack` header.
+
+use salvo::{
+    hyper::header::{HeaderValue, ACCEPT, CONTENT_TYPE},
+    oapi::{Components, Content, EndpointOutRegister, Operation},
+    prelude::*,
+};
+use serde::Serialize;
+
+use crate::error::Error;
+
+/// MessagePack MIME type
+pub const MSGPACK_MIME: &str = "application/msgpack";
+
+/// Response body serialized according to the `Accept` header
+///
+/// JSON is used by default.
+#[derive(Debug)]
+pub struct Negotiated<T>(pub T);
+
+/// Checks if the request accepts a MessagePack response
+pub fn accepts_msgpack(req: &Request) -> bool {
+    req.headers()
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|mime| {
+            let mime = mime.split(';').next().unwrap_or_default().trim();
+            mime == MSGPACK_MIME || mime == "application/x-msgpack"
+        })
+}
+
+#[async_trait]
+impl<T> Writer for Negotiated<T>
+where
+    T: Serialize + Send,
+{
+    async fn write(mut self, req: &mut Request, depot: &mut Depot, res: &mut Response) {
+        if !accepts_msgpack(req) {
+            res.render(Json(self.0));
+            return;
+        }
+
+        match rmp_serde::to_vec_named(&self.0) {
+            Ok(bytes) => {
+                res.headers_mut()
+                    .insert(CONTENT_TYPE, HeaderValue::from_static(MSGPACK_MIME));
+                res.write_body(bytes).ok();
+            }
+            Err(err) => {
+                Error::Internal(format!("MessagePack serialization failed ({err})"), None)
+                    .write(req, depot, res)
+                    .await;
+            }
+        }
+    }
+}
+
+// NB: needed for OpenAPI specs
+impl<T> EndpointOutRegister for Negotiated<T>
+where
+    T: ToSchema,
+{
+    fn register(components: &mut Components, operation: &mut Operation) {
+        let schema = T::to_schema(components);
+        let content = Content::new(schema);
+        let res = salvo::oapi::Response::new("Response with success")
+            .add_content("application/json", content.clone())
+            .add_content(MSGPACK_MIME, content);
+        operation.responses.insert("200", res);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use salvo::test::{ResponseExt, TestClient};
+    use serde::Deserialize;
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Body {
+        values: Vec<f32>,
+    }
+
+    #[handler]
+    async fn negotiated() -> Negotiated<Body> {
+        Negotiated(Body {
+            values: vec![0.5, 1.0],
+        })
+    }
+
+    #[tokio::test]
+    async fn test_negotiate() {
+        let service = Service::new(Router::new().get(negotiated));
+
+        let mut res = TestClient::get("http://localhost:3000")
+            .send(&service)
+            .await;
+        let body = res.take_json::<Body>().await.unwrap();
+        assert_eq!(body.values, vec![0.5, 1.0]);
+
+        let mut res = TestClient::get("http://localhost:3000")
+            .add_header(ACCEPT, "application/msgpack", true)
+            .send(&service)
+            .await;
+        assert_eq!(
+            res.headers().get(CONTENT_TYPE).unwrap().to_str().unwrap(),
+            MSGPACK_MIME
+        );
+        let bytes = res.take_bytes(None).await.unwrap();
+        let body = rmp_serde::from_slice::<Body>(&bytes).unwrap();
+        assert_eq!(body.values, vec![0.5, 1.0]);
+    }
+}
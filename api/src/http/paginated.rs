@@ -0,0 +1,3 @@
+//! Pagination
+
+pub use crate::mdl::body::Paginated;
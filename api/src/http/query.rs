@@ -0,0 +1,136 @@
+//! Query parameters
+//!
+//! Listing endpoints share the same query parameters:
+//!
+//! - `sort`: comma separated fields, prefixed with `-` for a descending order (eg. `?sort=-name,url`)
+//! - `filter[<field>]`: filters on a field value (eg. `?filter[name]=news`)
+//! - `cursor`: pagination cursor
+//! - `limit`: maximum number of items
+
+use salvo::prelude::*;
+
+use crate::{
+    error::{Error, FieldError},
+    mdl::query::{ListOptions, Sort, SortDir},
+};
+
+/// Specification of the options accepted by a listing endpoint
+#[derive(Debug, Clone, Copy)]
+pub struct QuerySpec {
+    /// Fields which can be sorted on
+    pub sortable: &'static [&'static str],
+    /// Fields which can be filtered on
+    pub filterable: &'static [&'static str],
+    /// Maximum page size
+    pub max_limit: usize,
+}
+
+/// Parses the listing options from the request query
+pub fn parse_list_options(req: &Request, spec: &QuerySpec) -> Result<ListOptions, Error> {
+    let mut opts = ListOptions::default();
+    let mut fields = vec![];
+
+    for (key, values) in req.queries().iter_all() {
+        // NB: the last value wins if a key is repeated
+        let value = match values.last() {
+            Some(v) => v.as_str(),
+            None => continue,
+        };
+
+        if key == "sort" {
+            for item in value.split(',').map(|s| s.trim()).filter(|s| !s.is_empty()) {
+                let (field, dir) = match item.strip_prefix('-') {
+                    Some(field) => (field, SortDir::Desc),
+                    None => (item.strip_prefix('+').unwrap_or(item), SortDir::Asc),
+                };
+                if spec.sortable.contains(&field) {
+                    opts.sort.push(Sort {
+                        field: field.to_string(),
+                        dir,
+                    });
+                } else {
+                    fields.push(FieldError::new(
+                        "sort",
+                        &format!("cannot sort on '{field}'"),
+                    ));
+                }
+            }
+        } else if let Some(field) = key
+            .strip_prefix("filter[")
+            .and_then(|s| s.strip_suffix(']'))
+        {
+            if spec.filterable.contains(&field) {
+                opts.filters.insert(field.to_string(), value.to_string());
+            } else {
+                fields.push(FieldError::new(key, &format!("cannot filter on '{field}'")));
+            }
+        } else if key == "cursor" {
+            opts.cursor = Some(value.to_string());
+        } else if key == "limit" {
+            match value.parse::<usize>() {
+                Ok(limit) if limit > 0 => opts.limit = Some(limit.min(spec.max_limit)),
+                _ => fields.push(FieldError::new("limit", "must be a positive integer")),
+            }
+        }
+    }
+
+    if fields.is_empty() {
+        Ok(opts)
+    } else {
+        Err(Error::InvalidFields(
+            "invalid query parameters".to_string(),
+            fields,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use salvo::test::TestClient;
+
+    const SPEC: QuerySpec = QuerySpec {
+        sortable: &["name", "url"],
+        filterable: &["name"],
+        max_limit: 100,
+    };
+
+    #[test]
+    fn test_parse_list_options() {
+        let req: Request = TestClient::get(
+            "http://localhost:3000/feeds?sort=-name,url&filter[name]=news&limit=1000&cursor=abc",
+        )
+        .build()
+        .into();
+        let opts = parse_list_options(&req, &SPEC).unwrap();
+        assert_eq!(
+            opts.sort,
+            vec![
+                Sort {
+                    field: "name".to_string(),
+                    dir: SortDir::Desc
+                },
+                Sort {
+                    field: "url".to_string(),
+                    dir: SortDir::Asc
+                }
+            ]
+        );
+        assert_eq!(opts.filter("name"), Some("news"));
+        assert_eq!(opts.limit, Some(100));
+        assert_eq!(opts.cursor, Some("abc".to_string()));
+    }
+
+    #[test]
+    fn test_parse_list_options_invalid() {
+        let req: Request = TestClient::get("http://localhost:3000/feeds?sort=id&filter[url]=x")
+            .build()
+            .into();
+        let err = parse_list_options(&req, &SPEC).unwrap_err();
+        match err {
+            Error::InvalidFields(_, fields) => assert_eq!(fields.len(), 2),
+            _ => panic!("unexpected error"),
+        }
+    }
+}
@@ -1,32 +1,218 @@
 //! Articles endpoints
 
-use salvo::{oapi::extract::JsonBody, prelude::*};
+use std::time::Duration;
+
+use futures::StreamExt;
+use salvo::{
+    hyper::header::{HeaderValue, CACHE_CONTROL, CONTENT_TYPE},
+    oapi::extract::{JsonBody, PathParam},
+    prelude::*,
+};
 use serde::{Deserialize, Serialize};
-use tracing::trace;
+use tokio::sync::broadcast::error::RecvError;
+use uuid::Uuid;
 
-use crate::{error::Error, http::ApiServices, mdl::Summary};
+pub use crate::mdl::body::SummariesRespBody;
+use crate::{
+    config::AppConfig,
+    error::Error,
+    http::ApiServices,
+    mdl::{
+        validate::{ArticleUrl, Validate},
+        Event, JobStatus, Summary, User,
+    },
+    svc::art::{SummarizeJob, SUMMARIZE_JOB},
+};
 
-/// Get articles response body
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
-pub struct SummariesRespBody {
-    /// Summaries
-    pub summaries: Vec<Summary>,
-}
+use super::negotiate::Negotiated;
 
 /// Creates (or retrieve) a summary for a list of articles
 ///
-/// The body contains a list of articles
-#[endpoint]
+/// The body contains a list of articles. The number of articles per request is limited.
+///
+/// The response is serialized as MessagePack if requested with the `Accept` header.
+#[endpoint(tags("summaries"), status_codes(200, 400, 405, 409, 413, 422, 500))]
 #[tracing::instrument(skip_all)]
 pub async fn post_summaries(
     depot: &mut Depot,
     body: JsonBody<Vec<String>>,
-) -> Result<Json<SummariesRespBody>, Error> {
-    trace!("received request");
+) -> Result<Negotiated<SummariesRespBody>, Error> {
     let services = depot.obtain::<ApiServices>().unwrap();
+    let limits = &depot.obtain::<AppConfig>().unwrap().limits;
 
     let urls = body.into_inner();
+    validate_urls(&urls, limits.summaries)?;
     let urls = urls.iter().map(|url| url.as_str()).collect::<Vec<_>>();
     let summaries = services.art.process_summaries(&urls).await?;
-    Ok(Json(SummariesRespBody { summaries }))
+    Ok(Negotiated(SummariesRespBody { summaries }))
+}
+
+/// Checks the articles of a summaries request
+fn validate_urls(urls: &[String], max: usize) -> Result<(), Error> {
+    if urls.len() > max {
+        return Err(Error::InvalidRequest(
+            format!("too many articles (max {max})"),
+            None,
+        ));
+    }
+    urls.iter()
+        .map(|url| ArticleUrl(url))
+        .collect::<Vec<_>>()
+        .validate()
+}
+
+/// Summaries job response body
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SummariesJobRespBody {
+    /// Job ID
+    pub id: Uuid,
+}
+
+/// Queues the summarization of a list of articles
+///
+/// The summaries are processed in the background (the job is retried on failure), and
+/// are then returned immediately by the summaries endpoint. The number of articles per
+/// request is limited. The status of the job is polled with its ID.
+#[endpoint(tags("summaries"), status_codes(200, 400, 401, 405, 409, 413, 422, 500), security(["bearerAuth" = []]))]
+#[tracing::instrument(skip_all)]
+pub async fn post_summaries_job(
+    depot: &mut Depot,
+    body: JsonBody<Vec<String>>,
+    res: &mut Response,
+) -> Result<Json<SummariesJobRespBody>, Error> {
+    let services = depot.obtain::<ApiServices>().unwrap();
+    let limits = &depot.obtain::<AppConfig>().unwrap().limits;
+    let user = depot.obtain::<User>().ok_or(Error::Unauthenticated(
+        "not authenticated".to_string(),
+        None,
+    ))?;
+
+    let urls = body.into_inner();
+    validate_urls(&urls, limits.summaries)?;
+    let job = services
+        .jobs
+        .enqueue(SUMMARIZE_JOB, Some(user.id), &SummarizeJob { urls })
+        .await?;
+
+    res.status_code(StatusCode::ACCEPTED);
+    Ok(Json(SummariesJobRespBody { id: job.id }))
+}
+
+/// Summaries job status response body
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SummariesJobStatusRespBody {
+    /// Job ID
+    pub id: Uuid,
+    /// Status
+    pub status: JobStatus,
+    /// Number of attempts so far
+    pub attempts: i32,
+    /// Error of the last attempt
+    pub last_error: Option<String>,
+}
+
+/// Returns the status of a summaries job
+///
+/// Only the user who queued the job can read its status. A completed job is kept for the
+/// `retention.jobs` period.
+#[endpoint(tags("summaries"), status_codes(200, 401, 404, 405, 500), security(["bearerAuth" = []]))]
+#[tracing::instrument(skip_all)]
+pub async fn get_summaries_job(
+    depot: &mut Depot,
+    id: PathParam<Uuid>,
+) -> Result<Json<SummariesJobStatusRespBody>, Error> {
+    let services = depot.obtain::<ApiServices>().unwrap();
+    let user = depot.obtain::<User>().ok_or(Error::Unauthenticated(
+        "not authenticated".to_string(),
+        None,
+    ))?;
+
+    // NB: the jobs of the other users are not disclosed
+    let job = services
+        .jobs
+        .get(id.into_inner())
+        .await?
+        .filter(|job| job.kind == SUMMARIZE_JOB && job.owner_id == Some(user.id))
+        .ok_or(Error::NotFound("job not found".to_string(), None))?;
+
+    Ok(Json(SummariesJobStatusRespBody {
+        id: job.id,
+        status: job.status,
+        attempts: job.attempts,
+        last_error: job.last_error,
+    }))
+}
+
+/// Period between 2 keep-alive comments of the events stream
+const EVENTS_KEEP_ALIVE: Duration = Duration::from_secs(30);
+
+/// Streams the summaries events
+///
+/// The events are sent as server-sent events (`text/event-stream`), named after their
+/// `type` (eg. `summary_created`), with their JSON data. The events sent before the
+/// subscription, or missed by a slow subscriber, are not replayed.
+#[endpoint(tags("summaries"), status_codes(200, 401, 405, 500), security(["bearerAuth" = []]))]
+#[tracing::instrument(skip_all)]
+pub async fn get_summaries_events(depot: &mut Depot, res: &mut Response) -> Result<(), Error> {
+    let services = depot.obtain::<ApiServices>().unwrap();
+    depot.obtain::<User>().ok_or(Error::Unauthenticated(
+        "not authenticated".to_string(),
+        None,
+    ))?;
+
+    let events = futures::stream::unfold(services.art.db.subscribe(), |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(event) => return Some((sse_event(&event), rx)),
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    // NB: the comments keep the idle connection open through the proxies
+    let keep_alive = futures::stream::unfold((), |_| async {
+        tokio::time::sleep(EVENTS_KEEP_ALIVE).await;
+        Some((":\n\n".to_string(), ()))
+    });
+    let stream = futures::stream::select(events, keep_alive).map(Ok::<_, std::io::Error>);
+
+    let headers = res.headers_mut();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/event-stream"));
+    headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    res.streaming(stream).map_err(|err| {
+        Error::Internal(
+            "failed to stream the events".to_string(),
+            Some(err.to_string()),
+        )
+    })
+}
+
+/// Formats a server-sent event
+fn sse_event(event: &Event) -> String {
+    let name = match event {
+        Event::SummaryCreated { .. } => "summary_created",
+    };
+    // NB: the compact JSON has no line breaks, so it fits in a single `data` field
+    let data = serde_json::to_string(event).unwrap_or_default();
+    format!("event: {name}\ndata: {data}\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sse_event() {
+        let id = Uuid::new_v4();
+        let event = sse_event(&Event::SummaryCreated {
+            id,
+            url: "https://www.link.com".to_string(),
+        });
+        assert_eq!(
+            event,
+            format!(
+                "event: summary_created\ndata: {{\"type\":\"summary_created\",\"id\":\"{id}\",\"url\":\"https://www.link.com\"}}\n\n"
+            )
+        );
+    }
 }
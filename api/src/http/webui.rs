@@ -0,0 +1,79 @@
+//! Embedded web UI
+//!
+//! The web UI is a single page application. Its assets are embedded in the binary from the
+//! `webui` folder, and unknown paths fall back to `index.html` to support client-side routing.
+
+use rust_embed::RustEmbed;
+use salvo::{
+    hyper::header::{HeaderValue, CACHE_CONTROL, CONTENT_TYPE},
+    prelude::*,
+};
+use tracing::trace;
+
+/// Web UI assets
+#[derive(RustEmbed)]
+#[folder = "webui/"]
+struct Assets;
+
+/// Entry point of the web UI
+const INDEX_FILE: &str = "index.html";
+
+/// Returns the web UI router
+pub fn router() -> Router {
+    Router::with_path("/app/<**path>").get(serve_asset)
+}
+
+/// Serves a web UI asset
+#[handler]
+pub async fn serve_asset(req: &mut Request, res: &mut Response) {
+    let path = req.param::<String>("**path").unwrap_or_default();
+    trace!(path, "web UI asset");
+
+    let (path, file) = match Assets::get(&path) {
+        Some(file) => (path.as_str(), file),
+        None => match Assets::get(INDEX_FILE) {
+            Some(file) => (INDEX_FILE, file),
+            None => {
+                res.status_code(StatusCode::NOT_FOUND);
+                return;
+            }
+        },
+    };
+
+    let mime = file.metadata.mimetype();
+    if let Ok(v) = HeaderValue::from_str(mime) {
+        res.headers_mut().insert(CONTENT_TYPE, v);
+    }
+    // NB: the index must not be cached, so that new asset versions are picked up
+    let cache_control = if path == INDEX_FILE {
+        "no-cache"
+    } else {
+        "public, max-age=3600"
+    };
+    res.headers_mut()
+        .insert(CACHE_CONTROL, HeaderValue::from_static(cache_control));
+    res.write_body(file.data.into_owned()).ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use salvo::test::TestClient;
+
+    #[tokio::test]
+    async fn test_serve_index() {
+        let service = Service::new(router());
+        let res = TestClient::get("http://localhost:3000/app/some/client/route")
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code.unwrap(), StatusCode::OK);
+        assert!(res
+            .headers()
+            .get(CONTENT_TYPE)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .starts_with("text/html"));
+    }
+}
@@ -2,32 +2,58 @@
 //!
 //! # Features
 //!
-//! - **None**: document features here
+//! - **server** (default): the REST API service. Without it, the crate only contains the
+//!   models shared with the clients ([mdl], [error] and [docgen]), which also build for
+//!   WebAssembly.
+//! - **webui**: serves the web UI embedded from the `webui` folder at `/app`
+//! - **export**: builds the `export` binary (see below)
+//!
+//! # Demo data
+//!
+//! Run the server with the `--seed` option to create demo users, feeds and summaries.
 //!
 //! # Other binaries
 //!
-//! - **docgen**: The docgen binary generates the OpenAPI documentation.
+//! - **docgen**: The docgen binary generates the OpenAPI documentation, the Markdown API
+//!   reference and the client models (see [docgen]).
+//! - **export**: The export binary dumps the summaries and their embeddings to a Parquet
+//!   file, for offline analysis.
 
 #![deny(missing_docs)]
 
-use crate::config::AppConfig;
+#[cfg(feature = "server")]
+use std::time::Duration;
+
+#[cfg(feature = "server")]
+use crate::{
+    config::AppConfig,
+    db::{init_schema_with_retry, init_store},
+};
+#[cfg(feature = "server")]
 use salvo::prelude::*;
 
+#[cfg(feature = "server")]
 pub mod config;
+#[cfg(feature = "server")]
 pub mod db;
+pub mod docgen;
 pub mod error;
+#[cfg(feature = "server")]
 pub mod http;
 pub mod mdl;
+#[cfg(feature = "server")]
 pub mod svc;
+#[cfg(feature = "server")]
 pub mod trace;
 
 /// Starts the server
+#[cfg(feature = "server")]
 pub async fn start_server(cfg: AppConfig) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     // init the tracing framework
     trace::init_tracer(&cfg);
 
-    // create the HTTP service
-    let service = http::init_service(&cfg).await;
+    // create the HTTP service (NB: the DB schema is migrated)
+    let service = http::init_service(&cfg).await?;
 
     // start the server
     let addr = cfg.server.addr().unwrap();
@@ -37,3 +63,22 @@ pub async fn start_server(cfg: AppConfig) -> Result<(), Box<dyn std::error::Erro
     Server::new(acceptor).serve(service).await;
     Ok(())
 }
+
+/// Creates the demo data (see [svc::seed])
+#[cfg(feature = "server")]
+pub async fn seed_demo_data(
+    cfg: &AppConfig,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let db = init_store(cfg)?;
+    init_schema_with_retry(&db, Duration::from_secs(cfg.store.timeout)).await?;
+
+    let report = svc::seed::seed(db).await?;
+    eprintln!(
+        "Seeded {} users, {} feeds and {} summaries (password of the demo users: {})",
+        report.users,
+        report.feeds,
+        report.summaries,
+        svc::seed::DEMO_PASSWORD
+    );
+    Ok(())
+}
@@ -1,9 +1,16 @@
 //! Server
+//!
+//! Usage: `newsie-api [--seed]`
+//!
+//! - `--seed`: creates the demo data before starting the server
 
 use newsie_api::config::AppConfig;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let cfg = AppConfig::load();
+    if std::env::args().skip(1).any(|arg| arg == "--seed") {
+        newsie_api::seed_demo_data(&cfg).await?;
+    }
     newsie_api::start_server(cfg).await
 }
@@ -0,0 +1,143 @@
+//! HTTP request and response bodies
+
+#[cfg(feature = "server")]
+use salvo::prelude::ToSchema;
+use serde::{Deserialize, Serialize};
+
+use super::{Feed, Summary, User};
+
+/// Bound of the generic bodies, without the server feature (no schema is generated)
+#[cfg(not(feature = "server"))]
+#[doc(hidden)]
+pub trait ToSchema {}
+
+#[cfg(not(feature = "server"))]
+impl<T> ToSchema for T {}
+
+/// Signup response body
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
+pub struct SignupRespBody {
+    /// JWT auth token
+    pub token: String,
+    /// User
+    pub user: User,
+}
+
+/// Login request body
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
+pub struct LoginReqBody {
+    /// Email
+    #[cfg_attr(feature = "server", schema(example = "john@doe.com"))]
+    pub email: String,
+    /// Password
+    #[cfg_attr(feature = "server", schema(example = "my-password"))]
+    pub password: String,
+}
+
+/// Login response body
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
+pub struct LoginRespBody {
+    /// JWT auth token
+    pub token: String,
+    /// User
+    pub user: User,
+}
+
+/// Get user response body
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
+pub struct GetUserRespBody {
+    /// User
+    pub user: User,
+}
+
+/// Header of the data export checksum (hexadecimal SHA-256 of the body)
+pub const EXPORT_CHECKSUM_HEADER: &str = "x-checksum-sha256";
+
+/// Get feeds response body
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
+pub struct GetFeedsRespBody {
+    /// Feeds
+    pub feeds: Vec<Feed>,
+}
+
+/// Paginated response body
+///
+/// All the listing endpoints return their items with this envelope. The next page is
+/// requested by passing `next_cursor` as the `cursor` query parameter.
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
+pub struct Paginated<T: ToSchema> {
+    /// Items of the page
+    pub items: Vec<T>,
+    /// Cursor of the next page (none if this is the last page)
+    pub next_cursor: Option<String>,
+    /// Total number of items (if known)
+    pub total: Option<u64>,
+}
+
+impl<T: ToSchema> Paginated<T> {
+    /// Creates a page
+    pub fn new(items: Vec<T>, next_cursor: Option<String>) -> Self {
+        Self {
+            items,
+            next_cursor,
+            total: None,
+        }
+    }
+
+    /// Creates a single page holding all the items
+    pub fn all(items: Vec<T>) -> Self {
+        let total = items.len() as u64;
+        Self {
+            items,
+            next_cursor: None,
+            total: Some(total),
+        }
+    }
+
+    /// Sets the total number of items
+    pub fn total(mut self, total: u64) -> Self {
+        self.total = Some(total);
+        self
+    }
+
+    /// Checks if this is the last page
+    pub fn is_last(&self) -> bool {
+        self.next_cursor.is_none()
+    }
+}
+
+/// Get articles response body
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
+pub struct SummariesRespBody {
+    /// Summaries
+    pub summaries: Vec<Summary>,
+}
+
+/// Version response body
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
+pub struct VersionRespBody {
+    /// Service name
+    pub name: String,
+    /// Service version
+    pub version: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_all() {
+        let page = Paginated::<Feed>::all(vec![]);
+        assert!(page.is_last());
+        assert_eq!(page.total, Some(0));
+    }
+}
@@ -1,14 +1,21 @@
 //! Models
 
+#[cfg(feature = "server")]
 use postgres_types::{FromSql, ToSql};
+#[cfg(feature = "server")]
 use salvo::prelude::ToSchema;
 use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
 use uuid::Uuid;
 
-use crate::db::postgres::util::Vector;
+pub mod body;
+pub mod query;
+pub mod report;
+pub mod validate;
 
 /// User
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
 pub struct User {
     /// ID
     pub id: Uuid,
@@ -23,39 +30,57 @@ pub struct User {
 }
 
 /// New user
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
 pub struct NewUser {
     /// Name
+    #[cfg_attr(feature = "server", schema(example = "John Doe"))]
     pub name: String,
     /// Email
+    #[cfg_attr(feature = "server", schema(example = "john@doe.com"))]
     pub email: String,
     /// Password
+    #[cfg_attr(feature = "server", schema(example = "my-password"))]
     pub password: String,
 }
 
 /// User update fields
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+///
+/// Only the defined fields are updated.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
 pub struct UserUpdate {
     /// Name
+    #[cfg_attr(feature = "server", schema(example = "John Doe"))]
     pub name: Option<String>,
     /// Email
+    #[cfg_attr(feature = "server", schema(example = "john@doe.com"))]
     pub email: Option<String>,
     /// Password
     pub password: Option<String>,
 }
 
+/// Password change
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
+pub struct PasswordChange {
+    /// Current password
+    pub current_password: String,
+    /// New password
+    pub new_password: String,
+}
+
 /// Subscription
-#[derive(
-    Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default, FromSql, ToSql,
-)]
-#[postgres(name = "subscription")]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "server", derive(ToSchema, FromSql, ToSql))]
+#[cfg_attr(feature = "server", postgres(name = "subscription"))]
 pub enum Subscription {
     /// Free tier
     #[default]
-    #[postgres(name = "FREE")]
+    #[cfg_attr(feature = "server", postgres(name = "FREE"))]
     Free,
     /// Mid tier
-    #[postgres(name = "MID")]
+    #[cfg_attr(feature = "server", postgres(name = "MID"))]
     Mid,
 }
 
@@ -70,14 +95,16 @@ impl std::fmt::Display for Subscription {
 }
 
 /// Subscription update
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
 pub struct SubscriptionUpdate {
     /// Free tier
     pub subscription: Subscription,
 }
 
 /// User feed
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
 pub struct Feed {
     /// ID
     pub id: Uuid,
@@ -90,20 +117,27 @@ pub struct Feed {
 }
 
 /// Feed update
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
 pub struct FeedUpdate {
     /// ID
     ///
     /// If set, feed already exists
     pub id: Option<Uuid>,
     /// Url
+    #[cfg_attr(
+        feature = "server",
+        schema(example = "https://ai.googleblog.com/atom.xml")
+    )]
     pub url: String,
     /// Name
+    #[cfg_attr(feature = "server", schema(example = "Google AI blog"))]
     pub name: Option<String>,
 }
 
 /// An article summary
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
 pub struct Summary {
     /// ID
     pub id: Uuid,
@@ -116,3 +150,235 @@ pub struct Summary {
     /// Embeddings (1536 values)
     pub embeddings: Vector,
 }
+
+/// A vector type
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
+pub struct Vector(Vec<f32>);
+
+impl From<Vec<f32>> for Vector {
+    fn from(value: Vec<f32>) -> Self {
+        Vector(value)
+    }
+}
+
+impl From<Vector> for Vec<f32> {
+    fn from(value: Vector) -> Self {
+        value.0
+    }
+}
+
+impl AsRef<[f32]> for Vector {
+    fn as_ref(&self) -> &[f32] {
+        &self.0
+    }
+}
+
+/// Number of users with a given number of feeds
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
+pub struct FeedCount {
+    /// Number of feeds
+    pub feeds: u64,
+    /// Number of users
+    pub users: u64,
+}
+
+/// Number of summaries created on a day
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
+pub struct DailyCount {
+    /// Day (UTC, formatted as `YYYY-MM-DD`)
+    #[cfg_attr(feature = "server", schema(example = "2023-07-01"))]
+    pub day: String,
+    /// Number of summaries
+    pub count: u64,
+}
+
+/// Recall of the vector index, measured on a sample of summaries
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
+pub struct VectorIndexReport {
+    /// Whether the search uses an approximate index (otherwise the recall is exact)
+    pub indexed: bool,
+    /// Number of sampled summaries
+    pub sample: usize,
+    /// Number of neighbors searched for each sampled summary
+    pub k: usize,
+    /// Mean recall of the neighbors (1 if the sample is empty)
+    pub recall: f64,
+    /// Lowest recall of the sample (1 if the sample is empty)
+    pub min_recall: f64,
+}
+
+impl VectorIndexReport {
+    /// Creates a report from the recall of each sampled summary
+    pub fn new(indexed: bool, k: usize, recalls: &[f64]) -> Self {
+        VectorIndexReport {
+            indexed,
+            sample: recalls.len(),
+            k,
+            recall: if recalls.is_empty() {
+                1.0
+            } else {
+                recalls.iter().sum::<f64>() / recalls.len() as f64
+            },
+            min_recall: recalls.iter().copied().fold(1.0, f64::min),
+        }
+    }
+
+    /// Returns the recall of the neighbors found with an index, compared to the exact ones
+    pub fn recall(found: &[Uuid], exact: &[Uuid]) -> f64 {
+        if exact.is_empty() {
+            return 1.0;
+        }
+        let hits = exact.iter().filter(|id| found.contains(id)).count();
+        hits as f64 / exact.len() as f64
+    }
+}
+
+/// Data event
+///
+/// The events are broadcast to all the API replicas which share the same DB.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    /// A summary has been created
+    SummaryCreated {
+        /// Summary ID
+        id: Uuid,
+        /// Article URL
+        url: String,
+    },
+}
+
+/// A stored response to an idempotent request
+///
+/// NB: the key is reserved (not completed) while its request is running
+#[derive(Debug, Clone)]
+pub struct IdempotentResponse {
+    /// Idempotency key
+    pub key: String,
+    /// Scope of the key (method, path, user and response format)
+    pub scope: String,
+    /// HTTP status code
+    pub status: u16,
+    /// Response content type (JSON if not set)
+    pub content_type: Option<String>,
+    /// Response body
+    pub body: Vec<u8>,
+    /// Creation time
+    pub created_at: OffsetDateTime,
+    /// Hash of the request body (SHA-256, hex)
+    pub request_hash: String,
+    /// The request has completed, and its response is stored
+    pub completed: bool,
+}
+
+/// Status of a background job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    /// Waiting to be run (or retried)
+    Pending,
+    /// Claimed by a worker
+    Running,
+    /// Completed
+    Done,
+    /// Failed after all its attempts (dead-lettered)
+    Dead,
+}
+
+impl JobStatus {
+    /// Returns the status as stored in the DB
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Pending => "pending",
+            JobStatus::Running => "running",
+            JobStatus::Done => "done",
+            JobStatus::Dead => "dead",
+        }
+    }
+}
+
+impl std::str::FromStr for JobStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(JobStatus::Pending),
+            "running" => Ok(JobStatus::Running),
+            "done" => Ok(JobStatus::Done),
+            "dead" => Ok(JobStatus::Dead),
+            _ => Err(format!("invalid job status: {s}")),
+        }
+    }
+}
+
+/// A background job
+#[derive(Debug, Clone)]
+pub struct Job {
+    /// ID
+    pub id: Uuid,
+    /// Kind (selects the handler)
+    pub kind: String,
+    /// Parameters (JSON)
+    pub payload: serde_json::Value,
+    /// Status
+    pub status: JobStatus,
+    /// User who enqueued the job (none for the system jobs)
+    pub owner_id: Option<Uuid>,
+    /// Lease of the worker running the job
+    ///
+    /// NB: a new lease is set each time the job is claimed, so that a worker whose lease has
+    /// expired cannot record the outcome of the job.
+    pub lease_id: Option<Uuid>,
+    /// Number of attempts so far
+    pub attempts: i32,
+    /// Maximum number of attempts
+    pub max_attempts: i32,
+    /// Error of the last attempt
+    pub last_error: Option<String>,
+    /// Time after which the job can be run
+    pub run_at: OffsetDateTime,
+    /// Creation time
+    pub created_at: OffsetDateTime,
+}
+
+impl Job {
+    /// Creates a pending job, to be run now
+    #[cfg(feature = "server")]
+    pub fn new(kind: &str, payload: serde_json::Value, max_attempts: i32) -> Self {
+        let now = OffsetDateTime::now_utc();
+        Job {
+            id: Uuid::new_v4(),
+            kind: kind.to_string(),
+            payload,
+            status: JobStatus::Pending,
+            owner_id: None,
+            lease_id: None,
+            attempts: 0,
+            max_attempts,
+            last_error: None,
+            run_at: now,
+            created_at: now,
+        }
+    }
+
+    /// Sets the user who enqueued the job
+    pub fn owner(mut self, owner_id: Uuid) -> Self {
+        self.owner_id = Some(owner_id);
+        self
+    }
+}
+
+/// Number of jobs with a given status
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
+pub struct JobCount {
+    /// Status
+    pub status: JobStatus,
+    /// Number of jobs
+    pub count: u64,
+}
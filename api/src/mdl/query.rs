@@ -0,0 +1,46 @@
+//! Listing options
+
+use std::collections::BTreeMap;
+
+/// Sort direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortDir {
+    /// Ascending
+    Asc,
+    /// Descending
+    Desc,
+}
+
+/// Sort criteria
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sort {
+    /// Field name
+    pub field: String,
+    /// Direction
+    pub dir: SortDir,
+}
+
+/// Options of a listing (sort, filters, pagination)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ListOptions {
+    /// Sort criteria, by order of precedence
+    pub sort: Vec<Sort>,
+    /// Filters (field -> value)
+    pub filters: BTreeMap<String, String>,
+    /// Pagination cursor
+    pub cursor: Option<String>,
+    /// Maximum number of items
+    pub limit: Option<usize>,
+}
+
+impl ListOptions {
+    /// Returns the value of a filter
+    pub fn filter(&self, field: &str) -> Option<&str> {
+        self.filters.get(field).map(|v| v.as_str())
+    }
+
+    /// Checks if all the items are listed (no filter nor pagination)
+    pub fn is_all(&self) -> bool {
+        self.filters.is_empty() && self.cursor.is_none() && self.limit.is_none()
+    }
+}
@@ -0,0 +1,201 @@
+//! Service reports (health, administration)
+
+#[cfg(feature = "server")]
+use salvo::prelude::ToSchema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{DailyCount, Feed, FeedCount, Subscription, Summary, User};
+
+/// Readiness report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
+pub struct Readiness {
+    /// Overall readiness
+    pub ready: bool,
+    /// A database connection could be acquired from the pool
+    pub database: bool,
+    /// The database schema is initialized
+    pub schema: bool,
+    /// The background tasks (jobs worker, retention job) are running
+    pub scheduler: bool,
+}
+
+/// Logical dump of the data
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
+pub struct Backup {
+    /// Format version
+    pub version: u32,
+    /// Users (with their password hash)
+    pub users: Vec<User>,
+    /// Feeds
+    pub feeds: Vec<Feed>,
+    /// Summaries
+    pub summaries: Vec<Summary>,
+}
+
+/// Data export of a user
+///
+/// NB: unlike a [Backup], the export does not contain the password hash.
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
+pub struct UserExport {
+    /// Format version
+    pub version: u32,
+    /// User
+    pub user: ExportedUser,
+    /// Feeds
+    pub feeds: Vec<Feed>,
+}
+
+/// User of a data export
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
+pub struct ExportedUser {
+    /// ID
+    pub id: Uuid,
+    /// Name
+    pub name: String,
+    /// Email
+    pub email: String,
+    /// Subscription
+    pub subscription: Subscription,
+}
+
+impl From<User> for ExportedUser {
+    fn from(user: User) -> Self {
+        Self {
+            id: user.id,
+            name: user.name,
+            email: user.email,
+            subscription: user.subscription,
+        }
+    }
+}
+
+/// Restore report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
+pub struct RestoreReport {
+    /// Number of restored users
+    pub users: usize,
+    /// Number of restored feeds
+    pub feeds: usize,
+    /// Number of restored summaries
+    pub summaries: usize,
+}
+
+/// Number of rows deleted by a cleanup
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
+pub struct CleanupReport {
+    /// Summaries deleted because they are older than the retention period
+    pub articles: u64,
+    /// Summaries deleted because they have not been accessed during the retention period
+    pub summaries: u64,
+    /// Expired idempotency keys
+    pub idempotency_keys: u64,
+    /// Completed jobs
+    pub jobs: u64,
+}
+
+/// Snapshot of the retention metrics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
+pub struct RetentionStats {
+    /// Number of cleanups
+    pub runs: u64,
+    /// Number of failed cleanups
+    pub failures: u64,
+    /// Total number of deleted rows
+    pub deleted: CleanupReport,
+    /// Time of the last cleanup (unix timestamp)
+    pub last_run: Option<i64>,
+}
+
+/// Instance statistics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
+pub struct InstanceStats {
+    /// Number of users
+    pub users: u64,
+    /// Distribution of the number of feeds per user
+    pub feeds_per_user: Vec<FeedCount>,
+    /// Number of summaries created per day (days without summaries are omitted)
+    pub summaries_per_day: Vec<DailyCount>,
+    /// Summary cache usage
+    pub cache: CacheStats,
+}
+
+/// Summary cache statistics (since the start of the service)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
+pub struct CacheStats {
+    /// Number of summaries found in the DB
+    pub hits: u64,
+    /// Number of summaries processed with OpenAI
+    pub misses: u64,
+    /// Ratio of hits (0 if no summary has been requested)
+    pub hit_rate: f64,
+}
+
+/// Service metrics (since the start of the service)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
+pub struct Metrics {
+    /// Query and pool metrics (not recorded by the SQLite backend)
+    pub db: Option<DbMetricsReport>,
+    /// Summary cache usage
+    pub cache: CacheStats,
+}
+
+/// DB metrics report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
+pub struct DbMetricsReport {
+    /// Current state of the connection pool (primary)
+    pub pool: Option<PoolStats>,
+    /// Number of connection acquisitions
+    pub acquisitions: u64,
+    /// Number of failed connection acquisitions (eg. pool timeouts)
+    pub acquire_errors: u64,
+    /// Mean wait time to acquire a connection (ms)
+    pub wait_mean_ms: f64,
+    /// Longest wait time to acquire a connection (ms)
+    pub wait_max_ms: f64,
+    /// Statistics per operation
+    pub queries: Vec<QueryStats>,
+}
+
+/// State of a connection pool
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
+pub struct PoolStats {
+    /// Maximum number of connections
+    pub max_size: u64,
+    /// Number of open connections
+    pub size: u64,
+    /// Number of idle connections
+    pub available: u64,
+    /// Number of tasks waiting for a connection
+    pub waiting: u64,
+}
+
+/// Statistics of an operation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(ToSchema))]
+pub struct QueryStats {
+    /// Name of the operation
+    pub name: String,
+    /// Number of calls
+    pub count: u64,
+    /// Number of failed calls
+    pub errors: u64,
+    /// Cumulated duration (ms)
+    pub total_ms: f64,
+    /// Mean duration (ms)
+    pub mean_ms: f64,
+    /// Longest duration (ms)
+    pub max_ms: f64,
+}
@@ -0,0 +1,211 @@
+//! Validation
+
+use http::Uri;
+
+use crate::error::{Error, FieldError};
+
+use super::{FeedUpdate, NewUser, PasswordChange, UserUpdate};
+
+/// Validation of input models
+pub trait Validate {
+    /// Returns the invalid fields
+    fn invalid_fields(&self) -> Vec<FieldError>;
+
+    /// Validates the model
+    ///
+    /// An [Error::InvalidFields] is returned if some fields are invalid.
+    fn validate(&self) -> Result<(), Error> {
+        let fields = self.invalid_fields();
+        if fields.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::InvalidFields("invalid fields".to_string(), fields))
+        }
+    }
+}
+
+impl<T: Validate> Validate for [T] {
+    fn invalid_fields(&self) -> Vec<FieldError> {
+        self.iter()
+            .enumerate()
+            .flat_map(|(i, item)| {
+                item.invalid_fields()
+                    .into_iter()
+                    .map(move |err| FieldError {
+                        field: if err.field.is_empty() {
+                            format!("[{i}]")
+                        } else {
+                            format!("[{i}].{}", err.field)
+                        },
+                        message: err.message,
+                    })
+            })
+            .collect()
+    }
+}
+
+impl Validate for NewUser {
+    fn invalid_fields(&self) -> Vec<FieldError> {
+        let mut fields = vec![];
+        check_name("name", &self.name, &mut fields);
+        check_email("email", &self.email, &mut fields);
+        check_password("password", &self.password, &mut fields);
+        fields
+    }
+}
+
+impl Validate for UserUpdate {
+    fn invalid_fields(&self) -> Vec<FieldError> {
+        let mut fields = vec![];
+        if let Some(name) = &self.name {
+            check_name("name", name, &mut fields);
+        }
+        if let Some(email) = &self.email {
+            check_email("email", email, &mut fields);
+        }
+        if let Some(password) = &self.password {
+            check_password("password", password, &mut fields);
+        }
+        fields
+    }
+}
+
+impl Validate for PasswordChange {
+    fn invalid_fields(&self) -> Vec<FieldError> {
+        let mut fields = vec![];
+        check_password("new_password", &self.new_password, &mut fields);
+        fields
+    }
+}
+
+impl Validate for FeedUpdate {
+    fn invalid_fields(&self) -> Vec<FieldError> {
+        let mut fields = vec![];
+        check_url("url", &self.url, &mut fields);
+        fields
+    }
+}
+
+/// Article URL
+///
+/// NB: used to validate the summaries payload, which is a list of URLs
+pub struct ArticleUrl<'a>(pub &'a str);
+
+impl Validate for ArticleUrl<'_> {
+    fn invalid_fields(&self) -> Vec<FieldError> {
+        let mut fields = vec![];
+        check_url("", self.0, &mut fields);
+        fields
+    }
+}
+
+/// Checks a user name
+fn check_name(field: &str, name: &str, fields: &mut Vec<FieldError>) {
+    if name.trim().is_empty() {
+        fields.push(FieldError::new(field, "name must not be empty"));
+    }
+}
+
+/// Checks an email address
+fn check_email(field: &str, email: &str, fields: &mut Vec<FieldError>) {
+    if !is_email(email) {
+        fields.push(FieldError::new(field, "invalid email address"));
+    }
+}
+
+/// Checks a password
+fn check_password(field: &str, password: &str, fields: &mut Vec<FieldError>) {
+    if password.is_empty() {
+        fields.push(FieldError::new(field, "password must not be empty"));
+    }
+}
+
+/// Checks an URL
+fn check_url(field: &str, url: &str, fields: &mut Vec<FieldError>) {
+    if !is_http_url(url) {
+        fields.push(FieldError::new(field, "invalid http(s) URL"));
+    }
+}
+
+/// Checks if a string looks like an email address
+pub fn is_email(value: &str) -> bool {
+    match value.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && !domain.is_empty()
+                && !domain.contains('@')
+                && domain.contains('.')
+                && !value.chars().any(char::is_whitespace)
+        }
+        None => false,
+    }
+}
+
+/// Checks if a string is an absolute http(s) URL
+pub fn is_http_url(value: &str) -> bool {
+    match value.parse::<Uri>() {
+        Ok(uri) => {
+            matches!(uri.scheme_str(), Some("http") | Some("https"))
+                && uri.host().map(|h| !h.is_empty()).unwrap_or(false)
+        }
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_email() {
+        assert!(is_email("john@doe.com"));
+        assert!(!is_email(""));
+        assert!(!is_email("john"));
+        assert!(!is_email("john@doe"));
+        assert!(!is_email("jo hn@doe.com"));
+    }
+
+    #[test]
+    fn test_is_http_url() {
+        assert!(is_http_url("https://ai.googleblog.com/atom.xml"));
+        assert!(is_http_url("http://www.google.com"));
+        assert!(!is_http_url("ftp://www.google.com"));
+        assert!(!is_http_url("www.google.com"));
+        assert!(!is_http_url(""));
+    }
+
+    #[test]
+    fn test_validate_new_user() {
+        let new_user = NewUser {
+            name: "".to_string(),
+            email: "john".to_string(),
+            password: "1234".to_string(),
+        };
+        let fields = new_user.invalid_fields();
+        assert_eq!(
+            fields.iter().map(|f| f.field.as_str()).collect::<Vec<_>>(),
+            vec!["name", "email"]
+        );
+    }
+
+    #[test]
+    fn test_validate_feeds() {
+        let feeds = vec![
+            FeedUpdate {
+                id: None,
+                url: "https://ai.googleblog.com/atom.xml".to_string(),
+                name: None,
+            },
+            FeedUpdate {
+                id: None,
+                url: "not a url".to_string(),
+                name: None,
+            },
+        ];
+        let fields = feeds.invalid_fields();
+        assert_eq!(
+            fields,
+            vec![FieldError::new("[1].url", "invalid http(s) URL")]
+        );
+    }
+}
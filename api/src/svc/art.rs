@@ -4,30 +4,64 @@ use async_openai::types::{
     ChatCompletionRequestMessageArgs, CreateChatCompletionRequestArgs, CreateEmbeddingRequestArgs,
     Role,
 };
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
 use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
 use uuid::Uuid;
 
-use crate::{config::OpenAiClient, db::postgres::PostgresClient, error::Error, mdl::Summary};
+use crate::{
+    config::OpenAiClient,
+    db::Db,
+    error::Error,
+    mdl::{Job, Summary},
+};
 
 /// Article service
 #[derive(Clone)]
 pub struct ArticleService {
-    /// Postgres client
-    pub db: PostgresClient,
+    /// Data store
+    pub db: Db,
     /// OpenAI client
     pub openai: OpenAiClient,
+    /// Summary cache metrics
+    pub cache: Arc<CacheMetrics>,
 }
 
 impl ArticleService {
     /// Creates a new service instance
-    pub fn new(postgres_client: PostgresClient, openai_client: OpenAiClient) -> Self {
+    pub fn new(db: Db, openai_client: OpenAiClient) -> Self {
         Self {
-            db: postgres_client,
+            db,
             openai: openai_client,
+            cache: Arc::new(CacheMetrics::default()),
         }
     }
 }
 
+/// Kind of the jobs which process the summaries of a batch of articles (see [SummarizeJob])
+pub const SUMMARIZE_JOB: &str = "summarize";
+
+/// Payload of a [SUMMARIZE_JOB] job
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SummarizeJob {
+    /// Article URLs
+    pub urls: Vec<String>,
+}
+
+/// Summary cache metrics (since the start of the service)
+#[derive(Debug, Default)]
+pub struct CacheMetrics {
+    /// Number of summaries found in the DB
+    pub hits: AtomicU64,
+    /// Number of summaries processed with OpenAI
+    pub misses: AtomicU64,
+}
+
 impl ArticleService {
     /// Retrieves a list of articles with their summaries
     ///
@@ -36,6 +70,15 @@ impl ArticleService {
     /// To keep a cache of already processed articles, we first check if articles are
     /// already in the database of articles
     pub async fn process_summaries(&self, urls: &[&str]) -> Result<Vec<Summary>, Error> {
+        // remove duplicated urls
+        let mut unique_urls: Vec<&str> = vec![];
+        for url in urls {
+            if !unique_urls.contains(url) {
+                unique_urls.push(*url);
+            }
+        }
+        let urls = unique_urls.as_slice();
+
         // search articles by ID to retrieve already processed articles
         let mut found_articles = self.db.search_summaries_by_urls(urls).await?;
 
@@ -49,6 +92,20 @@ impl ArticleService {
             .filter(|url| !found_urls.contains(url))
             .collect::<Vec<_>>();
 
+        self.cache
+            .hits
+            .fetch_add(found_urls.len() as u64, Ordering::Relaxed);
+        self.cache
+            .misses
+            .fetch_add(not_found_urls.len() as u64, Ordering::Relaxed);
+
+        // keep the accessed summaries from being deleted (see [crate::svc::retention])
+        if !found_urls.is_empty() {
+            if let Err(err) = self.db.touch_summaries(&found_urls).await {
+                warn!(%err, "failed to mark the summaries as accessed");
+            }
+        }
+
         // process new articles in parallel
         let mut new_articles = if !not_found_urls.is_empty() {
             let mut tasks = vec![];
@@ -70,6 +127,22 @@ impl ArticleService {
         Ok(articles)
     }
 
+    /// Runs a [SUMMARIZE_JOB] job
+    ///
+    /// The summaries are stored, so that they are found by the next summaries requests.
+    pub async fn run_summarize_job(&self, job: Job) -> Result<(), Error> {
+        let payload = serde_json::from_value::<SummarizeJob>(job.payload).map_err(|err| {
+            Error::Internal("invalid job payload".to_string(), Some(err.to_string()))
+        })?;
+        let urls = payload
+            .urls
+            .iter()
+            .map(|url| url.as_str())
+            .collect::<Vec<_>>();
+        self.process_summaries(&urls).await?;
+        Ok(())
+    }
+
     /// Processes an article
     async fn process_article(&self, url: &str) -> Result<Summary, Error> {
         let summary = self.summarize(url).await?;
@@ -186,17 +259,16 @@ impl ArticleService {
 mod tests {
     use time::Instant;
 
-    use crate::config::AppConfig;
+    use crate::{config::AppConfig, db::init_store};
 
     use super::*;
 
     async fn setup() -> ArticleService {
         let cfg = AppConfig::load();
-        let postgres_pool = cfg.postgres.new_pool();
-        let postgres_client = PostgresClient::new(postgres_pool);
+        let db = init_store(&cfg).unwrap();
         let openai_client = cfg.openai.new_client();
 
-        ArticleService::new(postgres_client, openai_client)
+        ArticleService::new(db, openai_client)
     }
 
     #[tokio::test]
@@ -5,24 +5,24 @@ use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::{
-    db::postgres::PostgresClient,
-    error::Error,
-    mdl::{NewUser, SubscriptionUpdate, User, UserUpdate},
+    db::{tenant::with_system, Db},
+    error::{Error, FieldError},
+    mdl::{NewUser, PasswordChange, SubscriptionUpdate, User, UserUpdate},
 };
 
 /// Authentication service
 #[derive(Debug, Clone)]
 pub struct AuthService {
-    /// Postgres db
-    pub db: PostgresClient,
+    /// Data store
+    pub db: Db,
     /// Secret used to sign the JWT token
     pub secret: String,
 }
 
 impl AuthService {
     /// Creates a new service instance
-    pub fn new(client: PostgresClient, secret: String) -> Self {
-        Self { db: client, secret }
+    pub fn new(db: Db, secret: String) -> Self {
+        Self { db, secret }
     }
 }
 
@@ -39,11 +39,11 @@ struct AuthJwtClaims {
 
 impl AuthService {
     /// Creates a new [User]
+    ///
+    /// NB: the user is not known yet, so the queries run in the system scope
     pub async fn create_user(&self, mut new_user: NewUser) -> Result<User, Error> {
         // check that the user with the email exists
-        if let Some(u) = self
-            .db
-            .read_user_with_email(&new_user.email)
+        if let Some(u) = with_system(self.db.read_user_with_email(&new_user.email))
             .await
             .map_err(Error::from)?
         {
@@ -57,7 +57,7 @@ impl AuthService {
         let hashed_pwd = hash_password(&new_user.password)?;
         new_user.password = hashed_pwd;
 
-        self.db.create_user(new_user).await
+        with_system(self.db.create_user(new_user)).await
     }
 
     /// Queries a user with its ID
@@ -76,14 +76,48 @@ impl AuthService {
         self.db.update_user(user_id, fields).await
     }
 
+    /// Changes the password of a user
+    ///
+    /// The current password must be provided.
+    pub async fn change_password(
+        &self,
+        user_id: Uuid,
+        change: PasswordChange,
+    ) -> Result<User, Error> {
+        let user = self.read(user_id).await?.ok_or(Error::NotFound(
+            format!("no user with id '{user_id}'"),
+            None,
+        ))?;
+        if !verify_password(&user.password, &change.current_password)? {
+            return Err(Error::InvalidFields(
+                "invalid fields".to_string(),
+                vec![FieldError::new("current_password", "invalid password")],
+            ));
+        }
+
+        let fields = UserUpdate {
+            name: None,
+            email: None,
+            password: Some(hash_password(&change.new_password)?),
+        };
+        self.db.update_user(user_id, fields).await
+    }
+
     /// Deletes a user
+    ///
+    /// The user feeds are deleted in the same transaction.
     pub async fn delete_user(&self, user_id: Uuid) -> Result<(), Error> {
-        self.db.delete_user(user_id).await
+        let mut tx = self.db.begin().await?;
+        tx.delete_user_feeds(user_id).await?;
+        tx.delete_user(user_id).await?;
+        tx.commit().await
     }
 
     /// Login a new user
+    ///
+    /// NB: the user is not known yet, so the query runs in the system scope
     pub async fn login(&self, email: &str, password: &str) -> Result<User, Error> {
-        let user = match self.db.read_user_with_email(email).await? {
+        let user = match with_system(self.db.read_user_with_email(email)).await? {
             Some(u) => u,
             None => {
                 return Err(Error::NotFound(
@@ -113,7 +147,8 @@ impl AuthService {
         )?;
 
         // Query the user by ID
-        self.read(token_data.claims.user_id).await
+        // NB: the user is not authenticated yet, so the query runs in the system scope
+        with_system(self.read(token_data.claims.user_id)).await
     }
 
     /// Updates the user subscription
@@ -167,7 +202,7 @@ pub fn verify_password(hash: &str, password: &str) -> Result<bool, Error> {
 
 #[cfg(test)]
 mod tests {
-    use crate::config::AppConfig;
+    use crate::{config::AppConfig, db::init_store, mdl::FeedUpdate};
 
     use super::*;
 
@@ -179,8 +214,8 @@ mod tests {
     /// Setup a test
     async fn setup() -> (AuthService, User) {
         let cfg = AppConfig::load();
-        let postgres_client = PostgresClient::new(cfg.postgres.new_pool());
-        let service = AuthService::new(postgres_client, cfg.auth.secret.clone());
+        let db = init_store(&cfg).unwrap();
+        let service = AuthService::new(db, cfg.auth.secret.clone());
 
         // create dummy user
         let name: String = Name().fake();
@@ -219,6 +254,31 @@ mod tests {
         teardown(service, user).await;
     }
 
+    #[tokio::test]
+    async fn test_delete_user_with_feeds() {
+        let (service, user) = setup().await;
+        service
+            .db
+            .sync_user_feeds(
+                user.id,
+                vec![FeedUpdate {
+                    id: None,
+                    url: "https://ai.googleblog.com/atom.xml".to_string(),
+                    name: None,
+                }],
+            )
+            .await
+            .unwrap();
+        service.delete_user(user.id).await.unwrap();
+        assert!(service.read(user.id).await.unwrap().is_none());
+        assert!(service
+            .db
+            .read_user_feeds(user.id)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
     #[tokio::test]
     async fn test_issue_token() {
         let (service, user) = setup().await;
@@ -0,0 +1,350 @@
+//! Backup service
+
+use serde::Serialize;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use uuid::Uuid;
+
+pub use crate::mdl::report::{Backup, RestoreReport, UserExport};
+use crate::{db::Db, error::Error, mdl::FeedUpdate};
+
+/// Version of the backup format
+pub const BACKUP_VERSION: u32 = 1;
+
+/// Number of summaries inserted per statement during a restore
+const RESTORE_BATCH_SIZE: usize = 500;
+
+/// Default number of summaries read per query during a backup
+pub const BACKUP_PAGE_SIZE: usize = 500;
+
+/// Backup service
+#[derive(Debug, Clone)]
+pub struct BackupService {
+    /// Data store
+    pub db: Db,
+}
+
+impl BackupService {
+    /// Creates a new service instance
+    pub fn new(db: Db) -> Self {
+        Self { db }
+    }
+}
+
+impl BackupService {
+    /// Writes a dump of the data of a user, or of the whole instance, as a JSON [Backup]
+    ///
+    /// The rows are written as they are read, and the summaries are read by pages of
+    /// `page_size`, so that the dump is never loaded in memory. Summaries are shared between
+    /// users, so they are only part of an instance backup.
+    pub async fn write_backup<W: AsyncWrite + Unpin + Send>(
+        &self,
+        user_id: Option<Uuid>,
+        writer: &mut W,
+        page_size: usize,
+    ) -> Result<(), Error> {
+        let users = match user_id {
+            Some(user_id) => vec![self
+                .db
+                .read_user(user_id)
+                .await?
+                .ok_or(Error::NotFound(format!("no user for id {user_id}"), None))?],
+            None => self.db.read_users().await?,
+        };
+
+        write_raw(
+            writer,
+            &format!("{{\"version\":{BACKUP_VERSION},\"users\":["),
+        )
+        .await?;
+        for (i, user) in users.iter().enumerate() {
+            write_row(writer, i, user).await?;
+        }
+
+        write_raw(writer, "],\"feeds\":[").await?;
+        let mut count = 0;
+        for user in &users {
+            for feed in self.db.read_user_feeds(user.id).await? {
+                write_row(writer, count, &feed).await?;
+                count += 1;
+            }
+        }
+
+        write_raw(writer, "],\"summaries\":[").await?;
+        if user_id.is_none() {
+            let mut count = 0;
+            let mut cursor = None;
+            loop {
+                let page = self
+                    .db
+                    .read_summaries_page(cursor.as_deref(), page_size)
+                    .await?;
+                for summary in &page.items {
+                    write_row(writer, count, summary).await?;
+                    count += 1;
+                }
+                match page.next_cursor {
+                    Some(next) => cursor = Some(next.encode()),
+                    None => break,
+                }
+            }
+        }
+
+        write_raw(writer, "]}").await?;
+        writer.flush().await.map_err(backup_error)
+    }
+
+    /// Reads the data export of a user (their account and feeds)
+    pub async fn read_export(&self, user_id: Uuid) -> Result<UserExport, Error> {
+        let user = self
+            .db
+            .read_user(user_id)
+            .await?
+            .ok_or(Error::NotFound(format!("no user for id {user_id}"), None))?;
+        let feeds = self.db.read_user_feeds(user_id).await?;
+        Ok(UserExport {
+            version: BACKUP_VERSION,
+            user: user.into(),
+            feeds,
+        })
+    }
+
+    /// Restores a backup
+    ///
+    /// Users are created or replaced (with the same ID), and their feeds are replaced by the
+    /// feeds of the backup. Summaries which already exist for an URL are kept. The backup is
+    /// restored in a single transaction.
+    pub async fn restore(&self, backup: Backup) -> Result<RestoreReport, Error> {
+        if backup.version != BACKUP_VERSION {
+            return Err(Error::InvalidRequest(
+                format!("unsupported backup version {}", backup.version),
+                None,
+            ));
+        }
+
+        let mut report = RestoreReport {
+            users: 0,
+            feeds: 0,
+            summaries: 0,
+        };
+
+        let mut tx = self.db.begin().await?;
+        for user in &backup.users {
+            tx.upsert_user(user).await?;
+            report.users += 1;
+        }
+        for user in &backup.users {
+            let feeds = backup
+                .feeds
+                .iter()
+                .filter(|f| f.user_id == user.id)
+                .map(|f| FeedUpdate {
+                    id: Some(f.id),
+                    url: f.url.clone(),
+                    name: f.name.clone(),
+                })
+                .collect::<Vec<_>>();
+            report.feeds += tx.sync_user_feeds(user.id, feeds).await?.len();
+        }
+
+        let mut summaries = backup.summaries;
+        while !summaries.is_empty() {
+            let batch = summaries
+                .drain(..RESTORE_BATCH_SIZE.min(summaries.len()))
+                .collect::<Vec<_>>();
+            report.summaries += tx.insert_summaries(batch).await?.len();
+        }
+        tx.commit().await?;
+
+        Ok(report)
+    }
+}
+
+/// Writes a data export as JSON
+///
+/// The feeds are written one by one, so that the JSON document is never buffered.
+pub async fn write_export<W: AsyncWrite + Unpin + Send>(
+    export: &UserExport,
+    writer: &mut W,
+) -> Result<(), Error> {
+    write_raw(
+        writer,
+        &format!("{{\"version\":{},\"user\":", export.version),
+    )
+    .await?;
+    write_row(writer, 0, &export.user).await?;
+    write_raw(writer, ",\"feeds\":[").await?;
+    for (i, feed) in export.feeds.iter().enumerate() {
+        write_row(writer, i, feed).await?;
+    }
+    write_raw(writer, "]}").await?;
+    writer.flush().await.map_err(backup_error)
+}
+
+/// Writes a string of the JSON dump
+async fn write_raw<W: AsyncWrite + Unpin + Send>(writer: &mut W, s: &str) -> Result<(), Error> {
+    writer.write_all(s.as_bytes()).await.map_err(backup_error)
+}
+
+/// Writes an item of a JSON array (`index` is the position of the item in the array)
+async fn write_row<W: AsyncWrite + Unpin + Send, T: Serialize>(
+    writer: &mut W,
+    index: usize,
+    row: &T,
+) -> Result<(), Error> {
+    let mut bytes = if index > 0 { vec![b','] } else { vec![] };
+    serde_json::to_writer(&mut bytes, row).map_err(backup_error)?;
+    writer.write_all(&bytes).await.map_err(backup_error)
+}
+
+/// Converts a serialization or IO error
+fn backup_error(err: impl std::fmt::Display) -> Error {
+    Error::Internal(
+        "failed to write the backup".to_string(),
+        Some(err.to_string()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{
+        db::sqlite::SqliteClient,
+        mdl::{NewUser, Summary},
+    };
+
+    use super::*;
+
+    /// Setup a test with an in-memory store
+    async fn setup() -> BackupService {
+        let db = SqliteClient::open(":memory:").unwrap();
+        db.init_schema().await.unwrap();
+        BackupService::new(Arc::new(db))
+    }
+
+    #[tokio::test]
+    async fn test_backup_restore() {
+        let service = setup().await;
+        let user = service
+            .db
+            .create_user(NewUser {
+                name: "John Doe".to_string(),
+                email: "john@doe.com".to_string(),
+                password: "dummy".to_string(),
+            })
+            .await
+            .unwrap();
+        service
+            .db
+            .sync_user_feeds(
+                user.id,
+                vec![FeedUpdate {
+                    id: None,
+                    url: "https://ai.googleblog.com/atom.xml".to_string(),
+                    name: None,
+                }],
+            )
+            .await
+            .unwrap();
+
+        let mut dump = vec![];
+        service
+            .write_backup(Some(user.id), &mut dump, BACKUP_PAGE_SIZE)
+            .await
+            .unwrap();
+        let backup = serde_json::from_slice::<Backup>(&dump).unwrap();
+        assert_eq!(backup.users.len(), 1);
+        assert_eq!(backup.feeds.len(), 1);
+
+        // restore into another instance
+        let other = setup().await;
+        let report = other.restore(backup).await.unwrap();
+        assert_eq!(report.users, 1);
+        assert_eq!(report.feeds, 1);
+        let restored = other.db.read_user(user.id).await.unwrap().unwrap();
+        assert_eq!(restored.email, user.email);
+        assert_eq!(restored.password, user.password);
+    }
+
+    #[tokio::test]
+    async fn test_export() {
+        let service = setup().await;
+        let user = service
+            .db
+            .create_user(NewUser {
+                name: "John Doe".to_string(),
+                email: "john@doe.com".to_string(),
+                password: "dummy".to_string(),
+            })
+            .await
+            .unwrap();
+        service
+            .db
+            .sync_user_feeds(
+                user.id,
+                vec![FeedUpdate {
+                    id: None,
+                    url: "https://ai.googleblog.com/atom.xml".to_string(),
+                    name: None,
+                }],
+            )
+            .await
+            .unwrap();
+
+        let export = service.read_export(user.id).await.unwrap();
+        let mut body = vec![];
+        write_export(&export, &mut body).await.unwrap();
+        let value = serde_json::from_slice::<serde_json::Value>(&body).unwrap();
+        assert_eq!(value["user"]["email"], "john@doe.com");
+        assert!(value["user"].get("password").is_none());
+        let export = serde_json::from_slice::<UserExport>(&body).unwrap();
+        assert_eq!(export.user.id, user.id);
+        assert_eq!(export.feeds.len(), 1);
+
+        assert!(service.read_export(Uuid::new_v4()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_backup_restore_summaries() {
+        let service = setup().await;
+        service
+            .db
+            .insert_summaries(
+                (0..3)
+                    .map(|i| Summary {
+                        id: Uuid::new_v4(),
+                        url: format!("https://www.link.com/{i}"),
+                        summary: "Lore ipsum".to_string(),
+                        keywords: vec!["kw1".to_string()],
+                        embeddings: vec![0.5, 0.5].into(),
+                    })
+                    .collect(),
+            )
+            .await
+            .unwrap();
+
+        let mut dump = vec![];
+        service.write_backup(None, &mut dump, 2).await.unwrap();
+        let backup = serde_json::from_slice::<Backup>(&dump).unwrap();
+        assert_eq!(backup.summaries.len(), 3);
+
+        let other = setup().await;
+        let report = other.restore(backup).await.unwrap();
+        assert_eq!(report.summaries, 3);
+        assert_eq!(other.db.read_summaries().await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_restore_unsupported_version() {
+        let service = setup().await;
+        let res = service
+            .restore(Backup {
+                version: BACKUP_VERSION + 1,
+                users: vec![],
+                feeds: vec![],
+                summaries: vec![],
+            })
+            .await;
+        assert!(matches!(res, Err(Error::InvalidRequest(_, _))));
+    }
+}
@@ -0,0 +1,154 @@
+//! Parquet export
+//!
+//! The summaries and their embeddings are exported to a Parquet file for offline analysis
+//! (see the `export` binary). The table is read by pages, each page being written as a
+//! row group, so that it is never loaded in memory.
+
+use std::{io::Write, sync::Arc};
+
+use arrow_array::{
+    builder::{Float32Builder, ListBuilder, StringBuilder},
+    ArrayRef, RecordBatch,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
+
+use crate::{db::Db, error::Error, mdl::Summary};
+
+/// Default number of summaries per row group
+pub const EXPORT_PAGE_SIZE: usize = 1000;
+
+/// Returns the schema of the exported summaries
+pub fn summaries_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new("url", DataType::Utf8, false),
+        Field::new("summary", DataType::Utf8, false),
+        Field::new(
+            "keywords",
+            DataType::List(Arc::new(Field::new("item", DataType::Utf8, true))),
+            false,
+        ),
+        Field::new(
+            "embeddings",
+            DataType::List(Arc::new(Field::new("item", DataType::Float32, true))),
+            false,
+        ),
+    ]))
+}
+
+/// Exports the summaries to Parquet
+///
+/// The summaries are read by pages of `page_size`. Returns the number of exported summaries.
+pub async fn export_summaries<W: Write + Send>(
+    db: &Db,
+    writer: W,
+    page_size: usize,
+) -> Result<u64, Error> {
+    let schema = summaries_schema();
+    let props = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .set_max_row_group_size(page_size)
+        .build();
+    let mut writer =
+        ArrowWriter::try_new(writer, schema.clone(), Some(props)).map_err(export_error)?;
+
+    let mut count = 0;
+    let mut cursor = None;
+    loop {
+        let page = db.read_summaries_page(cursor.as_deref(), page_size).await?;
+        if !page.items.is_empty() {
+            count += page.items.len() as u64;
+            let batch = to_record_batch(&schema, &page.items)?;
+            writer.write(&batch).map_err(export_error)?;
+            // NB: the row group is written, so that its memory is released
+            writer.flush().map_err(export_error)?;
+        }
+
+        match page.next_cursor {
+            Some(next) => cursor = Some(next.encode()),
+            None => break,
+        }
+    }
+
+    writer.close().map_err(export_error)?;
+    Ok(count)
+}
+
+/// Converts a page of summaries to an Arrow record batch
+fn to_record_batch(schema: &SchemaRef, summaries: &[Summary]) -> Result<RecordBatch, Error> {
+    let mut ids = StringBuilder::new();
+    let mut urls = StringBuilder::new();
+    let mut texts = StringBuilder::new();
+    let mut keywords = ListBuilder::new(StringBuilder::new());
+    let mut embeddings = ListBuilder::new(Float32Builder::new());
+
+    for summary in summaries {
+        ids.append_value(summary.id.to_string());
+        urls.append_value(&summary.url);
+        texts.append_value(&summary.summary);
+        for keyword in &summary.keywords {
+            keywords.values().append_value(keyword);
+        }
+        keywords.append(true);
+        embeddings
+            .values()
+            .append_slice(summary.embeddings.as_ref());
+        embeddings.append(true);
+    }
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(ids.finish()),
+        Arc::new(urls.finish()),
+        Arc::new(texts.finish()),
+        Arc::new(keywords.finish()),
+        Arc::new(embeddings.finish()),
+    ];
+    RecordBatch::try_new(schema.clone(), columns).map_err(export_error)
+}
+
+/// Converts an Arrow or Parquet error
+fn export_error(err: impl std::fmt::Display) -> Error {
+    Error::Internal("failed to export".to_string(), Some(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use uuid::Uuid;
+
+    use crate::db::sqlite::SqliteClient;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_export_summaries() {
+        let db = SqliteClient::open(":memory:").unwrap();
+        db.init_schema().await.unwrap();
+        let db: Db = Arc::new(db);
+        db.insert_summaries(
+            (0..5)
+                .map(|i| Summary {
+                    id: Uuid::new_v4(),
+                    url: format!("https://www.link.com/{i}"),
+                    summary: "Lore ipsum".to_string(),
+                    keywords: vec!["kw1".to_string(), "kw2".to_string()],
+                    embeddings: vec![0.5, 0.5].into(),
+                })
+                .collect(),
+        )
+        .await
+        .unwrap();
+
+        let path = std::env::temp_dir().join(format!("newsie-{}.parquet", Uuid::new_v4()));
+        let file = std::fs::File::create(&path).unwrap();
+        let count = export_summaries(&db, file, 2).await.unwrap();
+        assert_eq!(count, 5);
+
+        let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let metadata = reader.metadata();
+        assert_eq!(metadata.file_metadata().num_rows(), 5);
+        assert_eq!(metadata.num_row_groups(), 3);
+        std::fs::remove_file(path).unwrap();
+    }
+}
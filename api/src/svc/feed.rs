@@ -1,26 +1,26 @@
 //! Feed service
 
+use salvo::prelude::ToSchema;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::{
-    db::postgres::PostgresClient,
+    db::{page::Page, Db},
     error::Error,
-    mdl::{Feed, FeedUpdate},
+    mdl::{query::ListOptions, Feed, FeedUpdate},
 };
 
 /// Feed service
 #[derive(Debug, Clone)]
 pub struct FeedService {
-    /// Postgres db
-    pub db: PostgresClient,
+    /// Data store
+    pub db: Db,
 }
 
 impl FeedService {
     /// Creates a new service instance
-    pub fn new(postgres_client: PostgresClient) -> Self {
-        Self {
-            db: postgres_client,
-        }
+    pub fn new(db: Db) -> Self {
+        Self { db }
     }
 }
 
@@ -30,12 +30,136 @@ impl FeedService {
         self.db.read_user_feeds(user_id).await
     }
 
+    /// Queries the user feeds
+    ///
+    /// If all the feeds are listed, they are up to date with the last sync (see [feeds_version]).
+    pub async fn query_feeds(
+        &self,
+        user_id: Uuid,
+        opts: &ListOptions,
+    ) -> Result<Page<Feed>, Error> {
+        self.db.query_user_feeds(user_id, opts).await
+    }
+
     /// Sync the user feeds
+    ///
+    /// If an expected version is set (`*` matches any version), the feeds are only replaced if
+    /// they have not changed since, otherwise a [Error::Conflict] is returned with the current
+    /// [FeedsState] (as JSON) in its detail.
     pub async fn sync_feeds(
         &self,
         user_id: Uuid,
         feeds: Vec<FeedUpdate>,
+        expected_version: Option<&str>,
     ) -> Result<Vec<Feed>, Error> {
-        self.db.sync_user_feeds(user_id, feeds).await
+        let mut tx = self.db.begin().await?;
+        let current = tx.lock_user_feeds(user_id).await?;
+        if let Some(expected) = expected_version {
+            let version = feeds_version(&current);
+            if expected != "*" && expected != version {
+                tx.rollback().await?;
+                let state = FeedsState {
+                    version,
+                    feeds: current,
+                };
+                return Err(Error::Conflict(
+                    "the feeds have changed since the expected version".to_string(),
+                    serde_json::to_string(&state).ok(),
+                ));
+            }
+        }
+        let feeds = tx.sync_user_feeds(user_id, feeds).await?;
+        tx.commit().await?;
+        Ok(feeds)
+    }
+}
+
+/// Current state of the user feeds
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct FeedsState {
+    /// Version (also sent in the `ETag` header)
+    pub version: String,
+    /// Feeds
+    pub feeds: Vec<Feed>,
+}
+
+/// Computes the version of a list of feeds
+///
+/// The version is a hash of the feeds (FNV-1a), which does not depend on their order.
+pub fn feeds_version(feeds: &[Feed]) -> String {
+    let mut feeds = feeds.iter().collect::<Vec<_>>();
+    feeds.sort_by_key(|f| f.id);
+
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for f in feeds {
+        let line = format!(
+            "{}\t{}\t{}\n",
+            f.id,
+            f.url,
+            f.name.as_deref().unwrap_or_default()
+        );
+        for b in line.bytes() {
+            hash ^= b as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    format!("{hash:016x}")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{db::sqlite::SqliteClient, mdl::NewUser};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_sync_feeds_version() {
+        let db = SqliteClient::open(":memory:").unwrap();
+        db.init_schema().await.unwrap();
+        let service = FeedService::new(Arc::new(db));
+        let user = service
+            .db
+            .create_user(NewUser {
+                name: "John Doe".to_string(),
+                email: "john@doe.com".to_string(),
+                password: "dummy".to_string(),
+            })
+            .await
+            .unwrap();
+        let update = |url: &str| FeedUpdate {
+            id: None,
+            url: url.to_string(),
+            name: None,
+        };
+
+        let empty = feeds_version(&[]);
+        let feeds = service
+            .sync_feeds(user.id, vec![update("https://a.com/rss")], Some(&empty))
+            .await
+            .unwrap();
+        let version = feeds_version(&feeds);
+        assert_ne!(version, empty);
+
+        // NB: a stale version is rejected with the current state
+        let res = service
+            .sync_feeds(user.id, vec![update("https://b.com/rss")], Some(&empty))
+            .await;
+        let Err(Error::Conflict(_, Some(detail))) = res else {
+            panic!("expected a conflict");
+        };
+        let state = serde_json::from_str::<FeedsState>(&detail).unwrap();
+        assert_eq!(state.version, version);
+        assert_eq!(state.feeds.len(), 1);
+
+        service
+            .sync_feeds(user.id, vec![update("https://b.com/rss")], Some(&version))
+            .await
+            .unwrap();
+        service
+            .sync_feeds(user.id, vec![], Some("*"))
+            .await
+            .unwrap();
     }
 }
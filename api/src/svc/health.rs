@@ -0,0 +1,132 @@
+//! Health service
+
+use std::{
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use time::OffsetDateTime;
+use tracing::warn;
+
+use crate::db::Db;
+pub use crate::mdl::report::Readiness;
+
+/// Health service
+#[derive(Debug, Clone)]
+pub struct HealthService {
+    /// Data store
+    pub db: Db,
+    /// Heartbeats of the running background tasks
+    heartbeats: Vec<(&'static str, Heartbeat)>,
+}
+
+impl HealthService {
+    /// Creates a new service instance
+    pub fn new(db: Db) -> Self {
+        Self {
+            db,
+            heartbeats: vec![],
+        }
+    }
+
+    /// Watches a background task, which must beat for the service to be ready
+    pub fn watch(mut self, task: &'static str, heartbeat: Heartbeat) -> Self {
+        self.heartbeats.push((task, heartbeat));
+        self
+    }
+}
+
+/// Heartbeat of a background task
+///
+/// The task beats on each iteration of its loop, so a task which has died (eg. it panicked)
+/// or is stuck stops beating.
+#[derive(Debug, Clone)]
+pub struct Heartbeat {
+    /// Time of the last beat (unix timestamp, 0 if none)
+    last: Arc<AtomicI64>,
+    /// Period without a beat after which the task is considered dead
+    max_age: Duration,
+}
+
+impl Heartbeat {
+    /// Creates a new heartbeat
+    pub fn new(max_age: Duration) -> Self {
+        Self {
+            last: Arc::new(AtomicI64::new(0)),
+            max_age,
+        }
+    }
+
+    /// Records a beat
+    pub fn beat(&self) {
+        self.last.store(
+            OffsetDateTime::now_utc().unix_timestamp(),
+            Ordering::Relaxed,
+        );
+    }
+
+    /// Checks if the task has beaten recently
+    pub fn is_alive(&self) -> bool {
+        let last = self.last.load(Ordering::Relaxed);
+        let age = OffsetDateTime::now_utc().unix_timestamp() - last;
+        last > 0 && age <= self.max_age.as_secs() as i64
+    }
+}
+
+impl HealthService {
+    /// Checks if the service is ready to accept traffic
+    pub async fn readiness(&self) -> Readiness {
+        let database = match self.db.ping().await {
+            Ok(()) => true,
+            Err(err) => {
+                warn!(%err, "database is not reachable");
+                false
+            }
+        };
+        let schema = database
+            && match self.db.is_schema_init().await {
+                Ok(ok) => ok,
+                Err(err) => {
+                    warn!(%err, "failed to check the database schema");
+                    false
+                }
+            };
+        let mut scheduler = true;
+        for (task, heartbeat) in &self.heartbeats {
+            if !heartbeat.is_alive() {
+                warn!(task, "background task is not running");
+                scheduler = false;
+            }
+        }
+
+        Readiness {
+            ready: database && schema && scheduler,
+            database,
+            schema,
+            scheduler,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heartbeat() {
+        let heartbeat = Heartbeat::new(Duration::from_secs(60));
+        assert!(!heartbeat.is_alive());
+        heartbeat.beat();
+        assert!(heartbeat.is_alive());
+
+        let heartbeat = Heartbeat::new(Duration::from_secs(60));
+        heartbeat.last.store(
+            OffsetDateTime::now_utc().unix_timestamp() - 120,
+            Ordering::Relaxed,
+        );
+        assert!(!heartbeat.is_alive());
+    }
+}
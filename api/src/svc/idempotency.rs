@@ -0,0 +1,151 @@
+//! Idempotency service
+
+use time::OffsetDateTime;
+
+use crate::{config::IdempotencyConfig, db::Db, error::Error, mdl::IdempotentResponse};
+
+/// Idempotency service
+#[derive(Debug, Clone)]
+pub struct IdempotencyService {
+    /// Data store
+    pub db: Db,
+    /// Period during which a response is replayed
+    pub ttl: time::Duration,
+    /// Period after which a reserved key is considered lost
+    pub lock: time::Duration,
+}
+
+impl IdempotencyService {
+    /// Creates a new service instance
+    pub fn new(db: Db, cfg: IdempotencyConfig) -> Self {
+        Self {
+            db,
+            ttl: time::Duration::seconds(cfg.ttl as i64),
+            lock: time::Duration::seconds(cfg.lock as i64),
+        }
+    }
+}
+
+impl IdempotencyService {
+    /// Returns the stored response for a key, if it has not expired
+    pub async fn get(&self, key: &str, scope: &str) -> Result<Option<IdempotentResponse>, Error> {
+        let since = OffsetDateTime::now_utc() - self.ttl;
+        self.db.read_idempotent_response(key, scope, since).await
+    }
+
+    /// Reserves a key before running its request
+    ///
+    /// Returns `None` if the key has been reserved, so that the request can run, or the
+    /// stored response to replay. An [Error::Conflict] is returned if the request of the key
+    /// is still running, and an [Error::Unprocessable] if the key has been used with another
+    /// request body.
+    pub async fn reserve(
+        &self,
+        key: &str,
+        scope: &str,
+        request_hash: &str,
+    ) -> Result<Option<IdempotentResponse>, Error> {
+        let now = OffsetDateTime::now_utc();
+        let pending = IdempotentResponse {
+            key: key.to_string(),
+            scope: scope.to_string(),
+            status: 0,
+            content_type: None,
+            body: vec![],
+            created_at: now,
+            request_hash: request_hash.to_string(),
+            completed: false,
+        };
+        if self
+            .db
+            .reserve_idempotency_key(&pending, now - self.ttl, now - self.lock)
+            .await?
+        {
+            return Ok(None);
+        }
+
+        match self.get(key, scope).await? {
+            // NB: the responses stored before the requests were hashed have no hash
+            Some(stored)
+                if !stored.request_hash.is_empty() && stored.request_hash != request_hash =>
+            {
+                Err(Error::Unprocessable(
+                    "idempotency key already used with another request".to_string(),
+                    None,
+                ))
+            }
+            Some(stored) if stored.completed => Ok(Some(stored)),
+            // NB: the key may also have been released meanwhile, so the request can be retried
+            _ => Err(Error::Conflict(
+                "a request with this idempotency key is in progress".to_string(),
+                None,
+            )),
+        }
+    }
+
+    /// Releases a reserved key, so that its request can be retried
+    pub async fn release(&self, key: &str, scope: &str) -> Result<(), Error> {
+        self.db.delete_idempotent_response(key, scope).await
+    }
+
+    /// Stores the response for a reserved key
+    pub async fn save(
+        &self,
+        key: &str,
+        scope: &str,
+        request_hash: &str,
+        status: u16,
+        content_type: Option<String>,
+        body: Vec<u8>,
+    ) -> Result<(), Error> {
+        self.db
+            .upsert_idempotent_response(&IdempotentResponse {
+                key: key.to_string(),
+                scope: scope.to_string(),
+                status,
+                content_type,
+                body,
+                created_at: OffsetDateTime::now_utc(),
+                request_hash: request_hash.to_string(),
+                completed: true,
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::db::sqlite::SqliteClient;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_reserve() {
+        let db = SqliteClient::open(":memory:").unwrap();
+        db.init_schema().await.unwrap();
+        let service = IdempotencyService::new(Arc::new(db), IdempotencyConfig::default());
+        let scope = "POST /summaries";
+
+        assert!(service.reserve("key", scope, "a").await.unwrap().is_none());
+        assert!(matches!(
+            service.reserve("key", scope, "a").await,
+            Err(Error::Conflict(_, _))
+        ));
+
+        service
+            .save("key", scope, "a", 200, None, b"{}".to_vec())
+            .await
+            .unwrap();
+        let stored = service.reserve("key", scope, "a").await.unwrap().unwrap();
+        assert_eq!(stored.body, b"{}");
+        assert!(matches!(
+            service.reserve("key", scope, "b").await,
+            Err(Error::Unprocessable(_, _))
+        ));
+
+        service.release("key", scope).await.unwrap();
+        assert!(service.reserve("key", scope, "b").await.unwrap().is_none());
+    }
+}
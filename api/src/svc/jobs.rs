@@ -0,0 +1,281 @@
+//! Background jobs service
+//!
+//! The background work is queued in the DB, so that it survives restarts and is shared by the
+//! API replicas. A worker loop claims the due jobs and runs them with the handler registered
+//! for their kind:
+//!
+//! - a failed job is retried with an exponential backoff (`jobs.backoff`)
+//! - a job which fails `jobs.attempts` times is dead-lettered: it is kept with the `dead`
+//!   status, and is not run again
+//! - a job whose worker is lost (eg. the replica crashed) is claimed again once its lease
+//!   (`jobs.lease`) has expired. The worker renews the lease while the job runs, and its
+//!   outcome is only recorded if the lease is still held.
+//! - a completed job is kept with the `done` status, so that its status can be polled, until
+//!   it is deleted by the retention service (`retention.jobs`)
+
+use std::{collections::HashMap, future::Future, sync::Arc, time::Duration};
+
+use futures::future::{join_all, BoxFuture};
+use serde::Serialize;
+use time::OffsetDateTime;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    config::JobsConfig,
+    db::{tenant::with_system, Db},
+    error::Error,
+    mdl::{Job, JobCount},
+    svc::health::Heartbeat,
+};
+
+/// Job handler
+pub type JobHandler = Arc<dyn Fn(Job) -> BoxFuture<'static, Result<(), Error>> + Send + Sync>;
+
+/// Maximum delay before retrying a failed job
+const MAX_BACKOFF: Duration = Duration::from_secs(60 * 60);
+
+/// Background jobs service
+#[derive(Clone)]
+pub struct JobService {
+    /// Data store
+    pub db: Db,
+    /// Jobs configuration
+    pub cfg: JobsConfig,
+    /// Handlers by job kind
+    handlers: HashMap<&'static str, JobHandler>,
+    /// Heartbeat of the worker loop
+    pub heartbeat: Heartbeat,
+}
+
+impl JobService {
+    /// Creates a new service instance
+    pub fn new(db: Db, cfg: JobsConfig) -> Self {
+        // NB: the worker beats between 2 batches, and when it renews the leases of a batch
+        let max_age = Duration::from_secs(cfg.lease) + Duration::from_millis(cfg.interval) * 2;
+        Self {
+            db,
+            cfg,
+            handlers: HashMap::new(),
+            heartbeat: Heartbeat::new(max_age),
+        }
+    }
+
+    /// Registers the handler of a job kind
+    pub fn handler<F, Fut>(mut self, kind: &'static str, f: F) -> Self
+    where
+        F: Fn(Job) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), Error>> + Send + 'static,
+    {
+        self.handlers
+            .insert(kind, Arc::new(move |job| Box::pin(f(job))));
+        self
+    }
+}
+
+impl JobService {
+    /// Enqueues a job, to be run as soon as possible
+    ///
+    /// The owner is the user on behalf of whom the job is run, if any.
+    pub async fn enqueue<T: Serialize>(
+        &self,
+        kind: &str,
+        owner_id: Option<Uuid>,
+        payload: &T,
+    ) -> Result<Job, Error> {
+        let payload = serde_json::to_value(payload).map_err(|err| {
+            Error::Internal("invalid job payload".to_string(), Some(err.to_string()))
+        })?;
+        let mut job = Job::new(kind, payload, self.cfg.attempts);
+        job.owner_id = owner_id;
+        self.db.enqueue_job(&job).await?;
+        Ok(job)
+    }
+
+    /// Claims and runs a batch of due jobs
+    ///
+    /// Returns the number of jobs which have been run.
+    pub async fn run_once(&self) -> Result<usize, Error> {
+        let kinds = self.handlers.keys().copied().collect::<Vec<_>>();
+        let locked_until = OffsetDateTime::now_utc() + self.lease();
+        let jobs = self
+            .db
+            .claim_jobs(&kinds, self.cfg.batch, locked_until)
+            .await?;
+
+        let count = jobs.len();
+        join_all(jobs.into_iter().map(|job| self.run_job(job))).await;
+        Ok(count)
+    }
+
+    /// Runs a claimed job, and records its outcome
+    ///
+    /// The lease of the job is renewed every third of its period while the handler runs.
+    async fn run_job(&self, job: Job) {
+        let Some(handler) = self.handlers.get(job.kind.as_str()) else {
+            // NB: only the jobs with a handler are claimed
+            return;
+        };
+        let Some(lease_id) = job.lease_id else {
+            // NB: the claimed jobs have a lease
+            return;
+        };
+        let (id, kind, attempts, max_attempts) =
+            (job.id, job.kind.clone(), job.attempts, job.max_attempts);
+
+        let period = (Duration::from_secs(self.cfg.lease) / 3).max(Duration::from_secs(1));
+        let mut renewals = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+        let run = handler(job);
+        tokio::pin!(run);
+        let outcome = loop {
+            tokio::select! {
+                outcome = &mut run => break outcome,
+                _ = renewals.tick() => {
+                    let locked_until = OffsetDateTime::now_utc() + self.lease();
+                    match self.db.renew_job(id, lease_id, locked_until).await {
+                        Ok(()) => self.heartbeat.beat(),
+                        // NB: the job may be claimed again, its outcome will not be recorded
+                        Err(err) => warn!(%id, %kind, %err, "failed to renew the job lease"),
+                    }
+                }
+            }
+        };
+
+        let res = match outcome {
+            Ok(()) => self.db.complete_job(id, lease_id).await,
+            Err(err) if attempts < max_attempts => {
+                let delay = self.backoff(attempts);
+                warn!(%id, %kind, attempts, %err, "job failed, retrying in {delay:?}");
+                let retry_at = OffsetDateTime::now_utc() + delay;
+                self.db
+                    .fail_job(id, lease_id, &err.to_string(), Some(retry_at))
+                    .await
+            }
+            Err(err) => {
+                error!(%id, %kind, attempts, %err, "job failed, dead-lettered");
+                self.db.fail_job(id, lease_id, &err.to_string(), None).await
+            }
+        };
+        // NB: the job is claimed again once its lease expires
+        if let Err(err) = res {
+            warn!(%id, %kind, %err, "failed to record the job outcome");
+        }
+    }
+
+    /// Returns the period of a job lease
+    fn lease(&self) -> time::Duration {
+        time::Duration::seconds(self.cfg.lease as i64)
+    }
+
+    /// Returns the delay before retrying a job which failed after some attempts
+    fn backoff(&self, attempts: i32) -> Duration {
+        let factor = 2_u32.saturating_pow(attempts.max(1) as u32 - 1);
+        Duration::from_secs(self.cfg.backoff)
+            .saturating_mul(factor)
+            .min(MAX_BACKOFF)
+    }
+
+    /// Reads a job
+    pub async fn get(&self, id: Uuid) -> Result<Option<Job>, Error> {
+        self.db.read_job(id).await
+    }
+
+    /// Counts the jobs by status
+    pub async fn stats(&self) -> Result<Vec<JobCount>, Error> {
+        self.db.count_jobs().await
+    }
+
+    /// Starts the worker loop
+    ///
+    /// The queue is polled every interval while it is empty. Returns `None` if the worker
+    /// is disabled (interval set to 0).
+    pub fn start(&self) -> Option<JoinHandle<()>> {
+        if self.cfg.interval == 0 {
+            return None;
+        }
+
+        let service = self.clone();
+        let period = Duration::from_millis(self.cfg.interval);
+        info!(kinds = ?service.handlers.keys().collect::<Vec<_>>(), "starting the job worker");
+        service.heartbeat.beat();
+        // NB: the jobs run in the system scope, they may access the data of any user
+        Some(tokio::spawn(with_system(async move {
+            loop {
+                service.heartbeat.beat();
+                match service.run_once().await {
+                    // NB: more jobs may be due
+                    Ok(count) if count > 0 => continue,
+                    Ok(_) => {}
+                    Err(err) => warn!(%err, "failed to claim jobs"),
+                }
+                tokio::time::sleep(period).await;
+            }
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::{db::sqlite::SqliteClient, mdl::JobStatus};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_jobs() {
+        let db = SqliteClient::open(":memory:").unwrap();
+        db.init_schema().await.unwrap();
+        let cfg = JobsConfig {
+            attempts: 2,
+            backoff: 0,
+            ..Default::default()
+        };
+
+        // NB: the job fails on its first attempt
+        let calls = Arc::new(AtomicUsize::new(0));
+        let service = JobService::new(Arc::new(db), cfg).handler("flaky", {
+            let calls = calls.clone();
+            move |_job| {
+                let n = calls.fetch_add(1, Ordering::Relaxed);
+                async move {
+                    if n == 0 {
+                        Err(Error::Internal("failed".to_string(), None))
+                    } else {
+                        Ok(())
+                    }
+                }
+            }
+        });
+        // NB: no handler is registered for this kind yet, so it is not claimed
+        service.enqueue("failing", None, &()).await.unwrap();
+        let flaky = service.enqueue("flaky", None, &vec!["a"]).await.unwrap();
+        assert_eq!(service.run_once().await.unwrap(), 1);
+        assert_eq!(service.run_once().await.unwrap(), 1);
+        assert_eq!(service.run_once().await.unwrap(), 0);
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+        let flaky = service.get(flaky.id).await.unwrap().unwrap();
+        assert_eq!(flaky.status, JobStatus::Done);
+
+        let service = service.handler("failing", |_job| async {
+            Err(Error::Internal("failed".to_string(), None))
+        });
+        assert_eq!(service.run_once().await.unwrap(), 1);
+        assert_eq!(service.run_once().await.unwrap(), 1);
+        assert_eq!(service.run_once().await.unwrap(), 0);
+        let stats = service.stats().await.unwrap();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].status, JobStatus::Dead);
+        assert_eq!(stats[1].status, JobStatus::Done);
+    }
+
+    #[test]
+    fn test_backoff() {
+        let db = SqliteClient::open(":memory:").unwrap();
+        let service = JobService::new(Arc::new(db), JobsConfig::default());
+        assert_eq!(service.backoff(1), Duration::from_secs(10));
+        assert_eq!(service.backoff(3), Duration::from_secs(40));
+        assert_eq!(service.backoff(30), MAX_BACKOFF);
+    }
+}
@@ -0,0 +1,81 @@
+//! Maintenance service
+//!
+//! The vector index is approximate, and its recall may degrade (eg. after bulk inserts), which
+//! silently degrades the semantic search. Its recall is measured on a sample of summaries, and
+//! the index can be rebuilt.
+
+use tracing::{info, warn};
+
+use crate::{db::Db, error::Error, mdl::VectorIndexReport};
+
+/// Recall below which the vector index is considered degraded
+pub const MIN_RECALL: f64 = 0.9;
+
+/// Maintenance service
+#[derive(Debug, Clone)]
+pub struct MaintenanceService {
+    /// Data store
+    pub db: Db,
+}
+
+impl MaintenanceService {
+    /// Creates a new service instance
+    pub fn new(db: Db) -> Self {
+        Self { db }
+    }
+}
+
+impl MaintenanceService {
+    /// Measures the recall of the vector index on a sample of summaries
+    ///
+    /// A recall below [MIN_RECALL] is logged.
+    pub async fn check_vector_index(
+        &self,
+        sample: usize,
+        k: usize,
+    ) -> Result<VectorIndexReport, Error> {
+        let report = self.db.check_vector_index(sample, k).await?;
+        if report.recall < MIN_RECALL {
+            warn!(
+                recall = report.recall,
+                min_recall = report.min_recall,
+                sample = report.sample,
+                "vector index recall is degraded"
+            );
+        }
+        Ok(report)
+    }
+
+    /// Rebuilds the vector index, and measures its recall again
+    pub async fn reindex_vectors(
+        &self,
+        sample: usize,
+        k: usize,
+    ) -> Result<VectorIndexReport, Error> {
+        info!("rebuilding the vector index");
+        self.db.reindex_vectors().await?;
+        self.check_vector_index(sample, k).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::db::sqlite::SqliteClient;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_check_vector_index() {
+        let db = SqliteClient::open(":memory:").unwrap();
+        db.init_schema().await.unwrap();
+        let service = MaintenanceService::new(Arc::new(db));
+
+        let report = service.check_vector_index(10, 5).await.unwrap();
+        assert!(!report.indexed);
+        assert_eq!(report.recall, 1.0);
+        let report = service.reindex_vectors(10, 5).await.unwrap();
+        assert_eq!(report.k, 5);
+    }
+}
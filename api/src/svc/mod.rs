@@ -2,4 +2,14 @@
 
 pub mod art;
 pub mod auth;
+pub mod backup;
+#[cfg(feature = "export")]
+pub mod export;
 pub mod feed;
+pub mod health;
+pub mod idempotency;
+pub mod jobs;
+pub mod maintenance;
+pub mod retention;
+pub mod seed;
+pub mod stats;
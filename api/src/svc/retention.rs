@@ -0,0 +1,237 @@
+//! Data retention service
+//!
+//! A background job periodically deletes the data which is past its retention period:
+//!
+//! - the article summaries created before the `retention.articles` period
+//! - the summaries which have not been accessed during the `retention.summaries` period
+//! - the expired idempotency keys (see [crate::config::IdempotencyConfig])
+//! - the completed jobs created before the `retention.jobs` period
+//!
+//! NB: the authentication is stateless (JWT), so there are no sessions to expire.
+
+use std::{
+    sync::{
+        atomic::{AtomicI64, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use time::OffsetDateTime;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+pub use crate::mdl::report::{CleanupReport, RetentionStats};
+use crate::{
+    config::RetentionConfig,
+    db::{tenant::with_system, Db},
+    error::Error,
+    svc::health::Heartbeat,
+};
+
+/// Data retention service
+#[derive(Debug, Clone)]
+pub struct RetentionService {
+    /// Data store
+    pub db: Db,
+    /// Retention configuration
+    pub cfg: RetentionConfig,
+    /// Period during which the idempotency keys are kept
+    pub idempotency_ttl: time::Duration,
+    /// Cumulated metrics
+    metrics: Arc<RetentionMetrics>,
+    /// Heartbeat of the cleanup job
+    pub heartbeat: Heartbeat,
+}
+
+impl RetentionService {
+    /// Creates a new service instance
+    pub fn new(db: Db, cfg: RetentionConfig, idempotency_ttl_secs: u64) -> Self {
+        // NB: the job beats after each cleanup, which may take a while
+        let heartbeat = Heartbeat::new(Duration::from_secs(cfg.interval.saturating_mul(2)));
+        Self {
+            db,
+            cfg,
+            idempotency_ttl: time::Duration::seconds(idempotency_ttl_secs as i64),
+            metrics: Arc::new(RetentionMetrics::default()),
+            heartbeat,
+        }
+    }
+}
+
+/// Cumulated metrics of the cleanups (since the start of the service)
+#[derive(Debug, Default)]
+struct RetentionMetrics {
+    /// Number of cleanups
+    runs: AtomicU64,
+    /// Number of failed cleanups
+    failures: AtomicU64,
+    /// Number of deleted articles
+    articles: AtomicU64,
+    /// Number of deleted summaries
+    summaries: AtomicU64,
+    /// Number of deleted idempotency keys
+    idempotency_keys: AtomicU64,
+    /// Number of deleted jobs
+    jobs: AtomicU64,
+    /// Time of the last cleanup (unix timestamp, 0 if none)
+    last_run: AtomicI64,
+}
+
+impl RetentionService {
+    /// Deletes the data past its retention period
+    pub async fn cleanup(&self) -> Result<CleanupReport, Error> {
+        let now = OffsetDateTime::now_utc();
+        let mut report = CleanupReport::default();
+
+        if self.cfg.articles > 0 {
+            let before = now - time::Duration::days(self.cfg.articles as i64);
+            report.articles = self.db.delete_summaries_created_before(before).await?;
+        }
+        if self.cfg.summaries > 0 {
+            let before = now - time::Duration::days(self.cfg.summaries as i64);
+            report.summaries = self.db.delete_summaries_accessed_before(before).await?;
+        }
+        report.idempotency_keys = self
+            .db
+            .delete_idempotent_responses_before(now - self.idempotency_ttl)
+            .await?;
+        if self.cfg.jobs > 0 {
+            let before = now - time::Duration::days(self.cfg.jobs as i64);
+            report.jobs = self.db.delete_jobs_done_before(before).await?;
+        }
+
+        Ok(report)
+    }
+
+    /// Runs a cleanup and records its metrics
+    pub async fn run(&self) -> Result<CleanupReport, Error> {
+        let metrics = &self.metrics;
+        metrics.runs.fetch_add(1, Ordering::Relaxed);
+        metrics.last_run.store(
+            OffsetDateTime::now_utc().unix_timestamp(),
+            Ordering::Relaxed,
+        );
+
+        match self.cleanup().await {
+            Ok(report) => {
+                metrics
+                    .articles
+                    .fetch_add(report.articles, Ordering::Relaxed);
+                metrics
+                    .summaries
+                    .fetch_add(report.summaries, Ordering::Relaxed);
+                metrics
+                    .idempotency_keys
+                    .fetch_add(report.idempotency_keys, Ordering::Relaxed);
+                metrics.jobs.fetch_add(report.jobs, Ordering::Relaxed);
+                info!(
+                    articles = report.articles,
+                    summaries = report.summaries,
+                    idempotency_keys = report.idempotency_keys,
+                    jobs = report.jobs,
+                    "retention cleanup"
+                );
+                Ok(report)
+            }
+            Err(err) => {
+                metrics.failures.fetch_add(1, Ordering::Relaxed);
+                warn!(%err, "retention cleanup failed");
+                Err(err)
+            }
+        }
+    }
+
+    /// Returns the cumulated metrics
+    pub fn stats(&self) -> RetentionStats {
+        let metrics = &self.metrics;
+        let last_run = metrics.last_run.load(Ordering::Relaxed);
+        RetentionStats {
+            runs: metrics.runs.load(Ordering::Relaxed),
+            failures: metrics.failures.load(Ordering::Relaxed),
+            deleted: CleanupReport {
+                articles: metrics.articles.load(Ordering::Relaxed),
+                summaries: metrics.summaries.load(Ordering::Relaxed),
+                idempotency_keys: metrics.idempotency_keys.load(Ordering::Relaxed),
+                jobs: metrics.jobs.load(Ordering::Relaxed),
+            },
+            last_run: (last_run > 0).then_some(last_run),
+        }
+    }
+
+    /// Starts the cleanup job
+    ///
+    /// The first cleanup runs after one interval. Returns `None` if the job is disabled
+    /// (interval set to 0).
+    pub fn start(&self) -> Option<JoinHandle<()>> {
+        if self.cfg.interval == 0 {
+            return None;
+        }
+
+        let service = self.clone();
+        let period = Duration::from_secs(self.cfg.interval);
+        service.heartbeat.beat();
+        // NB: the cleanup runs in the system scope, it may access the data of any user
+        Some(tokio::spawn(with_system(async move {
+            let mut interval =
+                tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+            // NB: a slow cleanup must not trigger a burst of cleanups
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                // NB: the error is already logged, the next run will retry
+                let _res = service.run().await;
+                service.heartbeat.beat();
+            }
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::{db::sqlite::SqliteClient, mdl::IdempotentResponse};
+
+    use super::*;
+
+    /// Setup a test with an in-memory store
+    async fn setup() -> RetentionService {
+        let db = SqliteClient::open(":memory:").unwrap();
+        db.init_schema().await.unwrap();
+        RetentionService::new(Arc::new(db), RetentionConfig::default(), 60)
+    }
+
+    #[tokio::test]
+    async fn test_cleanup() {
+        let service = setup().await;
+        let now = OffsetDateTime::now_utc();
+        for (key, created_at) in [("old", now - time::Duration::hours(1)), ("new", now)] {
+            service
+                .db
+                .upsert_idempotent_response(&IdempotentResponse {
+                    key: key.to_string(),
+                    scope: Uuid::new_v4().to_string(),
+                    status: 200,
+                    content_type: None,
+                    body: b"{}".to_vec(),
+                    created_at,
+                    request_hash: String::new(),
+                    completed: true,
+                })
+                .await
+                .unwrap();
+        }
+
+        let report = service.run().await.unwrap();
+        assert_eq!(report.idempotency_keys, 1);
+        assert_eq!(report.summaries, 0);
+        assert_eq!(report.jobs, 0);
+
+        let stats = service.stats();
+        assert_eq!(stats.runs, 1);
+        assert_eq!(stats.failures, 0);
+        assert_eq!(stats.deleted.idempotency_keys, 1);
+        assert!(stats.last_run.is_some());
+    }
+}
@@ -0,0 +1,221 @@
+//! Demo data
+//!
+//! The demo data lets the API be explored without any manual setup (see the `--seed` option
+//! of the server). Seeding is idempotent: the demo users which already exist are skipped.
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    db::{tenant::with_system, Db},
+    error::Error,
+    mdl::{FeedUpdate, NewUser, Summary},
+};
+
+use super::auth::AuthService;
+
+/// Password of the demo users
+pub const DEMO_PASSWORD: &str = "newsie-demo";
+
+/// Demo users (name, email)
+const DEMO_USERS: &[(&str, &str)] = &[
+    ("Demo User", "demo@newsie.rocks"),
+    ("Ada Lovelace", "ada@newsie.rocks"),
+];
+
+/// Curated feeds (name, URL)
+const DEMO_FEEDS: &[(&str, &str)] = &[
+    ("Google AI blog", "https://ai.googleblog.com/atom.xml"),
+    ("Hacker News", "https://news.ycombinator.com/rss"),
+    ("Rust blog", "https://blog.rust-lang.org/feed.xml"),
+    ("This Week in Rust", "https://this-week-in-rust.org/rss.xml"),
+    ("The Verge", "https://www.theverge.com/rss/index.xml"),
+];
+
+/// Sample summaries (URL, summary, keywords)
+const DEMO_SUMMARIES: &[(&str, &str, &[&str])] = &[
+    (
+        "https://blog.rust-lang.org/2023/06/01/Rust-1.70.0.html",
+        "Rust 1.70 enables the sparse protocol for crates.io by default, which speeds up \
+        the index updates, and stabilizes OnceCell and OnceLock.",
+        &["rust", "release", "cargo"],
+    ),
+    (
+        "https://ai.googleblog.com/2023/05/palm-2.html",
+        "PaLM 2 is a language model with improved multilingual, reasoning and coding \
+        capabilities, trained on a more diverse dataset.",
+        &["ai", "language model", "google"],
+    ),
+    (
+        "https://www.postgresql.org/about/news/postgresql-16-released-2715/",
+        "PostgreSQL 16 improves the query parallelism, adds logical replication from \
+        standby servers and brings new monitoring views.",
+        &["postgresql", "database", "release"],
+    ),
+];
+
+/// Dimension of the summary embeddings
+const EMBEDDINGS_DIM: usize = 1536;
+
+/// Seeding report
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SeedReport {
+    /// Number of created users
+    pub users: usize,
+    /// Number of created feeds
+    pub feeds: usize,
+    /// Number of inserted summaries
+    pub summaries: usize,
+}
+
+/// Creates the demo data
+///
+/// The demo users are created with the [DEMO_PASSWORD] password and the curated feeds.
+/// The sample summaries have synthetic embeddings (the OpenAI API is not called).
+///
+/// NB: the seed runs in the system scope, so that the existing demo users are visible with
+/// the row-level security.
+pub async fn seed(db: Db) -> Result<SeedReport, Error> {
+    with_system(seed_data(db)).await
+}
+
+/// Creates the demo data, in the current tenant scope
+async fn seed_data(db: Db) -> Result<SeedReport, Error> {
+    let auth = AuthService::new(db.clone(), String::new());
+    let mut report = SeedReport::default();
+
+    for (name, email) in DEMO_USERS {
+        if db.read_user_with_email(email).await?.is_some() {
+            continue;
+        }
+        let user = auth
+            .create_user(NewUser {
+                name: name.to_string(),
+                email: email.to_string(),
+                password: DEMO_PASSWORD.to_string(),
+            })
+            .await?;
+        report.users += 1;
+
+        let feeds = DEMO_FEEDS
+            .iter()
+            .map(|(name, url)| FeedUpdate {
+                id: None,
+                url: url.to_string(),
+                name: Some(name.to_string()),
+            })
+            .collect::<Vec<_>>();
+        report.feeds += db.sync_user_feeds(user.id, feeds).await?.len();
+    }
+
+    let urls = DEMO_SUMMARIES
+        .iter()
+        .map(|(url, _, _)| *url)
+        .collect::<Vec<_>>();
+    let existing = db.search_summaries_by_urls(&urls).await?;
+    let summaries = DEMO_SUMMARIES
+        .iter()
+        .filter(|(url, _, _)| !existing.iter().any(|s| s.url == *url))
+        .map(|(url, summary, keywords)| Summary {
+            id: Uuid::new_v4(),
+            url: url.to_string(),
+            summary: summary.to_string(),
+            keywords: keywords.iter().map(|k| k.to_string()).collect(),
+            embeddings: synthetic_embeddings(keywords).into(),
+        })
+        .collect::<Vec<_>>();
+    if !summaries.is_empty() {
+        report.summaries = db.insert_summaries(summaries).await?.len();
+    }
+
+    Ok(report)
+}
+
+/// Generates normalized embeddings from keywords
+///
+/// Each keyword sets a few dimensions, so that summaries sharing keywords are close.
+fn synthetic_embeddings(keywords: &[&str]) -> Vec<f32> {
+    let mut embeddings = vec![0.0_f32; EMBEDDINGS_DIM];
+    for keyword in keywords {
+        for i in 0..8_u64 {
+            let mut hasher = DefaultHasher::new();
+            (keyword, i).hash(&mut hasher);
+            embeddings[hasher.finish() as usize % EMBEDDINGS_DIM] += 1.0;
+        }
+    }
+    let norm = embeddings.iter().map(|v| v * v).sum::<f32>().sqrt();
+    embeddings.iter().map(|v| v / norm).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{
+        config::AppConfig,
+        db::{postgres::PostgresClient, sqlite::SqliteClient},
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_seed() {
+        let db = SqliteClient::open(":memory:").unwrap();
+        db.init_schema().await.unwrap();
+        let db: Db = Arc::new(db);
+
+        let report = seed(db.clone()).await.unwrap();
+        assert_eq!(report.users, DEMO_USERS.len());
+        assert_eq!(report.feeds, DEMO_USERS.len() * DEMO_FEEDS.len());
+        assert_eq!(report.summaries, DEMO_SUMMARIES.len());
+
+        // NB: seeding again is a no-op
+        let report = seed(db.clone()).await.unwrap();
+        assert_eq!(report.users, 0);
+        assert_eq!(report.summaries, 0);
+
+        let user = db
+            .read_user_with_email(DEMO_USERS[0].1)
+            .await
+            .unwrap()
+            .unwrap();
+        let auth = AuthService::new(db, String::new());
+        assert!(auth.login(&user.email, DEMO_PASSWORD).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_seed_rls() {
+        // NB: the tables are created in their own schema, so that the row-level security is
+        // not enforced on the tables of the other tests
+        let cfg = AppConfig::load();
+        let admin = cfg.postgres.new_pool().get().await.unwrap();
+        admin
+            .batch_execute("CREATE SCHEMA IF NOT EXISTS seed_rls")
+            .await
+            .unwrap();
+        let mut pg = cfg.postgres.clone();
+        let sep = if pg.url.contains('?') { '&' } else { '?' };
+        pg.url = format!(
+            "{}{sep}options=-c%20search_path%3Dseed_rls%2Cpublic",
+            pg.url
+        );
+        let db = PostgresClient::new(pg.new_pool()).rls(true);
+        db.init_schema().await.unwrap();
+        let db: Db = Arc::new(db);
+
+        seed(db.clone()).await.unwrap();
+        let report = seed(db.clone()).await.unwrap();
+        assert_eq!(report.users, 0);
+        assert_eq!(report.summaries, 0);
+
+        let user = with_system(db.read_user_with_email(DEMO_USERS[0].1))
+            .await
+            .unwrap();
+        assert!(user.is_some());
+    }
+}
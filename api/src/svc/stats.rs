@@ -0,0 +1,88 @@
+//! Statistics service
+
+use std::sync::{atomic::Ordering, Arc};
+
+use time::OffsetDateTime;
+
+pub use crate::mdl::report::{CacheStats, InstanceStats, Metrics};
+use crate::{db::Db, error::Error};
+
+use super::art::CacheMetrics;
+
+/// Statistics service
+#[derive(Debug, Clone)]
+pub struct StatsService {
+    /// Data store
+    pub db: Db,
+    /// Summary cache metrics (shared with the article service)
+    pub cache: Arc<CacheMetrics>,
+}
+
+impl StatsService {
+    /// Creates a new service instance
+    pub fn new(db: Db, cache: Arc<CacheMetrics>) -> Self {
+        Self { db, cache }
+    }
+}
+
+impl StatsService {
+    /// Returns the service metrics
+    pub fn metrics(&self) -> Metrics {
+        Metrics {
+            db: self.db.metrics(),
+            cache: self.cache_stats(),
+        }
+    }
+
+    /// Computes the instance statistics
+    ///
+    /// The summaries are counted over the last `days` days.
+    pub async fn stats(&self, days: u32) -> Result<InstanceStats, Error> {
+        let since = OffsetDateTime::now_utc() - time::Duration::days(days as i64);
+
+        Ok(InstanceStats {
+            users: self.db.count_users().await?,
+            feeds_per_user: self.db.count_users_by_feeds().await?,
+            summaries_per_day: self.db.count_summaries_by_day(since).await?,
+            cache: self.cache_stats(),
+        })
+    }
+
+    /// Returns the summary cache statistics
+    pub fn cache_stats(&self) -> CacheStats {
+        let hits = self.cache.hits.load(Ordering::Relaxed);
+        let misses = self.cache.misses.load(Ordering::Relaxed);
+        let total = hits + misses;
+        CacheStats {
+            hits,
+            misses,
+            hit_rate: if total > 0 {
+                hits as f64 / total as f64
+            } else {
+                0.0
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::sqlite::SqliteClient;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_stats() {
+        let db = SqliteClient::open(":memory:").unwrap();
+        db.init_schema().await.unwrap();
+        let service = StatsService::new(Arc::new(db), Arc::new(CacheMetrics::default()));
+        service.cache.hits.fetch_add(3, Ordering::Relaxed);
+        service.cache.misses.fetch_add(1, Ordering::Relaxed);
+
+        let stats = service.stats(30).await.unwrap();
+        assert_eq!(stats.users, 0);
+        assert!(stats.feeds_per_user.is_empty());
+        assert_eq!(stats.cache.hit_rate, 0.75);
+        assert!(service.metrics().db.is_none());
+    }
+}
@@ -1,24 +1,71 @@
 //! Commands
 
+use std::{collections::HashMap, path::PathBuf, time::Duration};
+
 use anyhow::Error;
-use clap::{Parser, Subcommand};
-use inquire::{Password, Text};
-use newsie_client::NewUser;
+use atom_syndication::FixedDateTime;
+use chrono::{Local, Utc};
+use clap::{ArgAction, CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use colored::Colorize;
+use comfy_table::{Attribute, Cell, Color};
+use futures::{stream, StreamExt};
+use inquire::{Confirm, MultiSelect, Password, Select, Text};
+use newsie_client::{profile::Profile, summary::ArticleSummary, NewUser, User, UserUpdate};
+use serde_json::json;
 
 use crate::{
-    model::Feed,
-    svc::Service,
-    util::{info, success, ResultExt},
+    daemon::{self, Definition},
+    digest::{self, parse_duration, Entry, Format},
+    export::{self, Exporter},
+    import,
+    model::{Article, Feed, FeedHealth},
+    opml::to_opml,
+    svc::{Service, ServiceOptions},
+    trace::{default_log_file, init_tracer, level},
+    tui,
+    util::{
+        html_to_text, info, page, print_json, progress_bar, report_failures, set_plain, set_quiet,
+        spinner, success, table, warn, ResultExt,
+    },
+    watch::{self, QuietHours, WatchOptions},
 };
 
 /// Runs the program
 pub async fn run() -> Result<(), Error> {
     let args = MainArgs::parse();
+    let opts = ServiceOptions {
+        profile: args.profile,
+        offline: args.offline,
+    };
+    let json = args.json;
+    set_plain(args.plain);
+    set_quiet(args.quiet);
+    let log_file = args.log_file.map(|p| p.unwrap_or_else(default_log_file));
+    init_tracer(level(args.verbose, args.quiet), log_file)?;
     match args.commands {
-        MainCommands::Config(args) => run_config_cmd(args).await,
-        MainCommands::Auth(args) => run_auth_cmd(args).await,
-        MainCommands::Feeds(args) => run_feeds_cmd(args).await,
-        MainCommands::Read => run_read_cmd().await,
+        MainCommands::Config(args) => run_config_cmd(args, &opts, json).await,
+        MainCommands::Auth(args) => run_auth_cmd(args, &opts, json).await,
+        MainCommands::Feeds(args) => run_feeds_cmd(args, &opts, json).await,
+        MainCommands::Folders(args) => run_folders_cmd(args, &opts, json),
+        MainCommands::Read { unread, summarize } => {
+            run_read_cmd(unread, summarize, &opts, json).await
+        }
+        MainCommands::MarkRead { feed } => run_mark_read_cmd(&feed, &opts, json).await,
+        MainCommands::Open { article } => run_open_cmd(&article, &opts, json),
+        MainCommands::Star { url, remove } => run_star_cmd(&url, remove, &opts, json),
+        MainCommands::Starred => run_starred_cmd(&opts, json),
+        MainCommands::Search(args) => run_search_cmd(args, &opts, json).await,
+        MainCommands::Summarize(args) => run_summarize_cmd(args, &opts, json).await,
+        MainCommands::Digest(args) => run_digest_cmd(args, &opts, json).await,
+        MainCommands::Export(args) => run_export_cmd(args, &opts, json).await,
+        MainCommands::Sync => run_sync_cmd(&opts, json).await,
+        MainCommands::Stats(args) => run_stats_cmd(args, &opts, json).await,
+        MainCommands::Fetch(args) => run_fetch_cmd(args, &opts, json).await,
+        MainCommands::Tui => tui::run(&Service::new(&opts)?).await,
+        MainCommands::Watch(args) => run_watch_cmd(args, &opts).await,
+        MainCommands::Service(args) => run_service_cmd(args, &opts, json),
+        MainCommands::Completions { shell } => run_completions_cmd(shell),
         // MainCommands::Subsc(args) => subsc::run(args).await,
         // MainCommands::Feeds(args) => feed::run(args).await,
     }
@@ -29,6 +76,27 @@ pub async fn run() -> Result<(), Error> {
 #[command(author, version, about, long_about = None)]
 #[command(propagate_version = true)]
 pub struct MainArgs {
+    /// Server profile (defined in the profiles file, the default one is used if not set)
+    #[arg(long, global = true, env = "NEWSIE_PROFILE")]
+    pub profile: Option<String>,
+    /// Use the local cache only (the cache is also used if a feed is unreachable)
+    #[arg(long, global = true)]
+    pub offline: bool,
+    /// Print the output as JSON (the interactive prompts are skipped when listing articles)
+    #[arg(long, global = true)]
+    pub json: bool,
+    /// Print plain lists, without colors nor table borders (as with `NO_COLOR`)
+    #[arg(long, global = true)]
+    pub plain: bool,
+    /// Trace the diagnostics (-v for info, -vv for debug, -vvv for trace)
+    #[arg(short, long, global = true, action = ArgAction::Count, conflicts_with = "quiet")]
+    pub verbose: u8,
+    /// Only print the errors
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+    /// Trace to a log file (in the data dir if no path is set), eg. to report a bug
+    #[arg(long, global = true, value_name = "PATH")]
+    pub log_file: Option<Option<PathBuf>>,
     #[command(subcommand)]
     pub commands: MainCommands,
 }
@@ -42,8 +110,63 @@ pub enum MainCommands {
     Auth(AuthArgs),
     /// Feeds commands
     Feeds(FeedsArgs),
+    /// Folders commands
+    Folders(FoldersArgs),
     /// Read the articles
-    Read,
+    Read {
+        /// Only the unread articles
+        #[arg(long)]
+        unread: bool,
+        /// Show the summary of an article above its content (the summaries are cached)
+        #[arg(long)]
+        summarize: bool,
+    },
+    /// Open an article in the browser (and mark it as read)
+    Open {
+        /// Article number (in the last read or search listing) or url
+        article: String,
+    },
+    /// Mark all the articles of a feed as read
+    MarkRead {
+        /// Feed url or name
+        feed: String,
+    },
+    /// Star an article
+    Star {
+        /// Article url
+        url: String,
+        /// Remove the star
+        #[arg(long, short)]
+        remove: bool,
+    },
+    /// List the starred articles
+    Starred,
+    /// Search the cached articles
+    Search(SearchArgs),
+    /// Summarize articles
+    Summarize(SummarizeArgs),
+    /// Compile a digest of the unread articles, with their summaries
+    Digest(DigestArgs),
+    /// Export the cached articles to files (eg. to a notes folder)
+    Export(ExportArgs),
+    /// Sync the feeds with the server
+    Sync,
+    /// Show the reading stats, and the summaries usage
+    Stats(StatsArgs),
+    /// Download the contents of the unread articles, to read them offline
+    Fetch(FetchArgs),
+    /// Read the articles in a full-screen UI
+    Tui,
+    /// Watch the feeds, with desktop notifications of the new articles
+    Watch(WatchArgs),
+    /// Run the watch mode (or sync) as a background user service
+    Service(ServiceArgs),
+    /// Print the shell completion script
+    Completions {
+        /// Shell
+        #[arg(value_enum)]
+        shell: Shell,
+    },
 }
 
 /// Configuration commands
@@ -62,28 +185,176 @@ pub enum ConfigCommands {
     Show,
     /// Updates the CLI configuration
     Update,
+    /// Server profiles commands
+    Profile(ProfileArgs),
+}
+
+/// Server profiles arguments
+#[derive(Parser)]
+pub struct ProfileArgs {
+    #[command(subcommand)]
+    commands: ProfileCommands,
+}
+
+/// Server profiles commands
+#[derive(Subcommand)]
+pub enum ProfileCommands {
+    /// Lists the profiles
+    Ls,
+    /// Adds (or replaces) a profile
+    Add {
+        /// Profile name
+        name: String,
+        /// API url
+        url: String,
+    },
+    /// Removes a profile
+    Rm {
+        /// Profile name
+        name: String,
+    },
+    /// Sets the default profile (the local configuration is used if not set)
+    Use {
+        /// Profile name
+        name: Option<String>,
+    },
 }
 
 /// Runs the config commands
-async fn run_config_cmd(args: ConfigArgs) -> Result<(), Error> {
-    let service = Service::new()?;
+///
+/// With an active profile, the configuration of the profile is shown and updated.
+async fn run_config_cmd(args: ConfigArgs, opts: &ServiceOptions, json: bool) -> Result<(), Error> {
+    let service = Service::new(opts)?;
     let mut config = service.get_config()?;
+    let mut profiles = service.get_profiles()?;
     match args.commands {
         ConfigCommands::Show => {
+            let token = service.get_token()?;
+            if json {
+                let api_url = match service.active_profile() {
+                    Some(name) => &profiles.profiles[name].url,
+                    None => &config.api_url,
+                };
+                print_json(&json!({
+                    "profile": service.active_profile(),
+                    "api_url": api_url,
+                    "token": token,
+                    "proxy": config.proxy,
+                }))?;
+                return Ok(());
+            }
             println!("Configuration:");
-            println!("  - API url: {}", config.api_url);
-            println!("  - token: {}", config.token.unwrap_or("none".to_string()));
+            match service.active_profile() {
+                Some(name) => {
+                    let profile = &profiles.profiles[name];
+                    println!("  - profile: {name}");
+                    println!("  - API url: {}", profile.url);
+                    println!("  - token: {}", token.as_deref().unwrap_or("none"));
+                }
+                None => {
+                    println!("  - API url: {}", config.api_url);
+                    println!("  - token: {}", token.as_deref().unwrap_or("none"));
+                }
+            }
+            println!("  - proxy: {}", config.proxy.as_deref().unwrap_or("none"));
         }
         ConfigCommands::Update => {
             info("Update the configuration values");
-            let api_url = Text::new("API url:")
-                .with_initial_value(&config.api_url)
+            match service.active_profile() {
+                Some(name) => {
+                    let profile = profiles.profiles.get_mut(name).unwrap();
+                    profile.url = Text::new("API url:")
+                        .with_initial_value(&profile.url)
+                        .prompt()
+                        .unwrap_or_exit();
+                    service.save_profiles(&profiles)?;
+                }
+                None => {
+                    let api_url = Text::new("API url:")
+                        .with_initial_value(&config.api_url)
+                        .prompt()
+                        .unwrap_or_exit();
+                    config.api_url = api_url;
+                }
+            }
+            let proxy = Text::new("HTTP proxy (empty for none):")
+                .with_initial_value(config.proxy.as_deref().unwrap_or_default())
                 .prompt()
                 .unwrap_or_exit();
-            config.api_url = api_url;
+            config.proxy = Some(proxy.trim().to_string()).filter(|p| !p.is_empty());
             service.update_config(config)?;
+            if json {
+                print_json(&json!({ "updated": true }))?;
+            }
             success("configuration updated");
         }
+        ConfigCommands::Profile(args) => match args.commands {
+            ProfileCommands::Ls => {
+                if json {
+                    let profiles = profiles
+                        .profiles
+                        .iter()
+                        .map(|(name, profile)| {
+                            json!({
+                                "name": name,
+                                "url": profile.url,
+                                "active": service.active_profile() == Some(name.as_str()),
+                            })
+                        })
+                        .collect::<Vec<_>>();
+                    print_json(&profiles)?;
+                    return Ok(());
+                }
+                if profiles.profiles.is_empty() {
+                    info("No profiles");
+                    return Ok(());
+                }
+                let mut table = table(&["Name", "URL", "Active"]);
+                for (name, profile) in &profiles.profiles {
+                    let active = service.active_profile() == Some(name.as_str());
+                    table.add_row(vec![
+                        Cell::new(name),
+                        Cell::new(&profile.url),
+                        Cell::new(if active { "yes" } else { "" }).fg(Color::Green),
+                    ]);
+                }
+                println!("{table}");
+            }
+            ProfileCommands::Add { name, url } => {
+                profiles.insert(&name, Profile::new(&url));
+                service.save_profiles(&profiles)?;
+                if json {
+                    print_json(&json!({ "name": name, "url": url }))?;
+                }
+                success(&format!("profile '{name}' added"));
+            }
+            ProfileCommands::Rm { name } => {
+                if profiles.remove(&name).is_none() {
+                    return Err(Error::msg(format!("unknown profile '{name}'")));
+                }
+                service.save_profiles(&profiles)?;
+                if json {
+                    print_json(&json!({ "name": name }))?;
+                }
+                success(&format!("profile '{name}' removed"));
+            }
+            ProfileCommands::Use { name } => {
+                if let Some(name) = &name {
+                    if profiles.get(name).is_none() {
+                        return Err(Error::msg(format!("unknown profile '{name}'")));
+                    }
+                }
+                profiles.default = name.clone();
+                service.save_profiles(&profiles)?;
+                if json {
+                    print_json(&json!({ "default": name }))?;
+                }
+                match name {
+                    Some(name) => success(&format!("profile '{name}' used by default")),
+                    None => success("local configuration used by default"),
+                }
+            }
+        },
     }
     Ok(())
 }
@@ -108,13 +379,15 @@ pub enum AuthCommands {
     Me,
     /// Update the logged in user
     Update,
+    /// Change the password of the logged in user
+    Passwd,
     /// Deletes the logged in user
     Delete,
 }
 
 /// Runs the auth commands
-async fn run_auth_cmd(args: AuthArgs) -> Result<(), Error> {
-    let mut service = Service::new()?;
+async fn run_auth_cmd(args: AuthArgs, opts: &ServiceOptions, json: bool) -> Result<(), Error> {
+    let mut service = Service::new(opts)?;
     match args.commands {
         AuthCommands::Signup => {
             let name = Text::new("Name:").prompt()?;
@@ -127,6 +400,9 @@ async fn run_auth_cmd(args: AuthArgs) -> Result<(), Error> {
                     password,
                 })
                 .await?;
+            if json {
+                print_json(&user_json(&user))?;
+            }
             success(&format!("Signed up as {}", user.name));
         }
         AuthCommands::Login => {
@@ -134,47 +410,84 @@ async fn run_auth_cmd(args: AuthArgs) -> Result<(), Error> {
             let email = Text::new("Email:").prompt()?;
             let password = Password::new("Password:").prompt()?;
             let user = service.login(&email, &password).await?;
+            if json {
+                print_json(&user_json(&user))?;
+            }
             success(&format!("Logged in as {}", user.name));
         }
         AuthCommands::Me => {
             let user = service.me().await?;
+            if json {
+                print_json(&user_json(&user))?;
+                return Ok(());
+            }
             println!("Logged-in user:");
             println!("- name: {}", user.name);
             println!("- email: {}", user.email);
         }
         AuthCommands::Update => {
-            todo!()
-            // let user = client.me().await.unwrap_or_exit().user;
-            // let name = Text::new("Name:")
-            //     .with_initial_value(&user.name)
-            //     .prompt()
-            //     .unwrap_or_exit();
-            // let email = Text::new("Email:")
-            //     .with_initial_value(&user.email)
-            //     .prompt()
-            //     .unwrap_or_exit();
-            // let password = Password::new("Password:").prompt().unwrap_or_exit();
-
-            // client
-            //     .update_me(UserUpdate {
-            //         name: Some(name),
-            //         email: Some(email),
-            //         password: Some(password),
-            //     })
-            //     .await
-            //     .unwrap_or_exit();
-            // success("User has been updated");
+            let user = service.me().await?;
+            info("Update your info (leave the password empty to keep it):");
+            let name = Text::new("Name:").with_initial_value(&user.name).prompt()?;
+            let email = Text::new("Email:")
+                .with_initial_value(&user.email)
+                .prompt()?;
+            let password = Password::new("Password:").prompt()?;
+            let user = service
+                .update_me(UserUpdate {
+                    name: Some(name).filter(|n| *n != user.name),
+                    email: Some(email).filter(|e| *e != user.email),
+                    password: Some(password).filter(|p| !p.is_empty()),
+                })
+                .await?;
+            if json {
+                print_json(&user_json(&user))?;
+            }
+            success(&format!("Updated user {}", user.name));
+        }
+        AuthCommands::Passwd => {
+            let current = Password::new("Current password:")
+                .without_confirmation()
+                .prompt()?;
+            let new = Password::new("New password:")
+                .with_custom_confirmation_message("Confirm the new password:")
+                .prompt()?;
+            service.change_password(&current, &new).await?;
+            if json {
+                print_json(&json!({ "changed": true }))?;
+            }
+            success("Password has been changed");
         }
         AuthCommands::Delete => {
-            todo!()
-            // client.delete_me().await.unwrap_or_exit();
-            // success("User has been deleted");
-            // cfg.unset_token(true);
+            let confirmed = Confirm::new("Delete your account and all its data?")
+                .with_default(false)
+                .prompt()?;
+            if json {
+                print_json(&json!({ "deleted": confirmed }))?;
+            }
+            if confirmed {
+                service.delete_me().await?;
+                success("User has been deleted");
+            } else {
+                info("Deletion cancelled");
+            }
         }
     }
     Ok(())
 }
 
+/// Returns the JSON of a user
+///
+/// NB: the password is left out
+fn user_json(user: &User) -> serde_json::Value {
+    json!({
+        "id": user.id,
+        "name": user.name,
+        "email": user.email,
+        "subscription": user.subscription,
+    })
+}
+
 /// Feeds commands
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -187,8 +500,12 @@ pub struct FeedsArgs {
 /// Feed commands
 #[derive(Subcommand)]
 pub enum FeedsCommands {
-    /// List all the feeds
-    Ls,
+    /// List all the feeds, with their unread counts
+    Ls {
+        /// Sort order
+        #[arg(long, value_enum, default_value_t = FeedsSort::Folder)]
+        sort: FeedsSort,
+    },
     /// Adds a feed
     Add {
         /// Feed url
@@ -202,46 +519,1189 @@ pub enum FeedsCommands {
     },
     /// Removes feeds
     Rm {
-        /// Feeds urls
+        /// Feeds urls (or names), picked interactively if none
         urls: Vec<String>,
+        /// Do not ask for confirmation
+        #[arg(long, short)]
+        yes: bool,
     },
+    /// Moves a feed to a folder
+    Mv {
+        /// Feed url
+        url: String,
+        /// Folder name (the feed is moved out of its folder if not set)
+        #[arg(long, short)]
+        folder: Option<String>,
+    },
+    /// Exports the feeds as OPML
+    Export {
+        /// Output file (the standard output if not set)
+        #[arg(long, short)]
+        out: Option<PathBuf>,
+    },
+    /// Imports the subscriptions of another reader (the tags or categories become folders)
+    Import {
+        /// Subscriptions file (eg. ~/.newsboat/urls)
+        path: PathBuf,
+        /// Reader of the subscriptions
+        #[arg(long, value_enum)]
+        from: import::Source,
+    },
+}
+
+/// Resolves the feed of a url, and returns its url and advertised title
+///
+/// If the url is a website, its feeds are discovered, and one is picked if there are several
+/// (in JSON mode, the prompt is skipped and the choices are returned as an error).
+///
+/// NB: an unreachable url is kept as is
+async fn discover_feed(
+    service: &Service,
+    url: String,
+    json: bool,
+) -> Result<(String, Option<String>), Error> {
+    let spinner = spinner(&format!("looking for the feeds of {url}"));
+    let res = service.discover_feeds(&url).await;
+    spinner.finish_and_clear();
+    let mut feeds = match res {
+        Ok(feeds) => feeds,
+        Err(err) => {
+            warn(&format!(
+                "failed to load {url} ({err}), the url is added as is"
+            ));
+            return Ok((url, None));
+        }
+    };
+    let feed = match feeds.len() {
+        0 => return Err(Error::msg(format!("no feed found at {url}"))),
+        1 => feeds.remove(0),
+        _ if json => {
+            let urls = feeds.iter().map(|f| f.url.as_str()).collect::<Vec<_>>();
+            return Err(Error::msg(format!(
+                "several feeds found at {url}, pick one of: {}",
+                urls.join(", ")
+            )));
+        }
+        _ => Select::new("Several feeds were found, pick one:", feeds)
+            .prompt()
+            .unwrap_or_exit(),
+    };
+    if feed.url != url {
+        info(&format!("found the feed {}", feed.url));
+    }
+    Ok((feed.url, feed.title))
+}
+
+/// Sort order of the feeds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum FeedsSort {
+    /// By folder
+    Folder,
+    /// By name (or url)
+    Name,
+    /// By unread count, the most first
+    Unread,
+}
+
+/// Returns the cell of an unread count (highlighted if not 0)
+fn unread_cell(unread: usize) -> Cell {
+    if unread > 0 {
+        Cell::new(unread)
+            .fg(Color::Yellow)
+            .add_attribute(Attribute::Bold)
+    } else {
+        Cell::new(unread)
+    }
+}
+
+/// Returns the label of a feed (its name and url)
+fn feed_label(feed: &Feed) -> String {
+    match &feed.name {
+        Some(name) => format!("{name} ({})", feed.url),
+        None => feed.url.clone(),
+    }
+}
+
+/// Prompts for feeds to pick (none if the prompt is skipped)
+fn pick_feeds(feeds: &[Feed]) -> Result<Vec<&Feed>, Error> {
+    let labels = feeds.iter().map(feed_label).collect::<Vec<_>>();
+    let picked = MultiSelect::new("Feeds to remove:", labels.clone())
+        .with_page_size(15)
+        .prompt_skippable()?
+        .unwrap_or_default();
+    Ok(picked
+        .iter()
+        .filter_map(|label| labels.iter().position(|l| l == label))
+        .map(|i| &feeds[i])
+        .collect())
 }
 
 /// Runs the feeds commands
-async fn run_feeds_cmd(args: FeedsArgs) -> Result<(), Error> {
-    let mut service = Service::new()?;
+///
+/// NB: the export is printed as OPML, even in JSON mode
+async fn run_feeds_cmd(args: FeedsArgs, opts: &ServiceOptions, json: bool) -> Result<(), Error> {
+    let mut service = Service::new(opts)?;
     match args.commands {
-        FeedsCommands::Ls => {
-            let feeds = service.get_feeds().await?;
-            println!("FEEDS:");
-            for feed in &feeds {
-                println!("  - {}", feed.url);
+        FeedsCommands::Ls { sort } => {
+            let mut feeds = service.get_feeds_stats().await?;
+            match sort {
+                FeedsSort::Folder => feeds.sort_by(|(a, _), (b, _)| a.folder.cmp(&b.folder)),
+                FeedsSort::Name => {
+                    feeds.sort_by_key(|(f, _)| f.name.as_deref().unwrap_or(&f.url).to_lowercase())
+                }
+                FeedsSort::Unread => feeds.sort_by(|(_, a), (_, b)| b.unread.cmp(&a.unread)),
             }
+            if json {
+                let feeds = feeds
+                    .iter()
+                    .map(|(feed, stats)| json!({ "feed": feed, "stats": stats }))
+                    .collect::<Vec<_>>();
+                print_json(&feeds)?;
+                return Ok(());
+            }
+            if feeds.is_empty() {
+                info("No feeds");
+                return Ok(());
+            }
+            let mut table = table(&["Folder", "Name", "URL", "Unread", "Last updated", "Health"]);
+            for (feed, stats) in &feeds {
+                let last_updated = stats
+                    .last_updated
+                    .map(|d| d.format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or_default();
+                let health = match &stats.health {
+                    FeedHealth::Unknown => Cell::new("-"),
+                    FeedHealth::Ok => Cell::new("ok").fg(Color::Green),
+                    FeedHealth::Failing(err) => Cell::new(format!("failing: {err}")).fg(Color::Red),
+                };
+                table.add_row(vec![
+                    Cell::new(feed.folder.as_deref().unwrap_or_default()),
+                    Cell::new(feed.name.as_deref().unwrap_or_default()),
+                    Cell::new(&feed.url),
+                    unread_cell(stats.unread),
+                    Cell::new(last_updated),
+                    health,
+                ]);
+            }
+            let total = feeds.iter().map(|(_, s)| s.unread).sum::<usize>();
+            table.add_row(vec![
+                Cell::new("Total").add_attribute(Attribute::Bold),
+                Cell::new(""),
+                Cell::new(""),
+                unread_cell(total),
+            ]);
+            println!("{table}");
         }
         FeedsCommands::Add { url, name, folder } => {
-            let feed = Feed { url, name, folder };
-            service.add_feeds(vec![feed]).await?;
+            let (url, title) = if opts.offline {
+                (url, None)
+            } else {
+                discover_feed(&service, url, json).await?
+            };
+            let feed = Feed {
+                url,
+                name: name.or(title),
+                folder,
+            };
+            let feeds = service.add_feeds(vec![feed]).await?;
+            if json {
+                print_json(&feeds)?;
+            }
             success("feed added");
         }
-        FeedsCommands::Rm { urls } => {
-            service.remove_feeds(urls).await?;
-            success("feed(s) removed");
+        FeedsCommands::Rm { urls, yes } => {
+            let feeds = service.get_feeds().await?;
+            let selected = if urls.is_empty() {
+                pick_feeds(&feeds)?
+            } else {
+                urls.iter()
+                    .map(|url| {
+                        feeds
+                            .iter()
+                            .find(|f| f.url == *url || f.name.as_deref() == Some(url.as_str()))
+                            .ok_or(Error::msg(format!("unknown feed '{url}'")))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?
+            };
+
+            let confirmed = selected.is_empty() || yes || {
+                info("The following feeds will be removed:");
+                for feed in &selected {
+                    eprintln!("  - {}", feed_label(feed));
+                }
+                Confirm::new(&format!("Remove {} feed(s)?", selected.len()))
+                    .with_default(false)
+                    .prompt()?
+            };
+            let removed = if confirmed {
+                let urls = selected.iter().map(|f| f.url.clone()).collect();
+                service.remove_feeds(urls).await?
+            } else {
+                vec![]
+            };
+            if json {
+                print_json(&json!({ "removed": removed }))?;
+            }
+            match (confirmed, removed.len()) {
+                (false, _) => info("Removal cancelled"),
+                (true, 0) => info("No feeds removed"),
+                (true, n) => success(&format!("{n} feed(s) removed")),
+            }
+        }
+        FeedsCommands::Mv { url, folder } => {
+            service.move_feed(&url, folder.as_deref())?;
+            if json {
+                print_json(&json!({ "url": url, "folder": folder }))?;
+            }
+            match folder {
+                Some(folder) => success(&format!("feed moved to {folder}")),
+                None => success("feed moved out of its folder"),
+            }
+        }
+        FeedsCommands::Import { path, from } => {
+            let data = std::fs::read_to_string(&path)?;
+            let existing = service.get_feeds().await?;
+            let mut feeds: Vec<Feed> = vec![];
+            let mut skipped = 0;
+            for feed in import::parse(&data, from)? {
+                if existing.iter().chain(&feeds).any(|f| f.url == feed.url) {
+                    skipped += 1;
+                } else {
+                    feeds.push(feed);
+                }
+            }
+            let feeds = service.add_feeds(feeds).await?;
+            if json {
+                print_json(&feeds)?;
+            }
+            if skipped > 0 {
+                info(&format!("{skipped} feed(s) skipped (already added)"));
+            }
+            success(&format!("{} feed(s) imported", feeds.len()));
+        }
+        FeedsCommands::Export { out } => {
+            let feeds = service.get_feeds().await?;
+            let opml = to_opml(&feeds);
+            match out {
+                Some(path) => {
+                    std::fs::write(&path, opml)?;
+                    success(&format!(
+                        "{} feed(s) exported to {}",
+                        feeds.len(),
+                        path.display()
+                    ));
+                }
+                None => print!("{opml}"),
+            }
         }
     }
     Ok(())
 }
 
+/// Folders commands
+#[derive(Parser)]
+pub struct FoldersArgs {
+    #[command(subcommand)]
+    commands: FoldersCommands,
+}
+
+/// Folders commands
+#[derive(Subcommand)]
+pub enum FoldersCommands {
+    /// Lists the folders
+    Ls,
+    /// Removes a folder (its feeds are kept)
+    Rm {
+        /// Folder name
+        name: String,
+    },
+    /// Renames a folder
+    Rename {
+        /// Folder name
+        name: String,
+        /// New folder name
+        new_name: String,
+    },
+}
+
+/// Runs the folders commands
+///
+/// NB: the folders are local, the server has none
+fn run_folders_cmd(args: FoldersArgs, opts: &ServiceOptions, json: bool) -> Result<(), Error> {
+    let service = Service::new(opts)?;
+    match args.commands {
+        FoldersCommands::Ls => {
+            let folders = service.get_folders()?;
+            if json {
+                let folders = folders
+                    .iter()
+                    .map(|(name, feeds)| json!({ "name": name, "feeds": feeds }))
+                    .collect::<Vec<_>>();
+                print_json(&folders)?;
+                return Ok(());
+            }
+            if folders.is_empty() {
+                info("No folders");
+                return Ok(());
+            }
+            let mut table = table(&["Name", "Feeds"]);
+            for (name, feeds) in folders {
+                table.add_row(vec![Cell::new(name), Cell::new(feeds)]);
+            }
+            println!("{table}");
+        }
+        FoldersCommands::Rm { name } => {
+            let n = service.remove_folder(&name)?;
+            if json {
+                print_json(&json!({ "name": name, "feeds": n }))?;
+            }
+            success(&format!("folder removed ({n} feed(s) moved out)"));
+        }
+        FoldersCommands::Rename { name, new_name } => {
+            let n = service.rename_folder(&name, &new_name)?;
+            if json {
+                print_json(&json!({ "name": new_name, "feeds": n }))?;
+            }
+            success(&format!("folder renamed to {new_name}"));
+        }
+    }
+    Ok(())
+}
+
+/// Maximum width of the article titles in the table
+const TITLE_WIDTH: usize = 72;
+
 /// Runs the read command
-async fn run_read_cmd() -> Result<(), Error> {
-    let service = Service::new()?;
+///
+/// The articles of the feeds are listed in a table, then can be opened one by one. An opened
+/// article is marked as read.
+///
+/// In JSON mode, the articles are printed by feed, without prompt.
+async fn run_read_cmd(
+    unread: bool,
+    summarize: bool,
+    opts: &ServiceOptions,
+    json: bool,
+) -> Result<(), Error> {
+    let mut service = Service::new(opts)?;
     let feeds = service.get_feeds().await?;
+    let feeds = load_articles(&service, feeds).await;
+
+    let mut table = String::new();
+    let mut articles = vec![];
+    let mut feeds_json = vec![];
+    for (feed, feed_articles) in feeds {
+        let feed_articles = feed_articles
+            .into_iter()
+            .filter(|a| !(unread && a.read))
+            .collect::<Vec<_>>();
+        if json {
+            feeds_json.push(json!({
+                "url": feed.url,
+                "name": feed.name,
+                "articles": feed_articles,
+            }));
+            continue;
+        }
+        if feed_articles.is_empty() {
+            continue;
+        }
+        let name = feed.name.as_deref().unwrap_or(&feed.url);
+        table.push_str(&format!("{}\n", name.bold()));
+        for article in feed_articles {
+            table.push_str(&format!("{}\n", table_row(articles.len() + 1, &article)));
+            articles.push(article);
+        }
+        table.push('\n');
+    }
+    if json {
+        print_json(&feeds_json)?;
+        return Ok(());
+    }
+    if articles.is_empty() {
+        info(if unread {
+            "No unread articles"
+        } else {
+            "No articles"
+        });
+        return Ok(());
+    }
+    service.save_listing(&articles)?;
+    page(&table)?;
+    open_articles(&service, &mut articles, summarize).await
+}
+
+/// Loads the articles of feeds, with a progress bar
+///
+/// NB: the feeds which fail to load are skipped, and reported at the end
+async fn load_articles(service: &Service, feeds: Vec<Feed>) -> Vec<(Feed, Vec<Article>)> {
+    let bar = progress_bar(feeds.len(), "loading the feeds");
+    let (mut loaded, mut failures) = (vec![], vec![]);
     for feed in feeds {
-        println!("FEED: {}", feed.url);
-        let articles = service.get_articles(&feed).await?;
-        for article in articles {
-            println!("  - {}", article.url);
+        match service.get_articles(&feed).await {
+            Ok(articles) => loaded.push((feed, articles)),
+            Err(err) => failures.push((feed.url, err.to_string())),
         }
+        bar.inc(1);
+    }
+    bar.finish_and_clear();
+    report_failures(&failures);
+    loaded
+}
+
+/// Prompts for the articles to open, until the prompt is skipped
+///
+/// An opened article is marked as read. With `summarize`, its summary is shown above its
+/// content.
+async fn open_articles(
+    service: &Service,
+    articles: &mut [Article],
+    summarize: bool,
+) -> Result<(), Error> {
+    let labels = articles
+        .iter()
+        .enumerate()
+        .map(|(i, article)| format!("{:>4}  {}", i + 1, article_title(article)))
+        .collect::<Vec<_>>();
+    while let Some(label) = Select::new("Open an article:", labels.clone())
+        .with_page_size(15)
+        .prompt_skippable()?
+    {
+        let i = labels.iter().position(|l| *l == label).unwrap();
+        let summary = if summarize {
+            let spinner = spinner("summarizing the article");
+            let res = service.summarize(&articles[i].url).await;
+            spinner.finish_and_clear();
+            match res {
+                Ok(summary) => Some(summary),
+                Err(err) => {
+                    warn(&format!("failed to summarize the article: {err}"));
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        page(&render_article(&articles[i], summary.as_ref()))?;
+        service.mark_read(&mut articles[i])?;
+    }
+    Ok(())
+}
+
+/// Returns the table row of an article
+fn table_row(n: usize, article: &Article) -> String {
+    let date = article
+        .date
+        .map(|d| d.format("%Y-%m-%d").to_string())
+        .unwrap_or_else(|| "-".repeat(10));
+    let mut title = article_title(article).to_string();
+    if title.chars().count() > TITLE_WIDTH {
+        title = title.chars().take(TITLE_WIDTH - 1).collect::<String>() + "…";
+    }
+    if article.read {
+        format!("{n:>4}    {}  {}", date.dimmed(), title.dimmed())
+    } else {
+        format!("{n:>4} {} {}  {title}", "●".cyan(), date.dimmed())
+    }
+}
+
+/// Returns the title of an article (its url if untitled)
+fn article_title(article: &Article) -> &str {
+    article.title.as_deref().unwrap_or(&article.url)
+}
+
+/// Renders the content of an article (with its summary, if any)
+fn render_article(article: &Article, summary: Option<&ArticleSummary>) -> String {
+    let mut text = format!("{}\n", article_title(article).bold());
+    if let Some(date) = article.date {
+        text.push_str(&format!(
+            "{}\n",
+            date.format("%Y-%m-%d %H:%M").to_string().dimmed()
+        ));
+    }
+    text.push_str(&format!("{}\n\n", article.url.blue()));
+    if let Some(summary) = summary {
+        text.push_str(&format!("{}\n", summary.summary.italic()));
+        if !summary.keywords.is_empty() {
+            text.push_str(&format!("{}\n", summary.keywords.join(", ").dimmed()));
+        }
+        text.push('\n');
+    }
+    match article.content.as_deref().map(html_to_text) {
+        Some(content) if !content.is_empty() => text.push_str(&content),
+        _ => text.push_str("(no content, open the url to read the article)"),
+    }
+    text.push('\n');
+    text
+}
+
+/// Runs the mark-read command
+async fn run_mark_read_cmd(feed: &str, opts: &ServiceOptions, json: bool) -> Result<(), Error> {
+    let service = Service::new(opts)?;
+    let feeds = service.get_feeds().await?;
+    let Some(feed) = feeds
+        .iter()
+        .find(|f| f.url == feed || f.name.as_deref() == Some(feed))
+    else {
+        return Err(Error::msg(format!("unknown feed '{feed}'")));
+    };
+    let n = service.mark_feed_read(feed).await?;
+    if json {
+        print_json(&json!({ "feed": feed.url, "marked": n }))?;
     }
-    println!("OK");
+    success(&format!("{n} article(s) marked as read"));
+    Ok(())
+}
+
+/// Runs the open command
+fn run_open_cmd(article: &str, opts: &ServiceOptions, json: bool) -> Result<(), Error> {
+    let service = Service::new(opts)?;
+    let url = service.open(article)?;
+    if json {
+        print_json(&json!({ "url": url }))?;
+    }
+    success(&format!("opened {url}"));
+    Ok(())
+}
+
+/// Runs the star command
+fn run_star_cmd(url: &str, remove: bool, opts: &ServiceOptions, json: bool) -> Result<(), Error> {
+    let service = Service::new(opts)?;
+    let changed = service.star(url, !remove)?;
+    if json {
+        print_json(&json!({ "url": url, "starred": !remove, "changed": changed }))?;
+    }
+    match (remove, changed) {
+        (false, true) => success("article starred"),
+        (false, false) => info("article already starred"),
+        (true, true) => success("star removed"),
+        (true, false) => info("article not starred"),
+    }
+    Ok(())
+}
+
+/// Runs the starred command
+fn run_starred_cmd(opts: &ServiceOptions, json: bool) -> Result<(), Error> {
+    let service = Service::new(opts)?;
+    let stars = service.get_stars()?;
+    if json {
+        let stars = stars
+            .iter()
+            .map(|(url, starred_at)| json!({ "url": url, "starred_at": starred_at }))
+            .collect::<Vec<_>>();
+        print_json(&stars)?;
+        return Ok(());
+    }
+    if stars.is_empty() {
+        info("No starred articles");
+        return Ok(());
+    }
+    let mut table = table(&["Starred at", "URL"]);
+    for (url, starred_at) in stars {
+        table.add_row(vec![starred_at, url]);
+    }
+    println!("{table}");
+    Ok(())
+}
+
+/// Search arguments
+#[derive(Parser)]
+pub struct SearchArgs {
+    /// Search terms (matched in the titles and contents)
+    #[arg(required = true)]
+    query: Vec<String>,
+    /// Only the articles of a feed (url or name)
+    #[arg(long, short)]
+    feed: Option<String>,
+    /// Only the articles published since a date (YYYY-MM-DD or RFC 3339)
+    #[arg(long, short, value_parser = parse_since)]
+    since: Option<FixedDateTime>,
+    /// Only the unread articles
+    #[arg(long)]
+    unread: bool,
+}
+
+/// Parses the date of the `--since` option
+fn parse_since(value: &str) -> Result<FixedDateTime, String> {
+    let rfc3339 = if value.len() == 10 {
+        format!("{value}T00:00:00Z")
+    } else {
+        value.to_string()
+    };
+    FixedDateTime::parse_from_rfc3339(&rfc3339).map_err(|_| format!("invalid date '{value}'"))
+}
+
+/// Runs the search command
+///
+/// The articles are searched in the local cache, filled by the read command. In JSON mode, the
+/// matching articles are printed without prompt.
+async fn run_search_cmd(args: SearchArgs, opts: &ServiceOptions, json: bool) -> Result<(), Error> {
+    let mut service = Service::new(opts)?;
+    let feed = match &args.feed {
+        Some(name) => {
+            let feed = service
+                .get_feeds()
+                .await?
+                .into_iter()
+                .find(|f| f.url == *name || f.name.as_deref() == Some(name.as_str()))
+                .ok_or(Error::msg(format!("unknown feed '{name}'")))?;
+            Some(feed)
+        }
+        None => None,
+    };
+
+    let mut articles = service.search(
+        &args.query.join(" "),
+        feed.as_ref(),
+        args.since,
+        args.unread,
+    )?;
+    if json {
+        print_json(&articles)?;
+        return Ok(());
+    }
+    if articles.is_empty() {
+        info("No matching articles");
+        return Ok(());
+    }
+    let table = articles
+        .iter()
+        .enumerate()
+        .map(|(i, article)| format!("{}\n", table_row(i + 1, article)))
+        .collect::<String>();
+    service.save_listing(&articles)?;
+    page(&table)?;
+    open_articles(&service, &mut articles, false).await
+}
+
+/// Summarize arguments
+#[derive(Parser)]
+pub struct SummarizeArgs {
+    /// Articles urls
+    urls: Vec<String>,
+    /// Summarize the articles of a feed (url or name)
+    #[arg(long, short)]
+    feed: Option<String>,
+    /// Only the unread articles (of all the feeds if no feed is set)
+    #[arg(long)]
+    unread: bool,
+    /// Maximum number of concurrent requests
+    #[arg(long, short, default_value_t = 4)]
+    concurrency: usize,
+}
+
+/// Runs the summarize command
+async fn run_summarize_cmd(
+    args: SummarizeArgs,
+    opts: &ServiceOptions,
+    json: bool,
+) -> Result<(), Error> {
+    let service = Service::new(opts)?;
+    let mut urls = args.urls;
+
+    let feeds = service.get_feeds().await?;
+    let feeds = match &args.feed {
+        Some(name) => {
+            let feed = feeds
+                .into_iter()
+                .find(|f| f.url == *name || f.name.as_deref() == Some(name.as_str()))
+                .ok_or(Error::msg(format!("unknown feed '{name}'")))?;
+            vec![feed]
+        }
+        None if args.unread && urls.is_empty() => feeds,
+        None => vec![],
+    };
+    for (_, articles) in load_articles(&service, feeds).await {
+        urls.extend(
+            articles
+                .into_iter()
+                .filter(|a| !(args.unread && a.read))
+                .map(|a| a.url),
+        );
+    }
+    if urls.is_empty() {
+        if json {
+            print_json(&urls)?;
+        }
+        info("No articles to summarize");
+        return Ok(());
+    }
+
+    let urls = urls.iter().map(|u| u.as_str()).collect::<Vec<_>>();
+    let bar = progress_bar(urls.len(), "summarizing");
+    let (summaries, failures) = service
+        .summarize_all(&urls, args.concurrency, |done| {
+            bar.set_position(done as u64)
+        })
+        .await;
+    bar.finish_and_clear();
+
+    if json {
+        print_json(&summaries)?;
+    } else {
+        for summary in summaries {
+            println!("{}", summary.url.blue());
+            println!("{}", summary.summary);
+            if !summary.keywords.is_empty() {
+                println!("{}", summary.keywords.join(", ").dimmed());
+            }
+            println!();
+        }
+    }
+    report_failures(&failures);
+    Ok(())
+}
+
+/// Digest arguments
+#[derive(Parser)]
+pub struct DigestArgs {
+    /// Period of the articles (eg. 12h, 7d)
+    #[arg(long, short, default_value = "24h", value_parser = parse_duration)]
+    since: Duration,
+    /// Format
+    #[arg(long, short, value_enum, default_value_t = Format::Md)]
+    format: Format,
+    /// Send the digest to an email address (with sendmail), instead of printing it
+    #[arg(long, short)]
+    email: Option<String>,
+    /// Maximum number of concurrent summary requests
+    #[arg(long, short, default_value_t = 4)]
+    concurrency: usize,
+}
+
+/// Runs the digest command
+///
+/// The articles without date are included. If the articles cannot be summarized, the digest
+/// is compiled without the summaries.
+async fn run_digest_cmd(args: DigestArgs, opts: &ServiceOptions, json: bool) -> Result<(), Error> {
+    let service = Service::new(opts)?;
+    let since = Utc::now() - chrono::Duration::from_std(args.since)?;
+
+    let feeds = service.get_feeds().await?;
+    let mut entries = vec![];
+    for (feed, articles) in load_articles(&service, feeds).await {
+        for article in articles
+            .into_iter()
+            .filter(|a| !a.read && !a.date.is_some_and(|d| d < since))
+        {
+            entries.push(Entry {
+                feed: feed.clone(),
+                article,
+                summary: None,
+            });
+        }
+    }
+    if entries.is_empty() {
+        if json {
+            print_json(&entries)?;
+        }
+        info("No unread articles");
+        return Ok(());
+    }
+
+    let urls = entries
+        .iter()
+        .map(|e| e.article.url.as_str())
+        .collect::<Vec<_>>();
+    let bar = progress_bar(urls.len(), "summarizing");
+    let (summaries, failures) = service
+        .summarize_all(&urls, args.concurrency, |done| {
+            bar.set_position(done as u64)
+        })
+        .await;
+    bar.finish_and_clear();
+    report_failures(&failures);
+    for summary in summaries {
+        if let Some(entry) = entries.iter_mut().find(|e| e.article.url == summary.url) {
+            entry.summary = Some(summary.summary);
+        }
+    }
+    if json {
+        print_json(&entries)?;
+        return Ok(());
+    }
+
+    let title = format!("Newsie digest - {}", Local::now().format("%Y-%m-%d"));
+    let digest = digest::render(&title, &entries, args.format);
+    match args.email {
+        Some(to) => {
+            digest::send_email(&to, &title, &digest, args.format)?;
+            success(&format!(
+                "digest of {} article(s) sent to {to}",
+                entries.len()
+            ));
+        }
+        None => print!("{digest}"),
+    }
+    Ok(())
+}
+
+/// Export arguments
+#[derive(Parser)]
+pub struct ExportArgs {
+    /// Feed (url or name), or `all`
+    feed: String,
+    /// Format
+    #[arg(long, short, value_enum, default_value_t = export::Format::Md)]
+    format: export::Format,
+    /// Output folder (a sub-folder is created by feed)
+    #[arg(long, short, default_value = ".")]
+    out: PathBuf,
+    /// Do not summarize the articles
+    #[arg(long)]
+    no_summary: bool,
+    /// Maximum number of concurrent summary requests
+    #[arg(long, short, default_value_t = 4)]
+    concurrency: usize,
+}
+
+/// Runs the export command
+///
+/// The cached articles are exported, ie. the ones fetched before. They are not summarized in
+/// offline mode.
+async fn run_export_cmd(args: ExportArgs, opts: &ServiceOptions, json: bool) -> Result<(), Error> {
+    let service = Service::new(opts)?;
+    let feeds = service.get_feeds().await?;
+    let feeds = if args.feed == "all" {
+        feeds
+    } else {
+        let feed = feeds
+            .into_iter()
+            .find(|f| f.url == args.feed || f.name.as_deref() == Some(args.feed.as_str()))
+            .ok_or(Error::msg(format!("unknown feed '{}'", args.feed)))?;
+        vec![feed]
+    };
+
+    let mut articles = vec![];
+    for feed in &feeds {
+        for article in service.get_cached_articles(feed)? {
+            articles.push((feed, article));
+        }
+    }
+    if articles.is_empty() {
+        if json {
+            print_json(&Vec::<PathBuf>::new())?;
+        }
+        info("No cached articles (read the feeds first)");
+        return Ok(());
+    }
+
+    let mut summaries = HashMap::new();
+    if !args.no_summary && !opts.offline {
+        let urls = articles
+            .iter()
+            .map(|(_, a)| a.url.as_str())
+            .collect::<Vec<_>>();
+        let bar = progress_bar(urls.len(), "summarizing");
+        let (results, failures) = service
+            .summarize_all(&urls, args.concurrency, |done| {
+                bar.set_position(done as u64)
+            })
+            .await;
+        bar.finish_and_clear();
+        report_failures(&failures);
+        summaries.extend(results.into_iter().map(|s| (s.url, s.summary)));
+    }
+
+    let mut exporter = Exporter::new(&args.out, args.format);
+    let mut paths = vec![];
+    for (feed, article) in &articles {
+        let summary = summaries.get(&article.url).map(|s| s.as_str());
+        paths.push(exporter.write(feed, article, summary)?);
+    }
+    if json {
+        print_json(&paths)?;
+    }
+    success(&format!(
+        "{} article(s) exported to {}",
+        paths.len(),
+        args.out.display()
+    ));
+    Ok(())
+}
+
+/// Runs the sync command
+async fn run_sync_cmd(opts: &ServiceOptions, json: bool) -> Result<(), Error> {
+    let mut service = Service::new(opts)?;
+    let spinner = spinner("syncing the feeds");
+    let res = service.sync_feeds().await;
+    spinner.finish_and_clear();
+    let report = res?;
+    if json {
+        print_json(&report)?;
+    }
+    for url in &report.conflicts {
+        warn(&format!(
+            "{url} changed on both sides, the local change is kept"
+        ));
+    }
+    success(&format!(
+        "feeds synced ({} pushed, {} pulled)",
+        report.pushed.len(),
+        report.pulled.len()
+    ));
+    Ok(())
+}
+
+/// Stats arguments
+#[derive(Parser)]
+pub struct StatsArgs {
+    /// Period, in days
+    #[arg(long, short, default_value_t = 14)]
+    days: u32,
+    /// Number of top feeds
+    #[arg(long, short, default_value_t = 5)]
+    top: usize,
+}
+
+/// Runs the stats command
+///
+/// The stats are computed from the local DB, and the API rate limit is read from the server
+/// (unless offline).
+async fn run_stats_cmd(args: StatsArgs, opts: &ServiceOptions, json: bool) -> Result<(), Error> {
+    let service = Service::new(opts)?;
+    let stats = service.get_reading_stats(args.days, args.top)?;
+    let rate_limit = if opts.offline {
+        None
+    } else {
+        match service.get_api_rate_limit().await {
+            Ok(rate_limit) => rate_limit,
+            Err(err) => {
+                warn(&format!("failed to read the API usage: {err}"));
+                None
+            }
+        }
+    };
+    let feeds = service.get_feeds().await?;
+    let feed_name = |url: &str| {
+        feeds
+            .iter()
+            .find(|f| f.url == url)
+            .and_then(|f| f.name.clone())
+            .unwrap_or_else(|| url.to_string())
+    };
+
+    if json {
+        print_json(&json!({
+            "per_day": stats.per_day,
+            "per_week": stats.per_week,
+            "top_feeds": stats
+                .top_feeds
+                .iter()
+                .map(|(url, n)| json!({ "url": url, "name": feed_name(url), "read": n }))
+                .collect::<Vec<_>>(),
+            "summaries": stats.summaries,
+            "rate_limit": rate_limit.map(|r| json!({ "limit": r.limit, "remaining": r.remaining })),
+        }))?;
+        return Ok(());
+    }
+
+    let total = stats.per_day.iter().map(|(_, n)| n).sum::<usize>();
+    println!(
+        "{}",
+        format!("Read in the last {} days: {total}", args.days).bold()
+    );
+    if !stats.per_day.is_empty() {
+        let mut days = table(&["Day", "Read"]);
+        for (day, n) in &stats.per_day {
+            days.add_row(vec![day.clone(), n.to_string()]);
+        }
+        println!("{days}");
+        let mut weeks = table(&["Week", "Read"]);
+        for (week, n) in &stats.per_week {
+            weeks.add_row(vec![week.clone(), n.to_string()]);
+        }
+        println!("{weeks}");
+    }
+
+    if !stats.top_feeds.is_empty() {
+        println!("{}", "Top feeds".bold());
+        let mut top = table(&["Feed", "Read"]);
+        for (url, n) in &stats.top_feeds {
+            top.add_row(vec![feed_name(url), n.to_string()]);
+        }
+        println!("{top}");
+    }
+
+    let summarized = stats.summaries.iter().map(|(_, n)| n).sum::<usize>();
+    println!(
+        "{}",
+        format!("Summarized in the last {} days: {summarized}", args.days).bold()
+    );
+    if let Some(rate_limit) = rate_limit {
+        match (rate_limit.remaining, rate_limit.limit) {
+            (Some(remaining), Some(limit)) => {
+                println!("API requests left: {remaining}/{limit}")
+            }
+            (Some(remaining), None) => println!("API requests left: {remaining}"),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Fetch arguments
+#[derive(Parser)]
+pub struct FetchArgs {
+    /// Maximum size of the downloaded contents, in MB (the least recently read are evicted)
+    #[arg(long, short, default_value_t = 100)]
+    max_size: usize,
+    /// Maximum number of concurrent downloads
+    #[arg(long, short, default_value_t = 4)]
+    concurrency: usize,
+}
+
+/// Runs the fetch command
+///
+/// The contents of the unread articles are downloaded (once), then read by `read` and the TUI,
+/// also in offline mode.
+async fn run_fetch_cmd(args: FetchArgs, opts: &ServiceOptions, json: bool) -> Result<(), Error> {
+    if opts.offline {
+        return Err(Error::msg("the contents cannot be fetched in offline mode"));
+    }
+    let mut service = Service::new(opts)?;
+    let feeds = service.get_feeds().await?;
+    let mut articles = vec![];
+    for (_, feed_articles) in load_articles(&service, feeds).await {
+        for article in feed_articles.into_iter().filter(|a| !a.read) {
+            if !service.has_content(&article)? {
+                articles.push(article);
+            }
+        }
+    }
+
+    let bar = progress_bar(articles.len(), "downloading");
+    let results = stream::iter(&articles)
+        .map(|article| async {
+            let res = service.fetch_content(article).await;
+            bar.inc(1);
+            (article, res)
+        })
+        .buffer_unordered(args.concurrency.max(1))
+        .collect::<Vec<_>>()
+        .await;
+    bar.finish_and_clear();
+    let failures = results
+        .into_iter()
+        .filter_map(|(article, res)| res.err().map(|err| (article.url.clone(), err.to_string())))
+        .collect::<Vec<_>>();
+    let fetched = articles.len() - failures.len();
+    report_failures(&failures);
+
+    let evicted = service.evict_contents(args.max_size * 1024 * 1024)?;
+    if json {
+        print_json(&json!({
+            "fetched": fetched,
+            "failed": failures.len(),
+            "evicted": evicted,
+        }))?;
+    }
+    success(&format!("{fetched} article(s) downloaded"));
+    if evicted > 0 {
+        info(&format!(
+            "{evicted} article(s) evicted (over {} MB)",
+            args.max_size
+        ));
+    }
+    Ok(())
+}
+
+/// Watch arguments
+#[derive(Parser)]
+pub struct WatchArgs {
+    /// Polling interval, in minutes
+    #[arg(long, short, default_value_t = 15)]
+    interval: u64,
+    /// Only notify the articles with a keyword (in their title or content)
+    #[arg(long = "keyword", short)]
+    keywords: Vec<String>,
+    /// Quiet hours, without polling (eg. 22:00-07:00)
+    #[arg(long = "quiet-hours")]
+    quiet_hours: Option<QuietHours>,
+}
+
+/// Runs the watch command
+async fn run_watch_cmd(args: WatchArgs, opts: &ServiceOptions) -> Result<(), Error> {
+    let service = Service::new(opts)?;
+    let opts = WatchOptions {
+        interval: Duration::from_secs(args.interval.max(1) * 60),
+        keywords: args.keywords,
+        quiet: args.quiet_hours,
+    };
+    watch::run(&service, opts).await
+}
+
+/// Service arguments
+#[derive(Parser)]
+pub struct ServiceArgs {
+    #[command(subcommand)]
+    commands: ServiceCommands,
+}
+
+/// Service commands
+#[derive(Subcommand)]
+pub enum ServiceCommands {
+    /// Installs (or replaces) the service (systemd user unit, or launchd agent), and starts it
+    Install {
+        /// Watch the feeds, or sync them periodically
+        #[arg(long, value_enum, default_value_t = daemon::Mode::Watch)]
+        mode: daemon::Mode,
+        /// Polling (or sync) interval, in minutes
+        #[arg(long, short, default_value_t = 15)]
+        interval: u64,
+    },
+    /// Shows the status of the service
+    Status,
+    /// Stops and removes the service
+    Uninstall,
+}
+
+/// Runs the service commands
+fn run_service_cmd(args: ServiceArgs, opts: &ServiceOptions, json: bool) -> Result<(), Error> {
+    match args.commands {
+        ServiceCommands::Install { mode, interval } => {
+            let mut args = vec![];
+            if let Some(profile) = &opts.profile {
+                args.extend(["--profile".to_string(), profile.clone()]);
+            }
+            let def = Definition {
+                exe: std::env::current_exe()?,
+                args,
+                mode,
+                interval: interval.max(1),
+            };
+            let paths = daemon::install(&def)?;
+            if json {
+                print_json(&json!({ "installed": paths }))?;
+            }
+            for path in &paths {
+                info(&format!("{} written", path.display()));
+            }
+            success("service installed and started");
+        }
+        ServiceCommands::Status => daemon::status()?,
+        ServiceCommands::Uninstall => {
+            let paths = daemon::uninstall()?;
+            if json {
+                print_json(&json!({ "removed": paths }))?;
+            }
+            if paths.is_empty() {
+                warn("the service is not installed");
+            } else {
+                success("service uninstalled");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs the completions command
+///
+/// NB: the script is printed to stdout, to be saved where the shell loads its completions
+/// (eg. `newsie-cli completions bash > /etc/bash_completion.d/newsie-cli`)
+fn run_completions_cmd(shell: Shell) -> Result<(), Error> {
+    let mut cmd = MainArgs::command();
+    let bin_name = cmd.get_name().to_string();
+    clap_complete::generate(shell, &mut cmd, bin_name, &mut std::io::stdout());
     Ok(())
 }
@@ -0,0 +1,75 @@
+//! Article contents
+//!
+//! The pages of the articles are downloaded for offline reading, and their main content is
+//! extracted: the `<article>` element, or else `<main>` or `<body>`, without the scripts and
+//! styles.
+
+/// Elements holding the content, by priority
+const CONTENT_TAGS: [&str; 3] = ["article", "main", "body"];
+
+/// Elements removed from the content
+const REMOVED_TAGS: [&str; 4] = ["script", "style", "noscript", "iframe"];
+
+/// Extracts the main content of an HTML page
+pub fn extract(html: &str) -> String {
+    let lower = html.to_ascii_lowercase();
+    let (start, end) = CONTENT_TAGS
+        .iter()
+        .find_map(|tag| inner(&lower, tag))
+        .unwrap_or((0, html.len()));
+    strip_elements(&html[start..end])
+}
+
+/// Returns the bounds of the inner HTML of the outermost element of a tag
+fn inner(lower: &str, tag: &str) -> Option<(usize, usize)> {
+    let open = format!("<{tag}");
+    let mut offset = 0;
+    let start = loop {
+        let i = offset + lower[offset..].find(&open)?;
+        let next = lower[i + open.len()..].chars().next()?;
+        if next == '>' || next == '/' || next.is_whitespace() {
+            break i;
+        }
+        offset = i + open.len();
+    };
+    let start = start + lower[start..].find('>')? + 1;
+    let end = lower
+        .rfind(&format!("</{tag}>"))
+        .filter(|end| *end >= start)?;
+    Some((start, end))
+}
+
+/// Removes the scripts, styles and frames of an HTML fragment
+fn strip_elements(html: &str) -> String {
+    let mut html = html.to_string();
+    for tag in REMOVED_TAGS {
+        let (open, close) = (format!("<{tag}"), format!("</{tag}>"));
+        while let Some(start) = html.to_ascii_lowercase().find(&open) {
+            let end = html.to_ascii_lowercase()[start..]
+                .find(&close)
+                .map(|end| start + end + close.len())
+                .unwrap_or(html.len());
+            html.replace_range(start..end, "");
+        }
+    }
+    html.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract() {
+        let html = r#"<html><head><style>p { color: red }</style></head>
+            <body><nav>Menu</nav><article class="post"><h1>Title</h1>
+            <script>track()</script><p>Text</p></article></body></html>"#;
+        assert_eq!(extract(html), "<h1>Title</h1>\n            <p>Text</p>");
+
+        let html = "<html><body><main><p>Main</p></main></body></html>";
+        assert_eq!(extract(html), "<p>Main</p>");
+
+        let html = "<p>Fragment <articles>x</articles></p>";
+        assert_eq!(extract(html), html);
+    }
+}
@@ -0,0 +1,329 @@
+//! Background service
+//!
+//! The CLI is installed as a user service, which runs the watch mode, or syncs the feeds
+//! periodically:
+//!
+//! - Linux: a systemd user unit (with a timer to sync)
+//! - macOS: a launchd agent
+//!
+//! NB: the service runs the current executable, with the active profile if any
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::Error;
+use clap::ValueEnum;
+
+use crate::opml::escape;
+
+/// Service name (systemd units)
+const UNIT_NAME: &str = "newsie-cli";
+
+/// Service label (launchd)
+const LAUNCHD_LABEL: &str = "rocks.newsie.cli";
+
+/// Service mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Mode {
+    /// Watch the feeds, with desktop notifications
+    Watch,
+    /// Sync the feeds with the server
+    Sync,
+}
+
+/// Service definition
+pub struct Definition {
+    /// Executable
+    pub exe: PathBuf,
+    /// Global arguments (eg. the profile)
+    pub args: Vec<String>,
+    /// Mode
+    pub mode: Mode,
+    /// Interval, in minutes
+    pub interval: u64,
+}
+
+impl Definition {
+    /// Returns the command line of the service
+    fn command(&self) -> Vec<String> {
+        let mut command = vec![self.exe.display().to_string()];
+        command.extend(self.args.iter().cloned());
+        match self.mode {
+            Mode::Watch => command.extend([
+                "watch".to_string(),
+                "--interval".to_string(),
+                self.interval.to_string(),
+            ]),
+            Mode::Sync => command.push("sync".to_string()),
+        }
+        command
+    }
+
+    /// Renders the systemd units (file names and contents)
+    ///
+    /// NB: the sync mode is a oneshot service, started by a timer
+    pub fn systemd_units(&self) -> Vec<(String, String)> {
+        let exec = self
+            .command()
+            .iter()
+            .map(|arg| systemd_quote(arg))
+            .collect::<Vec<_>>()
+            .join(" ");
+        match self.mode {
+            Mode::Watch => vec![(
+                format!("{UNIT_NAME}.service"),
+                format!(
+                    "[Unit]\nDescription=Newsie feeds watch\n\n\
+                     [Service]\nExecStart={exec}\nRestart=on-failure\nRestartSec=60\n\n\
+                     [Install]\nWantedBy=default.target\n"
+                ),
+            )],
+            Mode::Sync => vec![
+                (
+                    format!("{UNIT_NAME}.service"),
+                    format!(
+                        "[Unit]\nDescription=Newsie feeds sync\n\n\
+                         [Service]\nType=oneshot\nExecStart={exec}\n"
+                    ),
+                ),
+                (
+                    format!("{UNIT_NAME}.timer"),
+                    format!(
+                        "[Unit]\nDescription=Newsie feeds sync timer\n\n\
+                         [Timer]\nOnBootSec=5min\nOnUnitActiveSec={}min\n\n\
+                         [Install]\nWantedBy=timers.target\n",
+                        self.interval
+                    ),
+                ),
+            ],
+        }
+    }
+
+    /// Renders the launchd agent
+    pub fn launchd_plist(&self) -> String {
+        let args = self
+            .command()
+            .iter()
+            .map(|arg| format!("    <string>{}</string>\n", escape(arg)))
+            .collect::<String>();
+        let schedule = match self.mode {
+            Mode::Watch => "  <key>KeepAlive</key>\n  <true/>\n".to_string(),
+            Mode::Sync => format!(
+                "  <key>StartInterval</key>\n  <integer>{}</integer>\n",
+                self.interval * 60
+            ),
+        };
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+             <plist version=\"1.0\">\n<dict>\n\
+             \x20 <key>Label</key>\n  <string>{LAUNCHD_LABEL}</string>\n\
+             \x20 <key>ProgramArguments</key>\n  <array>\n{args}  </array>\n\
+             {schedule}\
+             \x20 <key>RunAtLoad</key>\n  <true/>\n\
+             </dict>\n</plist>\n"
+        )
+    }
+}
+
+/// Installs (or replaces) the service, and starts it
+pub fn install(def: &Definition) -> Result<Vec<PathBuf>, Error> {
+    if cfg!(target_os = "macos") {
+        let path = launchd_file()?;
+        // NB: a loaded agent is not reloaded otherwise
+        unload_launchd(&path)?;
+        write(&path, &def.launchd_plist())?;
+        run("launchctl", &["load", "-w", &path.display().to_string()])?;
+        Ok(vec![path])
+    } else if cfg!(target_os = "linux") {
+        // NB: a previous unit of the other mode is removed
+        uninstall()?;
+        let dir = systemd_dir()?;
+        let mut paths = vec![];
+        for (name, unit) in def.systemd_units() {
+            let path = dir.join(name);
+            write(&path, &unit)?;
+            paths.push(path);
+        }
+        run("systemctl", &["--user", "daemon-reload"])?;
+        let unit = match def.mode {
+            Mode::Watch => format!("{UNIT_NAME}.service"),
+            Mode::Sync => format!("{UNIT_NAME}.timer"),
+        };
+        run("systemctl", &["--user", "enable", "--now", &unit])?;
+        Ok(paths)
+    } else {
+        Err(unsupported())
+    }
+}
+
+/// Prints the status of the service
+pub fn status() -> Result<(), Error> {
+    if cfg!(target_os = "macos") {
+        if !launchd_file()?.exists() {
+            return Err(Error::msg("the service is not installed"));
+        }
+        run("launchctl", &["list", LAUNCHD_LABEL])
+    } else if cfg!(target_os = "linux") {
+        let dir = systemd_dir()?;
+        if !dir.join(format!("{UNIT_NAME}.service")).exists() {
+            return Err(Error::msg("the service is not installed"));
+        }
+        let mut units = vec![format!("{UNIT_NAME}.service")];
+        if dir.join(format!("{UNIT_NAME}.timer")).exists() {
+            units.push(format!("{UNIT_NAME}.timer"));
+        }
+        // NB: `systemctl status` fails if a unit is inactive, which is not an error here
+        let mut args = vec!["--user", "status", "--no-pager"];
+        args.extend(units.iter().map(|u| u.as_str()));
+        Command::new("systemctl").args(args).status()?;
+        Ok(())
+    } else {
+        Err(unsupported())
+    }
+}
+
+/// Stops and removes the service, and returns the removed files
+pub fn uninstall() -> Result<Vec<PathBuf>, Error> {
+    let mut removed = vec![];
+    if cfg!(target_os = "macos") {
+        let path = launchd_file()?;
+        if path.exists() {
+            unload_launchd(&path)?;
+            fs::remove_file(&path)?;
+            removed.push(path);
+        }
+    } else if cfg!(target_os = "linux") {
+        let dir = systemd_dir()?;
+        for ext in ["timer", "service"] {
+            let path = dir.join(format!("{UNIT_NAME}.{ext}"));
+            if path.exists() {
+                run(
+                    "systemctl",
+                    &["--user", "disable", "--now", &format!("{UNIT_NAME}.{ext}")],
+                )?;
+                fs::remove_file(&path)?;
+                removed.push(path);
+            }
+        }
+        if !removed.is_empty() {
+            run("systemctl", &["--user", "daemon-reload"])?;
+        }
+    } else {
+        return Err(unsupported());
+    }
+    Ok(removed)
+}
+
+/// Returns the folder of the systemd user units
+fn systemd_dir() -> Result<PathBuf, Error> {
+    let config = dirs::config_dir().ok_or(Error::msg("no config folder"))?;
+    Ok(config.join("systemd/user"))
+}
+
+/// Returns the path of the launchd agent
+fn launchd_file() -> Result<PathBuf, Error> {
+    let home = dirs::home_dir().ok_or(Error::msg("no home folder"))?;
+    Ok(home.join(format!("Library/LaunchAgents/{LAUNCHD_LABEL}.plist")))
+}
+
+/// Unloads the launchd agent, if it is installed and loaded
+///
+/// NB: `launchctl unload` fails if the agent is not loaded (eg. unloaded by the user)
+fn unload_launchd(path: &Path) -> Result<(), Error> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let loaded = Command::new("launchctl")
+        .args(["list", LAUNCHD_LABEL])
+        .output()
+        .map_err(|err| Error::msg(format!("failed to run launchctl: {err}")))?
+        .status
+        .success();
+    if loaded {
+        run("launchctl", &["unload", "-w", &path.display().to_string()])?;
+    }
+    Ok(())
+}
+
+/// Writes a file, and creates its folder if missing
+fn write(path: &Path, content: &str) -> Result<(), Error> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    Ok(fs::write(path, content)?)
+}
+
+/// Runs a command, and fails if it is not successful
+fn run(program: &str, args: &[&str]) -> Result<(), Error> {
+    let status = Command::new(program)
+        .args(args)
+        .status()
+        .map_err(|err| Error::msg(format!("failed to run {program}: {err}")))?;
+    if !status.success() {
+        return Err(Error::msg(format!(
+            "{program} {} failed ({status})",
+            args.join(" ")
+        )));
+    }
+    Ok(())
+}
+
+/// Returns the error of an unsupported OS
+fn unsupported() -> Error {
+    Error::msg("the service is only supported with systemd (Linux) and launchd (macOS)")
+}
+
+/// Quotes an argument of a systemd command line
+fn systemd_quote(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains(|c: char| c.is_whitespace() || "\"'\\%$".contains(c)) {
+        return arg.to_string();
+    }
+    let escaped = arg
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('%', "%%")
+        .replace('$', "$$");
+    format!("\"{escaped}\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definition(mode: Mode) -> Definition {
+        Definition {
+            exe: PathBuf::from("/home/me/.cargo/bin/newsie-cli"),
+            args: vec!["--profile".to_string(), "my work".to_string()],
+            mode,
+            interval: 30,
+        }
+    }
+
+    #[test]
+    fn test_systemd_units() {
+        let units = definition(Mode::Watch).systemd_units();
+        assert_eq!(units.len(), 1);
+        assert!(units[0].1.contains(
+            "ExecStart=/home/me/.cargo/bin/newsie-cli --profile \"my work\" watch --interval 30\n"
+        ));
+
+        // NB: the services retry on their own, and do not wait for the network
+        assert!(!units[0].1.contains("After="));
+
+        let units = definition(Mode::Sync).systemd_units();
+        assert_eq!(units[1].0, "newsie-cli.timer");
+        assert!(units[0].1.contains("Type=oneshot\n"));
+        assert!(units[1].1.contains("OnUnitActiveSec=30min\n"));
+    }
+
+    #[test]
+    fn test_launchd_plist() {
+        let plist = definition(Mode::Sync).launchd_plist();
+        assert!(plist.contains("    <string>my work</string>\n    <string>sync</string>\n"));
+        assert!(plist.contains("<key>StartInterval</key>\n  <integer>1800</integer>\n"));
+    }
+}
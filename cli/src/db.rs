@@ -1,11 +1,21 @@
 //! SQlite DB
 
-use std::{fs, path::PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::PathBuf,
+};
 
 use anyhow::{Error, Ok};
-use rusqlite::Connection;
+use atom_syndication::FixedDateTime;
+use newsie_client::summary::ArticleSummary;
+use rusqlite::{Connection, Row, ToSql};
 
-use crate::model::{Config, Feed};
+use crate::{
+    model::{Article, Config, Feed, FeedHealth, FeedStats, ReadingStats},
+    sync::FeedNames,
+    util::html_to_text,
+};
 
 /// Database client
 pub struct DbClient {
@@ -67,23 +77,60 @@ impl DbClient {
 
     /// Inititializes the SQLite schema
     pub fn init_db_schema(&self) -> Result<(), Error> {
-        Ok(self.conn.execute_batch("
+        self.conn.execute_batch("
             CREATE TABLE config (id INTEGER PRIMARY KEY, api_url TEXT NOT NULL, token TEXT);
             CREATE TABLE feeds (id INTEGER PRIMARY KEY AUTOINCREMENT, url TEXT NOT NULL UNIQUE, name TEXT, folder TEXT);
-        ")?)
+        ")?;
+        self.migrate_db_schema()
+    }
+
+    /// Adds the tables (and columns) missing from a schema initialized by a previous version
+    pub fn migrate_db_schema(&self) -> Result<(), Error> {
+        self.conn.execute_batch("
+            CREATE TABLE IF NOT EXISTS articles (guid TEXT PRIMARY KEY, feed_url TEXT NOT NULL, read INTEGER NOT NULL DEFAULT 0);
+            CREATE TABLE IF NOT EXISTS stars (url TEXT PRIMARY KEY, starred_at TEXT NOT NULL);
+            CREATE TABLE IF NOT EXISTS article_cache (guid TEXT PRIMARY KEY, feed_url TEXT NOT NULL, url TEXT NOT NULL, title TEXT, date TEXT, content TEXT);
+            CREATE TABLE IF NOT EXISTS synced_feeds (url TEXT PRIMARY KEY, name TEXT);
+            CREATE TABLE IF NOT EXISTS sync (id INTEGER PRIMARY KEY, synced_at TEXT NOT NULL);
+            CREATE TABLE IF NOT EXISTS feed_status (url TEXT PRIMARY KEY, fetched_at TEXT NOT NULL, error TEXT);
+            CREATE TABLE IF NOT EXISTS listing (n INTEGER PRIMARY KEY, guid TEXT NOT NULL, url TEXT NOT NULL);
+            CREATE VIRTUAL TABLE IF NOT EXISTS article_search USING fts5(guid UNINDEXED, title, content);
+            CREATE TABLE IF NOT EXISTS summary_usage (day TEXT PRIMARY KEY, count INTEGER NOT NULL);
+            CREATE TABLE IF NOT EXISTS summary_cache (url TEXT PRIMARY KEY, summary TEXT NOT NULL, cached_at TEXT NOT NULL);
+            CREATE TABLE IF NOT EXISTS article_content (guid TEXT PRIMARY KEY, content TEXT NOT NULL, size INTEGER NOT NULL, accessed_at TEXT NOT NULL);
+        ")?;
+        self.add_column("config", "proxy", "TEXT")?;
+        self.add_column("articles", "read_at", "TEXT")?;
+        Ok(())
+    }
+
+    /// Adds a column to a table, if missing
+    fn add_column(&self, table: &str, column: &str, def: &str) -> Result<(), Error> {
+        let exists = self
+            .conn
+            .prepare("SELECT name FROM pragma_table_info(?1) WHERE name = ?2")?
+            .exists([table, column])?;
+        if !exists {
+            let sql = format!("ALTER TABLE {table} ADD COLUMN {column} {def}");
+            self.conn.execute(&sql, [])?;
+        }
+        Ok(())
     }
 }
 
 impl DbClient {
     /// Reads the configuration
     pub fn read_config(&self) -> Result<Option<Config>, Error> {
-        let mut stmt = self.conn.prepare("SELECT * FROM config WHERE id=1")?;
+        let mut stmt = self
+            .conn
+            .prepare("SELECT api_url, token, proxy FROM config WHERE id=1")?;
         let mut rows = stmt.query([])?;
         let mut configs = vec![];
         while let Some(row) = rows.next()? {
             configs.push(Config {
-                api_url: row.get(1)?,
-                token: row.get(2)?,
+                api_url: row.get(0)?,
+                token: row.get(1)?,
+                proxy: row.get(2)?,
             })
         }
         Ok(configs.into_iter().next())
@@ -92,8 +139,8 @@ impl DbClient {
     /// Creates the config entry
     pub fn create_config(&self, config: Config) -> Result<Config, Error> {
         let _n_inserted = self.conn.execute(
-            "INSERT INTO config (id, api_url, token) VALUES (1, ?1, ?2)",
-            (&config.api_url, &config.token),
+            "INSERT INTO config (id, api_url, token, proxy) VALUES (1, ?1, ?2, ?3)",
+            (&config.api_url, &config.token, &config.proxy),
         )?;
         Ok(config)
     }
@@ -101,8 +148,8 @@ impl DbClient {
     /// Updates the configuration
     pub fn update_config(&self, config: Config) -> Result<Config, Error> {
         let _n_updated = self.conn.execute(
-            "UPDATE config SET api_url = ?1, token = ?2 WHERE id = 1",
-            (&config.api_url, &config.token),
+            "UPDATE config SET api_url = ?1, token = ?2, proxy = ?3 WHERE id = 1",
+            (&config.api_url, &config.token, &config.proxy),
         )?;
         Ok(config)
     }
@@ -137,12 +184,486 @@ impl DbClient {
         Ok(feeds)
     }
 
+    /// Records the result of the last fetch of a feed (`None` if it succeeded)
+    pub fn set_feed_status(&self, feed_url: &str, error: Option<&str>) -> Result<(), Error> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO feed_status (url, fetched_at, error) VALUES (?1, datetime('now'), ?2)",
+            (feed_url, error),
+        )?;
+        Ok(())
+    }
+
+    /// Reads the stats of the feeds (by url)
+    pub fn get_feed_stats(&self) -> Result<HashMap<String, FeedStats>, Error> {
+        let mut stats = HashMap::<String, FeedStats>::new();
+
+        let mut stmt = self
+            .conn
+            .prepare("SELECT feed_url, COUNT(*) FROM articles WHERE read = 0 GROUP BY feed_url")?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            stats.entry(row.get(0)?).or_default().unread = row.get(1)?;
+        }
+
+        let mut stmt = self
+            .conn
+            .prepare("SELECT feed_url, date FROM article_cache WHERE date IS NOT NULL")?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let date: String = row.get(1)?;
+            let Ok(date) = FixedDateTime::parse_from_rfc3339(&date) else {
+                continue;
+            };
+            let stats = stats.entry(row.get(0)?).or_default();
+            if !stats.last_updated.is_some_and(|d| d >= date) {
+                stats.last_updated = Some(date);
+            }
+        }
+
+        let mut stmt = self
+            .conn
+            .prepare("SELECT url, fetched_at, error FROM feed_status")?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            stats.entry(row.get(0)?).or_default().health = match row.get::<_, Option<String>>(2)? {
+                Some(error) => FeedHealth::Failing(error),
+                None => FeedHealth::Ok,
+            };
+        }
+        Ok(stats)
+    }
+
+    /// Reads the reading stats, over a number of days
+    ///
+    /// NB: the articles read before the read dates were recorded are only counted in the top
+    /// feeds
+    pub fn get_reading_stats(&self, days: u32, top: usize) -> Result<ReadingStats, Error> {
+        let since = format!("-{days} days");
+        let counts = |sql: &str, params: &[&dyn ToSql]| -> Result<Vec<(String, usize)>, Error> {
+            let mut stmt = self.conn.prepare(sql)?;
+            let counts = stmt
+                .query_map(params, |row| {
+                    rusqlite::Result::Ok((row.get(0)?, row.get(1)?))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(counts)
+        };
+        Ok(ReadingStats {
+            per_day: counts(
+                "SELECT date(read_at, 'localtime') AS d, COUNT(*) FROM articles WHERE read_at >= datetime('now', ?1) GROUP BY d ORDER BY d DESC",
+                &[&since],
+            )?,
+            per_week: counts(
+                "SELECT strftime('%Y-W%W', read_at, 'localtime') AS w, COUNT(*) FROM articles WHERE read_at >= datetime('now', ?1) GROUP BY w ORDER BY w DESC",
+                &[&since],
+            )?,
+            top_feeds: counts(
+                "SELECT feed_url, COUNT(*) AS n FROM articles WHERE read = 1 GROUP BY feed_url ORDER BY n DESC LIMIT ?1",
+                &[&top],
+            )?,
+            summaries: counts(
+                "SELECT day, count FROM summary_usage WHERE day >= date('now', 'localtime', ?1) ORDER BY day DESC",
+                &[&since],
+            )?,
+        })
+    }
+
+    /// Checks if the content of an article is stored
+    pub fn has_content(&self, guid: &str) -> Result<bool, Error> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT guid FROM article_content WHERE guid = ?1")?;
+        Ok(stmt.exists([guid])?)
+    }
+
+    /// Stores the content of an article
+    pub fn store_content(&self, guid: &str, content: &str) -> Result<(), Error> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO article_content (guid, content, size, accessed_at) VALUES (?1, ?2, ?3, datetime('now'))",
+            (guid, content, content.len()),
+        )?;
+        Ok(())
+    }
+
+    /// Reads the stored contents of articles (by guid), and marks them as accessed
+    pub fn get_contents(&self, guids: &[&str]) -> Result<HashMap<String, String>, Error> {
+        let mut select = self
+            .conn
+            .prepare("SELECT content FROM article_content WHERE guid = ?1")?;
+        let mut touch = self
+            .conn
+            .prepare("UPDATE article_content SET accessed_at = datetime('now') WHERE guid = ?1")?;
+        let mut contents = HashMap::new();
+        for guid in guids {
+            let mut rows = select.query_map([guid], |row| row.get::<_, String>(0))?;
+            if let Some(content) = rows.next().transpose()? {
+                touch.execute([guid])?;
+                contents.insert(guid.to_string(), content);
+            }
+        }
+        Ok(contents)
+    }
+
+    /// Evicts the least recently accessed contents beyond a total size (in bytes), and returns
+    /// their number
+    pub fn evict_contents(&mut self, max_size: usize) -> Result<usize, Error> {
+        let trx = self.conn.transaction()?;
+        let evicted = {
+            let mut stmt =
+                trx.prepare("SELECT guid, size FROM article_content ORDER BY accessed_at DESC")?;
+            let rows = stmt
+                .query_map([], |row| {
+                    rusqlite::Result::Ok((row.get::<_, String>(0)?, row.get::<_, usize>(1)?))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut total = 0;
+            let mut evicted = vec![];
+            for (guid, size) in rows {
+                total += size;
+                if total > max_size {
+                    evicted.push(guid);
+                }
+            }
+            evicted
+        };
+        for guid in &evicted {
+            trx.execute("DELETE FROM article_content WHERE guid = ?1", [guid])?;
+        }
+        trx.commit()?;
+        Ok(evicted.len())
+    }
+
+    /// Reads the cached summary of an article
+    pub fn get_cached_summary(&self, url: &str) -> Result<Option<ArticleSummary>, Error> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT summary FROM summary_cache WHERE url = ?1")?;
+        let mut rows = stmt.query_map([url], |row| row.get::<_, String>(0))?;
+        match rows.next().transpose()? {
+            Some(summary) => Ok(Some(serde_json::from_str(&summary)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Caches the summary of an article
+    pub fn cache_summary(&self, summary: &ArticleSummary) -> Result<(), Error> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO summary_cache (url, summary, cached_at) VALUES (?1, ?2, datetime('now'))",
+            (&summary.url, serde_json::to_string(summary)?),
+        )?;
+        Ok(())
+    }
+
+    /// Records the summarized articles of the day
+    pub fn record_summaries(&self, count: usize) -> Result<(), Error> {
+        self.conn.execute(
+            "INSERT INTO summary_usage (day, count) VALUES (date('now', 'localtime'), ?1) ON CONFLICT(day) DO UPDATE SET count = count + excluded.count",
+            [count],
+        )?;
+        Ok(())
+    }
+
+    /// Reads the guids of the seen articles of a feed
+    pub fn get_article_guids(&self, feed_url: &str) -> Result<HashSet<String>, Error> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT guid FROM articles WHERE feed_url = ?1")?;
+        let guids = stmt
+            .query_map([feed_url], |row| row.get::<_, String>(0))?
+            .collect::<Result<HashSet<_>, _>>()?;
+        Ok(guids)
+    }
+
+    /// Records the articles of a feed as seen, and returns the guids of the read ones
+    pub fn see_articles(&self, feed_url: &str, guids: &[&str]) -> Result<HashSet<String>, Error> {
+        let mut insert = self
+            .conn
+            .prepare("INSERT OR IGNORE INTO articles (guid, feed_url, read) VALUES (?1, ?2, 0)")?;
+        for guid in guids {
+            insert.execute((guid, feed_url))?;
+        }
+
+        let mut stmt = self
+            .conn
+            .prepare("SELECT guid FROM articles WHERE feed_url = ?1 AND read = 1")?;
+        let read = stmt
+            .query_map([feed_url], |row| row.get::<_, String>(0))?
+            .collect::<Result<HashSet<_>, _>>()?;
+        Ok(read)
+    }
+
+    /// Marks articles as read
+    pub fn mark_articles_read(&self, guids: &[&str]) -> Result<(), Error> {
+        let mut stmt = self
+            .conn
+            .prepare("UPDATE articles SET read = 1, read_at = datetime('now') WHERE guid = ?1")?;
+        for guid in guids {
+            stmt.execute([guid])?;
+        }
+        Ok(())
+    }
+
+    /// Marks the articles of a url as read
+    pub fn mark_url_read(&self, url: &str) -> Result<(), Error> {
+        self.conn.execute(
+            "UPDATE articles SET read = 1, read_at = datetime('now') WHERE guid = ?1 OR guid IN (SELECT guid FROM article_cache WHERE url = ?1)",
+            [url],
+        )?;
+        Ok(())
+    }
+
+    /// Replaces the last listing of articles (guids and urls, numbered from 1)
+    pub fn save_listing(&mut self, articles: &[(&str, &str)]) -> Result<(), Error> {
+        let trx = self.conn.transaction()?;
+        trx.execute("DELETE FROM listing", [])?;
+        for (i, (guid, url)) in articles.iter().enumerate() {
+            trx.execute(
+                "INSERT INTO listing (n, guid, url) VALUES (?1, ?2, ?3)",
+                (i + 1, guid, url),
+            )?;
+        }
+        trx.commit()?;
+        Ok(())
+    }
+
+    /// Reads an article of the last listing (guid and url), by number
+    pub fn get_listed(&self, n: usize) -> Result<Option<(String, String)>, Error> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT guid, url FROM listing WHERE n = ?1")?;
+        let mut rows =
+            stmt.query_map([n], |row| rusqlite::Result::Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(rows.next().transpose()?)
+    }
+
+    /// Marks all the seen articles of a feed as read, and returns their number
+    pub fn mark_feed_read(&self, feed_url: &str) -> Result<usize, Error> {
+        Ok(self.conn.execute(
+            "UPDATE articles SET read = 1, read_at = datetime('now') WHERE feed_url = ?1 AND read = 0",
+            [feed_url],
+        )?)
+    }
+
+    /// Caches the articles of a feed (for the offline mode), and indexes them for the search
+    pub fn cache_articles(&self, feed_url: &str, articles: &[Article]) -> Result<(), Error> {
+        let mut stmt = self.conn.prepare(
+            "INSERT OR REPLACE INTO article_cache (guid, feed_url, url, title, date, content) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?;
+        let mut unindex = self
+            .conn
+            .prepare("DELETE FROM article_search WHERE guid = ?1")?;
+        let mut index = self
+            .conn
+            .prepare("INSERT INTO article_search (guid, title, content) VALUES (?1, ?2, ?3)")?;
+        for article in articles {
+            stmt.execute((
+                &article.guid,
+                feed_url,
+                &article.url,
+                &article.title,
+                article.date.map(|d| d.to_rfc3339()),
+                &article.content,
+            ))?;
+            unindex.execute([&article.guid])?;
+            index.execute((
+                &article.guid,
+                &article.title,
+                article.content.as_deref().map(html_to_text),
+            ))?;
+        }
+        Ok(())
+    }
+
+    /// Searches the cached articles, the best matches first
+    ///
+    /// The query terms must all match the title or the content of an article. The read flag is
+    /// set.
+    pub fn search_articles(
+        &self,
+        query: &str,
+        feed_url: Option<&str>,
+        unread: bool,
+    ) -> Result<Vec<Article>, Error> {
+        let mut stmt = self.conn.prepare(
+            "SELECT c.guid, c.url, c.title, c.date, c.content, COALESCE(a.read, 0)
+            FROM article_search
+            JOIN article_cache c ON c.guid = article_search.guid
+            LEFT JOIN articles a ON a.guid = c.guid
+            WHERE article_search MATCH ?1
+            AND (?2 IS NULL OR c.feed_url = ?2)
+            AND (?3 = 0 OR COALESCE(a.read, 0) = 0)
+            ORDER BY article_search.rank",
+        )?;
+        let articles = stmt
+            .query_map((fts_query(query), feed_url, unread), |row| {
+                let mut article = cached_article(row)?;
+                article.read = row.get(5)?;
+                rusqlite::Result::Ok(article)
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(articles)
+    }
+
+    /// Reads the cached articles of a feed, the latest first
+    ///
+    /// NB: the read flag is not set
+    pub fn get_cached_articles(&self, feed_url: &str) -> Result<Vec<Article>, Error> {
+        let mut stmt = self.conn.prepare(
+            "SELECT guid, url, title, date, content FROM article_cache WHERE feed_url = ?1 ORDER BY date DESC",
+        )?;
+        let articles = stmt
+            .query_map([feed_url], cached_article)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(articles)
+    }
+
+    /// Stars an article, and returns `false` if it was starred already
+    pub fn star(&self, url: &str) -> Result<bool, Error> {
+        let n = self.conn.execute(
+            "INSERT OR IGNORE INTO stars (url, starred_at) VALUES (?1, datetime('now'))",
+            [url],
+        )?;
+        Ok(n > 0)
+    }
+
+    /// Unstars an article, and returns `false` if it was not starred
+    pub fn unstar(&self, url: &str) -> Result<bool, Error> {
+        let n = self
+            .conn
+            .execute("DELETE FROM stars WHERE url = ?1", [url])?;
+        Ok(n > 0)
+    }
+
+    /// Reads the starred articles (url and date), the latest first
+    pub fn get_stars(&self) -> Result<Vec<(String, String)>, Error> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT url, starred_at FROM stars ORDER BY starred_at DESC")?;
+        let stars = stmt
+            .query_map([], |row| rusqlite::Result::Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(stars)
+    }
+
     /// Remove feeds
-    pub async fn remove_feeds(&mut self, feeds_urls: Vec<String>) -> Result<(), Error> {
-        let _n_deleted = self.conn.execute(
-            "DELETE FROM feeds WHERE url IN (?1)",
-            [feeds_urls.into_iter().collect::<Vec<_>>().join(", ")],
+    pub async fn remove_feeds(&mut self, feeds_urls: Vec<String>) -> Result<Vec<String>, Error> {
+        let trx = self.conn.transaction()?;
+        let mut removed = vec![];
+        for url in feeds_urls {
+            if trx.execute("DELETE FROM feeds WHERE url = ?1", [&url])? > 0 {
+                removed.push(url);
+            }
+        }
+        trx.commit()?;
+        Ok(removed)
+    }
+
+    /// Moves a feed to a folder (`None` for no folder), and returns `false` if it is unknown
+    pub fn move_feed(&self, url: &str, folder: Option<&str>) -> Result<bool, Error> {
+        let n = self
+            .conn
+            .execute("UPDATE feeds SET folder = ?2 WHERE url = ?1", (url, folder))?;
+        Ok(n > 0)
+    }
+
+    /// Reads the folders, with their number of feeds
+    pub fn get_folders(&self) -> Result<Vec<(String, usize)>, Error> {
+        let mut stmt = self.conn.prepare(
+            "SELECT folder, COUNT(*) FROM feeds WHERE folder IS NOT NULL AND folder != '' GROUP BY folder ORDER BY folder",
+        )?;
+        let folders = stmt
+            .query_map([], |row| rusqlite::Result::Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(folders)
+    }
+
+    /// Renames a folder, and returns the number of its feeds
+    pub fn rename_folder(&self, name: &str, new_name: &str) -> Result<usize, Error> {
+        Ok(self.conn.execute(
+            "UPDATE feeds SET folder = ?2 WHERE folder = ?1",
+            (name, new_name),
+        )?)
+    }
+
+    /// Removes a folder (its feeds are kept, without folder), and returns the number of its feeds
+    pub fn remove_folder(&self, name: &str) -> Result<usize, Error> {
+        Ok(self
+            .conn
+            .execute("UPDATE feeds SET folder = NULL WHERE folder = ?1", [name])?)
+    }
+
+    /// Reads the feeds of the last sync
+    pub fn get_synced_feeds(&self) -> Result<FeedNames, Error> {
+        let mut stmt = self.conn.prepare("SELECT url, name FROM synced_feeds")?;
+        let feeds = stmt
+            .query_map([], |row| rusqlite::Result::Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<FeedNames, _>>()?;
+        Ok(feeds)
+    }
+
+    /// Saves the feeds of a sync, as the local feeds and the base of the next sync
+    ///
+    /// NB: the folders are local, and kept
+    pub fn save_synced_feeds(&mut self, feeds: &FeedNames) -> Result<(), Error> {
+        let trx = self.conn.transaction()?;
+        let urls = trx
+            .prepare("SELECT url FROM feeds")?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        for url in urls.iter().filter(|url| !feeds.contains_key(*url)) {
+            trx.execute("DELETE FROM feeds WHERE url = ?1", [url])?;
+        }
+        trx.execute("DELETE FROM synced_feeds", [])?;
+        for (url, name) in feeds {
+            trx.execute(
+                "INSERT INTO feeds (url, name) VALUES (?1, ?2) ON CONFLICT (url) DO UPDATE SET name = excluded.name",
+                (url, name),
+            )?;
+            trx.execute(
+                "INSERT INTO synced_feeds (url, name) VALUES (?1, ?2)",
+                (url, name),
+            )?;
+        }
+        trx.execute(
+            "INSERT OR REPLACE INTO sync (id, synced_at) VALUES (1, datetime('now'))",
+            [],
         )?;
+        trx.commit()?;
         Ok(())
     }
 }
+
+/// Reads a cached article (guid, url, title, date and content columns)
+fn cached_article(row: &Row) -> rusqlite::Result<Article> {
+    let date: Option<String> = row.get(3)?;
+    rusqlite::Result::Ok(Article {
+        guid: row.get(0)?,
+        url: row.get(1)?,
+        title: row.get(2)?,
+        date: date.and_then(|d| FixedDateTime::parse_from_rfc3339(&d).ok()),
+        content: row.get(4)?,
+        read: false,
+    })
+}
+
+/// Converts a search query to an FTS5 query
+///
+/// NB: the terms are quoted, so that the FTS5 operators and punctuation are matched literally
+fn fts_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fts_query() {
+        assert_eq!(fts_query("rust  async"), "\"rust\" \"async\"");
+        assert_eq!(fts_query("a\"b OR c-d"), "\"a\"\"b\" \"OR\" \"c-d\"");
+    }
+}
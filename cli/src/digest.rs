@@ -0,0 +1,177 @@
+//! Digest
+//!
+//! The unread articles of a period are compiled, by feed, with their summaries, as a document
+//! which can be piped to a mailer or saved to a notes folder.
+
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+use anyhow::Error;
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::{
+    model::{Article, Feed},
+    opml::escape,
+};
+
+/// Digest format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Markdown
+    Md,
+    /// HTML
+    Html,
+    /// Plain text
+    Text,
+}
+
+/// Digest entry
+#[derive(Serialize)]
+pub struct Entry {
+    /// Feed
+    pub feed: Feed,
+    /// Article
+    pub article: Article,
+    /// Summary (if the article could be summarized)
+    pub summary: Option<String>,
+}
+
+/// Parses a duration, eg. `24h` (with a `m`, `h`, `d` or `w` unit)
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid duration '{value}' (eg. 30m, 24h, 7d)");
+    let (n, unit) = value.split_at(value.find(|c: char| !c.is_ascii_digit()).unwrap_or(0));
+    let n = n.parse::<u64>().map_err(|_| invalid())?;
+    let secs = match unit {
+        "m" => 60,
+        "h" => 3_600,
+        "d" => 86_400,
+        "w" => 604_800,
+        _ => return Err(invalid()),
+    };
+    Ok(Duration::from_secs(n * secs))
+}
+
+/// Renders the digest
+///
+/// NB: the entries are grouped by feed, in their order
+pub fn render(title: &str, entries: &[Entry], format: Format) -> String {
+    let mut groups: Vec<(&Feed, Vec<&Entry>)> = vec![];
+    for entry in entries {
+        match groups.iter_mut().find(|(f, _)| f.url == entry.feed.url) {
+            Some((_, group)) => group.push(entry),
+            None => groups.push((&entry.feed, vec![entry])),
+        }
+    }
+
+    let mut doc = String::new();
+    match format {
+        Format::Md => {
+            doc.push_str(&format!("# {title}\n"));
+            for (feed, entries) in groups {
+                doc.push_str(&format!("\n## {}\n\n", feed_name(feed)));
+                for entry in entries {
+                    let article = &entry.article;
+                    doc.push_str(&format!("- [{}]({})", article_title(article), article.url));
+                    if let Some(summary) = &entry.summary {
+                        doc.push_str(&format!("\n  {summary}"));
+                    }
+                    doc.push('\n');
+                }
+            }
+        }
+        Format::Html => {
+            doc.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+            doc.push_str(&format!("<title>{}</title>\n", escape(title)));
+            doc.push_str("</head>\n<body>\n");
+            doc.push_str(&format!("<h1>{}</h1>\n", escape(title)));
+            for (feed, entries) in groups {
+                doc.push_str(&format!("<h2>{}</h2>\n<ul>\n", escape(feed_name(feed))));
+                for entry in entries {
+                    let article = &entry.article;
+                    doc.push_str(&format!(
+                        "<li><a href=\"{}\">{}</a>",
+                        escape(&article.url),
+                        escape(article_title(article))
+                    ));
+                    if let Some(summary) = &entry.summary {
+                        doc.push_str(&format!("<p>{}</p>", escape(summary)));
+                    }
+                    doc.push_str("</li>\n");
+                }
+                doc.push_str("</ul>\n");
+            }
+            doc.push_str("</body>\n</html>\n");
+        }
+        Format::Text => {
+            doc.push_str(&format!("{title}\n{}\n", "=".repeat(title.chars().count())));
+            for (feed, entries) in groups {
+                let name = feed_name(feed);
+                doc.push_str(&format!("\n{name}\n{}\n", "-".repeat(name.chars().count())));
+                for entry in entries {
+                    let article = &entry.article;
+                    doc.push_str(&format!(
+                        "\n* {}\n  {}\n",
+                        article_title(article),
+                        article.url
+                    ));
+                    if let Some(summary) = &entry.summary {
+                        doc.push_str(&format!("  {summary}\n"));
+                    }
+                }
+            }
+        }
+    }
+    doc
+}
+
+/// Sends the digest by email, with the local `sendmail`
+pub fn send_email(to: &str, subject: &str, digest: &str, format: Format) -> Result<(), Error> {
+    let content_type = match format {
+        Format::Html => "text/html",
+        Format::Md | Format::Text => "text/plain",
+    };
+    let mut child = Command::new("sendmail")
+        .arg("-t")
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|err| Error::msg(format!("failed to run sendmail: {err}")))?;
+    let mut stdin = child.stdin.take().unwrap();
+    write!(
+        stdin,
+        "To: {to}\nSubject: {subject}\nMIME-Version: 1.0\nContent-Type: {content_type}; charset=utf-8\n\n{digest}"
+    )?;
+    drop(stdin);
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(Error::msg(format!("sendmail failed ({status})")));
+    }
+    Ok(())
+}
+
+/// Returns the name of a feed (its url if unnamed)
+fn feed_name(feed: &Feed) -> &str {
+    feed.name.as_deref().unwrap_or(&feed.url)
+}
+
+/// Returns the title of an article (its url if untitled)
+fn article_title(article: &Article) -> &str {
+    article.title.as_deref().unwrap_or(&article.url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("24h"), Ok(Duration::from_secs(86_400)));
+        assert_eq!(parse_duration("30m"), Ok(Duration::from_secs(1_800)));
+        assert_eq!(parse_duration("2w"), Ok(Duration::from_secs(1_209_600)));
+        assert!(parse_duration("24").is_err());
+        assert!(parse_duration("h").is_err());
+    }
+}
@@ -0,0 +1,148 @@
+//! Feed discovery
+//!
+//! The feeds of a website are advertised by the `<link rel="alternate">` tags of its pages,
+//! with a RSS or Atom type. Without them, the usual feed paths of the website are tried.
+
+use std::{collections::HashMap, fmt::Display};
+
+use reqwest::Url;
+
+/// Feed types advertised by the websites
+const FEED_TYPES: [&str; 3] = [
+    "application/rss+xml",
+    "application/atom+xml",
+    "application/feed+xml",
+];
+
+/// Usual feed paths
+pub const COMMON_PATHS: [&str; 6] = [
+    "/feed",
+    "/rss",
+    "/feed.xml",
+    "/rss.xml",
+    "/atom.xml",
+    "/index.xml",
+];
+
+/// Discovered feed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Discovered {
+    /// Feed url
+    pub url: String,
+    /// Feed title (as advertised)
+    pub title: Option<String>,
+}
+
+impl Display for Discovered {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.title {
+            Some(title) => write!(f, "{title} ({})", self.url),
+            None => write!(f, "{}", self.url),
+        }
+    }
+}
+
+/// Finds the feeds advertised by an HTML page, with their urls resolved against the page url
+pub fn find_feeds(html: &str, base: &Url) -> Vec<Discovered> {
+    let mut feeds: Vec<Discovered> = vec![];
+    let lower = html.to_ascii_lowercase();
+    let mut offset = 0;
+    while let Some(start) = lower[offset..].find("<link") {
+        let start = offset + start;
+        let Some(end) = lower[start..].find('>') else {
+            break;
+        };
+        let end = start + end;
+        offset = end;
+
+        let attrs = attributes(&html[start + "<link".len()..end]);
+        let rel = attrs.get("rel").map(|r| r.to_ascii_lowercase());
+        let r#type = attrs.get("type").map(|t| t.to_ascii_lowercase());
+        if !rel.is_some_and(|r| r.split_whitespace().any(|r| r == "alternate"))
+            || !r#type.is_some_and(|t| FEED_TYPES.contains(&t.trim()))
+        {
+            continue;
+        }
+        let Some(url) = attrs.get("href").and_then(|h| base.join(h).ok()) else {
+            continue;
+        };
+        if feeds.iter().any(|f| f.url == url.as_str()) {
+            continue;
+        }
+        feeds.push(Discovered {
+            url: url.to_string(),
+            title: attrs.get("title").filter(|t| !t.is_empty()).cloned(),
+        });
+    }
+    feeds
+}
+
+/// Parses the attributes of a tag (names in lowercase, values unquoted)
+fn attributes(tag: &str) -> HashMap<String, String> {
+    let mut attrs = HashMap::new();
+    let mut rest = tag.trim_start();
+    while !rest.is_empty() {
+        let name_end = rest
+            .find(|c: char| c == '=' || c.is_whitespace() || c == '/')
+            .unwrap_or(rest.len());
+        let name = rest[..name_end].to_ascii_lowercase();
+        rest = rest[name_end..].trim_start();
+
+        let mut value = String::new();
+        if let Some(after) = rest.strip_prefix('=') {
+            let after = after.trim_start();
+            let (raw, next) = match after.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let inner = &after[1..];
+                    let end = inner.find(quote).unwrap_or(inner.len());
+                    (&inner[..end], inner.get(end + 1..).unwrap_or_default())
+                }
+                _ => {
+                    let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                    (&after[..end], &after[end..])
+                }
+            };
+            value = raw.replace("&amp;", "&");
+            rest = next.trim_start();
+        } else if name.is_empty() {
+            // NB: a stray character (eg. the `/` of a self-closing tag) is skipped
+            rest = rest.get(1..).unwrap_or_default().trim_start();
+            continue;
+        }
+        attrs.insert(name, value);
+    }
+    attrs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_feeds() {
+        let html = r#"<html><head>
+            <link rel="stylesheet" href="/style.css">
+            <LINK REL="alternate" TYPE="application/rss+xml" title="Blog" href="/feed.xml" />
+            <link rel=alternate type='application/atom+xml' href='https://example.com/atom?a=1&amp;b=2'>
+            <link rel="alternate" type="application/rss+xml" href="feed.xml">
+        </head></html>"#;
+        let base = Url::parse("https://example.com/blog/").unwrap();
+        assert_eq!(
+            find_feeds(html, &base),
+            vec![
+                Discovered {
+                    url: "https://example.com/feed.xml".to_string(),
+                    title: Some("Blog".to_string()),
+                },
+                Discovered {
+                    url: "https://example.com/atom?a=1&b=2".to_string(),
+                    title: None,
+                },
+                Discovered {
+                    url: "https://example.com/blog/feed.xml".to_string(),
+                    title: None,
+                },
+            ]
+        );
+    }
+}
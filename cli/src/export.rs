@@ -0,0 +1,180 @@
+//! Articles export
+//!
+//! Each cached article is written as a file (title, link, summary and content), eg. to be
+//! archived in a notes folder.
+
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Error;
+use clap::ValueEnum;
+
+use crate::{
+    model::{Article, Feed},
+    opml::escape,
+    util::html_to_text,
+};
+
+/// Maximum length of a file name (without its extension)
+const MAX_NAME_LEN: usize = 80;
+
+/// Export format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Markdown (with a YAML front matter)
+    Md,
+    /// HTML
+    Html,
+}
+
+impl Format {
+    /// Returns the file extension
+    fn extension(&self) -> &'static str {
+        match self {
+            Format::Md => "md",
+            Format::Html => "html",
+        }
+    }
+}
+
+/// Export writer, to a folder
+pub struct Exporter {
+    /// Output folder
+    dir: PathBuf,
+    /// Format
+    format: Format,
+    /// Written files (to avoid overwriting an article with another of the same title)
+    written: HashSet<PathBuf>,
+}
+
+impl Exporter {
+    /// Creates a new exporter
+    pub fn new(dir: &Path, format: Format) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            format,
+            written: HashSet::new(),
+        }
+    }
+
+    /// Writes an article, in the folder of its feed, and returns the file path
+    pub fn write(
+        &mut self,
+        feed: &Feed,
+        article: &Article,
+        summary: Option<&str>,
+    ) -> Result<PathBuf, Error> {
+        let dir = self
+            .dir
+            .join(slug(feed.name.as_deref().unwrap_or(&feed.url)));
+        fs::create_dir_all(&dir)?;
+
+        let name = slug(article.title.as_deref().unwrap_or(&article.url));
+        let ext = self.format.extension();
+        let mut path = dir.join(format!("{name}.{ext}"));
+        let mut n = 1;
+        while self.written.contains(&path) {
+            n += 1;
+            path = dir.join(format!("{name}-{n}.{ext}"));
+        }
+
+        fs::write(&path, render(feed, article, summary, self.format))?;
+        self.written.insert(path.clone());
+        Ok(path)
+    }
+}
+
+/// Renders an article
+pub fn render(feed: &Feed, article: &Article, summary: Option<&str>, format: Format) -> String {
+    let title = article.title.as_deref().unwrap_or(&article.url);
+    let feed_name = feed.name.as_deref().unwrap_or(&feed.url);
+    let date = article.date.map(|d| d.to_rfc3339());
+    let mut doc = String::new();
+    match format {
+        Format::Md => {
+            doc.push_str("---\n");
+            doc.push_str(&format!("title: {}\n", yaml_string(title)));
+            doc.push_str(&format!("url: {}\n", yaml_string(&article.url)));
+            doc.push_str(&format!("feed: {}\n", yaml_string(feed_name)));
+            if let Some(date) = &date {
+                doc.push_str(&format!("date: {date}\n"));
+            }
+            doc.push_str("---\n\n");
+            doc.push_str(&format!("# {title}\n\n<{}>\n", article.url));
+            if let Some(summary) = summary {
+                doc.push_str(&format!("\n> {}\n", summary.replace('\n', "\n> ")));
+            }
+            if let Some(content) = &article.content {
+                doc.push_str(&format!("\n{}\n", html_to_text(content)));
+            }
+        }
+        Format::Html => {
+            doc.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+            doc.push_str(&format!("<title>{}</title>\n", escape(title)));
+            doc.push_str("</head>\n<body>\n<article>\n");
+            doc.push_str(&format!("<h1>{}</h1>\n", escape(title)));
+            doc.push_str(&format!(
+                "<p><a href=\"{}\">{}</a> - {}",
+                escape(&article.url),
+                escape(&article.url),
+                escape(feed_name)
+            ));
+            if let Some(date) = &date {
+                doc.push_str(&format!(" - <time datetime=\"{date}\">{date}</time>"));
+            }
+            doc.push_str("</p>\n");
+            if let Some(summary) = summary {
+                doc.push_str(&format!("<blockquote>{}</blockquote>\n", escape(summary)));
+            }
+            // NB: the content is HTML already
+            if let Some(content) = &article.content {
+                doc.push_str(&format!("{content}\n"));
+            }
+            doc.push_str("</article>\n</body>\n</html>\n");
+        }
+    }
+    doc
+}
+
+/// Converts a title to a file name (lowercase alphanumeric words, joined by dashes)
+fn slug(title: &str) -> String {
+    let mut slug = String::new();
+    for word in title
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+    {
+        if slug.chars().count() + word.chars().count() >= MAX_NAME_LEN {
+            break;
+        }
+        if !slug.is_empty() {
+            slug.push('-');
+        }
+        slug.push_str(&word.to_lowercase());
+    }
+    if slug.is_empty() {
+        "untitled".to_string()
+    } else {
+        slug
+    }
+}
+
+/// Quotes a YAML string
+fn yaml_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slug() {
+        assert_eq!(slug("Hello, World: Rust 1.70!"), "hello-world-rust-1-70");
+        assert_eq!(slug("https://example.com/a"), "https-example-com-a");
+        assert_eq!(slug("?!"), "untitled");
+        assert!(slug(&"word ".repeat(100)).len() < MAX_NAME_LEN);
+    }
+}
@@ -0,0 +1,170 @@
+//! Subscriptions import
+//!
+//! The subscriptions of other readers are imported as feeds, their tags (or categories) being
+//! mapped to folders:
+//!
+//! - newsboat: the `urls` file, ie. a url per line followed by its tags, `~` prefixing the
+//!   title
+//! - miniflux: the JSON of the feeds (`GET /v1/feeds`), with their categories
+
+use anyhow::Error;
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::model::Feed;
+
+/// Import source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Source {
+    /// Newsboat urls file
+    Newsboat,
+    /// Miniflux feeds JSON
+    Miniflux,
+}
+
+/// Parses subscriptions
+pub fn parse(data: &str, source: Source) -> Result<Vec<Feed>, Error> {
+    match source {
+        Source::Newsboat => Ok(parse_newsboat(data)),
+        Source::Miniflux => parse_miniflux(data),
+    }
+}
+
+/// Parses a newsboat urls file
+///
+/// NB: the first tag is the folder, and the query feeds (`query:`), the commands (`exec:`,
+/// `filter:`) and the hidden tags (`!`) are skipped
+fn parse_newsboat(data: &str) -> Vec<Feed> {
+    let mut feeds = vec![];
+    for line in data.lines().map(|l| l.trim()) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut words = split_words(line).into_iter();
+        let Some(url) = words.next() else {
+            continue;
+        };
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            continue;
+        }
+        let (mut name, mut folder) = (None, None);
+        for word in words {
+            if word.starts_with('#') {
+                break;
+            } else if let Some(title) = word.strip_prefix('~') {
+                name = Some(title.to_string());
+            } else if !word.starts_with('!') && folder.is_none() {
+                folder = Some(word);
+            }
+        }
+        feeds.push(Feed { url, name, folder });
+    }
+    feeds
+}
+
+/// Splits a line in words (the quoted words may have spaces)
+fn split_words(line: &str) -> Vec<String> {
+    let mut words = vec![];
+    let mut word = String::new();
+    let mut quoted = false;
+    for c in line.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            c => word.push(c),
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+/// Miniflux feed
+#[derive(Deserialize)]
+struct MinifluxFeed {
+    /// Feed url
+    feed_url: String,
+    /// Title
+    #[serde(default)]
+    title: Option<String>,
+    /// Category
+    #[serde(default)]
+    category: Option<MinifluxCategory>,
+}
+
+/// Miniflux category
+#[derive(Deserialize)]
+struct MinifluxCategory {
+    /// Title
+    title: String,
+}
+
+/// Parses the miniflux feeds JSON
+fn parse_miniflux(data: &str) -> Result<Vec<Feed>, Error> {
+    let feeds = serde_json::from_str::<Vec<MinifluxFeed>>(data)
+        .map_err(|err| Error::msg(format!("invalid miniflux feeds: {err}")))?;
+    Ok(feeds
+        .into_iter()
+        .map(|feed| Feed {
+            url: feed.feed_url,
+            name: feed.title.filter(|t| !t.is_empty()),
+            folder: feed.category.map(|c| c.title),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_newsboat() {
+        let feeds = parse_newsboat(
+            r#"
+            # comment
+            https://example.com/feed.xml tech "~Example blog" "long reads"
+            http://example.org/rss !hidden news # other comment
+            "query:Unread:unread = \"yes\""
+            https://example.net/atom
+            "#,
+        );
+        let feeds = feeds
+            .iter()
+            .map(|f| (f.url.as_str(), f.name.as_deref(), f.folder.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            feeds,
+            vec![
+                (
+                    "https://example.com/feed.xml",
+                    Some("Example blog"),
+                    Some("tech")
+                ),
+                ("http://example.org/rss", None, Some("news")),
+                ("https://example.net/atom", None, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_miniflux() {
+        let feeds = parse_miniflux(
+            r#"[
+                {"id": 1, "feed_url": "https://example.com/feed.xml", "title": "Example", "category": {"id": 2, "title": "Tech"}},
+                {"id": 2, "feed_url": "https://example.org/rss", "title": ""}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(feeds[0].url, "https://example.com/feed.xml");
+        assert_eq!(feeds[0].name.as_deref(), Some("Example"));
+        assert_eq!(feeds[0].folder.as_deref(), Some("Tech"));
+        assert_eq!(feeds[1].name, None);
+        assert_eq!(feeds[1].folder, None);
+        assert!(parse_miniflux("{}").is_err());
+    }
+}
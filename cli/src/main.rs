@@ -3,10 +3,21 @@
 use crate::util::ResultExt;
 
 mod cmd;
+mod content;
+mod daemon;
 mod db;
+mod digest;
+mod discover;
+mod export;
+mod import;
 mod model;
+mod opml;
 mod svc;
+mod sync;
+mod trace;
+mod tui;
 mod util;
+mod watch;
 
 // pub mod auth;
 // pub mod feed;
@@ -1,7 +1,9 @@
 //! Models
 
 use anyhow::Error;
+use atom_syndication::FixedDateTime;
 use rss::validation::Validate;
+use serde::{Serialize, Serializer};
 
 /// Configuration
 #[derive(Debug, Clone)]
@@ -10,6 +12,8 @@ pub struct Config {
     pub api_url: String,
     /// Authentication token
     pub token: Option<String>,
+    /// HTTP proxy (the `HTTP_PROXY`, `HTTPS_PROXY` and `ALL_PROXY` variables are used if none)
+    pub proxy: Option<String>,
 }
 
 impl Default for Config {
@@ -17,25 +21,66 @@ impl Default for Config {
         Self {
             api_url: "http://localhost:3000".to_string(),
             token: None,
+            proxy: None,
         }
     }
 }
 
 /// A feed
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Feed {
     /// Feed URL
     pub url: String,
     /// Feed type
+    #[serde(skip)]
     pub r#type: FeedType,
     /// Feed name
     pub name: Option<String>,
     /// Folder
     pub folder: Option<String>,
     /// Articles
+    #[serde(skip)]
     pub articles: Vec<Article>,
 }
 
+/// Stats of a feed
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FeedStats {
+    /// Number of unread articles
+    pub unread: usize,
+    /// Date of the latest article
+    #[serde(serialize_with = "serialize_date")]
+    pub last_updated: Option<FixedDateTime>,
+    /// Health
+    pub health: FeedHealth,
+}
+
+/// Reading stats (counts by day, week, feed url)
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReadingStats {
+    /// Articles read per day, the latest first
+    pub per_day: Vec<(String, usize)>,
+    /// Articles read per week, the latest first
+    pub per_week: Vec<(String, usize)>,
+    /// Feeds with the most read articles
+    pub top_feeds: Vec<(String, usize)>,
+    /// Articles summarized per day, the latest first
+    pub summaries: Vec<(String, usize)>,
+}
+
+/// Health of a feed, from its last fetch
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "status", content = "error")]
+pub enum FeedHealth {
+    /// Never fetched
+    #[default]
+    Unknown,
+    /// Fetched
+    Ok,
+    /// Failed to fetch (with the error)
+    Failing(String),
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FeedType {
     Rss,
@@ -43,12 +88,21 @@ pub enum FeedType {
 }
 
 /// An article
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Article {
+    /// Unique identifier (the url if the feed has none)
+    pub guid: String,
     /// Article url
     pub url: String,
     /// Title
     pub title: Option<String>,
+    /// Publication date
+    #[serde(serialize_with = "serialize_date")]
+    pub date: Option<FixedDateTime>,
+    /// Content (HTML), or its excerpt
+    pub content: Option<String>,
+    /// Read flag (tracked locally)
+    pub read: bool,
 }
 
 impl From<rss::Channel> for Feed {
@@ -81,9 +135,16 @@ impl From<atom_syndication::Feed> for Feed {
 
 impl From<rss::Item> for Article {
     fn from(value: rss::Item) -> Self {
+        let url = value.link.unwrap_or_default();
         Article {
-            url: value.link.unwrap_or_default(),
+            guid: value.guid.map(|g| g.value).unwrap_or_else(|| url.clone()),
+            url,
             title: value.title,
+            date: value
+                .pub_date
+                .and_then(|d| FixedDateTime::parse_from_rfc2822(&d).ok()),
+            content: value.content.or(value.description),
+            read: false,
         }
     }
 }
@@ -91,49 +152,58 @@ impl From<rss::Item> for Article {
 impl From<atom_syndication::Entry> for Article {
     fn from(value: atom_syndication::Entry) -> Self {
         Article {
+            guid: value.id().to_string(),
             url: value.id().to_string(),
             title: Some(value.title.as_str().to_string()),
+            date: Some(value.published.unwrap_or(value.updated)),
+            content: value
+                .content
+                .and_then(|c| c.value)
+                .or(value.summary.map(|s| s.value)),
+            read: false,
         }
     }
 }
 
 impl Feed {
     /// Tries to load a RSS feed from its url
-    pub async fn from_url(url: &str) -> Result<Self, Error> {
-        let content = reqwest::get(url).await?.bytes().await?;
+    pub async fn from_url(http: &reqwest::Client, url: &str) -> Result<Self, Error> {
+        let content = http.get(url).send().await?.bytes().await?;
+        Self::parse(&content)
+    }
 
+    /// Tries to parse a RSS (or Atom) feed
+    pub fn parse(content: &[u8]) -> Result<Self, Error> {
         // try for RSS
-        match rss::Channel::read_from(&content[..]) {
-            Ok(channel) => {
-                channel.validate()?;
-                return Ok(channel.into());
-            }
-            Err(_err) => {
-                // continue
-            }
+        if let Ok(channel) = rss::Channel::read_from(content) {
+            channel.validate()?;
+            return Ok(channel.into());
         }
 
         // try for atom
-        match atom_syndication::Feed::read_from(&content[..]) {
-            Ok(feed) => {
-                return Ok(feed.into());
-            }
-            Err(err) => {
-                // continue
-            }
+        if let Ok(feed) = atom_syndication::Feed::read_from(content) {
+            return Ok(feed.into());
         }
 
         Err(Error::msg("invalid feed"))
     }
 }
 
+/// Serializes a date as RFC 3339
+fn serialize_date<S: Serializer>(
+    date: &Option<FixedDateTime>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    date.map(|d| d.to_rfc3339()).serialize(serializer)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[tokio::test]
     async fn test_rss_ok() {
-        let feed = Feed::from_url("https://news.ycombinator.com/rss")
+        let feed = Feed::from_url(&reqwest::Client::new(), "https://news.ycombinator.com/rss")
             .await
             .unwrap();
         assert_eq!(feed.r#type, FeedType::Rss);
@@ -142,6 +212,8 @@ mod tests {
     #[tokio::test]
     #[should_panic]
     async fn test_rss_err() {
-        let _feed = Feed::from_url("http://www.google.com").await.unwrap();
+        let _feed = Feed::from_url(&reqwest::Client::new(), "http://www.google.com")
+            .await
+            .unwrap();
     }
 }
@@ -0,0 +1,82 @@
+//! OPML
+//!
+//! The feeds are exported as an OPML document, the format shared by the feed readers. The feeds
+//! of a folder are nested in an outline named after the folder.
+
+use std::collections::BTreeMap;
+
+use crate::model::Feed;
+
+/// Generates the OPML document of feeds
+pub fn to_opml(feeds: &[Feed]) -> String {
+    let mut folders: BTreeMap<&str, Vec<&Feed>> = BTreeMap::new();
+    let mut root = vec![];
+    for feed in feeds {
+        match feed.folder.as_deref().filter(|f| !f.is_empty()) {
+            Some(folder) => folders.entry(folder).or_default().push(feed),
+            None => root.push(feed),
+        }
+    }
+
+    let mut opml = String::new();
+    opml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    opml.push_str("<opml version=\"2.0\">\n");
+    opml.push_str("  <head>\n    <title>Newsie feeds</title>\n  </head>\n");
+    opml.push_str("  <body>\n");
+    for (folder, feeds) in folders {
+        let folder = escape(folder);
+        opml.push_str(&format!(
+            "    <outline text=\"{folder}\" title=\"{folder}\">\n"
+        ));
+        for feed in feeds {
+            opml.push_str(&format!("      {}\n", outline(feed)));
+        }
+        opml.push_str("    </outline>\n");
+    }
+    for feed in root {
+        opml.push_str(&format!("    {}\n", outline(feed)));
+    }
+    opml.push_str("  </body>\n");
+    opml.push_str("</opml>\n");
+    opml
+}
+
+/// Returns the outline of a feed
+fn outline(feed: &Feed) -> String {
+    let url = escape(&feed.url);
+    let name = feed
+        .name
+        .as_deref()
+        .map(escape)
+        .unwrap_or_else(|| url.clone());
+    format!("<outline type=\"rss\" text=\"{name}\" title=\"{name}\" xmlUrl=\"{url}\"/>")
+}
+
+/// Escapes an XML attribute value (or text)
+pub fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            escape("https://host/feed?a=1&b=\"2\""),
+            "https://host/feed?a=1&amp;b=&quot;2&quot;"
+        );
+    }
+}
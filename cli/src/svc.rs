@@ -1,41 +1,150 @@
 //! Service
 
+use std::path::PathBuf;
+
 use anyhow::Error;
-use newsie_client::{Client as ApiClient, NewUser, User};
+use atom_syndication::FixedDateTime;
+use futures::{stream, StreamExt};
+#[cfg(feature = "keyring")]
+use newsie_client::token::KeyringTokenStore;
+use newsie_client::{
+    profile::Profiles,
+    ratelimit::RateLimitInfo,
+    summary::{ArticleSummary, DEFAULT_CHUNK_SIZE},
+    token::TokenStore,
+    Client as ApiClient, FeedUpdate, NewUser, User, UserUpdate,
+};
+use reqwest::NoProxy;
+use tracing::debug;
 
 use crate::{
+    content::extract,
     db::DbClient,
-    model::{Config, Feed},
+    discover::{find_feeds, Discovered, COMMON_PATHS},
+    model::{Article, Config, Feed, FeedStats, ReadingStats},
+    sync::{merge, FeedNames, SyncReport},
+    util::warn,
 };
 
+/// Keyring service of the tokens
+#[cfg(feature = "keyring")]
+const KEYRING_SERVICE: &str = "newsie-cli";
+
+/// Maximum number of attempts of a feeds sync (the feeds are merged again after a conflict)
+const MAX_SYNC_ATTEMPTS: usize = 3;
+
 /// Service
 pub struct Service {
     /// DB client
     db: DbClient,
     /// API client
     api: ApiClient,
+    /// HTTP client (to fetch the feeds)
+    http: reqwest::Client,
+    /// Active profile (the local config is used if none)
+    profile: Option<String>,
+    /// Offline mode
+    offline: bool,
+}
+
+/// Service options
+#[derive(Debug, Clone, Default)]
+pub struct ServiceOptions {
+    /// Server profile
+    pub profile: Option<String>,
+    /// Offline mode (the articles are read from the local cache)
+    pub offline: bool,
 }
 
 impl Service {
     /// Instantiates a new Service
-    pub fn new() -> Result<Self, Error> {
+    ///
+    /// With a profile (selected, or the default one), the API client targets the server of the
+    /// profile instead of the configured one.
+    pub fn new(opts: &ServiceOptions) -> Result<Self, Error> {
         // init DB client
         DbClient::init_db_file()?;
         let db_client = DbClient::new()?;
-        if !db_client.is_db_schema_init()? {
+        if db_client.is_db_schema_init()? {
+            db_client.migrate_db_schema()?;
+        } else {
             db_client.init_db_schema()?;
         }
 
         // read the config
         let config = Self::get_or_init_config(&db_client)?;
 
-        // init API client
-        let api_client = ApiClient::new(&config.api_url);
+        // init API client (of the selected or default profile, if any)
+        let profiles = Profiles::load(Self::profiles_file())?;
+        let profile = opts.profile.clone().or_else(|| profiles.default.clone());
+        let (builder, stored_token) = match profile.as_deref() {
+            Some(name) => (
+                profiles.builder(Some(name))?,
+                profiles.get(name).and_then(|p| p.token.clone()),
+            ),
+            None => (ApiClient::builder(&config.api_url), config.token.clone()),
+        };
+        let builder = match &config.proxy {
+            Some(proxy) => builder.proxy(proxy),
+            None => builder,
+        };
+        let keyring_token = Self::keyring(profile.as_deref()).and_then(|k| k.get());
+        let api_client = builder
+            .token(keyring_token.clone().or_else(|| stored_token.clone()))
+            .build()?;
 
-        Ok(Self {
+        let service = Self {
             db: db_client,
             api: api_client,
-        })
+            http: Self::http_client(config.proxy.as_deref())?,
+            profile,
+            offline: opts.offline,
+        };
+
+        // NB: a token stored in plain text is moved to the keyring, if any
+        if let (Some(token), None) = (stored_token, keyring_token) {
+            if let Some(keyring) = Self::keyring(service.active_profile()) {
+                if keyring.set(Some(&token)).is_ok() {
+                    service.store_token(None)?;
+                }
+            }
+        }
+        Ok(service)
+    }
+
+    /// Returns the keyring store of the token of a profile (`None` without keyring)
+    fn keyring(profile: Option<&str>) -> Option<Box<dyn TokenStore>> {
+        #[cfg(feature = "keyring")]
+        {
+            let user = match profile {
+                Some(name) => format!("profile:{name}"),
+                None => "local".to_string(),
+            };
+            KeyringTokenStore::new(KEYRING_SERVICE, &user)
+                .ok()
+                .map(|store| Box::new(store) as Box<dyn TokenStore>)
+        }
+        #[cfg(not(feature = "keyring"))]
+        {
+            let _ = profile;
+            None
+        }
+    }
+
+    /// Creates the HTTP client to fetch the feeds, with the proxy if any
+    ///
+    /// NB: without proxy, the proxy environment variables are honored
+    fn http_client(proxy: Option<&str>) -> Result<reqwest::Client, Error> {
+        let mut builder = reqwest::Client::builder();
+        if let Some(proxy) = proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy)?.no_proxy(NoProxy::from_env()));
+        }
+        Ok(builder.build()?)
+    }
+
+    /// Returns the path to the profiles file
+    fn profiles_file() -> PathBuf {
+        dirs::config_dir().unwrap().join("Newsie/profiles.toml")
     }
 
     /// Gets or intitializes the default config
@@ -59,12 +168,75 @@ impl Service {
         self.db.update_config(config)
     }
 
-    /// Saves the token in the config
+    /// Returns the name of the active profile
+    pub fn active_profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+
+    /// Returns the profiles
+    pub fn get_profiles(&self) -> Result<Profiles, Error> {
+        Ok(Profiles::load(Self::profiles_file())?)
+    }
+
+    /// Saves the profiles
+    pub fn save_profiles(&self, profiles: &Profiles) -> Result<(), Error> {
+        Ok(profiles.save(Self::profiles_file())?)
+    }
+
+    /// Returns the token (from the keyring, or the config, or the active profile)
+    pub fn get_token(&self) -> Result<Option<String>, Error> {
+        if let Some(token) = Self::keyring(self.active_profile()).and_then(|k| k.get()) {
+            return Ok(Some(token));
+        }
+        Ok(match &self.profile {
+            Some(name) => self.get_profiles()?.get(name).and_then(|p| p.token.clone()),
+            None => self.get_config()?.token,
+        })
+    }
+
+    /// Saves the token
     pub fn save_token(&self, token: &str) -> Result<(), Error> {
-        let mut config = self.db.read_config()?.unwrap();
-        config.token = Some(token.to_string());
-        self.db.update_config(config)?;
-        Ok(())
+        self.set_token(Some(token.to_string()))
+    }
+
+    /// Removes the token
+    pub fn clear_token(&self) -> Result<(), Error> {
+        self.set_token(None)
+    }
+
+    /// Sets the token in the keyring, or in the config (or the active profile) without keyring
+    fn set_token(&self, token: Option<String>) -> Result<(), Error> {
+        let stored = match Self::keyring(self.active_profile()) {
+            Some(keyring) => match keyring.set(token.as_deref()) {
+                Ok(()) => None,
+                Err(err) => {
+                    warn(&format!("{err}, the token is stored in plain text"));
+                    token
+                }
+            },
+            None => token,
+        };
+        self.store_token(stored)
+    }
+
+    /// Stores the token in the config (or in the active profile)
+    fn store_token(&self, token: Option<String>) -> Result<(), Error> {
+        match &self.profile {
+            Some(name) => {
+                let mut profiles = self.get_profiles()?;
+                let Some(profile) = profiles.profiles.get_mut(name) else {
+                    return Err(Error::msg(format!("unknown profile '{name}'")));
+                };
+                profile.token = token;
+                self.save_profiles(&profiles)
+            }
+            None => {
+                let mut config = self.db.read_config()?.unwrap();
+                config.token = token;
+                self.db.update_config(config)?;
+                Ok(())
+            }
+        }
     }
 }
 
@@ -92,6 +264,23 @@ impl Service {
         let res = self.api.me().await?;
         Ok(res.user)
     }
+
+    /// Updates the current user
+    pub async fn update_me(&self, fields: UserUpdate) -> Result<User, Error> {
+        Ok(self.api.update_me(fields).await?)
+    }
+
+    /// Changes the password of the current user, and saves the new token
+    pub async fn change_password(&mut self, current: &str, new: &str) -> Result<(), Error> {
+        let res = self.api.change_password(current, new).await?;
+        self.save_token(&res.token)
+    }
+
+    /// Deletes the current user, and removes the token from the config
+    pub async fn delete_me(&mut self) -> Result<(), Error> {
+        self.api.delete_me().await?;
+        self.clear_token()
+    }
 }
 
 impl Service {
@@ -100,27 +289,414 @@ impl Service {
         self.db.get_feeds().await
     }
 
+    /// Discovers the feeds of a url
+    ///
+    /// A feed url is returned as is. Otherwise, the feeds advertised by the page are returned,
+    /// or the first of the usual feed paths of the website which is a feed.
+    pub async fn discover_feeds(&self, url: &str) -> Result<Vec<Discovered>, Error> {
+        let res = self.http.get(url).send().await?.error_for_status()?;
+        let base = res.url().clone();
+        let content = res.bytes().await?;
+        if Feed::parse(&content).is_ok() {
+            return Ok(vec![Discovered {
+                url: url.to_string(),
+                title: None,
+            }]);
+        }
+
+        let feeds = find_feeds(&String::from_utf8_lossy(&content), &base);
+        if !feeds.is_empty() {
+            return Ok(feeds);
+        }
+        for path in COMMON_PATHS {
+            let Ok(feed_url) = base.join(path) else {
+                continue;
+            };
+            debug!(url = %feed_url, "trying a feed path");
+            if Feed::from_url(&self.http, feed_url.as_str()).await.is_ok() {
+                return Ok(vec![Discovered {
+                    url: feed_url.to_string(),
+                    title: None,
+                }]);
+            }
+        }
+        Ok(vec![])
+    }
+
+    /// Returns the feeds with their stats
+    ///
+    /// NB: the stats are local, from the last fetches of the feeds
+    pub async fn get_feeds_stats(&self) -> Result<Vec<(Feed, FeedStats)>, Error> {
+        let mut stats = self.db.get_feed_stats()?;
+        let feeds = self.db.get_feeds().await?;
+        Ok(feeds
+            .into_iter()
+            .map(|feed| {
+                let feed_stats = stats.remove(&feed.url).unwrap_or_default();
+                (feed, feed_stats)
+            })
+            .collect())
+    }
+
     /// Adds a feed
     pub async fn add_feeds(&mut self, feeds: Vec<Feed>) -> Result<Vec<Feed>, Error> {
         self.db.create_feeds(feeds).await
     }
 
     /// Removes feeds
-    pub async fn remove_feeds(&mut self, feeds_urls: Vec<String>) -> Result<(), Error> {
+    pub async fn remove_feeds(&mut self, feeds_urls: Vec<String>) -> Result<Vec<String>, Error> {
         self.db.remove_feeds(feeds_urls).await
     }
 
+    /// Moves a feed to a folder (`None` for no folder)
+    pub fn move_feed(&self, url: &str, folder: Option<&str>) -> Result<(), Error> {
+        if !self.db.move_feed(url, folder)? {
+            return Err(Error::msg(format!("unknown feed '{url}'")));
+        }
+        Ok(())
+    }
+
+    /// Returns the folders, with their number of feeds
+    ///
+    /// NB: the folders are local, the server has none
+    pub fn get_folders(&self) -> Result<Vec<(String, usize)>, Error> {
+        self.db.get_folders()
+    }
+
+    /// Renames a folder, and returns the number of its feeds
+    pub fn rename_folder(&self, name: &str, new_name: &str) -> Result<usize, Error> {
+        match self.db.rename_folder(name, new_name)? {
+            0 => Err(Error::msg(format!("unknown folder '{name}'"))),
+            n => Ok(n),
+        }
+    }
+
+    /// Removes a folder, and returns the number of its feeds (which are kept)
+    pub fn remove_folder(&self, name: &str) -> Result<usize, Error> {
+        match self.db.remove_folder(name)? {
+            0 => Err(Error::msg(format!("unknown folder '{name}'"))),
+            n => Ok(n),
+        }
+    }
+
+    /// Syncs the feeds with the server
+    ///
+    /// The local and remote changes since the last sync are merged (see [`merge`]), and the
+    /// merged feeds are saved on both sides. The remote feeds are only replaced if they have
+    /// not changed since they were read, otherwise they are merged again.
+    pub async fn sync_feeds(&mut self) -> Result<SyncReport, Error> {
+        if self.offline {
+            return Err(Error::msg("the feeds cannot be synced offline"));
+        }
+        let local_feeds = self
+            .db
+            .get_feeds()
+            .await?
+            .into_iter()
+            .map(|f| (f.url, f.name))
+            .collect::<FeedNames>();
+        let base = self.db.get_synced_feeds()?;
+
+        let mut attempt = 1;
+        loop {
+            let (remote, version) = self.api.get_feeds_versioned().await?;
+            let remote_feeds = remote
+                .iter()
+                .map(|f| (f.url.clone(), f.name.clone()))
+                .collect::<FeedNames>();
+
+            let (merged, report) = merge(&base, &local_feeds, &remote_feeds);
+            if merged != remote_feeds {
+                let updates = merged
+                    .iter()
+                    .map(|(url, name)| FeedUpdate {
+                        id: remote.iter().find(|f| f.url == *url).map(|f| f.id),
+                        url: url.clone(),
+                        name: name.clone(),
+                    })
+                    .collect::<Vec<_>>();
+                match self.api.replace_feeds(&updates, version.as_deref()).await {
+                    // NB: the remote feeds have changed since they were read
+                    Err(err) if err.code() == "CONFLICT" && attempt < MAX_SYNC_ATTEMPTS => {
+                        debug!(attempt, "feeds changed during the sync, merging again");
+                        attempt += 1;
+                        continue;
+                    }
+                    res => {
+                        res?;
+                    }
+                }
+            }
+            self.db.save_synced_feeds(&merged)?;
+            return Ok(report);
+        }
+    }
+
+    /// Summarizes an article
+    ///
+    /// NB: the summary is cached locally, and read from the cache afterwards (also offline)
+    pub async fn summarize(&self, url: &str) -> Result<ArticleSummary, Error> {
+        if let Some(summary) = self.db.get_cached_summary(url)? {
+            return Ok(summary);
+        }
+        if self.offline {
+            return Err(Error::msg("the summary is not cached (offline mode)"));
+        }
+        let summary = self
+            .api
+            .summarize(&[url])
+            .await?
+            .pop()
+            .ok_or(Error::msg("Missing article summary"))?;
+        self.db.record_summaries(1)?;
+        self.db.cache_summary(&summary)?;
+        Ok(summary)
+    }
+
+    /// Summarizes articles, up to `concurrency` requests at once
+    ///
+    /// `progress` is called with the number of processed articles. The summaries are returned
+    /// in the order of the urls, with the failures (urls and errors).
+    pub async fn summarize_all(
+        &self,
+        urls: &[&str],
+        concurrency: usize,
+        mut progress: impl FnMut(usize),
+    ) -> (Vec<ArticleSummary>, Vec<(String, String)>) {
+        // NB: the articles are spread over the requests, within the API limit
+        let concurrency = concurrency.max(1);
+        let chunk_size =
+            ((urls.len() + concurrency - 1) / concurrency).clamp(1, DEFAULT_CHUNK_SIZE);
+        let mut results = stream::iter(urls.chunks(chunk_size))
+            .map(|chunk| self.summarize_chunk(chunk))
+            .buffer_unordered(concurrency);
+
+        let (mut summaries, mut failures, mut done) = (vec![], vec![], 0);
+        while let Some((n, chunk_summaries, chunk_failures)) = results.next().await {
+            summaries.extend(chunk_summaries);
+            failures.extend(chunk_failures);
+            done += n;
+            progress(done);
+        }
+        summaries.sort_by_key(|s| urls.iter().position(|u| *u == s.url));
+        if let Err(err) = self.db.record_summaries(summaries.len()) {
+            warn(&format!("failed to record the summaries usage: {err}"));
+        }
+        (summaries, failures)
+    }
+
+    /// Returns the reading stats, over a number of days (and the `top` most read feeds)
+    pub fn get_reading_stats(&self, days: u32, top: usize) -> Result<ReadingStats, Error> {
+        self.db.get_reading_stats(days, top)
+    }
+
+    /// Returns the API rate limit of the user
+    ///
+    /// NB: the server has no usage endpoint, the rate limit headers of a user request are read
+    pub async fn get_api_rate_limit(&self) -> Result<Option<RateLimitInfo>, Error> {
+        self.api.me().await?;
+        Ok(self.api.rate_limit())
+    }
+
+    /// Summarizes a chunk of articles, and returns its size, summaries and failures
+    ///
+    /// NB: the articles of a failed chunk are summarized one by one, to isolate the failures
+    async fn summarize_chunk(
+        &self,
+        chunk: &[&str],
+    ) -> (usize, Vec<ArticleSummary>, Vec<(String, String)>) {
+        match self.api.summarize(chunk).await {
+            Ok(summaries) => (chunk.len(), summaries, vec![]),
+            Err(err) if chunk.len() == 1 => (
+                chunk.len(),
+                vec![],
+                vec![(chunk[0].to_string(), err.to_string())],
+            ),
+            Err(_) => {
+                let (mut summaries, mut failures) = (vec![], vec![]);
+                for url in chunk {
+                    match self.api.summarize(&[url]).await {
+                        Ok(summary) => summaries.extend(summary),
+                        Err(err) => failures.push((url.to_string(), err.to_string())),
+                    }
+                }
+                (chunk.len(), summaries, failures)
+            }
+        }
+    }
+
     /// Retrieves the feed articles
+    ///
+    /// The articles are recorded as seen, and flagged with their read state. They are cached,
+    /// and read from the cache in offline mode or if the feed is unreachable. Their content is
+    /// the downloaded one, if any (see `fetch_content`).
     pub async fn get_articles(&self, feed: &Feed) -> Result<Vec<Article>, Error> {
-        let channel = feed.load().await?;
-        let mut articles = vec![];
-        for item in channel.items {
-            articles.push(Article {
-                feed_url: feed.url.clone(),
-                url: item.link.ok_or(Error::msg("Missing article link"))?,
-                title: item.title,
-            });
+        let mut articles = if self.offline {
+            self.db.get_cached_articles(&feed.url)?
+        } else {
+            match Feed::from_url(&self.http, &feed.url).await {
+                Ok(loaded) => {
+                    self.db.set_feed_status(&feed.url, None)?;
+                    self.db.cache_articles(&feed.url, &loaded.articles)?;
+                    loaded.articles
+                }
+                Err(err) => {
+                    self.db.set_feed_status(&feed.url, Some(&err.to_string()))?;
+                    let cached = self.db.get_cached_articles(&feed.url)?;
+                    if cached.is_empty() {
+                        return Err(err);
+                    }
+                    warn(&format!(
+                        "{} is unreachable ({err}), using the cached articles",
+                        feed.url
+                    ));
+                    cached
+                }
+            }
+        };
+        debug!(url = %feed.url, count = articles.len(), "feed loaded");
+        let guids = articles.iter().map(|a| a.guid.as_str()).collect::<Vec<_>>();
+        let read = self.db.see_articles(&feed.url, &guids)?;
+        // NB: the downloaded contents replace the excerpts of the feed
+        let mut contents = self.db.get_contents(&guids)?;
+        for article in &mut articles {
+            article.read = read.contains(&article.guid);
+            if let Some(content) = contents.remove(&article.guid) {
+                article.content = Some(content);
+            }
         }
         Ok(articles)
     }
+
+    /// Returns the cached articles of a feed, the latest first
+    pub fn get_cached_articles(&self, feed: &Feed) -> Result<Vec<Article>, Error> {
+        self.db.get_cached_articles(&feed.url)
+    }
+
+    /// Checks if the content of an article is downloaded
+    pub fn has_content(&self, article: &Article) -> Result<bool, Error> {
+        self.db.has_content(&article.guid)
+    }
+
+    /// Downloads the content of an article, for offline reading
+    pub async fn fetch_content(&self, article: &Article) -> Result<(), Error> {
+        let html = self
+            .http
+            .get(&article.url)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        self.db.store_content(&article.guid, &extract(&html))
+    }
+
+    /// Evicts the least recently read contents beyond a total size (in bytes), and returns
+    /// their number
+    pub fn evict_contents(&mut self, max_size: usize) -> Result<usize, Error> {
+        self.db.evict_contents(max_size)
+    }
+
+    /// Searches the cached articles, the best matches first
+    ///
+    /// NB: the articles are searched offline, among the ones fetched before
+    pub fn search(
+        &self,
+        query: &str,
+        feed: Option<&Feed>,
+        since: Option<FixedDateTime>,
+        unread: bool,
+    ) -> Result<Vec<Article>, Error> {
+        let articles = self
+            .db
+            .search_articles(query, feed.map(|f| f.url.as_str()), unread)?;
+        Ok(match since {
+            Some(since) => articles
+                .into_iter()
+                .filter(|a| a.date.is_some_and(|d| d >= since))
+                .collect(),
+            None => articles,
+        })
+    }
+
+    /// Retrieves the articles of a feed which were not seen before
+    ///
+    /// NB: none is new the first time a feed is retrieved
+    pub async fn get_new_articles(&self, feed: &Feed) -> Result<Vec<Article>, Error> {
+        let known = self.db.get_article_guids(&feed.url)?;
+        let articles = self.get_articles(feed).await?;
+        if known.is_empty() {
+            return Ok(vec![]);
+        }
+        Ok(articles
+            .into_iter()
+            .filter(|a| !known.contains(&a.guid))
+            .collect())
+    }
+
+    /// Marks an article as read
+    pub fn mark_read(&self, article: &mut Article) -> Result<(), Error> {
+        self.db.mark_articles_read(&[article.guid.as_str()])?;
+        article.read = true;
+        Ok(())
+    }
+
+    /// Records the listing of articles, numbered from 1 (for [`Service::open`])
+    pub fn save_listing(&mut self, articles: &[Article]) -> Result<(), Error> {
+        let articles = articles
+            .iter()
+            .map(|a| (a.guid.as_str(), a.url.as_str()))
+            .collect::<Vec<_>>();
+        self.db.save_listing(&articles)
+    }
+
+    /// Opens an article in the browser, and marks it as read
+    ///
+    /// The article is either a number of the last listing, or a url. Its url is returned.
+    pub fn open(&self, article: &str) -> Result<String, Error> {
+        let url = match article.parse::<usize>() {
+            Ok(n) => {
+                let Some((guid, url)) = self.db.get_listed(n)? else {
+                    return Err(Error::msg(format!("no article #{n} in the last listing")));
+                };
+                open::that(&url)?;
+                self.db.mark_articles_read(&[guid.as_str()])?;
+                url
+            }
+            Err(_) => {
+                open::that(article)?;
+                self.db.mark_url_read(article)?;
+                article.to_string()
+            }
+        };
+        Ok(url)
+    }
+
+    /// Opens an article in the browser, and marks it as read
+    pub fn open_article(&self, article: &mut Article) -> Result<(), Error> {
+        open::that(&article.url)?;
+        self.mark_read(article)
+    }
+
+    /// Stars (or unstars) an article, and returns `false` if it was so already
+    pub fn star(&self, url: &str, starred: bool) -> Result<bool, Error> {
+        if starred {
+            self.db.star(url)
+        } else {
+            self.db.unstar(url)
+        }
+    }
+
+    /// Returns the starred articles (url and date), the latest first
+    pub fn get_stars(&self) -> Result<Vec<(String, String)>, Error> {
+        self.db.get_stars()
+    }
+
+    /// Marks all the articles of a feed as read, and returns their number
+    ///
+    /// NB: the articles are fetched first, so that the new ones are marked too
+    pub async fn mark_feed_read(&self, feed: &Feed) -> Result<usize, Error> {
+        self.get_articles(feed).await?;
+        self.db.mark_feed_read(&feed.url)
+    }
 }
@@ -0,0 +1,93 @@
+//! Sync
+//!
+//! The local feeds are reconciled with the server ones by a three-way merge: the feeds of the
+//! last sync are the common base, so that a feed changed on one side only (added, removed or
+//! renamed) takes the change of that side.
+//!
+//! NB: the server does not date the feed changes, so a feed changed differently on both sides
+//! (a conflict) takes the local change, which is the latest known one.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::Serialize;
+
+/// Feeds names, by url
+pub type FeedNames = BTreeMap<String, Option<String>>;
+
+/// Sync report
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct SyncReport {
+    /// Urls of the feeds whose local change is pushed to the server
+    pub pushed: Vec<String>,
+    /// Urls of the feeds whose remote change is pulled
+    pub pulled: Vec<String>,
+    /// Urls of the feeds changed differently on both sides
+    pub conflicts: Vec<String>,
+}
+
+/// Merges the local and remote feeds, from the feeds of the last sync
+pub fn merge(base: &FeedNames, local: &FeedNames, remote: &FeedNames) -> (FeedNames, SyncReport) {
+    let urls = base
+        .keys()
+        .chain(local.keys())
+        .chain(remote.keys())
+        .collect::<BTreeSet<_>>();
+
+    let mut merged = FeedNames::new();
+    let mut report = SyncReport::default();
+    for url in urls {
+        let (base, local, remote) = (base.get(url), local.get(url), remote.get(url));
+        let feed = if local != base {
+            if local != remote {
+                if remote != base {
+                    report.conflicts.push(url.clone());
+                }
+                report.pushed.push(url.clone());
+            }
+            local
+        } else {
+            if remote != base {
+                report.pulled.push(url.clone());
+            }
+            remote
+        };
+        if let Some(name) = feed {
+            merged.insert(url.clone(), name.clone());
+        }
+    }
+    (merged, report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feeds(feeds: &[(&str, Option<&str>)]) -> FeedNames {
+        feeds
+            .iter()
+            .map(|(url, name)| (url.to_string(), name.map(|n| n.to_string())))
+            .collect()
+    }
+
+    #[test]
+    fn test_merge() {
+        let base = feeds(&[("a", None), ("b", None), ("c", None), ("d", None)]);
+        // a: removed locally, b: renamed remotely, c: renamed on both sides, e: added locally
+        let local = feeds(&[("b", None), ("c", Some("local")), ("d", None), ("e", None)]);
+        let remote = feeds(&[("a", None), ("b", Some("remote")), ("c", Some("remote"))]);
+
+        let (merged, report) = merge(&base, &local, &remote);
+        assert_eq!(
+            merged,
+            feeds(&[("b", Some("remote")), ("c", Some("local")), ("e", None)])
+        );
+        assert_eq!(
+            report,
+            SyncReport {
+                pushed: vec!["a".to_string(), "c".to_string(), "e".to_string()],
+                pulled: vec!["b".to_string(), "d".to_string()],
+                conflicts: vec!["c".to_string()],
+            }
+        );
+    }
+}
@@ -0,0 +1,69 @@
+//! Tracing
+//!
+//! The diagnostics (including the API calls) are traced to the standard error, or to a log file
+//! to be attached to the bug reports.
+
+use std::{fs::OpenOptions, path::PathBuf, sync::Mutex};
+
+use anyhow::Error;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::EnvFilter;
+
+/// Returns the default log file path
+pub fn default_log_file() -> PathBuf {
+    dirs::data_dir().unwrap().join("Newsie/newsie.log")
+}
+
+/// Returns the level of a verbosity (the number of `-v` flags)
+///
+/// NB: only the warnings are traced by default, and only the errors in quiet mode
+pub fn level(verbose: u8, quiet: bool) -> LevelFilter {
+    match (quiet, verbose) {
+        (true, _) => LevelFilter::ERROR,
+        (false, 0) => LevelFilter::WARN,
+        (false, 1) => LevelFilter::INFO,
+        (false, 2) => LevelFilter::DEBUG,
+        (false, _) => LevelFilter::TRACE,
+    }
+}
+
+/// Initializes the tracer
+///
+/// NB: the level can be overridden with `RUST_LOG`
+pub fn init_tracer(level: LevelFilter, log_file: Option<PathBuf>) -> Result<(), Error> {
+    let filter = EnvFilter::builder()
+        .with_default_directive(level.into())
+        .from_env_lossy();
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    match log_file {
+        Some(path) => {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            let file = OpenOptions::new().create(true).append(true).open(&path)?;
+            builder
+                .with_ansi(false)
+                .with_writer(Mutex::new(file))
+                .try_init()
+                .map_err(Error::msg)?;
+        }
+        None => builder
+            .with_writer(std::io::stderr)
+            .try_init()
+            .map_err(Error::msg)?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level() {
+        assert_eq!(level(0, false), LevelFilter::WARN);
+        assert_eq!(level(2, false), LevelFilter::DEBUG);
+        assert_eq!(level(5, false), LevelFilter::TRACE);
+        assert_eq!(level(2, true), LevelFilter::ERROR);
+    }
+}
@@ -0,0 +1,484 @@
+//! Terminal UI
+//!
+//! Full-screen reader, with 3 panes: the feeds, the articles of the selected feed, and the
+//! summary and content of the selected article. The articles and summaries are loaded in the
+//! background, so that the UI stays responsive.
+//!
+//! Keybindings:
+//!
+//! - `j`/`k` (or arrows): next/previous item, or scroll the reading pane
+//! - `g`/`G`: first/last item
+//! - `h`/`l` (or arrows, `Tab`): previous/next pane
+//! - `Enter`: load the articles of a feed, or the summary of an article
+//! - `s`: load the summary of the highlighted article, without leaving the articles pane
+//! - `r`: reload the selected feed
+//! - `o`: open the selected article in the browser (and mark it as read)
+//! - `q` (or `Esc`): quit
+
+use std::io::{stdout, Stdout};
+
+use anyhow::Error;
+use crossterm::{
+    event::{Event, EventStream, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use futures::{future::LocalBoxFuture, stream::FuturesUnordered, FutureExt, StreamExt};
+use newsie_client::summary::ArticleSummary;
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
+    Frame, Terminal,
+};
+
+use crate::{
+    model::{Article, Feed},
+    svc::Service,
+    util::html_to_text,
+};
+
+/// Terminal backend
+type Backend = CrosstermBackend<Stdout>;
+
+/// Pane
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pane {
+    /// Feeds
+    Feeds,
+    /// Articles
+    Articles,
+    /// Reading pane
+    Reader,
+}
+
+/// Result of a background task
+enum Loaded {
+    /// Articles of a feed (by index)
+    Articles(usize, Result<Vec<Article>, Error>),
+    /// Summary of an article (by url)
+    Summary(String, Result<ArticleSummary, Error>),
+    /// Article opened in the browser (by guid)
+    Opened(String, Result<(), Error>),
+}
+
+/// State of the reading pane
+enum Reading {
+    /// No article selected
+    None,
+    /// Summary being loaded
+    Loading,
+    /// Summary
+    Summary(ArticleSummary),
+    /// Loading failure
+    Failed(String),
+}
+
+/// UI state
+struct App {
+    /// Feeds
+    feeds: Vec<Feed>,
+    /// Selected feed
+    feed_state: ListState,
+    /// Index of the feed of the articles
+    articles_feed: Option<usize>,
+    /// Articles
+    articles: Vec<Article>,
+    /// Selected article
+    article_state: ListState,
+    /// Reading pane
+    reading: Reading,
+    /// Scroll offset of the reading pane
+    scroll: u16,
+    /// Focused pane
+    focus: Pane,
+    /// Status message
+    status: String,
+    /// Quit flag
+    quit: bool,
+}
+
+/// Runs the terminal UI
+pub async fn run(service: &Service) -> Result<(), Error> {
+    let feeds = service.get_feeds().await?;
+
+    let mut terminal = setup_terminal()?;
+    let res = run_app(&mut terminal, service, feeds).await;
+    restore_terminal(&mut terminal)?;
+    res
+}
+
+/// Enters the full-screen mode
+fn setup_terminal() -> Result<Terminal<Backend>, Error> {
+    enable_raw_mode()?;
+    execute!(stdout(), EnterAlternateScreen)?;
+    Ok(Terminal::new(CrosstermBackend::new(stdout()))?)
+}
+
+/// Leaves the full-screen mode
+fn restore_terminal(terminal: &mut Terminal<Backend>) -> Result<(), Error> {
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+    Ok(())
+}
+
+/// Runs the event loop
+async fn run_app(
+    terminal: &mut Terminal<Backend>,
+    service: &Service,
+    feeds: Vec<Feed>,
+) -> Result<(), Error> {
+    let mut app = App::new(feeds);
+    let mut events = EventStream::new();
+    // NB: the tasks borrow the service, and are polled by the event loop
+    let mut tasks = FuturesUnordered::<LocalBoxFuture<'_, Loaded>>::new();
+
+    while !app.quit {
+        terminal.draw(|f| draw(f, &mut app))?;
+
+        tokio::select! {
+            event = events.next() => {
+                match event {
+                    Some(Ok(Event::Key(key))) if key.kind == KeyEventKind::Press => {
+                        if let Some(task) = app.on_key(key) {
+                            tasks.push(task.load(service));
+                        }
+                    }
+                    Some(Ok(_)) => {}
+                    Some(Err(err)) => return Err(err.into()),
+                    None => break,
+                }
+            }
+            Some(loaded) = tasks.next(), if !tasks.is_empty() => app.on_loaded(loaded),
+        }
+    }
+    Ok(())
+}
+
+/// Background task
+enum Task {
+    /// Loads the articles of a feed
+    Articles(usize, Feed),
+    /// Loads the summary of an article
+    Summary(String),
+    /// Opens an article in the browser
+    Open(Article),
+}
+
+impl Task {
+    /// Starts the task
+    fn load(self, service: &Service) -> LocalBoxFuture<'_, Loaded> {
+        match self {
+            Task::Articles(i, feed) => {
+                async move { Loaded::Articles(i, service.get_articles(&feed).await) }.boxed_local()
+            }
+            Task::Summary(url) => async move {
+                let res = service.summarize(&url).await;
+                Loaded::Summary(url, res)
+            }
+            .boxed_local(),
+            Task::Open(mut article) => async move {
+                let res = service.open_article(&mut article);
+                Loaded::Opened(article.guid, res)
+            }
+            .boxed_local(),
+        }
+    }
+}
+
+impl App {
+    /// Creates the UI state
+    fn new(feeds: Vec<Feed>) -> Self {
+        let mut feed_state = ListState::default();
+        if !feeds.is_empty() {
+            feed_state.select(Some(0));
+        }
+        let status = format!(
+            "{} feed(s) - press Enter to load a feed, q to quit",
+            feeds.len()
+        );
+        Self {
+            feeds,
+            feed_state,
+            articles_feed: None,
+            articles: vec![],
+            article_state: ListState::default(),
+            reading: Reading::None,
+            scroll: 0,
+            focus: Pane::Feeds,
+            status,
+            quit: false,
+        }
+    }
+
+    /// Handles a key press, and returns the task to start (if any)
+    fn on_key(&mut self, key: KeyEvent) -> Option<Task> {
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => self.quit = true,
+            KeyCode::Char('j') | KeyCode::Down => self.move_by(1),
+            KeyCode::Char('k') | KeyCode::Up => self.move_by(-1),
+            KeyCode::Char('g') | KeyCode::Home => self.move_by(isize::MIN),
+            KeyCode::Char('G') | KeyCode::End => self.move_by(isize::MAX),
+            KeyCode::Char('l') | KeyCode::Right | KeyCode::Tab => self.focus_next(),
+            KeyCode::Char('h') | KeyCode::Left | KeyCode::BackTab => self.focus_prev(),
+            KeyCode::Enter => return self.open(),
+            KeyCode::Char('r') => {
+                return self.feed_state.selected().map(|i| self.load_articles(i));
+            }
+            KeyCode::Char('s') if self.focus != Pane::Feeds => return self.summarize(),
+            KeyCode::Char('o') if self.focus != Pane::Feeds => {
+                let article = self.articles.get(self.article_state.selected()?)?;
+                self.status = format!("opening {}...", article.url);
+                return Some(Task::Open(article.clone()));
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Moves the selection (or scrolls the reading pane)
+    fn move_by(&mut self, delta: isize) {
+        let (state, len) = match self.focus {
+            Pane::Feeds => (&mut self.feed_state, self.feeds.len()),
+            Pane::Articles => (&mut self.article_state, self.articles.len()),
+            Pane::Reader => {
+                self.scroll = match delta {
+                    isize::MIN => 0,
+                    d if d < 0 => self.scroll.saturating_sub(1),
+                    _ => self.scroll.saturating_add(1),
+                };
+                return;
+            }
+        };
+        if len == 0 {
+            return;
+        }
+        let current = state.selected().unwrap_or(0) as isize;
+        let next = current.saturating_add(delta).clamp(0, len as isize - 1);
+        state.select(Some(next as usize));
+    }
+
+    /// Focuses the next pane
+    fn focus_next(&mut self) {
+        self.focus = match self.focus {
+            Pane::Feeds => Pane::Articles,
+            Pane::Articles | Pane::Reader => Pane::Reader,
+        };
+    }
+
+    /// Focuses the previous pane
+    fn focus_prev(&mut self) {
+        self.focus = match self.focus {
+            Pane::Feeds | Pane::Articles => Pane::Feeds,
+            Pane::Reader => Pane::Articles,
+        };
+    }
+
+    /// Opens the selected item
+    fn open(&mut self) -> Option<Task> {
+        match self.focus {
+            Pane::Feeds => {
+                let i = self.feed_state.selected()?;
+                self.focus = Pane::Articles;
+                Some(self.load_articles(i))
+            }
+            Pane::Articles => {
+                let task = self.summarize()?;
+                self.focus = Pane::Reader;
+                Some(task)
+            }
+            Pane::Reader => None,
+        }
+    }
+
+    /// Starts loading the summary of the selected article
+    ///
+    /// NB: the summaries are cached, so that reading an article again is immediate
+    fn summarize(&mut self) -> Option<Task> {
+        let article = self.articles.get(self.article_state.selected()?)?;
+        let url = article.url.clone();
+        self.reading = Reading::Loading;
+        self.scroll = 0;
+        self.status = format!("summarizing {url}...");
+        Some(Task::Summary(url))
+    }
+
+    /// Starts loading the articles of a feed
+    fn load_articles(&mut self, i: usize) -> Task {
+        let feed = self.feeds[i].clone();
+        self.status = format!("loading {}...", feed.url);
+        Task::Articles(i, feed)
+    }
+
+    /// Applies the result of a background task
+    fn on_loaded(&mut self, loaded: Loaded) {
+        match loaded {
+            Loaded::Articles(i, Ok(articles)) => {
+                self.status = format!("{} article(s)", articles.len());
+                self.articles_feed = Some(i);
+                self.articles = articles;
+                self.article_state
+                    .select((!self.articles.is_empty()).then_some(0));
+                self.reading = Reading::None;
+            }
+            Loaded::Articles(_, Err(err)) => {
+                self.status = format!("failed to load the feed: {err}")
+            }
+            Loaded::Opened(guid, Ok(())) => {
+                if let Some(article) = self.articles.iter_mut().find(|a| a.guid == guid) {
+                    article.read = true;
+                }
+                self.status = "article opened in the browser".to_string();
+            }
+            Loaded::Opened(_, Err(err)) => {
+                self.status = format!("failed to open the article: {err}")
+            }
+            Loaded::Summary(url, res) => {
+                // NB: the summary of an article which is not selected anymore is dropped
+                let selected = self
+                    .article_state
+                    .selected()
+                    .and_then(|i| self.articles.get(i));
+                if selected.map(|a| a.url.as_str()) != Some(url.as_str()) {
+                    return;
+                }
+                match res {
+                    Ok(summary) => {
+                        self.status = "summary loaded".to_string();
+                        self.reading = Reading::Summary(summary);
+                    }
+                    Err(err) => {
+                        self.status = "failed to summarize the article".to_string();
+                        self.reading = Reading::Failed(err.to_string());
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Draws the UI
+fn draw(f: &mut Frame<Backend>, app: &mut App) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(1)])
+        .split(f.size());
+    let panes = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage(20),
+            Constraint::Percentage(35),
+            Constraint::Percentage(45),
+        ])
+        .split(rows[0]);
+
+    let feeds = app
+        .feeds
+        .iter()
+        .map(|feed| ListItem::new(feed.name.clone().unwrap_or_else(|| feed.url.clone())))
+        .collect::<Vec<_>>();
+    let feeds = List::new(feeds)
+        .block(pane_block("Feeds", app.focus == Pane::Feeds))
+        .highlight_style(highlight_style());
+    f.render_stateful_widget(feeds, panes[0], &mut app.feed_state);
+
+    let title = match app.articles_feed.and_then(|i| app.feeds.get(i)) {
+        Some(feed) => feed.name.as_deref().unwrap_or(&feed.url),
+        None => "Articles",
+    };
+    let articles = app
+        .articles
+        .iter()
+        .map(|article| ListItem::new(article.title.clone().unwrap_or_else(|| article.url.clone())))
+        .collect::<Vec<_>>();
+    let articles = List::new(articles)
+        .block(pane_block(title, app.focus == Pane::Articles))
+        .highlight_style(highlight_style());
+    f.render_stateful_widget(articles, panes[1], &mut app.article_state);
+
+    draw_reader(f, app, panes[2]);
+
+    let status = Paragraph::new(app.status.as_str()).style(Style::default().fg(Color::DarkGray));
+    f.render_widget(status, rows[1]);
+}
+
+/// Draws the reading pane
+fn draw_reader(f: &mut Frame<Backend>, app: &App, area: Rect) {
+    let article = app
+        .article_state
+        .selected()
+        .and_then(|i| app.articles.get(i));
+    let mut lines = vec![];
+    if let Some(article) = article {
+        let title = article.title.as_deref().unwrap_or("(untitled)");
+        lines.push(Line::from(Span::styled(
+            title,
+            Style::default().add_modifier(Modifier::BOLD),
+        )));
+        lines.push(Line::from(Span::styled(
+            article.url.as_str(),
+            Style::default().fg(Color::Blue),
+        )));
+        lines.push(Line::default());
+    }
+    match &app.reading {
+        Reading::None if article.is_some() => {
+            lines.push(Line::from("Press Enter (or s) to summarize the article"))
+        }
+        Reading::None => {}
+        Reading::Loading => lines.push(Line::from("Summarizing...")),
+        Reading::Summary(summary) => {
+            lines.extend(summary.summary.lines().map(Line::from));
+            if !summary.keywords.is_empty() {
+                lines.push(Line::default());
+                lines.push(Line::from(Span::styled(
+                    summary.keywords.join(", "),
+                    Style::default().fg(Color::Yellow),
+                )));
+            }
+        }
+        Reading::Failed(err) => lines.push(Line::from(Span::styled(
+            err.as_str(),
+            Style::default().fg(Color::Red),
+        ))),
+    }
+    // NB: the content is the downloaded one (see the fetch command), or the excerpt of the feed
+    let content = article
+        .and_then(|a| a.content.as_deref())
+        .map(html_to_text)
+        .filter(|c| !c.is_empty());
+    if let Some(content) = content {
+        lines.push(Line::default());
+        lines.extend(content.lines().map(|l| Line::from(l.to_string())));
+    }
+
+    let reader = Paragraph::new(lines)
+        .block(pane_block("Reader", app.focus == Pane::Reader))
+        .wrap(Wrap { trim: false })
+        .scroll((app.scroll, 0));
+    f.render_widget(reader, area);
+}
+
+/// Returns the block of a pane
+fn pane_block(title: &str, focused: bool) -> Block<'_> {
+    let style = if focused {
+        Style::default().fg(Color::Cyan)
+    } else {
+        Style::default()
+    };
+    Block::default()
+        .borders(Borders::ALL)
+        .border_style(style)
+        .title(title)
+}
+
+/// Returns the style of the selected item
+fn highlight_style() -> Style {
+    Style::default()
+        .add_modifier(Modifier::REVERSED)
+        .add_modifier(Modifier::BOLD)
+}
@@ -1,16 +1,32 @@
 //! Utilities
 
-use std::{fmt::Display, process::exit};
+use std::{
+    env,
+    fmt::Display,
+    io::{stdout, IsTerminal, Write},
+    process::{exit, Command, Stdio},
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
 
 use colored::Colorize;
+use comfy_table::{modifiers::UTF8_ROUND_CORNERS, presets, ContentArrangement, Table};
+use indicatif::{ProgressBar, ProgressStyle};
+use serde::Serialize;
 
-/// Prints an info message
+/// Prints an info message (unless quiet)
 pub fn info(msg: &str) {
+    if is_quiet() {
+        return;
+    }
     eprintln!("{} {msg}", "i".yellow());
 }
 
-/// Prints a success message
+/// Prints a success message (unless quiet)
 pub fn success(msg: &str) {
+    if is_quiet() {
+        return;
+    }
     eprintln!("{} {msg}", "✔️".green());
 }
 
@@ -24,6 +40,61 @@ pub fn error(msg: &str) {
     eprintln!("{} {}", "x".red(), msg.red());
 }
 
+/// Plain output flag
+static PLAIN: AtomicBool = AtomicBool::new(false);
+
+/// Sets the plain output (no colors, no table borders)
+///
+/// NB: the output is also plain if `NO_COLOR` is set
+pub fn set_plain(plain: bool) {
+    let plain = plain || env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    PLAIN.store(plain, Ordering::Relaxed);
+    if plain {
+        colored::control::set_override(false);
+    }
+}
+
+/// Checks if the output is plain
+pub fn is_plain() -> bool {
+    PLAIN.load(Ordering::Relaxed)
+}
+
+/// Quiet output flag
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Sets the quiet output (no info nor success messages)
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+/// Checks if the output is quiet
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Creates a table, with a header
+///
+/// NB: a plain table has no borders and no colors, to be parsed by the usual tools
+pub fn table(header: &[&str]) -> Table {
+    let mut table = Table::new();
+    if is_plain() {
+        table.load_preset(presets::NOTHING).force_no_tty();
+    } else {
+        table
+            .load_preset(presets::UTF8_FULL_CONDENSED)
+            .apply_modifier(UTF8_ROUND_CORNERS)
+            .set_content_arrangement(ContentArrangement::Dynamic);
+    }
+    table.set_header(header.to_vec());
+    table
+}
+
+/// Prints a value as JSON (to stdout)
+pub fn print_json(value: &impl Serialize) -> Result<(), serde_json::Error> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
 /// Result extension trait
 pub trait ResultExt<T, E>
 where
@@ -65,3 +136,157 @@ impl<T> OptionExt<T> for Option<T> {
         }
     }
 }
+
+/// Creates a progress bar of `len` items, printed on the standard error
+///
+/// NB: nothing is printed if the standard error is not a terminal
+pub fn progress_bar(len: usize, message: &str) -> ProgressBar {
+    let template = if is_plain() {
+        "{msg} [{bar:30}] {pos}/{len}"
+    } else {
+        "{spinner:.cyan} {msg} [{bar:30.cyan/blue}] {pos}/{len}"
+    };
+    let bar = ProgressBar::new(len as u64).with_message(message.to_string());
+    bar.set_style(
+        ProgressStyle::with_template(template)
+            .unwrap()
+            .progress_chars("=> "),
+    );
+    bar.enable_steady_tick(Duration::from_millis(100));
+    bar
+}
+
+/// Creates a spinner, printed on the standard error
+///
+/// NB: nothing is printed if the standard error is not a terminal
+pub fn spinner(message: &str) -> ProgressBar {
+    let template = if is_plain() {
+        "{msg}"
+    } else {
+        "{spinner:.cyan} {msg}"
+    };
+    let spinner = ProgressBar::new_spinner().with_message(message.to_string());
+    spinner.set_style(ProgressStyle::with_template(template).unwrap());
+    spinner.enable_steady_tick(Duration::from_millis(100));
+    spinner
+}
+
+/// Reports the failures of a batch operation (the items and their errors)
+pub fn report_failures(failures: &[(String, String)]) {
+    if failures.is_empty() {
+        return;
+    }
+    warn(&format!("{} failure(s):", failures.len()));
+    for (item, err) in failures {
+        eprintln!("  - {item}: {err}");
+    }
+}
+
+/// Default pager (quits if the text fits on the screen, keeps the colors)
+const DEFAULT_PAGER: &str = "less -FRX";
+
+/// Prints a text through a pager
+///
+/// The pager is `$PAGER`, or `less`. The text is printed as is if the output is not a
+/// terminal, or if the pager cannot be started.
+pub fn page(text: &str) -> Result<(), std::io::Error> {
+    if !stdout().is_terminal() {
+        return stdout().write_all(text.as_bytes());
+    }
+
+    let pager = env::var("PAGER")
+        .ok()
+        .filter(|p| !p.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_PAGER.to_string());
+    let mut args = pager.split_whitespace();
+    let program = args.next().unwrap_or("less");
+    let child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(_) => return stdout().write_all(text.as_bytes()),
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        // NB: the pager may be quit before reading the whole text
+        if let Err(err) = stdin.write_all(text.as_bytes()) {
+            if err.kind() != std::io::ErrorKind::BrokenPipe {
+                return Err(err);
+            }
+        }
+    }
+    child.wait()?;
+    Ok(())
+}
+
+/// Converts HTML to plain text
+///
+/// The tags are removed, the block tags being replaced by line breaks, and the common entities
+/// are decoded.
+pub fn html_to_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('>') else {
+            rest = &rest[start..];
+            break;
+        };
+        let tag = rest[start + 1..start + end]
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        if matches!(
+            tag.as_str(),
+            "p" | "br"
+                | "div"
+                | "li"
+                | "h1"
+                | "h2"
+                | "h3"
+                | "h4"
+                | "h5"
+                | "h6"
+                | "tr"
+                | "blockquote"
+        ) {
+            text.push('\n');
+        }
+        rest = &rest[start + end + 1..];
+    }
+    text.push_str(rest);
+
+    let text = text
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&");
+
+    // NB: the blank lines are collapsed
+    let mut lines = vec![];
+    for line in text.lines().map(|l| l.trim()) {
+        if !line.is_empty() || lines.last().is_some_and(|l: &&str| !l.is_empty()) {
+            lines.push(line);
+        }
+    }
+    lines.join("\n").trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_html_to_text() {
+        assert_eq!(
+            html_to_text("<p>Hello <b>world</b> &amp; co</p>\n\n\n<p>Bye<br/>now</p>"),
+            "Hello world & co\n\nBye\nnow"
+        );
+    }
+}
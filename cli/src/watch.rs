@@ -0,0 +1,147 @@
+//! Watch mode
+//!
+//! The feeds are polled on an interval, and a desktop notification is raised for each new
+//! article matching the keywords (all the new articles if there are none). The feeds are not
+//! polled during the quiet hours, so that their new articles are notified afterwards.
+//!
+//! NB: the articles of a feed polled for the first time are all considered as known, so that
+//! adding a feed does not flood the notifications.
+
+use std::{str::FromStr, time::Duration};
+
+use anyhow::Error;
+use chrono::{Local, NaiveTime};
+use notify_rust::Notification;
+
+use crate::{
+    model::{Article, Feed},
+    svc::Service,
+    util::{info, warn},
+};
+
+/// Watch options
+pub struct WatchOptions {
+    /// Polling interval
+    pub interval: Duration,
+    /// Keywords (matched in the titles and contents, case-insensitively)
+    pub keywords: Vec<String>,
+    /// Quiet hours
+    pub quiet: Option<QuietHours>,
+}
+
+/// Quiet hours (local time), eg. `22:00-07:00`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuietHours {
+    /// Start
+    start: NaiveTime,
+    /// End
+    end: NaiveTime,
+}
+
+impl QuietHours {
+    /// Checks if a time is within the quiet hours
+    ///
+    /// NB: the quiet hours can span midnight
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            self.start <= time || time < self.end
+        }
+    }
+}
+
+impl FromStr for QuietHours {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid quiet hours '{s}' (expected HH:MM-HH:MM)");
+        let (start, end) = s.split_once('-').ok_or_else(invalid)?;
+        let parse = |t: &str| NaiveTime::parse_from_str(t.trim(), "%H:%M").map_err(|_| invalid());
+        Ok(Self {
+            start: parse(start)?,
+            end: parse(end)?,
+        })
+    }
+}
+
+/// Checks if an article matches the keywords (any of them)
+pub fn matches(article: &Article, keywords: &[String]) -> bool {
+    if keywords.is_empty() {
+        return true;
+    }
+    let text = format!(
+        "{} {}",
+        article.title.as_deref().unwrap_or_default(),
+        article.content.as_deref().unwrap_or_default()
+    )
+    .to_lowercase();
+    keywords.iter().any(|k| text.contains(&k.to_lowercase()))
+}
+
+/// Runs the watch loop, until interrupted
+pub async fn run(service: &Service, opts: WatchOptions) -> Result<(), Error> {
+    info(&format!(
+        "watching the feeds every {} min (Ctrl-C to stop)",
+        opts.interval.as_secs() / 60
+    ));
+    let mut interval = tokio::time::interval(opts.interval);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+        if opts.quiet.is_some_and(|q| q.contains(Local::now().time())) {
+            continue;
+        }
+
+        for feed in service.get_feeds().await? {
+            let articles = match service.get_new_articles(&feed).await {
+                Ok(articles) => articles,
+                Err(err) => {
+                    warn(&format!("failed to load {}: {err}", feed.url));
+                    continue;
+                }
+            };
+            for article in articles.iter().filter(|a| matches(a, &opts.keywords)) {
+                notify(&feed, article);
+            }
+        }
+    }
+}
+
+/// Raises the notification of a new article
+fn notify(feed: &Feed, article: &Article) {
+    let res = Notification::new()
+        .appname("Newsie")
+        .summary(feed.name.as_deref().unwrap_or(&feed.url))
+        .body(article.title.as_deref().unwrap_or(&article.url))
+        .show();
+    if let Err(err) = res {
+        warn(&format!("failed to notify {}: {err}", article.url));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(t: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(t, "%H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_quiet_hours() {
+        let night = "22:00-07:00".parse::<QuietHours>().unwrap();
+        assert!(night.contains(time("23:30")));
+        assert!(night.contains(time("06:59")));
+        assert!(!night.contains(time("07:00")));
+        assert!(!night.contains(time("12:00")));
+
+        let lunch = "12:00-13:30".parse::<QuietHours>().unwrap();
+        assert!(lunch.contains(time("12:45")));
+        assert!(!lunch.contains(time("14:00")));
+
+        assert!("22:00".parse::<QuietHours>().is_err());
+    }
+}
@@ -0,0 +1,125 @@
+//! Admin API
+//!
+//! The admin endpoints are reserved to the administrator of the instance, the calls of other
+//! users fail with a `FORBIDDEN` error.
+
+pub use newsie_api::mdl::{
+    report::{Backup, CleanupReport, InstanceStats, Metrics, RestoreReport, RetentionStats},
+    JobCount, VectorIndexReport,
+};
+use reqwest::Method;
+use uuid::Uuid;
+
+use crate::{error::Error, Client};
+
+/// Parameters of the vector index check
+///
+/// The unset parameters take the default values of the API.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VectorIndexParams {
+    /// Number of sampled summaries
+    pub sample: Option<usize>,
+    /// Number of searched neighbors
+    pub k: Option<usize>,
+}
+
+impl VectorIndexParams {
+    /// Returns the query parameters
+    fn query(&self) -> Vec<(&'static str, String)> {
+        let mut query = vec![];
+        if let Some(sample) = self.sample {
+            query.push(("sample", sample.to_string()));
+        }
+        if let Some(k) = self.k {
+            query.push(("k", k.to_string()));
+        }
+        query
+    }
+}
+
+impl Client {
+    /// Backs up the data of a user, or of the whole instance
+    pub async fn admin_backup(&self, user_id: Option<Uuid>) -> Result<Backup, Error> {
+        let mut req = self.request(Method::GET, "/admin/backup");
+        if let Some(user_id) = user_id {
+            req = req.query(&[("user_id", user_id.to_string())]);
+        }
+        self.send_json::<Backup>(req).await
+    }
+
+    /// Restores a backup
+    pub async fn admin_restore(&self, backup: &Backup) -> Result<RestoreReport, Error> {
+        let req = self.request(Method::POST, "/admin/restore").json(backup);
+        self.send_json::<RestoreReport>(req).await
+    }
+
+    /// Gets the instance statistics, with the summaries of the last `days` days
+    pub async fn admin_stats(&self, days: Option<u32>) -> Result<InstanceStats, Error> {
+        let mut req = self.request(Method::GET, "/admin/stats");
+        if let Some(days) = days {
+            req = req.query(&[("days", days.to_string())]);
+        }
+        self.send_json::<InstanceStats>(req).await
+    }
+
+    /// Gets the service metrics
+    pub async fn admin_metrics(&self) -> Result<Metrics, Error> {
+        let req = self.request(Method::GET, "/admin/metrics");
+        self.send_json::<Metrics>(req).await
+    }
+
+    /// Gets the number of background jobs by status
+    pub async fn admin_jobs(&self) -> Result<Vec<JobCount>, Error> {
+        let req = self.request(Method::GET, "/admin/jobs");
+        self.send_json::<Vec<JobCount>>(req).await
+    }
+
+    /// Gets the data retention metrics
+    pub async fn admin_retention(&self) -> Result<RetentionStats, Error> {
+        let req = self.request(Method::GET, "/admin/retention");
+        self.send_json::<RetentionStats>(req).await
+    }
+
+    /// Runs the data retention cleanup
+    pub async fn admin_cleanup(&self) -> Result<CleanupReport, Error> {
+        let req = self.request(Method::POST, "/admin/retention");
+        self.send_json::<CleanupReport>(req).await
+    }
+
+    /// Checks the recall of the vector index
+    pub async fn admin_check_vector_index(
+        &self,
+        params: VectorIndexParams,
+    ) -> Result<VectorIndexReport, Error> {
+        let req = self
+            .request(Method::GET, "/admin/maintenance/vector-index")
+            .query(&params.query());
+        self.send_json::<VectorIndexReport>(req).await
+    }
+
+    /// Rebuilds the vector index, and checks its recall
+    pub async fn admin_reindex_vectors(
+        &self,
+        params: VectorIndexParams,
+    ) -> Result<VectorIndexReport, Error> {
+        let req = self
+            .request(Method::POST, "/admin/maintenance/vector-index")
+            .query(&params.query());
+        self.send_json::<VectorIndexReport>(req).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vector_index_query() {
+        assert!(VectorIndexParams::default().query().is_empty());
+        let params = VectorIndexParams {
+            sample: Some(100),
+            k: None,
+        };
+        assert_eq!(params.query(), vec![("sample", "100".to_string())]);
+    }
+}
@@ -0,0 +1,37 @@
+//! Authentication
+
+use std::{fmt, future::Future, pin::Pin, sync::Arc};
+
+use crate::error::Error;
+
+/// Future returned by a token refresher
+pub type RefreshFuture = Pin<Box<dyn Future<Output = Result<String, Error>> + Send>>;
+
+/// Token refresher
+///
+/// The refresher is called when a request is rejected as unauthorized (401). It returns a
+/// new token (eg. by logging in again), and the request is retried once with it.
+#[derive(Clone)]
+pub struct TokenRefresher(Arc<dyn Fn() -> RefreshFuture + Send + Sync>);
+
+impl TokenRefresher {
+    /// Creates a new refresher
+    pub fn new<F, Fut>(f: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<String, Error>> + Send + 'static,
+    {
+        Self(Arc::new(move || Box::pin(f())))
+    }
+
+    /// Returns a new token
+    pub async fn refresh(&self) -> Result<String, Error> {
+        (self.0)().await
+    }
+}
+
+impl fmt::Debug for TokenRefresher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("TokenRefresher").finish()
+    }
+}
@@ -0,0 +1,282 @@
+//! Blocking API client
+//!
+//! The blocking client wraps the async client with its own single-threaded runtime, so that
+//! it can be used without an async runtime (eg. in scripts).
+//!
+//! NB: the blocking client must not be used within an async runtime, which would panic. The
+//! streams (eg. events, websockets) are only available with the async client, see
+//! [Client::inner].
+
+use std::{path::Path, sync::Arc};
+
+use tokio::runtime::{Builder, Runtime};
+use uuid::Uuid;
+
+#[cfg(feature = "admin")]
+use crate::admin::{
+    Backup, CleanupReport, InstanceStats, JobCount, Metrics, RestoreReport, RetentionStats,
+    VectorIndexParams, VectorIndexReport,
+};
+use crate::{
+    builder::ClientBuilder,
+    error::Error,
+    export::{DownloadProgress, ExportReport},
+    feed::{FeedPatch, NewFeed},
+    health::{Readiness, VersionRespBody},
+    query::FeedQuery,
+    queue::{FlushReport, MutationQueue},
+    summary::{ArticleSummary, ChunkProgress, RawSummaries},
+    Feed, FeedUpdate, GetUserRespBody, LoginRespBody, NewUser, Paginated, SignupRespBody,
+    SubscriptionUpdate, Summary, User, UserUpdate,
+};
+
+/// Generates the blocking wrappers of the async client methods
+///
+/// A wrapper is declared with the signature of its async method (without `async`), and
+/// blocks on it. NB: the generic methods are wrapped by hand.
+macro_rules! blocking {
+    () => {};
+    (
+        $(#[$meta:meta])*
+        fn $name:ident(&self $(, $arg:ident: $ty:ty)* $(,)?) -> $ret:ty;
+        $($rest:tt)*
+    ) => {
+        $(#[$meta])*
+        pub fn $name(&self $(, $arg: $ty)*) -> $ret {
+            self.rt.block_on(self.inner.$name($($arg),*))
+        }
+
+        blocking!($($rest)*);
+    };
+    (
+        $(#[$meta:meta])*
+        fn $name:ident(&mut self $(, $arg:ident: $ty:ty)* $(,)?) -> $ret:ty;
+        $($rest:tt)*
+    ) => {
+        $(#[$meta])*
+        pub fn $name(&mut self $(, $arg: $ty)*) -> $ret {
+            self.rt.block_on(self.inner.$name($($arg),*))
+        }
+
+        blocking!($($rest)*);
+    };
+}
+
+/// Blocking API client
+#[derive(Debug, Clone)]
+pub struct Client {
+    /// Async client
+    inner: crate::Client,
+    /// Runtime
+    rt: Arc<Runtime>,
+}
+
+impl Client {
+    /// Creates a new blocking API client with the default settings
+    pub fn new(url: &str) -> Result<Self, Error> {
+        Self::from_async(crate::Client::new(url))
+    }
+
+    /// Creates a new blocking API client from a builder
+    pub fn from_builder(builder: ClientBuilder) -> Result<Self, Error> {
+        Self::from_async(builder.build()?)
+    }
+
+    /// Wraps an async client
+    pub fn from_async(inner: crate::Client) -> Result<Self, Error> {
+        let rt = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|err| Error::new("INTERNAL", &format!("failed to start runtime: {err}")))?;
+        Ok(Self {
+            inner,
+            rt: Arc::new(rt),
+        })
+    }
+
+    /// Returns the async client
+    pub fn inner(&self) -> &crate::Client {
+        &self.inner
+    }
+
+    /// Returns the authentication token
+    pub fn get_token(&self) -> Option<String> {
+        self.inner.get_token()
+    }
+
+    /// Sets the authentication token
+    pub fn token(mut self, token: Option<String>) -> Self {
+        self.inner = self.inner.token(token);
+        self
+    }
+
+    /// Removes the authentication token
+    pub fn unset_token(&mut self) -> &mut Self {
+        self.inner.unset_token();
+        self
+    }
+}
+
+impl Client {
+    blocking! {
+        /// Checks that the API is up
+        fn health(&self) -> Result<(), Error>;
+
+        /// Checks that the API is ready to serve requests, with its dependencies
+        fn health_deep(&self) -> Result<Readiness, Error>;
+
+        /// Gets the version of the API
+        fn server_version(&self) -> Result<VersionRespBody, Error>;
+    }
+}
+
+impl Client {
+    blocking! {
+        /// Signup a new user
+        fn signup(&mut self, new_user: NewUser) -> Result<SignupRespBody, Error>;
+
+        /// Login a user
+        fn login(&mut self, email: &str, password: &str) -> Result<LoginRespBody, Error>;
+
+        /// Gets the user info
+        fn me(&self) -> Result<GetUserRespBody, Error>;
+
+        /// Update the user
+        fn update_me(&self, fields: UserUpdate) -> Result<User, Error>;
+
+        /// Changes the user password
+        fn change_password(
+            &mut self,
+            current_password: &str,
+            new_password: &str,
+        ) -> Result<LoginRespBody, Error>;
+
+        /// Deletes the user
+        fn delete_me(&mut self) -> Result<(), Error>;
+
+        /// Update the user subscription
+        fn update_subscription(&self, update: SubscriptionUpdate) -> Result<User, Error>;
+    }
+
+    /// Downloads the data export of the user to a file
+    pub fn export_my_data<F>(
+        &self,
+        path: impl AsRef<Path>,
+        progress: F,
+    ) -> Result<ExportReport, Error>
+    where
+        F: FnMut(DownloadProgress),
+    {
+        self.rt.block_on(self.inner.export_my_data(path, progress))
+    }
+}
+
+impl Client {
+    blocking! {
+        /// Get the user feeds
+        fn get_feeds(&self) -> Result<Vec<Feed>, Error>;
+
+        /// Sync the user feeds
+        fn sync_feeds(&self, feeds: &[FeedUpdate]) -> Result<Vec<Feed>, Error>;
+
+        /// Gets all the user feeds, with their version
+        fn get_feeds_versioned(&self) -> Result<(Vec<Feed>, Option<String>), Error>;
+
+        /// Replaces the user feeds, if they have not changed since their version
+        fn replace_feeds(
+            &self,
+            feeds: &[FeedUpdate],
+            version: Option<&str>,
+        ) -> Result<Vec<Feed>, Error>;
+
+        /// Gets a page of the user feeds
+        fn get_feeds_page(
+            &self,
+            limit: usize,
+            cursor: Option<&str>,
+        ) -> Result<Paginated<Feed>, Error>;
+
+        /// Lists the user feeds, with query options (sort, filters, pagination)
+        fn list_feeds(&self, query: &FeedQuery) -> Result<Paginated<Feed>, Error>;
+
+        /// Adds a feed
+        fn add_feed(&self, feed: NewFeed) -> Result<Feed, Error>;
+
+        /// Updates a feed
+        fn update_feed(&self, id: Uuid, patch: FeedPatch) -> Result<Feed, Error>;
+
+        /// Deletes a feed
+        fn delete_feed(&self, id: Uuid) -> Result<(), Error>;
+
+        /// Replays the mutations of a queue, in order
+        fn flush(&self, queue: &MutationQueue) -> Result<FlushReport, Error>;
+    }
+}
+
+impl Client {
+    blocking! {
+        /// Summarize a list of articles
+        fn summarize(&self, urls: &[&str]) -> Result<Vec<ArticleSummary>, Error>;
+
+        /// Summarize a list of articles, with the embeddings of the summaries
+        fn summarize_with_embeddings(&self, urls: &[&str]) -> Result<Vec<Summary>, Error>;
+
+        /// Summarize a list of articles, and returns the raw response body
+        fn summarize_raw(&self, urls: &[&str]) -> Result<RawSummaries, Error>;
+    }
+
+    /// Summarizes a list of articles, by chunks of `chunk_size` articles
+    pub fn summarize_chunked<F>(
+        &self,
+        urls: &[&str],
+        chunk_size: usize,
+        concurrency: usize,
+        progress: F,
+    ) -> Result<Vec<ArticleSummary>, Error>
+    where
+        F: FnMut(ChunkProgress),
+    {
+        self.rt.block_on(
+            self.inner
+                .summarize_chunked(urls, chunk_size, concurrency, progress),
+        )
+    }
+}
+
+#[cfg(feature = "admin")]
+impl Client {
+    blocking! {
+        /// Backs up the data of a user, or of the whole instance
+        fn admin_backup(&self, user_id: Option<Uuid>) -> Result<Backup, Error>;
+
+        /// Restores a backup
+        fn admin_restore(&self, backup: &Backup) -> Result<RestoreReport, Error>;
+
+        /// Gets the instance statistics, with the summaries of the last `days` days
+        fn admin_stats(&self, days: Option<u32>) -> Result<InstanceStats, Error>;
+
+        /// Gets the service metrics
+        fn admin_metrics(&self) -> Result<Metrics, Error>;
+
+        /// Gets the number of background jobs by status
+        fn admin_jobs(&self) -> Result<Vec<JobCount>, Error>;
+
+        /// Gets the data retention metrics
+        fn admin_retention(&self) -> Result<RetentionStats, Error>;
+
+        /// Runs the data retention cleanup
+        fn admin_cleanup(&self) -> Result<CleanupReport, Error>;
+
+        /// Checks the recall of the vector index
+        fn admin_check_vector_index(
+            &self,
+            params: VectorIndexParams,
+        ) -> Result<VectorIndexReport, Error>;
+
+        /// Rebuilds the vector index, and checks its recall
+        fn admin_reindex_vectors(
+            &self,
+            params: VectorIndexParams,
+        ) -> Result<VectorIndexReport, Error>;
+    }
+}
@@ -0,0 +1,195 @@
+//! Response cache
+//!
+//! The list responses are cached with their `ETag`, keyed by URL. The cached version is sent
+//! in the `If-None-Match` header, and the cached response is returned if the server answers
+//! `304 Not Modified`, so that syncing an unchanged list does not transfer it again.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use reqwest::{
+    header::{HeaderValue, ETAG, IF_NONE_MATCH},
+    RequestBuilder, StatusCode,
+};
+use serde::de::DeserializeOwned;
+
+use crate::{error::Error, Client};
+
+/// Default maximum number of cached responses
+pub const DEFAULT_CACHE_SIZE: usize = 100;
+
+/// Response cache
+///
+/// The cache is shared by its clones. The oldest responses are evicted once the cache is
+/// full.
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    /// Cached responses
+    inner: Arc<Mutex<CacheInner>>,
+    /// Maximum number of cached responses
+    size: usize,
+}
+
+/// Cached responses
+#[derive(Debug, Default)]
+struct CacheInner {
+    /// Responses by URL
+    entries: HashMap<String, CachedResponse>,
+    /// URLs by insertion order
+    order: VecDeque<String>,
+}
+
+/// Cached response
+#[derive(Debug, Clone)]
+struct CachedResponse {
+    /// Entity tag
+    etag: HeaderValue,
+    /// Body
+    body: Vec<u8>,
+}
+
+impl ResponseCache {
+    /// Creates a new cache
+    pub fn new(size: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(CacheInner::default())),
+            size,
+        }
+    }
+
+    /// Returns a cached response
+    fn get(&self, url: &str) -> Option<CachedResponse> {
+        self.inner.lock().unwrap().entries.get(url).cloned()
+    }
+
+    /// Caches a response
+    fn insert(&self, url: String, response: CachedResponse) {
+        if self.size == 0 {
+            return;
+        }
+        let mut inner = self.inner.lock().unwrap();
+        if inner.entries.insert(url.clone(), response).is_none() {
+            inner.order.push_back(url);
+        }
+        while inner.entries.len() > self.size {
+            let Some(oldest) = inner.order.pop_front() else {
+                break;
+            };
+            inner.entries.remove(&oldest);
+        }
+    }
+
+    /// Removes the cached responses
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.entries.clear();
+        inner.order.clear();
+    }
+
+    /// Returns the number of cached responses
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().entries.len()
+    }
+
+    /// Checks if the cache is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for ResponseCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CACHE_SIZE)
+    }
+}
+
+impl Client {
+    /// Sends an authenticated request, and deserializes the response body
+    ///
+    /// The response is served from the cache (if enabled) when it has not been modified.
+    pub(crate) async fn send_json_cached<T: DeserializeOwned>(
+        &self,
+        req: RequestBuilder,
+    ) -> Result<T, Error> {
+        let Some(cache) = &self.cache else {
+            return self.send_json(req).await;
+        };
+
+        let (http, req) = req.build_split();
+        let mut req = req?;
+        let url = req.url().to_string();
+        let cached = cache.get(&url);
+        if let Some(cached) = &cached {
+            req.headers_mut().insert(IF_NONE_MATCH, cached.etag.clone());
+        }
+        let res = self.send(RequestBuilder::from_parts(http, req)).await?;
+
+        let body = match cached {
+            Some(cached) if res.status() == StatusCode::NOT_MODIFIED => cached.body,
+            _ => {
+                let etag = res.headers().get(ETAG).cloned();
+                let body = res.into_body().bytes().await?.to_vec();
+                if let Some(etag) = etag {
+                    cache.insert(
+                        url,
+                        CachedResponse {
+                            etag,
+                            body: body.clone(),
+                        },
+                    );
+                }
+                body
+            }
+        };
+        serde_json::from_slice(&body)
+            .map_err(|err| Error::new("INTERNAL", &format!("invalid response body: {err}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eviction() {
+        let cache = ResponseCache::new(2);
+        for i in 0..3 {
+            cache.insert(
+                format!("http://host/{i}"),
+                CachedResponse {
+                    etag: HeaderValue::from_static("\"v1\""),
+                    body: vec![],
+                },
+            );
+        }
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get("http://host/0").is_none());
+        assert!(cache.get("http://host/2").is_some());
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_not_modified() {
+        use crate::mock::{Method, MockTransport, StatusCode};
+
+        let mock = MockTransport::new();
+        mock.on(Method::GET, "/feeds")
+            .header(ETAG, HeaderValue::from_static("\"v1\""))
+            .json(StatusCode::OK, &serde_json::json!({ "items": [] }));
+        let mut client = Client::mock(&mock);
+        client.cache = Some(ResponseCache::default());
+        assert!(client.get_feeds().await.unwrap().is_empty());
+
+        mock.on(Method::GET, "/feeds")
+            .body(StatusCode::NOT_MODIFIED, b"");
+        assert!(client.get_feeds().await.unwrap().is_empty());
+
+        let requests = mock.requests();
+        assert!(requests[0].headers.get(IF_NONE_MATCH).is_none());
+        assert_eq!(requests[1].headers.get(IF_NONE_MATCH).unwrap(), "\"v1\"");
+    }
+}
@@ -0,0 +1,129 @@
+//! Call options
+//!
+//! The calls of a client can be bounded by a timeout, or cancelled with a cancellation
+//! token (eg. when the user leaves a screen waiting for summaries):
+//!
+//! ```no_run
+//! # use std::time::Duration;
+//! # use newsie_client::{call::CancellationToken, Client};
+//! # async fn run(client: Client) {
+//! let cancel = CancellationToken::new();
+//! let res = client
+//!     .with_timeout(Duration::from_secs(10))
+//!     .with_cancel(cancel.clone())
+//!     .summarize(&["https://www.newsie.rocks"])
+//!     .await;
+//! # }
+//! ```
+
+use std::{future::Future, pin::pin, time::Duration};
+
+use futures::future::{select, Either};
+#[cfg(not(target_arch = "wasm32"))]
+pub use tokio_util::sync::CancellationToken;
+
+use crate::{error::Error, retry, Client};
+
+/// Options of the calls
+#[derive(Debug, Clone, Default)]
+pub struct CallOptions {
+    /// Maximum duration of a call, including its retries
+    pub timeout: Option<Duration>,
+    /// Cancellation token
+    #[cfg(not(target_arch = "wasm32"))]
+    pub cancel: Option<CancellationToken>,
+}
+
+impl CallOptions {
+    /// Runs a call with the options
+    pub(crate) async fn run<T, F>(&self, fut: F) -> Result<T, Error>
+    where
+        F: Future<Output = Result<T, Error>>,
+    {
+        let fut = async {
+            let Some(timeout) = self.timeout else {
+                return fut.await;
+            };
+            match select(pin!(fut), pin!(retry::sleep(timeout))).await {
+                Either::Left((res, _)) => res,
+                Either::Right(_) => Err(Error::new(
+                    "TIMEOUT",
+                    &format!("the call has timed out after {timeout:?}"),
+                )),
+            }
+        };
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(cancel) = &self.cancel {
+            return match select(pin!(fut), pin!(cancel.cancelled())).await {
+                Either::Left((res, _)) => res,
+                Either::Right(_) => Err(Error::new("CANCELLED", "the call has been cancelled")),
+            };
+        }
+        fut.await
+    }
+}
+
+impl Client {
+    /// Returns a client making its calls with options
+    ///
+    /// NB: the client shares its connections and token with this client
+    pub fn with_options(&self, options: CallOptions) -> Self {
+        let mut client = self.clone();
+        client.options = options;
+        client
+    }
+
+    /// Returns a client making its calls with a timeout
+    pub fn with_timeout(&self, timeout: Duration) -> Self {
+        let mut client = self.clone();
+        client.options.timeout = Some(timeout);
+        client
+    }
+
+    /// Returns a client making its calls with a cancellation token
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_cancel(&self, cancel: CancellationToken) -> Self {
+        let mut client = self.clone();
+        client.options.cancel = Some(cancel);
+        client
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_timeout() {
+        let options = CallOptions {
+            timeout: Some(Duration::from_millis(10)),
+            ..Default::default()
+        };
+        let res = options
+            .run(async {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                Ok(())
+            })
+            .await;
+        assert!(res.unwrap_err().to_string().starts_with("TIMEOUT"));
+        assert!(options.run(async { Ok(()) }).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_cancel() {
+        let cancel = CancellationToken::new();
+        let options = CallOptions {
+            cancel: Some(cancel.clone()),
+            ..Default::default()
+        };
+        cancel.cancel();
+        let res = options
+            .run(async {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                Ok(())
+            })
+            .await;
+        assert!(res.unwrap_err().to_string().starts_with("CANCELLED"));
+    }
+}
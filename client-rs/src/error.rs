@@ -1,30 +1,160 @@
 //! Error
 
-use newsie_api::error::HttpErrorResponse;
+use newsie_api::{
+    error::{FieldError, HttpErrorResponse},
+    mdl::validate::Validate,
+};
+use reqwest::StatusCode;
+
+use crate::transport::TransportError;
 
 #[derive(Debug, thiserror::Error)]
-#[error("{code}: {message}")]
-pub struct Error {
-    /// Code
-    code: String,
-    /// Error message
-    message: String,
+pub enum Error {
+    /// Error returned by the API, or raised by the client
+    #[error("{code}: {message}")]
+    Api {
+        /// Code
+        code: String,
+        /// Error message
+        message: String,
+    },
+    /// Unsuccessful response whose body is not an API error (eg. a proxy error page)
+    #[error("UNEXPECTED_RESPONSE: {status}: {body}")]
+    UnexpectedResponse {
+        /// Response status
+        status: StatusCode,
+        /// Raw response body
+        body: String,
+    },
+    /// Invalid input, rejected before the request is sent
+    #[error("INVALID_FIELDS: {}", fields_message(.fields))]
+    Validation {
+        /// Invalid fields
+        fields: Vec<FieldError>,
+    },
+}
+
+impl Error {
+    /// Creates a new error
+    pub(crate) fn new(code: &str, message: &str) -> Self {
+        Error::Api {
+            code: code.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    /// Returns the error code (eg. `NOT_FOUND`)
+    pub fn code(&self) -> &str {
+        match self {
+            Error::Api { code, .. } => code,
+            Error::UnexpectedResponse { .. } => "UNEXPECTED_RESPONSE",
+            Error::Validation { .. } => "INVALID_FIELDS",
+        }
+    }
+
+    /// Creates an error from an unsuccessful response body
+    ///
+    /// The body is decoded as an API error, or kept as is if it cannot be decoded.
+    pub(crate) fn from_response(status: StatusCode, body: &[u8]) -> Self {
+        match serde_json::from_slice::<HttpErrorResponse>(body) {
+            Ok(err) => err.into(),
+            Err(_) => Error::UnexpectedResponse {
+                status,
+                body: String::from_utf8_lossy(body).into_owned(),
+            },
+        }
+    }
+}
+
+/// Validates an input model
+///
+/// The rules are the ones of the API, so that an invalid input is rejected without a round
+/// trip to the server.
+pub(crate) fn validate<T: Validate + ?Sized>(input: &T) -> Result<(), Error> {
+    let fields = input.invalid_fields();
+    if fields.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Validation { fields })
+    }
+}
+
+/// Formats the invalid fields (eg. `email: invalid email address`)
+fn fields_message(fields: &[FieldError]) -> String {
+    fields
+        .iter()
+        .map(|f| {
+            if f.field.is_empty() {
+                f.message.clone()
+            } else {
+                format!("{}: {}", f.field, f.message)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
 impl From<HttpErrorResponse> for Error {
     fn from(value: HttpErrorResponse) -> Self {
-        Error {
+        Error::Api {
             code: value.error.code,
             message: value.error.message,
         }
     }
 }
 
+impl From<TransportError> for Error {
+    fn from(value: TransportError) -> Self {
+        Error::Api {
+            code: "INTERNAL".to_string(),
+            message: value.message,
+        }
+    }
+}
+
 impl From<reqwest::Error> for Error {
     fn from(value: reqwest::Error) -> Self {
-        Error {
+        Error::Api {
             code: "INTERNAL".to_string(),
             message: value.to_string(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_response() {
+        let err = Error::from_response(
+            StatusCode::BAD_REQUEST,
+            br#"{"error":{"code":"INVALID_REQUEST","message":"invalid URL"}}"#,
+        );
+        assert_eq!(err.to_string(), "INVALID_REQUEST: invalid URL");
+
+        let err = Error::from_response(StatusCode::BAD_GATEWAY, b"<html>Bad Gateway</html>");
+        assert!(matches!(
+            err,
+            Error::UnexpectedResponse { status: StatusCode::BAD_GATEWAY, ref body }
+                if body == "<html>Bad Gateway</html>"
+        ));
+
+        let err = Error::from_response(StatusCode::SERVICE_UNAVAILABLE, b"");
+        assert!(matches!(err, Error::UnexpectedResponse { .. }));
+    }
+
+    #[test]
+    fn test_validate() {
+        let new_user = crate::NewUser {
+            name: "John Doe".to_string(),
+            email: "john".to_string(),
+            password: "".to_string(),
+        };
+        let err = validate(&new_user).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "INVALID_FIELDS: email: invalid email address, password: password must not be empty"
+        );
+    }
+}
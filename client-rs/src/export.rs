@@ -0,0 +1,151 @@
+//! Data export
+//!
+//! The data of the user (account and feeds) is exported as a JSON file. The export is streamed
+//! to disk, and checked against the checksum sent by the API.
+
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use newsie_api::mdl::body::EXPORT_CHECKSUM_HEADER;
+use reqwest::{header::CONTENT_LENGTH, Method};
+use sha2::{Digest, Sha256};
+
+use crate::{error::Error, transport::Response, Client};
+
+/// Report of a data export
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportReport {
+    /// Size of the export (in bytes)
+    pub size: u64,
+    /// Checksum of the export (hexadecimal SHA-256)
+    pub checksum: String,
+}
+
+/// Progress of a download
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DownloadProgress {
+    /// Number of downloaded bytes
+    pub downloaded: u64,
+    /// Total number of bytes (if known)
+    pub total: Option<u64>,
+}
+
+impl Client {
+    /// Downloads the data export of the user to a file
+    ///
+    /// The export is written to a temporary file next to `path`, which replaces `path` once
+    /// its checksum is verified. `progress` is called after each downloaded chunk.
+    pub async fn export_my_data<F>(
+        &self,
+        path: impl AsRef<Path>,
+        mut progress: F,
+    ) -> Result<ExportReport, Error>
+    where
+        F: FnMut(DownloadProgress),
+    {
+        let path = path.as_ref();
+        let req = self.request(Method::GET, "/auth/me/export");
+        let res = self.send(req).await?;
+        let expected = res
+            .headers()
+            .get(EXPORT_CHECKSUM_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.trim().to_ascii_lowercase());
+
+        let tmp = part_path(path);
+        let report = match download(res, &tmp, &mut progress).await {
+            Ok(report) => report,
+            Err(err) => {
+                let _res = std::fs::remove_file(&tmp);
+                return Err(err);
+            }
+        };
+        // NB: an export without checksum cannot be verified, and is kept as is
+        if let Some(expected) = expected {
+            if expected != report.checksum {
+                let _res = std::fs::remove_file(&tmp);
+                return Err(Error::new(
+                    "CHECKSUM",
+                    &format!(
+                        "invalid export checksum (expected {expected}, got {})",
+                        report.checksum
+                    ),
+                ));
+            }
+        }
+        std::fs::rename(&tmp, path).map_err(io_error)?;
+        Ok(report)
+    }
+}
+
+/// Streams a response body to a file, and computes its checksum
+async fn download<F>(res: Response, path: &Path, progress: &mut F) -> Result<ExportReport, Error>
+where
+    F: FnMut(DownloadProgress),
+{
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(io_error)?;
+    }
+    let mut file = File::create(path).map_err(io_error)?;
+    let mut hasher = Sha256::new();
+    let total = res
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok());
+    let mut body = res.into_body();
+    let mut downloaded = 0;
+    while let Some(chunk) = body.chunk().await? {
+        file.write_all(&chunk).map_err(io_error)?;
+        hasher.update(&chunk);
+        downloaded += chunk.len() as u64;
+        progress(DownloadProgress { downloaded, total });
+    }
+    file.sync_all().map_err(io_error)?;
+
+    Ok(ExportReport {
+        size: downloaded,
+        checksum: to_hex(&hasher.finalize()),
+    })
+}
+
+/// Returns the path of a partial download
+fn part_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    path.with_file_name(name)
+}
+
+/// Formats bytes as hexadecimal
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Converts an IO error
+fn io_error(err: std::io::Error) -> Error {
+    Error::new("EXPORT", &format!("failed to write the export: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_part_path() {
+        assert_eq!(
+            part_path(Path::new("/tmp/export.json")),
+            Path::new("/tmp/export.json.part")
+        );
+    }
+
+    #[test]
+    fn test_to_hex() {
+        assert_eq!(
+            to_hex(&Sha256::digest(b"")),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+}
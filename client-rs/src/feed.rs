@@ -0,0 +1,239 @@
+//! Feed management
+//!
+//! The API syncs the whole list of feeds of a user (`PUT /feeds`). The feeds are managed one
+//! by one by reading the list, editing it, and writing it back with its version (`If-Match`),
+//! so that a concurrent change by another device fails with a `CONFLICT` error instead of
+//! being overwritten.
+
+use std::collections::HashSet;
+
+use futures::Stream;
+use newsie_api::{
+    error::FieldError,
+    mdl::validate::{is_http_url, Validate},
+};
+use reqwest::{
+    header::{ETAG, IF_MATCH},
+    Method,
+};
+use serde::{Deserialize, Deserializer, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    error::{validate, Error},
+    query::FeedQuery,
+    read_json, Client, Feed, FeedUpdate, GetFeedsRespBody, Paginated,
+};
+
+/// New feed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NewFeed {
+    /// Feed url
+    pub url: String,
+    /// Feed name
+    pub name: Option<String>,
+}
+
+impl NewFeed {
+    /// Creates a new feed
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            name: None,
+        }
+    }
+
+    /// Sets the feed name
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+}
+
+/// Changes to a feed
+///
+/// The fields which are not set are kept.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeedPatch {
+    /// Feed url
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Feed name (`Some(None)` to remove it)
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_set"
+    )]
+    pub name: Option<Option<String>>,
+}
+
+/// Deserializes a field which is set, possibly to `null`
+///
+/// NB: a missing field is `None` (see `#[serde(default)]`), and a `null` one is `Some(None)`
+fn deserialize_set<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+impl Validate for NewFeed {
+    fn invalid_fields(&self) -> Vec<FieldError> {
+        check_url(&self.url)
+    }
+}
+
+impl Validate for FeedPatch {
+    fn invalid_fields(&self) -> Vec<FieldError> {
+        self.url.as_deref().map(check_url).unwrap_or_default()
+    }
+}
+
+impl Client {
+    /// Gets a page of the user feeds
+    ///
+    /// The first page is requested without cursor, the next ones with the `next_cursor` of
+    /// the previous page.
+    pub async fn get_feeds_page(
+        &self,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> Result<Paginated<Feed>, Error> {
+        let mut query = FeedQuery::new().limit(limit);
+        if let Some(cursor) = cursor {
+            query = query.cursor(cursor);
+        }
+        self.list_feeds(&query).await
+    }
+
+    /// Lists the user feeds, with query options (sort, filters, pagination)
+    pub async fn list_feeds(&self, query: &FeedQuery) -> Result<Paginated<Feed>, Error> {
+        let req = self.request(Method::GET, "/feeds").query(query);
+        self.send_json::<Paginated<Feed>>(req).await
+    }
+
+    /// Streams the user feeds, requesting them by pages of `page_size`
+    pub fn feeds_stream(&self, page_size: usize) -> impl Stream<Item = Result<Feed, Error>> + '_ {
+        self.paginate("/feeds", page_size)
+    }
+
+    /// Adds a feed
+    pub async fn add_feed(&self, feed: NewFeed) -> Result<Feed, Error> {
+        validate(&feed)?;
+        let (feeds, version) = self.get_feeds_versioned().await?;
+        let ids = feeds.iter().map(|f| f.id).collect::<HashSet<_>>();
+
+        let mut updates = feeds.into_iter().map(to_update).collect::<Vec<_>>();
+        updates.push(FeedUpdate {
+            id: None,
+            url: feed.url.clone(),
+            name: feed.name,
+        });
+        self.replace_feeds(&updates, version.as_deref())
+            .await?
+            .into_iter()
+            .find(|f| !ids.contains(&f.id) && f.url == feed.url)
+            .ok_or_else(|| Error::new("INTERNAL", "the new feed is missing"))
+    }
+
+    /// Updates a feed
+    pub async fn update_feed(&self, id: Uuid, patch: FeedPatch) -> Result<Feed, Error> {
+        validate(&patch)?;
+        let (feeds, version) = self.get_feeds_versioned().await?;
+        if !feeds.iter().any(|f| f.id == id) {
+            return Err(feed_not_found(id));
+        }
+
+        let updates = feeds
+            .into_iter()
+            .map(|f| {
+                let mut update = to_update(f);
+                if update.id == Some(id) {
+                    if let Some(url) = &patch.url {
+                        update.url = url.clone();
+                    }
+                    if let Some(name) = &patch.name {
+                        update.name = name.clone();
+                    }
+                }
+                update
+            })
+            .collect::<Vec<_>>();
+        self.replace_feeds(&updates, version.as_deref())
+            .await?
+            .into_iter()
+            .find(|f| f.id == id)
+            .ok_or_else(|| feed_not_found(id))
+    }
+
+    /// Deletes a feed
+    pub async fn delete_feed(&self, id: Uuid) -> Result<(), Error> {
+        let (feeds, version) = self.get_feeds_versioned().await?;
+        if !feeds.iter().any(|f| f.id == id) {
+            return Err(feed_not_found(id));
+        }
+
+        let updates = feeds
+            .into_iter()
+            .filter(|f| f.id != id)
+            .map(to_update)
+            .collect::<Vec<_>>();
+        self.replace_feeds(&updates, version.as_deref()).await?;
+        Ok(())
+    }
+
+    /// Gets all the user feeds, with their version (see [Client::replace_feeds])
+    pub async fn get_feeds_versioned(&self) -> Result<(Vec<Feed>, Option<String>), Error> {
+        let res = self.send(self.request(Method::GET, "/feeds")).await?;
+        let version = res
+            .headers()
+            .get(ETAG)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+        let body = read_json::<Paginated<Feed>>(res).await?;
+        Ok((body.items, version))
+    }
+
+    /// Replaces the user feeds, if they have not changed since their version
+    ///
+    /// If the feeds have changed since, a `CONFLICT` error is returned: the feeds should be
+    /// read again, and the changes applied to them. Without a version, the feeds are replaced
+    /// unconditionally (see [Client::sync_feeds]).
+    pub async fn replace_feeds(
+        &self,
+        feeds: &[FeedUpdate],
+        version: Option<&str>,
+    ) -> Result<Vec<Feed>, Error> {
+        validate(feeds)?;
+        let mut req = self.request(Method::PUT, "/feeds").json(feeds);
+        if let Some(version) = version {
+            req = req.header(IF_MATCH, version);
+        }
+        let body = self.send_json::<GetFeedsRespBody>(req).await?;
+        Ok(body.feeds)
+    }
+}
+
+/// Checks a feed URL
+fn check_url(url: &str) -> Vec<FieldError> {
+    if is_http_url(url) {
+        vec![]
+    } else {
+        vec![FieldError::new("url", "invalid http(s) URL")]
+    }
+}
+
+/// Converts a feed to its update
+fn to_update(feed: Feed) -> FeedUpdate {
+    FeedUpdate {
+        id: Some(feed.id),
+        url: feed.url,
+        name: feed.name,
+    }
+}
+
+/// Returns the error of a missing feed
+fn feed_not_found(id: Uuid) -> Error {
+    Error::new("NOT_FOUND", &format!("feed {id} not found"))
+}
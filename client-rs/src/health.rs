@@ -0,0 +1,68 @@
+//! Health checks
+//!
+//! The health endpoints are not authenticated, so that the connectivity with the API can be
+//! checked before logging in (eg. by deployment tooling).
+
+pub use newsie_api::mdl::{body::VersionRespBody, report::Readiness};
+use reqwest::{Method, StatusCode};
+
+use crate::{error::Error, read_json, Client};
+
+impl Client {
+    /// Checks that the API is up
+    pub async fn health(&self) -> Result<(), Error> {
+        let req = self.request(Method::GET, "/health");
+        self.execute(req, false).await?;
+        Ok(())
+    }
+
+    /// Checks that the API is ready to serve requests, with its dependencies (eg. database)
+    ///
+    /// NB: a readiness report is returned even if the API is not ready
+    pub async fn health_deep(&self) -> Result<Readiness, Error> {
+        let req = self.request(Method::GET, "/readyz");
+        match self.execute(req, false).await {
+            Ok(res) => read_json::<Readiness>(res).await,
+            // NB: the API responds with a 503 and the report if it is not ready
+            Err(Error::UnexpectedResponse {
+                status: StatusCode::SERVICE_UNAVAILABLE,
+                body,
+            }) => serde_json::from_str(&body)
+                .map_err(|err| Error::new("INTERNAL", &format!("invalid readiness report: {err}"))),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Gets the version of the API
+    pub async fn server_version(&self) -> Result<VersionRespBody, Error> {
+        let req = self.request(Method::GET, "/version");
+        read_json::<VersionRespBody>(self.execute(req, false).await?).await
+    }
+}
+
+#[cfg(all(test, feature = "test-util"))]
+mod tests {
+    use super::*;
+    use crate::mock::MockTransport;
+
+    #[tokio::test]
+    async fn test_health_deep() {
+        let mock = MockTransport::new();
+        mock.on(Method::GET, "/readyz").json(
+            StatusCode::SERVICE_UNAVAILABLE,
+            &serde_json::json!({
+                "ready": false,
+                "database": false,
+                "schema": false,
+                "scheduler": true
+            }),
+        );
+        let client = Client::mock(&mock);
+        let readiness = client.health_deep().await.unwrap();
+        assert!(!readiness.ready);
+
+        mock.on(Method::GET, "/readyz")
+            .body(StatusCode::SERVICE_UNAVAILABLE, b"<html>down</html>");
+        assert!(client.health_deep().await.is_err());
+    }
+}
@@ -0,0 +1,119 @@
+//! In-process transport
+//!
+//! The in-process transport sends the requests of a client to an API service running in the
+//! same process, without a server. The client is tested against the actual API, so that the
+//! models of the client and of the API cannot drift apart unnoticed:
+//!
+//! ```no_run
+//! # use newsie_api::{config::AppConfig, http::init_service};
+//! # use newsie_client::{inproc::InProcessTransport, Client};
+//! # async fn run() {
+//! let service = init_service(&AppConfig::load()).await.unwrap();
+//! let transport = InProcessTransport::new(service);
+//! let client = Client::in_process(&transport);
+//! client.health().await.unwrap();
+//! # }
+//! ```
+//!
+//! NB: the API services are still backed by the configured store
+
+use std::sync::{Arc, Mutex};
+
+use futures::FutureExt;
+use reqwest::Method;
+use salvo::{
+    test::{RequestBuilder, ResponseExt},
+    Service,
+};
+
+use crate::{
+    retry::RetryPolicy,
+    transport::{
+        Body, HttpTransport, Request, Response, TransportError, TransportErrorKind, TransportFuture,
+    },
+    Client,
+};
+
+/// Base URL of the in-process clients
+pub const IN_PROCESS_URL: &str = "http://newsie.local";
+
+/// In-process transport
+///
+/// The transport is shared by its clones.
+#[derive(Clone)]
+pub struct InProcessTransport {
+    /// API service
+    service: Arc<Service>,
+    /// Endpoints called (method and path)
+    calls: Arc<Mutex<Vec<(Method, String)>>>,
+}
+
+impl std::fmt::Debug for InProcessTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InProcessTransport")
+            .field("calls", &self.calls)
+            .finish_non_exhaustive()
+    }
+}
+
+impl InProcessTransport {
+    /// Creates a new transport
+    pub fn new(service: Service) -> Self {
+        Self {
+            service: Arc::new(service),
+            calls: Arc::default(),
+        }
+    }
+
+    /// Returns the endpoints called so far (method and path), in order
+    pub fn calls(&self) -> Vec<(Method, String)> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// Sends a request to the service
+    async fn send(&self, req: Request) -> Result<Response, TransportError> {
+        self.calls
+            .lock()
+            .unwrap()
+            .push((req.method().clone(), req.uri().path().to_string()));
+
+        let mut builder = RequestBuilder::new(&req.uri().to_string(), req.method().clone());
+        if !req.body().is_empty() {
+            builder = builder.bytes(req.body().to_vec());
+        }
+        // NB: the headers of the request override the ones set with the body
+        for name in req.headers().keys() {
+            for (i, value) in req.headers().get_all(name).iter().enumerate() {
+                builder = builder.add_header(name.clone(), value.clone(), i == 0);
+            }
+        }
+
+        let mut res = builder.send(self.service.as_ref()).await;
+        let body = res
+            .take_bytes(None)
+            .await
+            .map_err(|err| TransportError::new(TransportErrorKind::Other, &err.to_string()))?;
+        let mut http_res = http::Response::new(Body::from(body));
+        *http_res.status_mut() = res.status_code.unwrap_or_default();
+        *http_res.headers_mut() = res.headers().clone();
+        Ok(http_res)
+    }
+}
+
+impl HttpTransport for InProcessTransport {
+    fn execute(&self, req: Request) -> TransportFuture<'_> {
+        self.send(req).boxed()
+    }
+}
+
+impl Client {
+    /// Creates a client sending its requests to an in-process API service
+    ///
+    /// NB: the retries are disabled, so that the API errors are returned at once
+    pub fn in_process(transport: &InProcessTransport) -> Self {
+        let mut client = Client::new(IN_PROCESS_URL);
+        client.retry = RetryPolicy::none();
+        client.transport = Arc::new(transport.clone());
+        client
+    }
+}
@@ -1,18 +1,62 @@
 //! API client
 
+#[cfg(feature = "admin")]
+pub mod admin;
+pub mod auth;
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+pub mod blocking;
+pub mod builder;
+pub mod cache;
+pub mod call;
 pub mod error;
-
-use error::Error;
-use newsie_api::error::HttpErrorResponse;
-pub use newsie_api::{
-    http::{
-        auth::{GetUserRespBody, LoginReqBody, LoginRespBody, SignupRespBody},
-        feed::GetFeedsRespBody,
-        summary::SummariesRespBody,
+#[cfg(not(target_arch = "wasm32"))]
+pub mod export;
+pub mod feed;
+pub mod health;
+#[cfg(all(feature = "it-tests", not(target_arch = "wasm32")))]
+pub mod inproc;
+#[cfg(feature = "test-util")]
+pub mod mock;
+mod paginate;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod profile;
+pub mod query;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod queue;
+pub mod ratelimit;
+pub mod retry;
+pub mod sse;
+pub mod summary;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod tls;
+pub mod token;
+#[cfg(feature = "tracing")]
+mod trace;
+pub mod transport;
+#[cfg(all(feature = "ws", not(target_arch = "wasm32")))]
+pub mod ws;
+
+use std::sync::Arc;
+
+use auth::TokenRefresher;
+use builder::ClientBuilder;
+use cache::ResponseCache;
+use call::CallOptions;
+use error::{validate, Error};
+pub use newsie_api::mdl::{
+    body::{
+        GetFeedsRespBody, GetUserRespBody, LoginReqBody, LoginRespBody, Paginated, SignupRespBody,
+        SummariesRespBody,
     },
-    mdl::{Feed, FeedUpdate, NewUser, Subscription, SubscriptionUpdate, Summary, User, UserUpdate},
+    Feed, FeedUpdate, NewUser, PasswordChange, Subscription, SubscriptionUpdate, Summary, User,
+    UserUpdate,
 };
-use reqwest::header::{HeaderMap, AUTHORIZATION};
+use ratelimit::RateLimiter;
+use reqwest::{header::AUTHORIZATION, Method, Request, RequestBuilder, StatusCode, Url};
+use retry::RetryPolicy;
+use serde::de::DeserializeOwned;
+use token::{MemoryTokenStore, TokenStore};
+use transport::{to_request, HttpTransport, ReqwestTransport, Response, TransportError};
 
 // Re-exports
 
@@ -21,246 +65,327 @@ use reqwest::header::{HeaderMap, AUTHORIZATION};
 pub struct Client {
     /// Base URL
     pub url: String,
-    /// Authentication token
-    pub token: Option<String>,
+    /// Authentication token store
+    ///
+    /// NB: the store is shared with the clones, so that a refreshed token is used by all
+    store: Arc<dyn TokenStore>,
+    /// HTTP client
+    http: reqwest::Client,
+    /// Retry policy
+    retry: RetryPolicy,
+    /// Token refresher
+    refresher: Option<TokenRefresher>,
+    /// Call options
+    options: CallOptions,
+    /// Response cache (disabled if `None`)
+    cache: Option<ResponseCache>,
+    /// Rate limit state
+    rate_limit: RateLimiter,
+    /// HTTP transport
+    transport: Arc<dyn HttpTransport>,
 }
 
 impl Client {
-    /// Creates a new API client
+    /// Creates a new API client with the default settings
+    ///
+    /// The base URL is not validated, use [`Client::builder`] to configure the client.
     pub fn new(url: &str) -> Self {
+        let http = reqwest::Client::new();
         Self {
-            url: url.to_string(),
-            token: None,
+            url: url.trim_end_matches('/').to_string(),
+            store: Arc::new(MemoryTokenStore::default()),
+            http: http.clone(),
+            retry: RetryPolicy::default(),
+            refresher: None,
+            options: CallOptions::default(),
+            cache: None,
+            rate_limit: RateLimiter::default(),
+            transport: Arc::new(ReqwestTransport::new(http)),
         }
     }
 
+    /// Returns a builder to configure the client
+    pub fn builder(url: &str) -> ClientBuilder {
+        ClientBuilder::new(url)
+    }
+
     /// Sets the authentication token
+    ///
+    /// The token is kept in memory, and the client no longer uses its previous store.
     pub fn token(mut self, token: Option<String>) -> Self {
-        self.token = token;
+        self.store = Arc::new(MemoryTokenStore::new(token));
         self
     }
 
+    /// Returns the authentication token
+    pub fn get_token(&self) -> Option<String> {
+        self.store.get()
+    }
+
     /// Removes the authentication token
+    ///
+    /// NB: an error to update the token store is ignored
     pub fn unset_token(&mut self) -> &mut Self {
-        self.token = None;
+        let _res = self.store.set(None);
         self
     }
 }
 
 impl Client {
-    /// Signup a new user
-    pub async fn signup(&mut self, new_user: NewUser) -> Result<SignupRespBody, Error> {
-        let mut headers = HeaderMap::new();
-
-        if let Some(token) = &self.token {
-            headers.insert(AUTHORIZATION, format!("Bearer {}", token).parse().unwrap());
+    /// Prepares a request to an API path
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        match self.endpoint(path) {
+            Ok(url) => self.http.request(method, url),
+            // NB: the invalid base URL is reported when the request is built
+            Err(_) => self.http.request(method, &self.url),
         }
+    }
 
-        let res = reqwest::Client::new()
-            .post(&format!("{}/auth/signup", self.url))
-            .headers(headers)
-            .json(&new_user)
-            .send()
-            .await?;
-
-        if res.status().is_success() {
-            let ok = res.json::<SignupRespBody>().await?;
-            self.token = Some(ok.token.clone());
-            Ok(ok)
+    /// Returns the URL of an API path
+    fn endpoint(&self, path: &str) -> Result<Url, Error> {
+        join_url(&self.url, path)
+    }
+
+    /// Sends an authenticated request, and returns the response if successful
+    async fn send(&self, req: RequestBuilder) -> Result<Response, Error> {
+        self.execute(req, true).await
+    }
+
+    /// Sends an authenticated request, and deserializes the response body
+    async fn send_json<T: DeserializeOwned>(&self, req: RequestBuilder) -> Result<T, Error> {
+        read_json(self.send(req).await?).await
+    }
+
+    /// Sends a request, and returns the response if successful
+    async fn execute(&self, req: RequestBuilder, auth: bool) -> Result<Response, Error> {
+        let req = req.build()?;
+        #[cfg(feature = "tracing")]
+        let res = trace::instrument(
+            req.method().clone(),
+            req.url().path().to_string(),
+            self.options.run(self.dispatch(req, auth)),
+        )
+        .await?;
+        #[cfg(not(feature = "tracing"))]
+        let res = self.options.run(self.dispatch(req, auth)).await?;
+
+        // NB: a `304 Not Modified` answers a conditional request, see [`cache`]
+        if res.status().is_success() || res.status() == StatusCode::NOT_MODIFIED {
+            Ok(res)
         } else {
-            let err = res.json::<HttpErrorResponse>().await?;
-            Err(err.into())
+            let status = res.status();
+            let body = res.into_body().bytes().await?;
+            Err(Error::from_response(status, &body))
         }
     }
 
-    /// Login a user
-    pub async fn login(&mut self, email: &str, password: &str) -> Result<LoginRespBody, Error> {
-        let mut headers = HeaderMap::new();
-        if let Some(token) = &self.token {
-            headers.insert(AUTHORIZATION, format!("Bearer {}", token).parse().unwrap());
+    /// Sends a request, with its authentication header
+    ///
+    /// If the authenticated request is rejected as unauthorized, the token is refreshed
+    /// with the token refresher (if any), and the request is sent again once.
+    async fn dispatch(&self, mut req: Request, auth: bool) -> Result<Response, Error> {
+        if auth {
+            self.authorize(&mut req);
         }
+        let retry = req.try_clone();
+        let mut res = self.attempt(req).await?;
+
+        if auth && res.status() == StatusCode::UNAUTHORIZED {
+            if let (Some(refresher), Some(mut req)) = (&self.refresher, retry) {
+                let token = refresher.refresh().await?;
+                self.store.set(Some(&token))?;
+                self.authorize(&mut req);
+                res = self.attempt(req).await?;
+            }
+        }
+        Ok(res)
+    }
 
+    /// Sends a request
+    ///
+    /// The transient failures are retried according to the retry policy.
+    async fn attempt(&self, req: Request) -> Result<Response, Error> {
+        let mut retry = 0;
+        loop {
+            // NB: a request with a streamed body cannot be cloned, and is not retried
+            let Some(attempt) = req.try_clone() else {
+                return Ok(self.transmit(req).await?);
+            };
+            let delay = match self.transmit(attempt).await {
+                Ok(res) => match self.retry.on_response(retry, &res) {
+                    Some(delay) => delay,
+                    None => return Ok(res),
+                },
+                Err(err) => match self.retry.on_error(retry, req.method(), &err) {
+                    Some(delay) => delay,
+                    None => return Err(err.into()),
+                },
+            };
+            retry += 1;
+            retry::sleep(delay).await;
+        }
+    }
+
+    /// Sends a request with the transport
+    ///
+    /// The rate limit of the response is recorded, and waited for if throttling is enabled.
+    async fn transmit(&self, req: Request) -> Result<Response, TransportError> {
+        self.rate_limit.throttle().await;
+        let res = self.transport.execute(to_request(req)?).await?;
+        self.rate_limit.record(res.headers());
+        Ok(res)
+    }
+
+    /// Sets the authentication header of a request
+    fn authorize(&self, req: &mut Request) {
+        if let Some(token) = self.get_token() {
+            req.headers_mut()
+                .insert(AUTHORIZATION, format!("Bearer {}", token).parse().unwrap());
+        }
+    }
+}
+
+impl Client {
+    /// Signup a new user
+    pub async fn signup(&mut self, new_user: NewUser) -> Result<SignupRespBody, Error> {
+        validate(&new_user)?;
+        let req = self.request(Method::POST, "/auth/signup").json(&new_user);
+        let ok = read_json::<SignupRespBody>(self.execute(req, false).await?).await?;
+        self.store.set(Some(&ok.token))?;
+        Ok(ok)
+    }
+
+    /// Login a user
+    pub async fn login(&mut self, email: &str, password: &str) -> Result<LoginRespBody, Error> {
         let body = LoginReqBody {
             email: email.to_string(),
             password: password.to_string(),
         };
 
-        let res = reqwest::Client::new()
-            .post(&format!("{}/auth/login", self.url))
-            .headers(headers)
-            .json(&body)
-            .send()
-            .await?;
-
-        if res.status().is_success() {
-            let ok = res.json::<LoginRespBody>().await?;
-            self.token = Some(ok.token.clone());
-            Ok(ok)
-        } else {
-            let err = res.json::<HttpErrorResponse>().await?;
-            Err(err.into())
-        }
+        let req = self.request(Method::POST, "/auth/login").json(&body);
+        let ok = read_json::<LoginRespBody>(self.execute(req, false).await?).await?;
+        self.store.set(Some(&ok.token))?;
+        Ok(ok)
     }
 
     /// Gets the user info
     pub async fn me(&self) -> Result<GetUserRespBody, Error> {
-        let mut headers = HeaderMap::new();
-        if let Some(token) = &self.token {
-            headers.insert(AUTHORIZATION, format!("Bearer {}", token).parse().unwrap());
-        }
-
-        let res = reqwest::Client::new()
-            .get(&format!("{}/auth/me", self.url))
-            .headers(headers)
-            .send()
-            .await?;
-
-        if res.status().is_success() {
-            let ok = res.json::<GetUserRespBody>().await?;
-            Ok(ok)
-        } else {
-            let err = res.json::<HttpErrorResponse>().await?;
-            Err(err.into())
-        }
+        let req = self.request(Method::GET, "/auth/me");
+        self.send_json::<GetUserRespBody>(req).await
     }
 
     /// Update the user
     pub async fn update_me(&self, fields: UserUpdate) -> Result<User, Error> {
-        let mut headers = HeaderMap::new();
-        if let Some(token) = &self.token {
-            headers.insert(AUTHORIZATION, format!("Bearer {}", token).parse().unwrap());
-        }
+        validate(&fields)?;
+        let req = self.request(Method::PATCH, "/auth/me").json(&fields);
+        let ok = self.send_json::<GetUserRespBody>(req).await?;
+        Ok(ok.user)
+    }
 
-        let res = reqwest::Client::new()
-            .patch(&format!("{}/auth/me", self.url))
-            .headers(headers)
-            .json(&fields)
-            .send()
-            .await?;
+    /// Changes the user password
+    ///
+    /// The new token issued by the API replaces the current one.
+    pub async fn change_password(
+        &mut self,
+        current_password: &str,
+        new_password: &str,
+    ) -> Result<LoginRespBody, Error> {
+        let body = PasswordChange {
+            current_password: current_password.to_string(),
+            new_password: new_password.to_string(),
+        };
+        validate(&body)?;
 
-        if res.status().is_success() {
-            let ok = res.json::<GetUserRespBody>().await?;
-            Ok(ok.user)
-        } else {
-            let err = res.json::<HttpErrorResponse>().await?;
-            Err(err.into())
-        }
+        let req = self.request(Method::PUT, "/auth/me/password").json(&body);
+        let ok = self.send_json::<LoginRespBody>(req).await?;
+        self.store.set(Some(&ok.token))?;
+        Ok(ok)
     }
 
     /// Deletes the user
     pub async fn delete_me(&mut self) -> Result<(), Error> {
-        let mut headers = HeaderMap::new();
-        if let Some(token) = &self.token {
-            headers.insert(AUTHORIZATION, format!("Bearer {}", token).parse().unwrap());
-        }
-
-        let res = reqwest::Client::new()
-            .delete(&format!("{}/auth/me", self.url))
-            .headers(headers)
-            .send()
-            .await?;
-
-        if res.status().is_success() {
-            self.unset_token();
-            Ok(())
-        } else {
-            let err = res.json::<HttpErrorResponse>().await?;
-            Err(err.into())
-        }
+        let req = self.request(Method::DELETE, "/auth/me");
+        self.send(req).await?;
+        self.unset_token();
+        Ok(())
     }
 
     /// Update the user subscription
     pub async fn update_subscription(&self, update: SubscriptionUpdate) -> Result<User, Error> {
-        let mut headers = HeaderMap::new();
-        if let Some(token) = &self.token {
-            headers.insert(AUTHORIZATION, format!("Bearer {}", token).parse().unwrap());
-        }
-
-        let res = reqwest::Client::new()
-            .put(&format!("{}/auth/me/subscription", self.url))
-            .headers(headers)
-            .json(&update)
-            .send()
-            .await?;
-
-        if res.status().is_success() {
-            let body = res.json::<GetUserRespBody>().await?;
-            Ok(body.user)
-        } else {
-            let err = res.json::<HttpErrorResponse>().await?;
-            Err(err.into())
-        }
+        let req = self
+            .request(Method::PUT, "/auth/me/subscription")
+            .json(&update);
+        let body = self.send_json::<GetUserRespBody>(req).await?;
+        Ok(body.user)
     }
 }
 
 impl Client {
     /// Get the user feeds
     pub async fn get_feeds(&self) -> Result<Vec<Feed>, Error> {
-        let mut headers = HeaderMap::new();
-        if let Some(token) = &self.token {
-            headers.insert(AUTHORIZATION, format!("Bearer {}", token).parse().unwrap());
-        }
-
-        let res = reqwest::Client::new()
-            .get(&format!("{}/feeds", self.url))
-            .headers(headers)
-            .send()
-            .await?;
-
-        if res.status().is_success() {
-            let body = res.json::<GetFeedsRespBody>().await?;
-            Ok(body.feeds)
-        } else {
-            let err = res.json::<HttpErrorResponse>().await?;
-            Err(err.into())
-        }
+        let req = self.request(Method::GET, "/feeds");
+        let body = self.send_json_cached::<Paginated<Feed>>(req).await?;
+        Ok(body.items)
     }
 
     /// Sync the user feeds
     pub async fn sync_feeds(&self, feeds: &[FeedUpdate]) -> Result<Vec<Feed>, Error> {
-        let mut headers = HeaderMap::new();
-        if let Some(token) = &self.token {
-            headers.insert(AUTHORIZATION, format!("Bearer {}", token).parse().unwrap());
-        }
-
-        let res = reqwest::Client::new()
-            .put(&format!("{}/feeds", self.url))
-            .headers(headers)
-            .json(feeds)
-            .send()
-            .await?;
-
-        if res.status().is_success() {
-            let body = res.json::<GetFeedsRespBody>().await?;
-            Ok(body.feeds)
-        } else {
-            let err = res.json::<HttpErrorResponse>().await?;
-            Err(err.into())
-        }
+        validate(feeds)?;
+        let req = self.request(Method::PUT, "/feeds").json(feeds);
+        let body = self.send_json::<GetFeedsRespBody>(req).await?;
+        Ok(body.feeds)
     }
 }
 
-impl Client {
-    /// Summarize a list of articles
-    pub async fn summarize(&self, urls: &[&str]) -> Result<Vec<Summary>, Error> {
-        let mut headers = HeaderMap::new();
-        if let Some(token) = &self.token {
-            headers.insert(AUTHORIZATION, format!("Bearer {}", token).parse().unwrap());
-        }
+/// Reads a response body, and deserializes it
+async fn read_json<T: DeserializeOwned>(res: Response) -> Result<T, Error> {
+    let body = res.into_body().bytes().await?;
+    serde_json::from_slice(&body)
+        .map_err(|err| Error::new("INTERNAL", &format!("invalid response body: {err}")))
+}
 
-        let res = reqwest::Client::new()
-            .post(&format!("{}/summaries", self.url))
-            .headers(headers)
-            .json(&urls.iter().map(|url| url.to_string()).collect::<Vec<_>>())
-            .send()
-            .await?;
+/// Joins an API path to a base URL
+///
+/// The path segments are percent-encoded, and appended to the path of the base URL
+/// (eg. `https://host/newsie/` and `/feeds` give `https://host/newsie/feeds`).
+fn join_url(base: &str, path: &str) -> Result<Url, Error> {
+    let mut url = Url::parse(base)
+        .map_err(|err| Error::new("CONFIG", &format!("invalid base URL '{base}': {err}")))?;
+    url.path_segments_mut()
+        .map_err(|_| Error::new("CONFIG", &format!("invalid base URL '{base}'")))?
+        .pop_if_empty()
+        .extend(path.split('/').filter(|segment| !segment.is_empty()));
+    Ok(url)
+}
 
-        if res.status().is_success() {
-            let body = res.json::<SummariesRespBody>().await?;
-            Ok(body.summaries)
-        } else {
-            let err = res.json::<HttpErrorResponse>().await?;
-            Err(err.into())
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_join_url() {
+        let join = |base, path| join_url(base, path).unwrap().to_string();
+        assert_eq!(
+            join("http://localhost:3000", "/auth/login"),
+            "http://localhost:3000/auth/login"
+        );
+        assert_eq!(
+            join("http://localhost:3000/", "/auth/login"),
+            "http://localhost:3000/auth/login"
+        );
+        assert_eq!(
+            join("https://host/newsie", "/feeds"),
+            "https://host/newsie/feeds"
+        );
+        assert_eq!(
+            join("https://host/newsie/", "/feeds"),
+            "https://host/newsie/feeds"
+        );
+        assert_eq!(
+            join("https://host", "/feeds/a b?c"),
+            "https://host/feeds/a%20b%3Fc"
+        );
+        assert!(join_url("localhost:3000", "/feeds").is_err());
+        assert!(join_url("not a url", "/feeds").is_err());
     }
 }
@@ -0,0 +1,333 @@
+//! Mock transport
+//!
+//! The mock transport answers the requests of a client with canned responses, so that the
+//! code using the client can be tested without the API:
+//!
+//! ```no_run
+//! # use newsie_client::{
+//! #     mock::{Method, MockTransport, StatusCode},
+//! #     Client,
+//! # };
+//! # async fn run() {
+//! let mock = MockTransport::new();
+//! mock.on(Method::GET, "/feeds")
+//!     .json(StatusCode::OK, &serde_json::json!({ "items": [] }));
+//! mock.on(Method::POST, "/summaries")
+//!     .error(StatusCode::TOO_MANY_REQUESTS, "RATE_LIMITED", "slow down");
+//!
+//! let client = Client::mock(&mock);
+//! assert!(client.get_feeds().await.unwrap().is_empty());
+//! assert_eq!(mock.requests().len(), 1);
+//! # }
+//! ```
+
+use std::sync::{Arc, Mutex};
+
+use futures::{future, FutureExt};
+
+use newsie_api::error::{HttpError, HttpErrorResponse};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+pub use reqwest::{Method, StatusCode};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    retry::RetryPolicy,
+    transport::{Body, HttpTransport, Request, Response, TransportFuture},
+    Client,
+};
+
+/// Base URL of the mocked clients
+pub const MOCK_URL: &str = "http://mock.newsie.local";
+
+/// Mock transport
+///
+/// The transport is shared by its clones.
+#[derive(Debug, Clone, Default)]
+pub struct MockTransport {
+    /// Routes (the last registered route matching a request wins)
+    routes: Arc<Mutex<Vec<MockRoute>>>,
+    /// Received requests
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+}
+
+/// Canned response of a route
+#[derive(Debug, Clone)]
+struct MockRoute {
+    /// Method
+    method: Method,
+    /// Path
+    path: String,
+    /// Response status
+    status: StatusCode,
+    /// Response headers
+    headers: HeaderMap,
+    /// Response body
+    body: Vec<u8>,
+    /// Number of remaining responses (unlimited if `None`)
+    times: Option<usize>,
+}
+
+/// Request received by the mock transport
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    /// Method
+    pub method: Method,
+    /// Path
+    pub path: String,
+    /// Query string
+    pub query: Option<String>,
+    /// Headers
+    pub headers: HeaderMap,
+    /// Body
+    pub body: Vec<u8>,
+}
+
+impl RecordedRequest {
+    /// Deserializes the JSON body
+    pub fn json<T: DeserializeOwned>(&self) -> serde_json::Result<T> {
+        serde_json::from_slice(&self.body)
+    }
+}
+
+/// Builder of a canned response
+#[derive(Debug)]
+#[must_use = "the response is registered by `json`, `error` or `body`"]
+pub struct MockResponder<'a> {
+    /// Transport
+    transport: &'a MockTransport,
+    /// Route
+    route: MockRoute,
+}
+
+impl MockTransport {
+    /// Creates a new transport
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Prepares the response to the requests with a method and path
+    pub fn on(&self, method: Method, path: &str) -> MockResponder<'_> {
+        MockResponder {
+            transport: self,
+            route: MockRoute {
+                method,
+                path: path.to_string(),
+                status: StatusCode::OK,
+                headers: HeaderMap::new(),
+                body: vec![],
+                times: None,
+            },
+        }
+    }
+
+    /// Returns the received requests
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Removes the routes and the received requests
+    pub fn reset(&self) {
+        self.routes.lock().unwrap().clear();
+        self.requests.lock().unwrap().clear();
+    }
+
+    /// Answers a request
+    ///
+    /// A request without route is answered with a `404 NOT_FOUND` error.
+    fn respond(&self, req: &Request) -> Response {
+        let path = req.uri().path().to_string();
+        self.requests.lock().unwrap().push(RecordedRequest {
+            method: req.method().clone(),
+            path: path.clone(),
+            query: req.uri().query().map(|q| q.to_string()),
+            headers: req.headers().clone(),
+            body: req.body().to_vec(),
+        });
+
+        let mut routes = self.routes.lock().unwrap();
+        let route = routes
+            .iter_mut()
+            .rev()
+            .find(|r| r.method == req.method() && r.path == path && r.times != Some(0));
+        let Some(route) = route else {
+            let route = error_route(
+                req.method().clone(),
+                &path,
+                StatusCode::NOT_FOUND,
+                "NOT_FOUND",
+                "no mocked response",
+            );
+            return to_response(&route);
+        };
+        if let Some(times) = &mut route.times {
+            *times -= 1;
+        }
+        to_response(route)
+    }
+}
+
+impl MockResponder<'_> {
+    /// Answers a limited number of requests (eg. to fail once, then succeed)
+    pub fn times(mut self, times: usize) -> Self {
+        self.route.times = Some(times);
+        self
+    }
+
+    /// Adds a response header
+    pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.route.headers.insert(name, value);
+        self
+    }
+
+    /// Responds with a JSON body
+    pub fn json<T: Serialize>(mut self, status: StatusCode, body: &T) {
+        self.route.status = status;
+        self.route.body = serde_json::to_vec(body).expect("invalid mocked body");
+        self.route
+            .headers
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        self.register();
+    }
+
+    /// Responds with an API error
+    pub fn error(mut self, status: StatusCode, code: &str, message: &str) {
+        let route = error_route(
+            self.route.method.clone(),
+            &self.route.path,
+            status,
+            code,
+            message,
+        );
+        self.route.status = route.status;
+        self.route.body = route.body;
+        self.route.headers.extend(route.headers);
+        self.register();
+    }
+
+    /// Responds with a raw body (eg. a proxy error page)
+    pub fn body(mut self, status: StatusCode, body: &[u8]) {
+        self.route.status = status;
+        self.route.body = body.to_vec();
+        self.register();
+    }
+
+    /// Registers the route
+    fn register(self) {
+        self.transport.routes.lock().unwrap().push(self.route);
+    }
+}
+
+/// Returns a route responding with an API error
+fn error_route(
+    method: Method,
+    path: &str,
+    status: StatusCode,
+    code: &str,
+    message: &str,
+) -> MockRoute {
+    let body = HttpErrorResponse {
+        error: HttpError {
+            code: code.to_string(),
+            message: message.to_string(),
+            detail: None,
+            fields: vec![],
+        },
+    };
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    MockRoute {
+        method,
+        path: path.to_string(),
+        status,
+        headers,
+        body: serde_json::to_vec(&body).unwrap(),
+        times: None,
+    }
+}
+
+/// Converts a route to its response
+fn to_response(route: &MockRoute) -> Response {
+    let mut res = http::Response::new(Body::from(route.body.clone()));
+    *res.status_mut() = route.status;
+    *res.headers_mut() = route.headers.clone();
+    res
+}
+
+impl HttpTransport for MockTransport {
+    fn execute(&self, req: Request) -> TransportFuture<'_> {
+        let res = self.respond(&req);
+        #[cfg(not(target_arch = "wasm32"))]
+        return future::ready(Ok(res)).boxed();
+        #[cfg(target_arch = "wasm32")]
+        return future::ready(Ok(res)).boxed_local();
+    }
+}
+
+impl Client {
+    /// Creates a client answered by a mock transport
+    ///
+    /// NB: the retries are disabled, so that the canned errors are returned at once
+    pub fn mock(transport: &MockTransport) -> Self {
+        let mut client = Client::new(MOCK_URL);
+        client.retry = RetryPolicy::none();
+        client.transport = Arc::new(transport.clone());
+        client
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_mock() {
+        let mock = MockTransport::new();
+        mock.on(Method::GET, "/feeds")
+            .json(StatusCode::OK, &serde_json::json!({ "items": [] }));
+        mock.on(Method::POST, "/summaries").error(
+            StatusCode::BAD_REQUEST,
+            "INVALID_REQUEST",
+            "invalid URL",
+        );
+
+        let client = Client::mock(&mock).token(Some("token".to_string()));
+        assert!(client.get_feeds().await.unwrap().is_empty());
+        let err = client
+            .summarize(&["https://blocked.example.com"])
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "INVALID_REQUEST: invalid URL");
+        assert!(client.me().await.is_err());
+        // NB: an invalid input is rejected without a request
+        let err = client.summarize(&["not a url"]).await.unwrap_err();
+        assert!(matches!(err, crate::error::Error::Validation { .. }));
+
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[0].path, "/feeds");
+        assert_eq!(
+            requests[0].headers.get("authorization").unwrap(),
+            "Bearer token"
+        );
+        assert_eq!(
+            requests[1].json::<Vec<String>>().unwrap(),
+            vec!["https://blocked.example.com".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_times() {
+        let mock = MockTransport::new();
+        mock.on(Method::GET, "/feeds")
+            .json(StatusCode::OK, &serde_json::json!({ "items": [] }));
+        mock.on(Method::GET, "/feeds").times(1).error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "UNAVAILABLE",
+            "down",
+        );
+
+        let client = Client::mock(&mock);
+        assert!(client.get_feeds().await.is_err());
+        assert!(client.get_feeds().await.is_ok());
+    }
+}
@@ -0,0 +1,60 @@
+//! Pagination
+//!
+//! The listing endpoints return their items by pages (see [`crate::Paginated`]), the next
+//! page being requested with the `cursor` of the previous one.
+
+use futures::{stream, Stream, TryStreamExt};
+use reqwest::Method;
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::{error::Error, Client};
+
+/// Page of items
+///
+/// NB: this mirrors [`crate::Paginated`], which is bound to the server schemas
+#[derive(Debug, Deserialize)]
+pub(crate) struct Page<T> {
+    /// Items of the page
+    pub items: Vec<T>,
+    /// Cursor of the next page (none if this is the last page)
+    pub next_cursor: Option<String>,
+}
+
+impl Client {
+    /// Gets a page of a listing endpoint
+    async fn get_page<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> Result<Page<T>, Error> {
+        let mut req = self
+            .request(Method::GET, path)
+            .query(&[("limit", limit.to_string())]);
+        if let Some(cursor) = cursor {
+            req = req.query(&[("cursor", cursor)]);
+        }
+        self.send_json_cached::<Page<T>>(req).await
+    }
+
+    /// Streams the items of a listing endpoint, requesting the pages as they are consumed
+    pub(crate) fn paginate<'a, T>(
+        &'a self,
+        path: &'static str,
+        limit: usize,
+    ) -> impl Stream<Item = Result<T, Error>> + 'a
+    where
+        T: DeserializeOwned + 'a,
+    {
+        // NB: the state is the cursor of the next page, or `None` after the last page
+        stream::try_unfold(Some(None::<String>), move |cursor| async move {
+            let Some(cursor) = cursor else {
+                return Ok::<_, Error>(None);
+            };
+            let page = self.get_page::<T>(path, limit, cursor.as_deref()).await?;
+            let items = stream::iter(page.items.into_iter().map(Ok));
+            Ok(Some((items, page.next_cursor.map(Some))))
+        })
+        .try_flatten()
+    }
+}
@@ -0,0 +1,185 @@
+//! Server profiles
+//!
+//! The tools targeting several servers (eg. staging and production) share a TOML file of named
+//! profiles:
+//!
+//! ```toml
+//! default = "production"
+//!
+//! [profiles.production]
+//! url = "https://api.newsie.rocks"
+//! token = "..."
+//!
+//! [profiles.staging]
+//! url = "https://staging.newsie.rocks"
+//! ```
+//!
+//! A client is created for a profile with [`Profiles::client`].
+
+use std::{collections::BTreeMap, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{builder::ClientBuilder, error::Error, Client};
+
+/// Server profile
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Profile {
+    /// API url
+    pub url: String,
+    /// Authentication token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl Profile {
+    /// Creates a new profile
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            token: None,
+        }
+    }
+
+    /// Sets the authentication token
+    pub fn token(mut self, token: &str) -> Self {
+        self.token = Some(token.to_string());
+        self
+    }
+
+    /// Returns a client builder for the profile
+    pub fn builder(&self) -> ClientBuilder {
+        ClientBuilder::new(&self.url).token(self.token.clone())
+    }
+}
+
+/// Named server profiles
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Profiles {
+    /// Name of the default profile
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    /// Profiles, by name
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+impl Profiles {
+    /// Loads the profiles from a file
+    ///
+    /// NB: a missing file has no profiles
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        match std::fs::read_to_string(path) {
+            Ok(data) => Self::parse(&data),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(io_error(err)),
+        }
+    }
+
+    /// Parses the profiles
+    pub fn parse(data: &str) -> Result<Self, Error> {
+        toml::from_str(data)
+            .map_err(|err| Error::new("CONFIG", &format!("invalid profiles: {err}")))
+    }
+
+    /// Saves the profiles to a file
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        let data = toml::to_string_pretty(self)
+            .map_err(|err| Error::new("CONFIG", &format!("invalid profiles: {err}")))?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(io_error)?;
+        }
+        std::fs::write(path, data).map_err(io_error)
+    }
+
+    /// Returns a profile
+    pub fn get(&self, name: &str) -> Option<&Profile> {
+        self.profiles.get(name)
+    }
+
+    /// Returns the default profile (or the only one)
+    pub fn get_default(&self) -> Option<&Profile> {
+        match &self.default {
+            Some(name) => self.get(name),
+            None if self.profiles.len() == 1 => self.profiles.values().next(),
+            None => None,
+        }
+    }
+
+    /// Adds or replaces a profile
+    pub fn insert(&mut self, name: &str, profile: Profile) {
+        self.profiles.insert(name.to_string(), profile);
+    }
+
+    /// Removes a profile
+    pub fn remove(&mut self, name: &str) -> Option<Profile> {
+        if self.default.as_deref() == Some(name) {
+            self.default = None;
+        }
+        self.profiles.remove(name)
+    }
+
+    /// Returns a client builder for a profile (the default one if `name` is `None`)
+    pub fn builder(&self, name: Option<&str>) -> Result<ClientBuilder, Error> {
+        let profile = match name {
+            Some(name) => self
+                .get(name)
+                .ok_or_else(|| Error::new("CONFIG", &format!("unknown profile '{name}'")))?,
+            None => self
+                .get_default()
+                .ok_or_else(|| Error::new("CONFIG", "no default profile"))?,
+        };
+        Ok(profile.builder())
+    }
+
+    /// Creates a client for a profile (the default one if `name` is `None`)
+    pub fn client(&self, name: Option<&str>) -> Result<Client, Error> {
+        self.builder(name)?.build()
+    }
+}
+
+/// Converts an IO error
+fn io_error(err: std::io::Error) -> Error {
+    Error::new("CONFIG", &format!("failed to access the profiles: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiles() {
+        let mut profiles = Profiles::parse(
+            r#"
+            default = "production"
+
+            [profiles.production]
+            url = "https://api.newsie.rocks"
+            token = "abc"
+
+            [profiles.staging]
+            url = "https://staging.newsie.rocks"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            profiles.get_default(),
+            Some(&Profile::new("https://api.newsie.rocks").token("abc"))
+        );
+        assert_eq!(
+            profiles.client(Some("staging")).unwrap().url,
+            "https://staging.newsie.rocks"
+        );
+        assert_eq!(profiles.client(Some("dev")).unwrap_err().code(), "CONFIG");
+
+        profiles.remove("production");
+        assert_eq!(profiles.get_default(), profiles.get("staging"));
+        let path = std::env::temp_dir()
+            .join(format!("newsie-profiles-{}", std::process::id()))
+            .join("profiles.toml");
+        profiles.save(&path).unwrap();
+        assert_eq!(Profiles::load(&path).unwrap(), profiles);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
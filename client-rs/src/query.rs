@@ -0,0 +1,114 @@
+//! Query options
+//!
+//! The options of the listing endpoints are set with builders, serialized to the query
+//! parameters of the request:
+//!
+//! ```
+//! # use newsie_client::query::FeedQuery;
+//! let query = FeedQuery::new().name("rust").sort_desc("name").limit(50);
+//! ```
+//!
+//! NB: the options which are not set are not sent, and left to the server defaults
+
+use serde::Serialize;
+
+/// Query options of the feeds
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct FeedQuery {
+    /// Sort criteria, comma separated (`-` for a descending order)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sort: Option<String>,
+    /// Filter on the feed name
+    #[serde(rename = "filter[name]", skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    /// Filter on the feed url
+    #[serde(rename = "filter[url]", skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    /// Pagination cursor
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cursor: Option<String>,
+    /// Maximum number of feeds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    limit: Option<usize>,
+}
+
+impl FeedQuery {
+    /// Creates new query options
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sorts the feeds on a field (`name` or `url`), in ascending order
+    ///
+    /// NB: the criteria are applied in the order they are added
+    pub fn sort_asc(self, field: &str) -> Self {
+        self.sort_by(field)
+    }
+
+    /// Sorts the feeds on a field (`name` or `url`), in descending order
+    pub fn sort_desc(self, field: &str) -> Self {
+        self.sort_by(&format!("-{field}"))
+    }
+
+    /// Filters the feeds by name
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// Filters the feeds by url
+    pub fn url(mut self, url: &str) -> Self {
+        self.url = Some(url.to_string());
+        self
+    }
+
+    /// Sets the pagination cursor (the `next_cursor` of the previous page)
+    pub fn cursor(mut self, cursor: &str) -> Self {
+        self.cursor = Some(cursor.to_string());
+        self
+    }
+
+    /// Sets the maximum number of feeds
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Adds a sort criteria
+    fn sort_by(mut self, item: &str) -> Self {
+        self.sort = Some(match self.sort {
+            Some(sort) => format!("{sort},{item}"),
+            None => item.to_string(),
+        });
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_feed_query() {
+        let query = FeedQuery::new()
+            .sort_desc("name")
+            .sort_asc("url")
+            .name("rust")
+            .limit(50);
+        assert_eq!(
+            to_query(&query).as_deref(),
+            Some("sort=-name%2Curl&filter%5Bname%5D=rust&limit=50")
+        );
+        assert_eq!(to_query(&FeedQuery::new()), None);
+    }
+
+    /// Serializes query options to a url query
+    fn to_query(query: &FeedQuery) -> Option<String> {
+        let req = reqwest::Client::new()
+            .get("http://localhost/feeds")
+            .query(query)
+            .build()
+            .unwrap();
+        req.url().query().map(|q| q.to_string())
+    }
+}
@@ -0,0 +1,266 @@
+//! Offline mutation queue
+//!
+//! The mutations made while offline are collected in a queue, stored in a file so that they
+//! survive a restart, and replayed once the API is reachable:
+//!
+//! ```no_run
+//! # use newsie_client::{
+//! #     feed::NewFeed,
+//! #     queue::{Mutation, MutationQueue},
+//! #     Client,
+//! # };
+//! # async fn run(client: Client) {
+//! let queue = MutationQueue::new("/tmp/newsie/queue.json");
+//! queue
+//!     .push(Mutation::AddFeed(NewFeed::new("https://www.newsie.rocks/feed")))
+//!     .unwrap();
+//! let report = client.flush(&queue).await.unwrap();
+//! # }
+//! ```
+//!
+//! When replayed, a mutation rejected by the API (eg. an update of a feed deleted on another
+//! device) is dropped and reported. A mutation conflicting with a concurrent change is
+//! attempted again, and the replay stops at the first other error (eg. still offline),
+//! leaving the remaining mutations in the queue.
+
+use std::{path::PathBuf, sync::Mutex};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    error::Error,
+    feed::{FeedPatch, NewFeed},
+    Client,
+};
+
+/// Maximum number of attempts of a conflicting mutation
+const MAX_CONFLICT_ATTEMPTS: usize = 3;
+
+/// Mutation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Mutation {
+    /// Adds a feed
+    AddFeed(NewFeed),
+    /// Updates a feed
+    UpdateFeed {
+        /// Feed ID
+        id: Uuid,
+        /// Changes
+        patch: FeedPatch,
+    },
+    /// Deletes a feed
+    DeleteFeed {
+        /// Feed ID
+        id: Uuid,
+    },
+}
+
+/// Mutation rejected by the API
+#[derive(Debug)]
+pub struct RejectedMutation {
+    /// Mutation
+    pub mutation: Mutation,
+    /// Error
+    pub error: Error,
+}
+
+/// Report of a replay
+#[derive(Debug, Default)]
+pub struct FlushReport {
+    /// Number of applied mutations
+    pub applied: usize,
+    /// Rejected mutations (dropped from the queue)
+    pub rejected: Vec<RejectedMutation>,
+}
+
+/// File-backed mutation queue
+///
+/// The mutations are stored as JSON.
+///
+/// NB: the queue must not be shared by several processes
+#[derive(Debug)]
+pub struct MutationQueue {
+    /// File path
+    path: PathBuf,
+    /// Lock of the file
+    lock: Mutex<()>,
+}
+
+impl MutationQueue {
+    /// Creates a new queue, stored in a file (created with the first mutation)
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Adds a mutation at the end of the queue
+    pub fn push(&self, mutation: Mutation) -> Result<(), Error> {
+        let _lock = self.lock.lock().unwrap();
+        let mut mutations = self.read()?;
+        mutations.push(mutation);
+        self.write(&mutations)
+    }
+
+    /// Returns the pending mutations
+    pub fn pending(&self) -> Result<Vec<Mutation>, Error> {
+        let _lock = self.lock.lock().unwrap();
+        self.read()
+    }
+
+    /// Returns the number of pending mutations
+    pub fn len(&self) -> Result<usize, Error> {
+        Ok(self.pending()?.len())
+    }
+
+    /// Checks if the queue is empty
+    pub fn is_empty(&self) -> Result<bool, Error> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Removes the pending mutations
+    pub fn clear(&self) -> Result<(), Error> {
+        let _lock = self.lock.lock().unwrap();
+        self.write(&[])
+    }
+
+    /// Removes the first mutation of the queue
+    fn pop_front(&self) -> Result<(), Error> {
+        let _lock = self.lock.lock().unwrap();
+        let mut mutations = self.read()?;
+        if !mutations.is_empty() {
+            mutations.remove(0);
+        }
+        self.write(&mutations)
+    }
+
+    /// Reads the mutations from the file
+    fn read(&self) -> Result<Vec<Mutation>, Error> {
+        match std::fs::read(&self.path) {
+            Ok(data) => serde_json::from_slice(&data)
+                .map_err(|err| Error::new("QUEUE", &format!("invalid mutation queue: {err}"))),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+            Err(err) => Err(io_error(err)),
+        }
+    }
+
+    /// Writes the mutations to the file
+    ///
+    /// NB: the file is replaced at once, so that it is not left half-written
+    fn write(&self, mutations: &[Mutation]) -> Result<(), Error> {
+        if mutations.is_empty() {
+            return match std::fs::remove_file(&self.path) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(io_error(err)),
+                _ => Ok(()),
+            };
+        }
+
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir).map_err(io_error)?;
+        }
+        let data = serde_json::to_vec(mutations)
+            .map_err(|err| Error::new("QUEUE", &format!("invalid mutation: {err}")))?;
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, data).map_err(io_error)?;
+        std::fs::rename(&tmp, &self.path).map_err(io_error)
+    }
+}
+
+/// Converts an IO error
+fn io_error(err: std::io::Error) -> Error {
+    Error::new(
+        "QUEUE",
+        &format!("failed to store the mutation queue: {err}"),
+    )
+}
+
+impl Client {
+    /// Replays the mutations of a queue, in order
+    ///
+    /// The applied and rejected mutations are removed from the queue. If the replay stops
+    /// with an error, the remaining mutations are kept for the next replay.
+    pub async fn flush(&self, queue: &MutationQueue) -> Result<FlushReport, Error> {
+        let mut report = FlushReport::default();
+        for mutation in queue.pending()? {
+            let mut attempt = 1;
+            let res = loop {
+                match self.apply(&mutation).await {
+                    Err(err) if err.code() == "CONFLICT" && attempt < MAX_CONFLICT_ATTEMPTS => {
+                        attempt += 1;
+                    }
+                    res => break res,
+                }
+            };
+            match res {
+                Ok(()) => report.applied += 1,
+                Err(err) if is_rejected(&err) => {
+                    report.rejected.push(RejectedMutation {
+                        mutation,
+                        error: err,
+                    });
+                }
+                Err(err) => return Err(err),
+            }
+            queue.pop_front()?;
+        }
+        Ok(report)
+    }
+
+    /// Applies a mutation
+    async fn apply(&self, mutation: &Mutation) -> Result<(), Error> {
+        match mutation {
+            Mutation::AddFeed(feed) => self.add_feed(feed.clone()).await.map(|_| ()),
+            Mutation::UpdateFeed { id, patch } => {
+                self.update_feed(*id, patch.clone()).await.map(|_| ())
+            }
+            Mutation::DeleteFeed { id } => self.delete_feed(*id).await,
+        }
+    }
+}
+
+/// Checks if a mutation is rejected, and would fail again if replayed
+fn is_rejected(err: &Error) -> bool {
+    matches!(
+        err.code(),
+        "NOT_FOUND" | "INVALID_REQUEST" | "INVALID_FIELDS"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queue() {
+        let path = std::env::temp_dir()
+            .join(format!("newsie-queue-{}", std::process::id()))
+            .join("queue.json");
+        let queue = MutationQueue::new(&path);
+        assert!(queue.is_empty().unwrap());
+
+        let id = Uuid::from_u128(1);
+        let mutations = vec![
+            Mutation::AddFeed(NewFeed::new("https://host/feed").name("Feed")),
+            Mutation::UpdateFeed {
+                id,
+                patch: FeedPatch {
+                    url: None,
+                    name: Some(None),
+                },
+            },
+            Mutation::DeleteFeed { id },
+        ];
+        for mutation in &mutations {
+            queue.push(mutation.clone()).unwrap();
+        }
+        assert_eq!(MutationQueue::new(&path).pending().unwrap(), mutations);
+
+        queue.pop_front().unwrap();
+        assert_eq!(queue.len().unwrap(), 2);
+        queue.clear().unwrap();
+        assert!(!path.exists());
+    }
+}
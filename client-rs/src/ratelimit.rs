@@ -0,0 +1,173 @@
+//! Rate limits
+//!
+//! The rate limit of the API is read from the headers of the responses:
+//!
+//! - `X-RateLimit-Limit`: number of requests allowed in the current window
+//! - `X-RateLimit-Remaining`: number of requests left in the current window
+//! - `X-RateLimit-Reset`: number of seconds before the window is reset
+//! - `Retry-After`: delay requested by the server before sending another request
+//!
+//! The last rate limit is kept by the client (see [`Client::rate_limit`]). When throttling is
+//! enabled, the requests wait for the window to be reset once it is exhausted, so that bulk
+//! operations cooperate with the limits of the server.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use reqwest::header::HeaderMap;
+
+use crate::{retry, Client};
+
+/// Header of the request limit
+pub const LIMIT_HEADER: &str = "x-ratelimit-limit";
+
+/// Header of the remaining requests
+pub const REMAINING_HEADER: &str = "x-ratelimit-remaining";
+
+/// Header of the delay before the reset of the limit
+pub const RESET_HEADER: &str = "x-ratelimit-reset";
+
+/// Rate limit of the API
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RateLimitInfo {
+    /// Number of requests allowed in the current window
+    pub limit: Option<u64>,
+    /// Number of requests left in the current window
+    pub remaining: Option<u64>,
+    /// Time at which the window is reset
+    pub reset: Option<SystemTime>,
+    /// Time before which no request should be sent (from the `Retry-After` header)
+    pub retry_at: Option<SystemTime>,
+}
+
+impl RateLimitInfo {
+    /// Parses the rate limit headers of a response
+    ///
+    /// Returns `None` if the response has no rate limit header.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let now = SystemTime::now();
+        let number = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse::<u64>().ok())
+        };
+        let info = Self {
+            limit: number(LIMIT_HEADER),
+            remaining: number(REMAINING_HEADER),
+            reset: number(RESET_HEADER).map(|secs| now + Duration::from_secs(secs)),
+            retry_at: retry::retry_after(headers).map(|delay| now + delay),
+        };
+        if info == Self::default() {
+            None
+        } else {
+            Some(info)
+        }
+    }
+
+    /// Returns the delay to wait before sending a request, if any
+    ///
+    /// The requests wait for the `Retry-After` delay, or for the reset of an exhausted window.
+    pub fn wait(&self) -> Option<Duration> {
+        let now = SystemTime::now();
+        let reset = match self.remaining {
+            Some(0) => self.reset,
+            _ => None,
+        };
+        [reset, self.retry_at]
+            .into_iter()
+            .flatten()
+            .filter_map(|time| time.duration_since(now).ok())
+            .max()
+    }
+}
+
+/// Rate limit state of a client
+///
+/// The state is shared by the clones of the client.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RateLimiter {
+    /// Last rate limit
+    last: Arc<Mutex<Option<RateLimitInfo>>>,
+    /// Whether the requests wait for the rate limit
+    throttle: bool,
+}
+
+impl RateLimiter {
+    /// Creates a new state
+    pub fn new(throttle: bool) -> Self {
+        Self {
+            last: Arc::default(),
+            throttle,
+        }
+    }
+
+    /// Records the rate limit of a response (if any)
+    pub fn record(&self, headers: &HeaderMap) {
+        if let Some(info) = RateLimitInfo::from_headers(headers) {
+            *self.last.lock().unwrap() = Some(info);
+        }
+    }
+
+    /// Returns the last rate limit
+    pub fn last(&self) -> Option<RateLimitInfo> {
+        *self.last.lock().unwrap()
+    }
+
+    /// Waits for the rate limit (if throttling is enabled)
+    pub async fn throttle(&self) {
+        if !self.throttle {
+            return;
+        }
+        if let Some(delay) = self.last().and_then(|info| info.wait()) {
+            retry::sleep(delay).await;
+        }
+    }
+}
+
+impl Client {
+    /// Returns the rate limit of the last response which had one
+    pub fn rate_limit(&self) -> Option<RateLimitInfo> {
+        self.rate_limit.last()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::{HeaderValue, RETRY_AFTER};
+
+    use super::*;
+
+    #[test]
+    fn test_from_headers() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(RateLimitInfo::from_headers(&headers), None);
+
+        headers.insert(LIMIT_HEADER, HeaderValue::from_static("100"));
+        headers.insert(REMAINING_HEADER, HeaderValue::from_static("42"));
+        headers.insert(RESET_HEADER, HeaderValue::from_static("30"));
+        let info = RateLimitInfo::from_headers(&headers).unwrap();
+        assert_eq!(info.limit, Some(100));
+        assert_eq!(info.remaining, Some(42));
+        assert!(info.reset.is_some());
+        assert_eq!(info.retry_at, None);
+        assert_eq!(info.wait(), None);
+    }
+
+    #[test]
+    fn test_wait() {
+        let mut headers = HeaderMap::new();
+        headers.insert(REMAINING_HEADER, HeaderValue::from_static("0"));
+        headers.insert(RESET_HEADER, HeaderValue::from_static("30"));
+        let info = RateLimitInfo::from_headers(&headers).unwrap();
+        let wait = info.wait().unwrap();
+        assert!(wait > Duration::from_secs(25) && wait <= Duration::from_secs(30));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("5"));
+        let info = RateLimitInfo::from_headers(&headers).unwrap();
+        assert!(info.wait().unwrap() <= Duration::from_secs(5));
+    }
+}
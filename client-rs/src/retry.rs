@@ -0,0 +1,161 @@
+//! Retries
+//!
+//! The transient failures are retried with an exponential backoff:
+//!
+//! - the connection errors, since the request has not reached the server
+//! - the timeouts, for the idempotent requests only (the server may have processed them)
+//! - the `429 Too Many Requests` and `503 Service Unavailable` responses, after the delay
+//!   requested by the server with the `Retry-After` header (if any)
+
+use std::time::{Duration, SystemTime};
+
+use reqwest::{
+    header::{HeaderMap, RETRY_AFTER},
+    Method, StatusCode,
+};
+
+use crate::transport::{Response, TransportError, TransportErrorKind};
+
+/// Retry policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Maximum number of retries (0 to disable)
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled at each retry
+    pub backoff: Duration,
+    /// Maximum delay before retrying
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 2,
+            backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Disables the retries
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            backoff: Duration::ZERO,
+            max_backoff: Duration::ZERO,
+        }
+    }
+
+    /// Returns the delay before a retry (starting at 0)
+    pub fn delay(&self, retry: u32) -> Duration {
+        self.backoff
+            .saturating_mul(2_u32.saturating_pow(retry))
+            .min(self.max_backoff)
+    }
+
+    /// Returns the delay before retrying a failed request, or `None` if it is not retried
+    pub(crate) fn on_error(
+        &self,
+        retry: u32,
+        method: &Method,
+        err: &TransportError,
+    ) -> Option<Duration> {
+        if retry >= self.max_retries {
+            return None;
+        }
+        let retried = match err.kind {
+            TransportErrorKind::Connect => true,
+            TransportErrorKind::Timeout => is_idempotent(method),
+            TransportErrorKind::Other => false,
+        };
+        if retried {
+            Some(self.delay(retry))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the delay before retrying a request after its response, or `None` if it is
+    /// not retried
+    pub(crate) fn on_response(&self, retry: u32, res: &Response) -> Option<Duration> {
+        if retry >= self.max_retries {
+            return None;
+        }
+        match res.status() {
+            StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE => Some(
+                retry_after(res.headers())
+                    .unwrap_or_else(|| self.delay(retry))
+                    .min(self.max_backoff),
+            ),
+            _ => None,
+        }
+    }
+}
+
+/// Checks if a request method is idempotent
+fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::PUT | Method::DELETE
+    )
+}
+
+/// Parses the `Retry-After` header (in seconds or as an HTTP date)
+pub(crate) fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(
+        date.duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+/// Waits for a delay
+pub(crate) async fn sleep(delay: Duration) {
+    #[cfg(not(target_arch = "wasm32"))]
+    tokio::time::sleep(delay).await;
+    #[cfg(target_arch = "wasm32")]
+    gloo_timers::future::sleep(delay).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    #[test]
+    fn test_delay() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.delay(0), Duration::from_millis(500));
+        assert_eq!(policy.delay(2), Duration::from_secs(2));
+        assert_eq!(policy.delay(20), policy.max_backoff);
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("120"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(120)));
+        headers.insert(
+            RETRY_AFTER,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(retry_after(&headers), Some(Duration::ZERO));
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("soon"));
+        assert_eq!(retry_after(&headers), None);
+    }
+
+    #[test]
+    fn test_idempotent() {
+        assert!(is_idempotent(&Method::GET));
+        assert!(is_idempotent(&Method::PUT));
+        assert!(!is_idempotent(&Method::POST));
+        assert!(!is_idempotent(&Method::PATCH));
+    }
+}
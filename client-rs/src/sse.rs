@@ -0,0 +1,258 @@
+//! Server-sent events
+//!
+//! An event stream endpoint is consumed as a stream of typed events. When the connection is
+//! closed or lost, the client reconnects after the delay requested by the server (`retry`
+//! field), and resumes from the last received event (`Last-Event-ID` header).
+
+use std::{collections::VecDeque, time::Duration};
+
+use futures::{stream, Stream};
+use reqwest::{
+    header::{ACCEPT, CACHE_CONTROL},
+    Method,
+};
+use serde::de::DeserializeOwned;
+
+use crate::{error::Error, retry, transport::Response, Client};
+
+/// Default delay before reconnecting
+const DEFAULT_RECONNECT: Duration = Duration::from_secs(3);
+
+/// Header holding the ID of the last received event
+const LAST_EVENT_ID: &str = "Last-Event-ID";
+
+/// Server-sent event
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event<T> {
+    /// Event ID
+    pub id: Option<String>,
+    /// Event type (`message` if not set by the server)
+    pub event: String,
+    /// Event data
+    pub data: T,
+}
+
+/// Event as received
+#[derive(Debug, Clone, Default, PartialEq)]
+struct RawEvent {
+    /// Event ID
+    id: Option<String>,
+    /// Event type
+    event: Option<String>,
+    /// Data lines
+    data: Vec<String>,
+}
+
+impl RawEvent {
+    /// Decodes the JSON data of the event
+    fn decode<T: DeserializeOwned>(self) -> Result<Event<T>, Error> {
+        let data = serde_json::from_str(&self.data.join("\n"))
+            .map_err(|err| Error::new("INVALID_EVENT", &format!("invalid event data: {err}")))?;
+        Ok(Event {
+            id: self.id,
+            event: self.event.unwrap_or_else(|| "message".to_string()),
+            data,
+        })
+    }
+}
+
+/// Event stream parser
+#[derive(Debug, Default)]
+struct EventParser {
+    /// Bytes of the incomplete line
+    buf: Vec<u8>,
+    /// Event being received
+    event: RawEvent,
+    /// Reconnection delay requested by the server
+    retry: Option<Duration>,
+}
+
+impl EventParser {
+    /// Parses a chunk of the stream, and returns the completed events
+    fn push(&mut self, chunk: &[u8]) -> Vec<RawEvent> {
+        self.buf.extend_from_slice(chunk);
+        let mut events = vec![];
+        while let Some(pos) = self.buf.iter().position(|b| *b == b'\n') {
+            let line = self.buf.drain(..=pos).collect::<Vec<_>>();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches('\n').trim_end_matches('\r');
+            if let Some(event) = self.parse_line(line) {
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    /// Parses a line, and returns the event if the line completes it
+    fn parse_line(&mut self, line: &str) -> Option<RawEvent> {
+        if line.is_empty() {
+            // NB: an event without data is not dispatched
+            let event = std::mem::take(&mut self.event);
+            return if event.data.is_empty() {
+                None
+            } else {
+                Some(event)
+            };
+        }
+        if line.starts_with(':') {
+            // comment (eg. keep-alive)
+            return None;
+        }
+
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "data" => self.event.data.push(value.to_string()),
+            "event" => self.event.event = Some(value.to_string()),
+            "id" if !value.contains('\0') => self.event.id = Some(value.to_string()),
+            "retry" => {
+                if let Ok(ms) = value.parse::<u64>() {
+                    self.retry = Some(Duration::from_millis(ms));
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+/// State of a subscription
+struct Subscription {
+    /// Current connection
+    res: Option<Response>,
+    /// Stream parser
+    parser: EventParser,
+    /// Events received but not yet consumed
+    pending: VecDeque<RawEvent>,
+    /// ID of the last received event
+    last_id: Option<String>,
+    /// Delay before reconnecting
+    reconnect: Duration,
+    /// Whether the subscription has ended
+    done: bool,
+}
+
+impl Client {
+    /// Subscribes to an event stream endpoint
+    ///
+    /// The event data are decoded as JSON. The stream reconnects when the connection is
+    /// lost (including when the request timeout of the client expires), and ends after an
+    /// error to connect (eg. unauthorized).
+    pub fn subscribe<'a, T>(
+        &'a self,
+        path: &'a str,
+    ) -> impl Stream<Item = Result<Event<T>, Error>> + 'a
+    where
+        T: DeserializeOwned + 'a,
+    {
+        let state = Subscription {
+            res: None,
+            parser: EventParser::default(),
+            pending: VecDeque::new(),
+            last_id: None,
+            reconnect: DEFAULT_RECONNECT,
+            done: false,
+        };
+
+        stream::unfold(state, move |mut state| async move {
+            loop {
+                if state.done {
+                    return None;
+                }
+                if let Some(event) = state.pending.pop_front() {
+                    if event.id.is_some() {
+                        state.last_id = event.id.clone();
+                    }
+                    return Some((event.decode::<T>(), state));
+                }
+
+                let res = match &mut state.res {
+                    Some(res) => res,
+                    None => {
+                        match self.connect(path, state.last_id.as_deref()).await {
+                            Ok(res) => {
+                                state.res = Some(res);
+                                state.parser = EventParser::default();
+                            }
+                            Err(err) => {
+                                state.done = true;
+                                return Some((Err(err), state));
+                            }
+                        }
+                        continue;
+                    }
+                };
+
+                match res.body_mut().chunk().await {
+                    Ok(Some(chunk)) => {
+                        let events = state.parser.push(&chunk);
+                        state.pending.extend(events);
+                        if let Some(retry) = state.parser.retry {
+                            state.reconnect = retry;
+                        }
+                    }
+                    // NB: the connection is closed or lost
+                    Ok(None) | Err(_) => {
+                        state.res = None;
+                        retry::sleep(state.reconnect).await;
+                    }
+                }
+            }
+        })
+    }
+
+    /// Connects to an event stream endpoint
+    async fn connect(&self, path: &str, last_id: Option<&str>) -> Result<Response, Error> {
+        let mut req = self
+            .request(Method::GET, path)
+            .header(ACCEPT, "text/event-stream")
+            .header(CACHE_CONTROL, "no-cache");
+        if let Some(id) = last_id {
+            req = req.header(LAST_EVENT_ID, id);
+        }
+        self.send(req).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parser() {
+        let mut parser = EventParser::default();
+        let events = parser.push(b": keep-alive\n\nretry: 1000\nid: 1\nevent: new\ndata: {\"a\"");
+        assert!(events.is_empty());
+        assert_eq!(parser.retry, Some(Duration::from_millis(1000)));
+
+        let events = parser.push(b":\ndata: 1}\r\n\r\ndata: 2\n\n");
+        assert_eq!(events.len(), 2);
+        assert_eq!(
+            events[0],
+            RawEvent {
+                id: Some("1".to_string()),
+                event: Some("new".to_string()),
+                data: vec!["{\"a\":".to_string(), "1}".to_string()],
+            }
+        );
+        assert_eq!(events[1].data, vec!["2".to_string()]);
+    }
+
+    #[test]
+    fn test_decode() {
+        let event = RawEvent {
+            id: None,
+            event: None,
+            data: vec!["[1,".to_string(), "2]".to_string()],
+        };
+        let event = event.decode::<Vec<u32>>().unwrap();
+        assert_eq!(event.event, "message");
+        assert_eq!(event.data, vec![1, 2]);
+
+        let event = RawEvent {
+            data: vec!["{".to_string()],
+            ..Default::default()
+        };
+        assert!(event.decode::<Vec<u32>>().is_err());
+    }
+}
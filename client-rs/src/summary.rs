@@ -0,0 +1,265 @@
+//! Summaries
+//!
+//! The summaries are returned without their embeddings (1536 values each), which are
+//! skipped while decoding the response, see [`Client::summarize_with_embeddings`] to get them.
+//!
+//! The API limits the number of articles of a summaries request. A big list of articles is
+//! split into chunks, which are summarized concurrently:
+//!
+//! ```no_run
+//! # use newsie_client::{summary::DEFAULT_CHUNK_SIZE, Client};
+//! # async fn run(client: Client, urls: Vec<&str>) {
+//! let summaries = client
+//!     .summarize_chunked(&urls, DEFAULT_CHUNK_SIZE, 4, |progress| {
+//!         println!("{}/{} chunks", progress.done, progress.chunks);
+//!     })
+//!     .await;
+//! # }
+//! ```
+
+use std::borrow::Cow;
+
+use bytes::Bytes;
+use futures::{stream, StreamExt};
+use newsie_api::mdl::validate::ArticleUrl;
+use reqwest::{Method, RequestBuilder};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    error::{validate, Error},
+    Client, Summary,
+};
+
+/// Default number of articles per chunk
+///
+/// NB: this is the default limit of the API
+pub const DEFAULT_CHUNK_SIZE: usize = 20;
+
+/// Article summary, without its embeddings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArticleSummary {
+    /// ID
+    pub id: Uuid,
+    /// Url
+    pub url: String,
+    /// Summary
+    pub summary: String,
+    /// Keywords
+    pub keywords: Vec<String>,
+}
+
+/// Article summary, borrowed from a raw response body
+///
+/// NB: the text is only copied if it contains escaped characters
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct SummaryRef<'a> {
+    /// ID
+    pub id: Uuid,
+    /// Url
+    #[serde(borrow)]
+    pub url: Cow<'a, str>,
+    /// Summary
+    #[serde(borrow)]
+    pub summary: Cow<'a, str>,
+    /// Keywords
+    #[serde(borrow)]
+    pub keywords: Vec<Cow<'a, str>>,
+}
+
+/// Raw summaries response body
+#[derive(Debug, Clone)]
+pub struct RawSummaries {
+    /// Body
+    body: Bytes,
+}
+
+impl RawSummaries {
+    /// Returns the body
+    pub fn bytes(&self) -> &Bytes {
+        &self.body
+    }
+
+    /// Returns the body, consuming the response
+    pub fn into_bytes(self) -> Bytes {
+        self.body
+    }
+
+    /// Decodes the summaries, without their embeddings
+    pub fn summaries(&self) -> Result<Vec<SummaryRef<'_>>, Error> {
+        self.decode::<SummaryRef>()
+    }
+
+    /// Decodes the summaries into a type borrowing from the body
+    pub fn decode<'a, T: Deserialize<'a>>(&'a self) -> Result<Vec<T>, Error> {
+        serde_json::from_slice::<SummariesBody<T>>(&self.body)
+            .map(|body| body.summaries)
+            .map_err(|err| Error::new("INTERNAL", &format!("invalid response body: {err}")))
+    }
+}
+
+/// Summaries response body
+///
+/// NB: this mirrors [`crate::SummariesRespBody`], with any summary type
+#[derive(Debug, Deserialize)]
+struct SummariesBody<T> {
+    /// Summaries
+    summaries: Vec<T>,
+}
+
+/// Progress of a batched summaries request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkProgress {
+    /// Index of the summarized chunk
+    pub chunk: usize,
+    /// Number of summarized chunks
+    pub done: usize,
+    /// Total number of chunks
+    pub chunks: usize,
+}
+
+impl Client {
+    /// Summarize a list of articles
+    pub async fn summarize(&self, urls: &[&str]) -> Result<Vec<ArticleSummary>, Error> {
+        self.post_summaries::<ArticleSummary>(urls).await
+    }
+
+    /// Summarize a list of articles, with the embeddings of the summaries
+    pub async fn summarize_with_embeddings(&self, urls: &[&str]) -> Result<Vec<Summary>, Error> {
+        self.post_summaries::<Summary>(urls).await
+    }
+
+    /// Summarize a list of articles, and returns the raw response body
+    ///
+    /// The summaries are decoded on demand, borrowing their text from the body (see
+    /// [`RawSummaries::summaries`]), so that the large responses are not copied.
+    pub async fn summarize_raw(&self, urls: &[&str]) -> Result<RawSummaries, Error> {
+        let req = self.summaries_request(urls)?;
+        let body = self.send(req).await?.into_body().bytes().await?;
+        Ok(RawSummaries { body })
+    }
+
+    /// Requests the summaries of a list of articles
+    async fn post_summaries<T: DeserializeOwned>(&self, urls: &[&str]) -> Result<Vec<T>, Error> {
+        let req = self.summaries_request(urls)?;
+        let body = self.send_json::<SummariesBody<T>>(req).await?;
+        Ok(body.summaries)
+    }
+
+    /// Prepares a summaries request
+    fn summaries_request(&self, urls: &[&str]) -> Result<RequestBuilder, Error> {
+        validate(
+            urls.iter()
+                .map(|url| ArticleUrl(url))
+                .collect::<Vec<_>>()
+                .as_slice(),
+        )?;
+        Ok(self
+            .request(Method::POST, "/summaries")
+            .json(&urls.iter().map(|url| url.to_string()).collect::<Vec<_>>()))
+    }
+
+    /// Summarizes a list of articles, by chunks of `chunk_size` articles
+    ///
+    /// Up to `concurrency` chunks are summarized at once, and `progress` is called after each
+    /// summarized chunk. The summaries are returned in the order of the articles.
+    ///
+    /// NB: the first failed chunk fails the whole call
+    pub async fn summarize_chunked<F>(
+        &self,
+        urls: &[&str],
+        chunk_size: usize,
+        concurrency: usize,
+        mut progress: F,
+    ) -> Result<Vec<ArticleSummary>, Error>
+    where
+        F: FnMut(ChunkProgress),
+    {
+        let chunks = urls.chunks(chunk_size.max(1)).collect::<Vec<_>>();
+        let mut results = chunks.iter().map(|_| None).collect::<Vec<_>>();
+        let mut done = 0;
+
+        let mut summaries = stream::iter(chunks.iter().enumerate())
+            .map(|(i, chunk)| async move { (i, self.summarize(chunk).await) })
+            .buffer_unordered(concurrency.max(1));
+        while let Some((i, res)) = summaries.next().await {
+            results[i] = Some(res?);
+            done += 1;
+            progress(ChunkProgress {
+                chunk: i,
+                done,
+                chunks: chunks.len(),
+            });
+        }
+        Ok(results.into_iter().flatten().flatten().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_summarize_chunked() {
+        use crate::mock::{Method, MockTransport, StatusCode};
+
+        let mock = MockTransport::new();
+        mock.on(Method::POST, "/summaries")
+            .json(StatusCode::OK, &serde_json::json!({ "summaries": [] }));
+        let client = Client::mock(&mock);
+
+        let urls = (0..5)
+            .map(|i| format!("https://host/{i}"))
+            .collect::<Vec<_>>();
+        let urls = urls.iter().map(|url| url.as_str()).collect::<Vec<_>>();
+        let mut progress = vec![];
+        let summaries = client
+            .summarize_chunked(&urls, 2, 2, |p| progress.push(p))
+            .await
+            .unwrap();
+        assert!(summaries.is_empty());
+
+        assert_eq!(progress.len(), 3);
+        assert_eq!(progress.last().unwrap().done, 3);
+        assert!(progress.iter().all(|p| p.chunks == 3));
+        let mut sizes = mock
+            .requests()
+            .iter()
+            .map(|req| req.json::<Vec<String>>().unwrap().len())
+            .collect::<Vec<_>>();
+        sizes.sort();
+        assert_eq!(sizes, vec![1, 2, 2]);
+    }
+
+    #[test]
+    fn test_skip_embeddings() {
+        let body = serde_json::json!({
+            "summaries": [{
+                "id": Uuid::nil(),
+                "url": "https://host/article",
+                "summary": "summary",
+                "keywords": ["news"],
+                "embeddings": vec![0.5; 1536],
+            }]
+        });
+        let body = serde_json::from_value::<SummariesBody<ArticleSummary>>(body).unwrap();
+        assert_eq!(body.summaries[0].url, "https://host/article");
+    }
+
+    #[test]
+    fn test_raw_summaries() {
+        let raw = RawSummaries {
+            body: Bytes::from_static(
+                br#"{"summaries":[{"id":"00000000-0000-0000-0000-000000000000","url":"https://host/article","summary":"a \"quoted\" summary","keywords":["news"],"embeddings":[0.5,0.25]}]}"#,
+            ),
+        };
+        let summaries = raw.summaries().unwrap();
+        assert!(matches!(
+            summaries[0].url,
+            Cow::Borrowed("https://host/article")
+        ));
+        assert_eq!(summaries[0].summary, "a \"quoted\" summary");
+        assert_eq!(summaries[0].keywords, vec!["news"]);
+    }
+}
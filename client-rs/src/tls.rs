@@ -0,0 +1,137 @@
+//! TLS settings
+//!
+//! Self-hosted servers may use certificates issued by a private CA: its root certificate is
+//! added to the trusted roots. The server certificate can also be pinned by its SHA-256
+//! fingerprint, in which case it must both be valid and match one of the pins.
+
+use std::{sync::Arc, time::SystemTime};
+
+use rustls::{
+    client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier},
+    Certificate, ClientConfig, OwnedTrustAnchor, RootCertStore, ServerName,
+};
+use sha2::{Digest, Sha256};
+
+use crate::error::Error;
+
+/// SHA-256 fingerprint of a certificate
+pub type Fingerprint = [u8; 32];
+
+/// Parses the certificates of a PEM bundle (as DER)
+pub(crate) fn parse_pem(pem: &[u8]) -> Result<Vec<Vec<u8>>, Error> {
+    let certs = rustls_pemfile::certs(&mut &pem[..])
+        .map_err(|err| Error::new("CONFIG", &format!("invalid PEM certificates: {err}")))?;
+    if certs.is_empty() {
+        return Err(Error::new("CONFIG", "no certificate in the PEM bundle"));
+    }
+    Ok(certs)
+}
+
+/// Parses a fingerprint, as hexadecimal (possibly separated by colons)
+pub(crate) fn parse_fingerprint(value: &str) -> Result<Fingerprint, Error> {
+    let invalid = || Error::new("CONFIG", &format!("invalid SHA-256 fingerprint '{value}'"));
+    let hex = value.replace(':', "");
+    if hex.len() != 64 || !hex.is_ascii() {
+        return Err(invalid());
+    }
+    let mut fingerprint = [0; 32];
+    for (i, byte) in fingerprint.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).map_err(|_| invalid())?;
+    }
+    Ok(fingerprint)
+}
+
+/// Returns the fingerprint of a certificate (DER)
+pub fn fingerprint(der: &[u8]) -> Fingerprint {
+    Sha256::digest(der).into()
+}
+
+/// Builds a TLS configuration with pinned certificates
+pub(crate) fn pinned_config(
+    builtin_roots: bool,
+    roots: &[Vec<u8>],
+    pins: Vec<Fingerprint>,
+) -> Result<ClientConfig, Error> {
+    let mut store = RootCertStore::empty();
+    if builtin_roots {
+        store.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+            OwnedTrustAnchor::from_subject_spki_name_constraints(
+                ta.subject,
+                ta.spki,
+                ta.name_constraints,
+            )
+        }));
+    }
+    for der in roots {
+        store
+            .add(&Certificate(der.clone()))
+            .map_err(|err| Error::new("CONFIG", &format!("invalid root certificate: {err}")))?;
+    }
+
+    let verifier = PinnedVerifier {
+        inner: WebPkiVerifier::new(store, None),
+        pins,
+    };
+    Ok(ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth())
+}
+
+/// Verifier of the pinned certificates
+struct PinnedVerifier {
+    /// Verifier of the certificate chain
+    inner: WebPkiVerifier,
+    /// Fingerprints of the accepted certificates
+    pins: Vec<Fingerprint>,
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            scts,
+            ocsp_response,
+            now,
+        )?;
+        if self.pins.contains(&fingerprint(&end_entity.0)) {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::General(
+                "the server certificate is not pinned".to_string(),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fingerprint() {
+        let hex = "AB".repeat(32);
+        assert_eq!(parse_fingerprint(&hex).unwrap(), [0xab; 32]);
+        let colons = vec!["01"; 32].join(":");
+        assert_eq!(parse_fingerprint(&colons).unwrap(), [1; 32]);
+        assert!(parse_fingerprint("abcd").is_err());
+        assert!(parse_fingerprint(&"zz".repeat(32)).is_err());
+    }
+
+    #[test]
+    fn test_parse_pem() {
+        assert!(parse_pem(b"not a certificate").is_err());
+        let pem = b"-----BEGIN CERTIFICATE-----\nAAEC\n-----END CERTIFICATE-----\n";
+        assert_eq!(parse_pem(pem).unwrap(), vec![vec![0, 1, 2]]);
+    }
+}
@@ -0,0 +1,166 @@
+//! Token storage
+//!
+//! The client reads the authentication token from its store before each request, and
+//! updates it on signup, login, logout and token refresh.
+
+use std::{fmt::Debug, path::PathBuf, sync::RwLock};
+
+use crate::error::Error;
+
+/// Token store
+pub trait TokenStore: Debug + Send + Sync {
+    /// Returns the token
+    fn get(&self) -> Option<String>;
+
+    /// Replaces the token (`None` to remove it)
+    fn set(&self, token: Option<&str>) -> Result<(), Error>;
+}
+
+/// In-memory token store
+#[derive(Debug, Default)]
+pub struct MemoryTokenStore {
+    /// Token
+    token: RwLock<Option<String>>,
+}
+
+impl MemoryTokenStore {
+    /// Creates a new store
+    pub fn new(token: Option<String>) -> Self {
+        Self {
+            token: RwLock::new(token),
+        }
+    }
+}
+
+impl TokenStore for MemoryTokenStore {
+    fn get(&self) -> Option<String> {
+        self.token.read().unwrap().clone()
+    }
+
+    fn set(&self, token: Option<&str>) -> Result<(), Error> {
+        *self.token.write().unwrap() = token.map(|t| t.to_string());
+        Ok(())
+    }
+}
+
+/// File token store
+///
+/// The token is stored as plain text, in a file readable by its owner only.
+#[derive(Debug)]
+pub struct FileTokenStore {
+    /// File path
+    path: PathBuf,
+}
+
+impl FileTokenStore {
+    /// Creates a new store
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl TokenStore for FileTokenStore {
+    fn get(&self) -> Option<String> {
+        let token = std::fs::read_to_string(&self.path).ok()?;
+        let token = token.trim();
+        if token.is_empty() {
+            None
+        } else {
+            Some(token.to_string())
+        }
+    }
+
+    fn set(&self, token: Option<&str>) -> Result<(), Error> {
+        let Some(token) = token else {
+            return match std::fs::remove_file(&self.path) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(io_error(err)),
+                _ => Ok(()),
+            };
+        };
+
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir).map_err(io_error)?;
+        }
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(&self.path).map_err(io_error)?;
+        std::io::Write::write_all(&mut file, token.as_bytes()).map_err(io_error)
+    }
+}
+
+/// Converts an IO error
+fn io_error(err: std::io::Error) -> Error {
+    Error::new("TOKEN_STORE", &format!("failed to store the token: {err}"))
+}
+
+/// OS keyring token store
+#[cfg(feature = "keyring")]
+#[derive(Debug)]
+pub struct KeyringTokenStore {
+    /// Keyring entry
+    entry: keyring::Entry,
+}
+
+#[cfg(feature = "keyring")]
+impl KeyringTokenStore {
+    /// Creates a new store, for a service and a user (eg. the server URL and the user email)
+    pub fn new(service: &str, user: &str) -> Result<Self, Error> {
+        let entry = keyring::Entry::new(service, user).map_err(keyring_error)?;
+        Ok(Self { entry })
+    }
+}
+
+#[cfg(feature = "keyring")]
+impl TokenStore for KeyringTokenStore {
+    fn get(&self) -> Option<String> {
+        self.entry.get_password().ok()
+    }
+
+    fn set(&self, token: Option<&str>) -> Result<(), Error> {
+        match token {
+            Some(token) => self.entry.set_password(token).map_err(keyring_error),
+            None => match self.entry.delete_password() {
+                Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+                Err(err) => Err(keyring_error(err)),
+            },
+        }
+    }
+}
+
+/// Converts a keyring error
+#[cfg(feature = "keyring")]
+fn keyring_error(err: keyring::Error) -> Error {
+    Error::new("TOKEN_STORE", &format!("keyring error: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_store() {
+        let store = MemoryTokenStore::default();
+        assert_eq!(store.get(), None);
+        store.set(Some("token")).unwrap();
+        assert_eq!(store.get().as_deref(), Some("token"));
+        store.set(None).unwrap();
+        assert_eq!(store.get(), None);
+    }
+
+    #[test]
+    fn test_file_store() {
+        let path = std::env::temp_dir()
+            .join(format!("newsie-{}", std::process::id()))
+            .join("token");
+        let store = FileTokenStore::new(&path);
+        assert_eq!(store.get(), None);
+        store.set(Some("token")).unwrap();
+        assert_eq!(store.get().as_deref(), Some("token"));
+        assert_eq!(FileTokenStore::new(&path).get().as_deref(), Some("token"));
+        store.set(None).unwrap();
+        assert_eq!(store.get(), None);
+        store.set(None).unwrap();
+    }
+}
@@ -0,0 +1,42 @@
+//! Tracing
+//!
+//! Each API call is traced within a span holding its method, path, status and duration, so
+//! that the API latency is visible in the traces of the application.
+
+use std::future::Future;
+
+use reqwest::Method;
+use tracing::{debug, field::Empty, info_span, warn, Instrument};
+
+use crate::{error::Error, transport::Response};
+
+/// Traces an API call
+pub(crate) async fn instrument<F>(method: Method, path: String, fut: F) -> Result<Response, Error>
+where
+    F: Future<Output = Result<Response, Error>>,
+{
+    let span = info_span!(
+        "api_call",
+        %method,
+        %path,
+        status = Empty,
+        duration_ms = Empty
+    );
+    // NB: there is no monotonic clock in the browser
+    #[cfg(not(target_arch = "wasm32"))]
+    let start = std::time::Instant::now();
+
+    let res = fut.instrument(span.clone()).await;
+
+    #[cfg(not(target_arch = "wasm32"))]
+    span.record("duration_ms", start.elapsed().as_secs_f64() * 1000.0);
+    let _enter = span.enter();
+    match &res {
+        Ok(res) => {
+            span.record("status", res.status().as_u16());
+            debug!("API call completed");
+        }
+        Err(err) => warn!(%err, "API call failed"),
+    }
+    res
+}
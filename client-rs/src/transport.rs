@@ -0,0 +1,257 @@
+//! HTTP transport
+//!
+//! The client sends its requests with a transport, which is the `reqwest` client by default.
+//! Another HTTP stack can be plugged by implementing [`HttpTransport`], on the types of the
+//! `http` crate: the request body is buffered, and the response body is a [`Body`], which
+//! streams its chunks.
+
+use std::fmt::{self, Debug, Display};
+
+use bytes::{Bytes, BytesMut};
+use futures::{future, stream, FutureExt, Stream, StreamExt, TryStreamExt};
+#[cfg(not(target_arch = "wasm32"))]
+use futures::{future::BoxFuture, stream::BoxStream};
+#[cfg(target_arch = "wasm32")]
+use futures::{future::LocalBoxFuture, stream::LocalBoxStream};
+
+/// Request of a transport
+pub type Request = http::Request<Bytes>;
+
+/// Response of a transport
+pub type Response = http::Response<Body>;
+
+/// Future of a transport response
+#[cfg(not(target_arch = "wasm32"))]
+pub type TransportFuture<'a> = BoxFuture<'a, Result<Response, TransportError>>;
+
+/// Future of a transport response
+///
+/// NB: the futures of the browser are not `Send`
+#[cfg(target_arch = "wasm32")]
+pub type TransportFuture<'a> = LocalBoxFuture<'a, Result<Response, TransportError>>;
+
+/// Stream of the chunks of a body
+#[cfg(not(target_arch = "wasm32"))]
+type BodyStream = BoxStream<'static, Result<Bytes, TransportError>>;
+
+/// Stream of the chunks of a body
+#[cfg(target_arch = "wasm32")]
+type BodyStream = LocalBoxStream<'static, Result<Bytes, TransportError>>;
+
+/// Response body
+pub struct Body(BodyStream);
+
+impl Body {
+    /// Creates an empty body
+    pub fn empty() -> Self {
+        Self::from(Bytes::new())
+    }
+
+    /// Creates a body from a stream of chunks
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_stream<S>(stream: S) -> Self
+    where
+        S: Stream<Item = Result<Bytes, TransportError>> + Send + 'static,
+    {
+        Self(stream.boxed())
+    }
+
+    /// Creates a body from a stream of chunks
+    #[cfg(target_arch = "wasm32")]
+    pub fn from_stream<S>(stream: S) -> Self
+    where
+        S: Stream<Item = Result<Bytes, TransportError>> + 'static,
+    {
+        Self(stream.boxed_local())
+    }
+
+    /// Returns the next chunk of the body, or `None` at its end
+    pub async fn chunk(&mut self) -> Result<Option<Bytes>, TransportError> {
+        self.0.try_next().await
+    }
+
+    /// Reads the whole body
+    pub async fn bytes(mut self) -> Result<Bytes, TransportError> {
+        let mut buf = BytesMut::new();
+        while let Some(chunk) = self.chunk().await? {
+            buf.extend_from_slice(&chunk);
+        }
+        Ok(buf.freeze())
+    }
+}
+
+impl Debug for Body {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Body").finish_non_exhaustive()
+    }
+}
+
+impl From<Bytes> for Body {
+    fn from(value: Bytes) -> Self {
+        Self::from_stream(stream::once(future::ready(Ok(value))))
+    }
+}
+
+impl From<Vec<u8>> for Body {
+    fn from(value: Vec<u8>) -> Self {
+        Self::from(Bytes::from(value))
+    }
+}
+
+impl From<String> for Body {
+    fn from(value: String) -> Self {
+        Self::from(Bytes::from(value))
+    }
+}
+
+/// HTTP transport
+pub trait HttpTransport: Debug + Send + Sync {
+    /// Sends a request, and returns its response (whatever its status)
+    fn execute(&self, req: Request) -> TransportFuture<'_>;
+}
+
+/// Kind of transport error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransportErrorKind {
+    /// The connection has failed, and the request has not reached the server
+    Connect,
+    /// The request has timed out
+    Timeout,
+    /// Other error
+    Other,
+}
+
+/// Transport error
+#[derive(Debug)]
+pub struct TransportError {
+    /// Kind
+    pub kind: TransportErrorKind,
+    /// Error message
+    pub message: String,
+}
+
+impl TransportError {
+    /// Creates a new error
+    pub fn new(kind: TransportErrorKind, message: &str) -> Self {
+        Self {
+            kind,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl Display for TransportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for TransportError {}
+
+impl From<reqwest::Error> for TransportError {
+    fn from(value: reqwest::Error) -> Self {
+        let kind = if is_connect(&value) {
+            TransportErrorKind::Connect
+        } else if value.is_timeout() {
+            TransportErrorKind::Timeout
+        } else {
+            TransportErrorKind::Other
+        };
+        Self::new(kind, &value.to_string())
+    }
+}
+
+/// Checks if a request has failed to connect
+#[cfg(not(target_arch = "wasm32"))]
+fn is_connect(err: &reqwest::Error) -> bool {
+    err.is_connect()
+}
+
+/// Checks if a request has failed to connect
+///
+/// NB: the browser does not tell the connection errors apart
+#[cfg(target_arch = "wasm32")]
+fn is_connect(err: &reqwest::Error) -> bool {
+    err.is_request()
+}
+
+/// Converts a request of the `reqwest` client to a transport request
+///
+/// NB: a streamed body cannot be buffered, and is not supported
+pub(crate) fn to_request(req: reqwest::Request) -> Result<Request, TransportError> {
+    let body = match req.body() {
+        Some(body) => body.as_bytes().map(Bytes::copy_from_slice).ok_or_else(|| {
+            TransportError::new(TransportErrorKind::Other, "unsupported streamed body")
+        })?,
+        None => Bytes::new(),
+    };
+    let mut builder = http::Request::builder()
+        .method(req.method().clone())
+        .uri(req.url().as_str());
+    if let Some(headers) = builder.headers_mut() {
+        *headers = req.headers().clone();
+    }
+    builder
+        .body(body)
+        .map_err(|err| TransportError::new(TransportErrorKind::Other, &err.to_string()))
+}
+
+/// Transport of the `reqwest` client
+#[derive(Debug, Clone)]
+pub struct ReqwestTransport(reqwest::Client);
+
+impl ReqwestTransport {
+    /// Creates a new transport
+    pub fn new(client: reqwest::Client) -> Self {
+        Self(client)
+    }
+
+    /// Sends a request
+    async fn send(&self, req: Request) -> Result<Response, TransportError> {
+        let res = self.0.execute(reqwest::Request::try_from(req)?).await?;
+        let status = res.status();
+        let headers = res.headers().clone();
+        let mut res = http::Response::new(Body::from_stream(
+            res.bytes_stream().map_err(TransportError::from),
+        ));
+        *res.status_mut() = status;
+        *res.headers_mut() = headers;
+        Ok(res)
+    }
+}
+
+impl HttpTransport for ReqwestTransport {
+    fn execute(&self, req: Request) -> TransportFuture<'_> {
+        #[cfg(not(target_arch = "wasm32"))]
+        return self.send(req).boxed();
+        #[cfg(target_arch = "wasm32")]
+        return self.send(req).boxed_local();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_to_request() {
+        let req = reqwest::Client::new()
+            .post("http://localhost:3000/summaries")
+            .json(&["https://example.com"])
+            .build()
+            .unwrap();
+        let req = to_request(req).unwrap();
+        assert_eq!(req.uri(), "http://localhost:3000/summaries");
+        assert_eq!(req.headers()["content-type"], "application/json");
+        assert_eq!(req.body().as_ref(), br#"["https://example.com"]"#);
+    }
+
+    #[tokio::test]
+    async fn test_body() {
+        let chunks = vec![Ok(Bytes::from("a")), Ok(Bytes::from("bc"))];
+        let mut body = Body::from_stream(stream::iter(chunks));
+        assert_eq!(body.chunk().await.unwrap().unwrap(), "a");
+        assert_eq!(body.bytes().await.unwrap(), "bc");
+        assert!(Body::empty().bytes().await.unwrap().is_empty());
+    }
+}
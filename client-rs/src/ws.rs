@@ -0,0 +1,111 @@
+//! WebSocket channel
+//!
+//! The realtime channel (`/ws`) is exposed as a typed stream of events, plus a sender of
+//! commands. The events and the commands are exchanged as JSON text messages.
+
+use std::marker::PhantomData;
+
+use futures::{
+    stream::{SplitSink, SplitStream},
+    SinkExt, Stream, StreamExt,
+};
+use reqwest::header::{HeaderValue, AUTHORIZATION};
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{client::IntoClientRequest, Message},
+    MaybeTlsStream, WebSocketStream,
+};
+
+use crate::{error::Error, Client};
+
+/// Path of the realtime channel
+pub const WS_PATH: &str = "/ws";
+
+/// WebSocket connection
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Sender of commands
+pub struct WsSender<C> {
+    /// Sink of the connection
+    sink: SplitSink<WsStream, Message>,
+    /// Type of the commands
+    cmd: PhantomData<fn(C)>,
+}
+
+impl<C: Serialize> WsSender<C> {
+    /// Sends a command
+    pub async fn send(&mut self, cmd: &C) -> Result<(), Error> {
+        let text = serde_json::to_string(cmd)
+            .map_err(|err| Error::new("INVALID_COMMAND", &format!("invalid command: {err}")))?;
+        self.sink.send(Message::Text(text)).await.map_err(ws_error)
+    }
+
+    /// Closes the connection
+    pub async fn close(&mut self) -> Result<(), Error> {
+        self.sink.close().await.map_err(ws_error)
+    }
+}
+
+impl Client {
+    /// Connects to the realtime channel
+    ///
+    /// Returns the sender of commands `C`, and the stream of events `E`. The stream ends when
+    /// the connection is closed.
+    ///
+    /// NB: the connection is not retried, nor its token refreshed
+    pub async fn connect_ws<E, C>(
+        &self,
+    ) -> Result<(WsSender<C>, impl Stream<Item = Result<E, Error>>), Error>
+    where
+        E: DeserializeOwned,
+        C: Serialize,
+    {
+        let mut url = self.endpoint(WS_PATH)?;
+        let scheme = match url.scheme() {
+            "https" => Some("wss"),
+            "http" => Some("ws"),
+            _ => None,
+        };
+        if let Some(scheme) = scheme {
+            // NB: the HTTP(S) and WS(S) schemes are all special, and can be swapped
+            let _res = url.set_scheme(scheme);
+        }
+        let mut req = url.as_str().into_client_request().map_err(ws_error)?;
+        if let Some(token) = self.get_token() {
+            let value = HeaderValue::from_str(&format!("Bearer {}", token))
+                .map_err(|err| Error::new("INVALID_TOKEN", &err.to_string()))?;
+            req.headers_mut().insert(AUTHORIZATION, value);
+        }
+
+        let (ws, _res) = connect_async(req).await.map_err(ws_error)?;
+        let (sink, stream) = ws.split();
+        let sender = WsSender {
+            sink,
+            cmd: PhantomData,
+        };
+        Ok((sender, events(stream)))
+    }
+}
+
+/// Decodes the events of a connection
+fn events<E: DeserializeOwned>(
+    stream: SplitStream<WsStream>,
+) -> impl Stream<Item = Result<E, Error>> {
+    stream.filter_map(|msg| async move {
+        let res = match msg {
+            Ok(Message::Text(text)) => serde_json::from_str(&text),
+            Ok(Message::Binary(data)) => serde_json::from_slice(&data),
+            // NB: the pings are answered by the connection
+            Ok(_) => return None,
+            Err(err) => return Some(Err(ws_error(err))),
+        };
+        Some(res.map_err(|err| Error::new("INVALID_EVENT", &format!("invalid event: {err}"))))
+    })
+}
+
+/// Converts a WebSocket error
+fn ws_error(err: tokio_tungstenite::tungstenite::Error) -> Error {
+    Error::new("WEBSOCKET", &err.to_string())
+}
@@ -0,0 +1,44 @@
+//! Blocking client tests
+
+#![cfg(feature = "blocking")]
+
+use fake::{
+    faker::{
+        internet::en::{FreeEmail, Password},
+        name::en::Name,
+    },
+    Fake,
+};
+use newsie_client::{blocking::Client, feed::NewFeed, NewUser};
+
+#[test]
+fn test_blocking() {
+    let mut client = Client::new("http://localhost:3000").unwrap();
+    client.health().unwrap();
+    let email: String = FreeEmail().fake();
+    let password: String = Password(10..20).fake();
+    let res = client
+        .signup(NewUser {
+            name: Name().fake(),
+            email: email.clone(),
+            password,
+        })
+        .unwrap();
+    assert!(client.get_token().is_some());
+
+    let me = client.me().unwrap();
+    assert_eq!(me.user.id, res.user.id);
+    assert_eq!(me.user.email, email);
+
+    let feeds = client.get_feeds().unwrap();
+    assert_eq!(feeds.len(), 0);
+
+    let feed = client
+        .add_feed(NewFeed::new("https://www.feed.com"))
+        .unwrap();
+    assert_eq!(client.get_feeds_page(10, None).unwrap().items.len(), 1);
+    client.delete_feed(feed.id).unwrap();
+
+    client.delete_me().unwrap();
+    assert!(client.get_token().is_none());
+}
@@ -1,6 +1,11 @@
 //! Feed tests
 
-use newsie_client::FeedUpdate;
+use futures::TryStreamExt;
+use newsie_client::{
+    feed::{FeedPatch, NewFeed},
+    query::FeedQuery,
+    FeedUpdate,
+};
 
 use crate::common::{setup, teardown};
 
@@ -21,7 +26,7 @@ async fn test_feeds() {
         },
         FeedUpdate {
             id: None,
-            url: "http://www.google.com".to_string(),
+            url: "http://www.google.com/news".to_string(),
             name: None,
         },
     ];
@@ -33,3 +38,124 @@ async fn test_feeds() {
 
     teardown(client).await;
 }
+
+#[tokio::test]
+async fn test_replace_feeds() {
+    let (client, _user, _) = setup().await;
+
+    let (feeds, version) = client.get_feeds_versioned().await.unwrap();
+    assert!(feeds.is_empty());
+    let version = version.unwrap();
+    let my_feeds = vec![FeedUpdate {
+        id: None,
+        url: "http://www.google.com".to_string(),
+        name: None,
+    }];
+    let feeds = client
+        .replace_feeds(&my_feeds, Some(&version))
+        .await
+        .unwrap();
+    assert_eq!(feeds.len(), 1);
+
+    // NB: the feeds have changed since the version
+    let err = client.replace_feeds(&[], Some(&version)).await.unwrap_err();
+    assert_eq!(err.code(), "CONFLICT");
+    assert_eq!(client.get_feeds().await.unwrap().len(), 1);
+
+    teardown(client).await;
+}
+
+#[tokio::test]
+async fn test_feed_crud() {
+    let (client, _user, _) = setup().await;
+
+    let feed = client
+        .add_feed(NewFeed::new("http://www.google.com").name("Google"))
+        .await
+        .unwrap();
+    assert_eq!(feed.name.as_deref(), Some("Google"));
+    let other = client
+        .add_feed(NewFeed::new("http://www.google.com/news"))
+        .await
+        .unwrap();
+    assert_eq!(client.get_feeds().await.unwrap().len(), 2);
+
+    let feed = client
+        .update_feed(
+            feed.id,
+            FeedPatch {
+                name: Some(None),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(feed.name, None);
+    assert_eq!(feed.url, "http://www.google.com");
+
+    client.delete_feed(other.id).await.unwrap();
+    let feeds = client.get_feeds().await.unwrap();
+    assert_eq!(feeds.len(), 1);
+    assert_eq!(feeds[0].id, feed.id);
+    assert!(client.delete_feed(other.id).await.is_err());
+
+    teardown(client).await;
+}
+
+#[tokio::test]
+async fn test_feeds_stream() {
+    let (client, _user, _) = setup().await;
+
+    let my_feeds = (0..5)
+        .map(|i| FeedUpdate {
+            id: None,
+            url: format!("http://www.google.com/{i}"),
+            name: None,
+        })
+        .collect::<Vec<_>>();
+    client.sync_feeds(&my_feeds).await.unwrap();
+
+    let page = client.get_feeds_page(2, None).await.unwrap();
+    assert_eq!(page.items.len(), 2);
+    assert!(page.next_cursor.is_some());
+
+    let feeds = client
+        .feeds_stream(2)
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(feeds.len(), 5);
+
+    teardown(client).await;
+}
+
+#[tokio::test]
+async fn test_list_feeds() {
+    let (client, _user, _) = setup().await;
+
+    let my_feeds = (0..3)
+        .map(|i| FeedUpdate {
+            id: None,
+            url: format!("http://www.google.com/{i}"),
+            name: Some(format!("feed {i}")),
+        })
+        .collect::<Vec<_>>();
+    client.sync_feeds(&my_feeds).await.unwrap();
+
+    let query = FeedQuery::new().sort_desc("name").limit(2);
+    let page = client.list_feeds(&query).await.unwrap();
+    let names = page
+        .items
+        .iter()
+        .map(|f| f.name.as_deref())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec![Some("feed 2"), Some("feed 1")]);
+
+    let page = client
+        .list_feeds(&FeedQuery::new().name("feed 0"))
+        .await
+        .unwrap();
+    assert_eq!(page.items.len(), 1);
+
+    teardown(client).await;
+}
@@ -0,0 +1,15 @@
+//! Health tests
+
+use newsie_client::Client;
+
+#[tokio::test]
+async fn test_health() {
+    let client = Client::new("http://localhost:3000");
+    client.health().await.unwrap();
+
+    let readiness = client.health_deep().await.unwrap();
+    assert!(readiness.ready);
+
+    let version = client.server_version().await.unwrap();
+    assert!(!version.version.is_empty());
+}
@@ -0,0 +1,176 @@
+//! In-process tests
+//!
+//! The client surface is run against the API service, in the same process. The endpoints of
+//! the OpenAPI specs which are not called must be listed in `UNCOVERED`, so that a new endpoint
+//! without client support fails the test.
+
+#![cfg(feature = "it-tests")]
+
+use fake::{
+    faker::{
+        internet::en::{FreeEmail, Password},
+        name::en::Name,
+    },
+    Fake,
+};
+use futures::TryStreamExt;
+use newsie_api::{config::AppConfig, http::init_service};
+use newsie_client::{
+    feed::{FeedPatch, NewFeed},
+    inproc::{InProcessTransport, IN_PROCESS_URL},
+    query::FeedQuery,
+    transport::HttpTransport,
+    Client, FeedUpdate, NewUser, Subscription, SubscriptionUpdate, UserUpdate,
+};
+use reqwest::Method;
+
+/// Methods of the OpenAPI path items
+const HTTP_METHODS: &[&str] = &["get", "post", "put", "patch", "delete"];
+
+/// Endpoints not called by the client surface (method and path)
+const UNCOVERED: &[(&str, &str)] = &[
+    // banner of the API
+    ("GET", "/"),
+    // liveness probe of the orchestrator
+    ("GET", "/healthz"),
+    // require an OpenAI account
+    ("POST", "/summaries"),
+    ("POST", "/summaries/jobs"),
+    ("GET", "/summaries/jobs/{id}"),
+    // require an admin user
+    ("GET", "/admin/backup"),
+    ("POST", "/admin/restore"),
+    ("GET", "/admin/stats"),
+    ("GET", "/admin/metrics"),
+    ("GET", "/admin/jobs"),
+    ("GET", "/admin/maintenance/vector-index"),
+    ("POST", "/admin/maintenance/vector-index"),
+    ("GET", "/admin/retention"),
+    ("POST", "/admin/retention"),
+];
+
+#[tokio::test]
+async fn test_client_surface() {
+    let cfg = AppConfig::load();
+    let transport = InProcessTransport::new(init_service(&cfg).await.unwrap());
+    let mut client = Client::in_process(&transport);
+
+    // health
+    client.health().await.unwrap();
+    client.health_deep().await.unwrap();
+    client.server_version().await.unwrap();
+
+    // auth
+    let email: String = FreeEmail().fake();
+    let password: String = Password(10..20).fake();
+    let user = client
+        .signup(NewUser {
+            name: Name().fake(),
+            email: email.clone(),
+            password: password.clone(),
+        })
+        .await
+        .unwrap()
+        .user;
+    client.login(&email, &password).await.unwrap();
+    assert_eq!(client.me().await.unwrap().user.id, user.id);
+    let updated = client
+        .update_me(UserUpdate {
+            name: Some("new name".to_string()),
+            email: None,
+            password: None,
+        })
+        .await
+        .unwrap();
+    assert_eq!(updated.name, "new name");
+    client
+        .change_password(&password, "new-password")
+        .await
+        .unwrap();
+    client
+        .update_subscription(SubscriptionUpdate {
+            subscription: Subscription::Mid,
+        })
+        .await
+        .unwrap();
+
+    // feeds
+    let feeds = client
+        .sync_feeds(&[FeedUpdate {
+            id: None,
+            url: "http://www.google.com/0".to_string(),
+            name: None,
+        }])
+        .await
+        .unwrap();
+    assert_eq!(feeds.len(), 1);
+    let feed = client
+        .add_feed(NewFeed::new("http://www.google.com/1").name("feed 1"))
+        .await
+        .unwrap();
+    client
+        .update_feed(
+            feed.id,
+            FeedPatch {
+                url: None,
+                name: Some(None),
+            },
+        )
+        .await
+        .unwrap();
+    let page = client
+        .list_feeds(&FeedQuery::new().sort_asc("url").limit(1))
+        .await
+        .unwrap();
+    assert_eq!(page.items.len(), 1);
+    let all = client
+        .feeds_stream(1)
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(all.len(), 2);
+    client.delete_feed(feed.id).await.unwrap();
+    assert_eq!(client.get_feeds().await.unwrap().len(), 1);
+
+    // export
+    let path = std::env::temp_dir().join(format!("newsie-export-{}.json", user.id));
+    client.export_my_data(&path, |_| {}).await.unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    client.delete_me().await.unwrap();
+
+    // coverage
+    let calls = transport.calls();
+    let uncovered = openapi_endpoints(&transport)
+        .await
+        .into_iter()
+        .filter(|(method, path)| !calls.iter().any(|(m, p)| m == method && p == path))
+        .filter(|(method, path)| {
+            !UNCOVERED
+                .iter()
+                .any(|(m, p)| *m == method.as_str() && p == path)
+        })
+        .collect::<Vec<_>>();
+    assert!(uncovered.is_empty(), "endpoints not covered: {uncovered:?}");
+}
+
+/// Returns the endpoints of the OpenAPI specs (method and path)
+async fn openapi_endpoints(transport: &InProcessTransport) -> Vec<(Method, String)> {
+    let req = reqwest::Client::new()
+        .get(format!("{IN_PROCESS_URL}/openapi"))
+        .build()
+        .unwrap();
+    let res = transport.execute(req).await.unwrap();
+    let specs = res.json::<serde_json::Value>().await.unwrap();
+
+    let mut endpoints = vec![];
+    for (path, item) in specs["paths"].as_object().unwrap() {
+        // NB: the path item also holds the shared parameters
+        for method in item.as_object().unwrap().keys() {
+            if HTTP_METHODS.contains(&method.as_str()) {
+                endpoints.push((method.to_uppercase().parse().unwrap(), path.clone()));
+            }
+        }
+    }
+    endpoints
+}
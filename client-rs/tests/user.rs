@@ -1,6 +1,6 @@
 //! User tests
 
-use newsie_client::UserUpdate;
+use newsie_client::{auth::TokenRefresher, Client, UserUpdate};
 
 use crate::common::{setup, teardown};
 
@@ -14,6 +14,28 @@ async fn test_login() {
     teardown(client).await;
 }
 
+#[tokio::test]
+async fn test_token_refresh() {
+    let (client, user, password) = setup().await;
+    let email = user.email.clone();
+    let refresher = TokenRefresher::new(move || {
+        let (email, password) = (email.clone(), password.clone());
+        async move {
+            let mut client = Client::new("http://localhost:3000");
+            Ok(client.login(&email, &password).await?.token)
+        }
+    });
+    let other = Client::builder("http://localhost:3000")
+        .token(Some("invalid".to_string()))
+        .refresher(refresher)
+        .build()
+        .unwrap();
+    let res = other.me().await.unwrap();
+    assert_eq!(res.user.email, user.email);
+    assert_ne!(other.get_token().as_deref(), Some("invalid"));
+    teardown(client).await;
+}
+
 #[tokio::test]
 async fn test_get_user() {
     let (client, user, _) = setup().await;
@@ -36,3 +58,34 @@ async fn test_update_user() {
     assert_eq!(res.name, "new_name".to_string());
     teardown(client).await;
 }
+
+#[tokio::test]
+async fn test_change_password() {
+    let (mut client, user, password) = setup().await;
+    let err = client
+        .change_password("wrong", "new-password")
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), "INVALID_FIELDS");
+    client
+        .change_password(&password, "new-password")
+        .await
+        .unwrap();
+    client.login(&user.email, "new-password").await.unwrap();
+    teardown(client).await;
+}
+
+#[tokio::test]
+async fn test_export() {
+    let (client, user, _) = setup().await;
+    let path = std::env::temp_dir().join(format!("newsie-export-{}.json", user.id));
+    let mut downloaded = 0;
+    let report = client
+        .export_my_data(&path, |progress| downloaded = progress.downloaded)
+        .await
+        .unwrap();
+    assert_eq!(report.size, downloaded);
+    assert_eq!(std::fs::metadata(&path).unwrap().len(), report.size);
+    std::fs::remove_file(&path).unwrap();
+    teardown(client).await;
+}